@@ -1,7 +1,7 @@
 use crate::{
     auth::credential::{Credential, CredentialId},
     session::{
-        cfg::SessionCfg,
+        cfg::{ClipboardCfg, SessionCfg, WidthCfg},
         session_grp_pers::SessionGrpPers,
     },
 };
@@ -115,6 +115,26 @@ impl PersistenceMgr {
         Some(INSTANCE.lock().sessions.get(&id)?.credential().clone())
     }
 
+    #[inline]
+    pub fn get_width_cfg(id: CredentialId) -> Option<WidthCfg> {
+        Some(INSTANCE.lock().sessions.get(&id)?.width().clone())
+    }
+
+    #[inline]
+    pub fn get_clipboard_cfg(id: CredentialId) -> Option<ClipboardCfg> {
+        Some(INSTANCE.lock().sessions.get(&id)?.clipboard())
+    }
+
+    #[inline]
+    pub fn get_allow_resize_request(id: CredentialId) -> Option<bool> {
+        Some(INSTANCE.lock().sessions.get(&id)?.allow_resize_request())
+    }
+
+    #[inline]
+    pub fn get_encoding(id: CredentialId) -> Option<String> {
+        Some(INSTANCE.lock().sessions.get(&id)?.encoding().to_string())
+    }
+
     #[inline]
     pub fn root_group(&self) -> &SessionGrpPers {
         self.root_group.as_ref().unwrap()
@@ -6,6 +6,105 @@ use serde::{Deserialize, Serialize};
 pub struct SessionCfg {
     credential: Credential,
     group: String,
+    #[serde(default)]
+    width: WidthCfg,
+    /// The character encoding of session, e.g. `UTF-8`, `GBK`, `Big5`.
+    #[serde(default = "default_encoding")]
+    encoding: String,
+    #[serde(default)]
+    clipboard: ClipboardCfg,
+    /// Whether the terminal program was allowed to resize the terminal by `CSI 8 t`.
+    #[serde(default)]
+    allow_resize_request: bool,
+}
+
+#[inline]
+fn default_encoding() -> String {
+    "UTF-8".to_string()
+}
+
+/// The character width options of session.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct WidthCfg {
+    /// Whether the East Asian ambiguous width characters were double width.
+    #[serde(default)]
+    ambiguous_wide: bool,
+    /// The custom width of hexadecimal code point ranges, e.g. `["2500-257F", 2]`.
+    #[serde(default)]
+    overrides: Vec<(String, u8)>,
+}
+
+impl WidthCfg {
+    #[inline]
+    pub fn new(ambiguous_wide: bool, overrides: Vec<(String, u8)>) -> Self {
+        Self {
+            ambiguous_wide,
+            overrides,
+        }
+    }
+
+    #[inline]
+    pub fn ambiguous_wide(&self) -> bool {
+        self.ambiguous_wide
+    }
+
+    #[inline]
+    pub fn overrides(&self) -> &[(String, u8)] {
+        &self.overrides
+    }
+}
+
+/// How the clipboard access requested by terminal program (`OSC 52`) was handled.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardAccessCfg {
+    Allow,
+    #[default]
+    Ask,
+    Deny,
+}
+
+/// The clipboard access options of session.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct ClipboardCfg {
+    /// The access of setting the clipboard.
+    #[serde(default = "default_clipboard_write")]
+    write: ClipboardAccessCfg,
+    /// The access of querying the clipboard, the user was asked by default.
+    #[serde(default)]
+    read: ClipboardAccessCfg,
+}
+
+#[inline]
+fn default_clipboard_write() -> ClipboardAccessCfg {
+    ClipboardAccessCfg::Allow
+}
+
+impl Default for ClipboardCfg {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            write: default_clipboard_write(),
+            read: ClipboardAccessCfg::default(),
+        }
+    }
+}
+
+impl ClipboardCfg {
+    #[inline]
+    pub fn new(write: ClipboardAccessCfg, read: ClipboardAccessCfg) -> Self {
+        Self { write, read }
+    }
+
+    #[inline]
+    pub fn write(&self) -> ClipboardAccessCfg {
+        self.write
+    }
+
+    #[inline]
+    pub fn read(&self) -> ClipboardAccessCfg {
+        self.read
+    }
 }
 
 impl SessionCfg {
@@ -14,6 +113,10 @@ impl SessionCfg {
         Self {
             credential,
             group,
+            width: WidthCfg::default(),
+            encoding: default_encoding(),
+            clipboard: ClipboardCfg::default(),
+            allow_resize_request: false,
         }
     }
 
@@ -26,6 +129,46 @@ impl SessionCfg {
     pub fn group(&self) -> &String {
         &self.group
     }
+
+    #[inline]
+    pub fn width(&self) -> &WidthCfg {
+        &self.width
+    }
+
+    #[inline]
+    pub fn set_width(&mut self, width: WidthCfg) {
+        self.width = width
+    }
+
+    #[inline]
+    pub fn encoding(&self) -> &str {
+        &self.encoding
+    }
+
+    #[inline]
+    pub fn set_encoding(&mut self, encoding: String) {
+        self.encoding = encoding
+    }
+
+    #[inline]
+    pub fn clipboard(&self) -> ClipboardCfg {
+        self.clipboard
+    }
+
+    #[inline]
+    pub fn set_clipboard(&mut self, clipboard: ClipboardCfg) {
+        self.clipboard = clipboard
+    }
+
+    #[inline]
+    pub fn allow_resize_request(&self) -> bool {
+        self.allow_resize_request
+    }
+
+    #[inline]
+    pub fn set_allow_resize_request(&mut self, allow: bool) {
+        self.allow_resize_request = allow
+    }
 }
 
 impl Persistence for SessionCfg {
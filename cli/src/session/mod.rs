@@ -64,6 +64,13 @@ pub trait SessionExt {
     fn establish_time(&self) -> Timestamp;
 }
 
+impl SessionProps {
+    #[inline]
+    pub fn credential(&self) -> &Credential {
+        &self.credential
+    }
+}
+
 impl SessionExt for SessionProps {
     fn create(credential: Credential) -> SessionPropsId {
         let props = SessionProps {
@@ -1,19 +1,33 @@
 #![allow(dead_code)]
 use crate::tools::{
+    char_width::CharWidth,
     character::{
-        Character, LineProperty, DEFAULT_RENDITION, LINE_DEFAULT, LINE_WRAPPED, RE_BOLD, RE_CURSOR,
-        RE_REVERSE,
+        Character, ExtendedCharTable, LineProperty, UnderlineStyle, DEFAULT_RENDITION,
+        LINE_DEFAULT, LINE_DOUBLE_WIDTH, LINE_SEMANTIC_MARKS, LINE_WRAPPED, RE_BLINK, RE_BOLD,
+        RE_CONCEAL, RE_CURSOR, RE_EXTEND_CHAR, RE_REVERSE, RE_UNDERLINE, RE_UNDERLINE_STYLE,
     },
     character_color::{
         CharacterColor, COLOR_SPACE_DEFAULT, DEFAULT_BACK_COLOR, DEFAULT_FORE_COLOR,
     },
+    grapheme::{
+        cluster_width, code_point, extends_cluster, is_high_surrogate, is_low_surrogate,
+        MAX_CLUSTER_LENGTH,
+    },
+    graphics::{ImagePlacement, TerminalImage},
     history::{HistoryScroll, HistoryScrollNone, HistoryScrollWrapper, HistoryType},
-    system_ffi::wcwidth,
+    hyperlink::NO_HYPERLINK,
+    kitty_graphics::KITTY_IMAGE_STORE_QUOTA,
+    reflow::{Reflow, ReflowLine},
     terminal_character_decoder::{PlainTextDecoder, TerminalCharacterDecoder},
     text_stream::TextStream,
 };
 use bitvec::vec::BitVec;
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    rc::{Rc, Weak},
+    sync::atomic::{AtomicU64, Ordering},
+};
 use tmui::{
     prelude::*,
     tlib::{
@@ -29,8 +43,15 @@ pub const MODE_INSERT: usize = 2;
 pub const MODE_SCREEN: usize = 3;
 pub const MODE_CURSOR: usize = 4;
 pub const MODE_NEWLINE: usize = 5;
+/// Left right margin mode (DECLRMM).
+pub const MODE_LEFT_RIGHT_MARGIN: usize = 6;
+/// Grapheme cluster mode (mode 2027), the characters were segmented into the extended
+/// grapheme clusters, otherwise only the zero width characters were combined.
+///
+/// Off by default, so the widths agree with the `wcwidth` of programs unaware of the mode.
+pub const MODE_GRAPHEME_CLUSTER: usize = 7;
 
-pub const MODES_SCREEN: usize = 6;
+pub const MODES_SCREEN: usize = 8;
 
 const MAX_CHARS: usize = 1024;
 
@@ -49,6 +70,7 @@ pub struct SavedState {
     rendition: wchar_t,
     foreground: CharacterColor,
     background: CharacterColor,
+    underline_color: CharacterColor,
 }
 impl SavedState {
     pub fn new() -> Self {
@@ -58,6 +80,7 @@ impl SavedState {
             rendition: 0,
             foreground: CharacterColor::empty(),
             background: CharacterColor::empty(),
+            underline_color: CharacterColor::empty(),
         }
     }
 }
@@ -101,6 +124,15 @@ pub struct Screen {
     ////// History buffer.
     #[derivative(Default(value = "Rc::new(HistoryScrollNone::new().wrap())"))]
     history: Rc<Box<dyn HistoryScrollWrapper>>,
+    // The lines in history which have semantic marks: (line index, marks), in ascending order.
+    history_marked_lines: Vec<(i32, LineProperty)>,
+    // Changed whenever the semantic marks were set, cleared or moved, see `marks_generation()`.
+    marks_generation: u64,
+    // The images placed on the screen or in history, anchored by the line index include history.
+    image_placements: Vec<ImagePlacement>,
+    // The memory quota of the placed images, the oldest placements were removed when exceeded.
+    #[derivative(Default(value = "KITTY_IMAGE_STORE_QUOTA"))]
+    image_quota: usize,
 
     ////// Cursor location.
     cursor_x: i32,
@@ -112,10 +144,17 @@ pub struct Screen {
     #[derivative(Default(value = "CharacterColor::default_background()"))]
     cursor_background: CharacterColor,
     cursor_rendition: wchar_t,
+    // The underline color of the characters to display, undefined means the foreground color.
+    cursor_underline_color: CharacterColor,
+    // Hyperlink id of the characters to display.
+    cursor_hyperlink: u32,
 
     ////// Margins
     top_margin: i32,
     bottom_margin: i32,
+    // The left and right margins can only be set in the `MODE_LEFT_RIGHT_MARGIN`.
+    left_margin: i32,
+    right_margin: i32,
 
     ////// States
     current_modes: [bool; MODES_SCREEN],
@@ -149,9 +188,36 @@ pub struct Screen {
     // Used in repeating char.
     last_drawn_char: wchar_t,
 
+    // Computes the width of characters to display.
+    char_width: CharWidth,
+    // The maximum number of the newest history lines rewrapped on column changes, `None`
+    // for all the lines.
+    reflow_history_lines: Option<usize>,
+
+    ////// Grapheme cluster
+    // The code points of the last displayed grapheme cluster.
+    last_cluster: Vec<wchar_t>,
+    // The character data of the cell stores the cluster, the hash of extended char if the
+    // cluster has multiple code points.
+    last_cluster_data: wchar_t,
+    last_cluster_x: i32,
+    last_cluster_y: i32,
+    last_cluster_width: i32,
+    // The high surrogate waiting for the low one, 0 if none.
+    high_surrogate: wchar_t,
+
     #[derivative(Default(value = "RefCell::new([Character::default(); MAX_CHARS])"))]
     character_buffer: RefCell<[Character; MAX_CHARS]>,
+
+    // Dropped with the screen, so the registration in `SCREENS` expires.
+    alive: Rc<()>,
+}
+
+thread_local! {
+    /// The screens created, whose characters reference the extended char table.
+    static SCREENS: RefCell<Vec<(Weak<()>, *const Screen)>> = RefCell::new(vec![]);
 }
+
 impl ObjectSubclass for Screen {
     const NAME: &'static str = "Screen";
 }
@@ -173,10 +239,59 @@ impl Screen {
         screen.init_tab_stops();
         screen.clear_selection();
         screen.reset(None);
+        screen.marks_changed();
+
+        SCREENS.with(|screens| {
+            let mut screens = screens.borrow_mut();
+            screens.retain(|(alive, _)| alive.strong_count() > 0);
+            screens.push((Rc::downgrade(&screen.alive), &**screen as *const Screen));
+        });
 
         screen
     }
 
+    /// Removes the unused sequences from the extended char table when it grows too large,
+    /// the sequences referenced by the cells of any screen or their history are kept.
+    fn collect_extended_chars(&self) {
+        let table = ExtendedCharTable::instance();
+        if !table.needs_collection() {
+            return;
+        }
+
+        let mut used = HashSet::new();
+        self.used_extended_chars(&mut used);
+        SCREENS.with(|screens| {
+            for &(ref alive, screen) in screens.borrow().iter() {
+                if alive.strong_count() == 0 || std::ptr::eq(screen, self) {
+                    continue;
+                }
+                // SAFETY: the boxed screen is alive, and never moved out of its box.
+                unsafe { &*screen }.used_extended_chars(&mut used);
+            }
+        });
+        table.collect_unused(&used);
+    }
+
+    /// Adds the hash of extended chars referenced by the screen and history to @p used.
+    fn used_extended_chars(&self, used: &mut HashSet<wchar_t>) {
+        let mut add = |line: &[Character]| {
+            used.extend(
+                line.iter()
+                    .filter(|ch| ch.rendition & RE_EXTEND_CHAR != 0)
+                    .map(|ch| ch.character_union.data()),
+            )
+        };
+        self.screen_lines.iter().for_each(|line| add(line));
+
+        let mut cells = vec![];
+        for lineno in 0..self.history.get_lines() {
+            let len = self.history.get_line_len(lineno);
+            cells.resize(len as usize, Character::default());
+            self.history.get_cells(lineno, 0, len, &mut cells);
+            add(&cells);
+        }
+    }
+
     /// Fills the buffer @p dest with @p count instances of the default (ie. blank) Character style.
     #[inline]
     pub fn fill_with_default_char(character: &mut [Character], count: i32) {
@@ -224,18 +339,29 @@ impl Screen {
     }
 
     /// Move the cursor to the left by @p n columns.
-    /// The cursor will stop at the first column.
+    /// The cursor will stop at the first column, or the left margin if it was inside the margins.
     pub fn cursor_left(&mut self, n: i32) {
         let n = if n == 0 { 1 } else { n };
+        let stop = if self.cursor_x < self.left_margin {
+            0
+        } else {
+            self.left_margin
+        };
         self.cursor_x = (self.columns - 1).min(self.cursor_x);
-        self.cursor_x = 0.max(self.cursor_x - n);
+        self.cursor_x = stop.max(self.cursor_x - n);
     }
 
-    /// Move the cursor to the left by @p n columns.
-    /// The cursor will stop at the first column.
+    /// Move the cursor to the right by @p n columns.
+    /// The cursor will stop at the last column, or the right margin if it was inside the margins.
     pub fn cursor_right(&mut self, n: i32) {
         let n = if n == 0 { 1 } else { n };
-        self.cursor_x = (self.columns - 1).min(self.cursor_x + n);
+        let stop = if self.cursor_x > self.right_margin {
+            self.columns - 1
+        } else {
+            self.right_margin
+        };
+        let stop = stop.min(self.line_columns(self.cursor_y) - 1);
+        self.cursor_x = stop.min(self.cursor_x + n);
     }
 
     /// Moves cursor to beginning of the line by @p n lines down.
@@ -281,7 +407,13 @@ impl Screen {
     pub fn set_cursor_x(&mut self, x: i32) {
         let mut x = if x == 0 { 1 } else { x };
         x -= 1;
-        self.cursor_x = 0.max((self.columns - 1).min(x));
+        self.cursor_x = if self.get_mode(MODE_ORIGIN) {
+            self.left_margin
+                .max(self.right_margin.min(x + self.left_margin))
+        } else {
+            0.max((self.columns - 1).min(x))
+        };
+        self.cursor_x = self.cursor_x.min(self.line_columns(self.cursor_y) - 1);
     }
 
     /// Position the cursor at line @p y, column @p x.
@@ -309,8 +441,50 @@ impl Screen {
 
         self.top_margin = top;
         self.bottom_margin = bottom;
-        self.cursor_x = 0;
-        self.cursor_y = if self.get_mode(MODE_ORIGIN) { top } else { 0 };
+        self.home_in_margins();
+    }
+
+    /// Sets the left and right margins (DECSLRM), only works in the `MODE_LEFT_RIGHT_MARGIN`.
+    ///
+    /// @param left_column The left column of the new margin.
+    /// @param right_column The right column of the new margin.
+    pub fn set_left_right_margins(&mut self, left_column: i32, right_column: i32) {
+        if !self.get_mode(MODE_LEFT_RIGHT_MARGIN) {
+            return;
+        }
+        let mut left = if left_column == 0 { 1 } else { left_column };
+        let mut right = if right_column == 0 {
+            self.columns
+        } else {
+            right_column
+        };
+        left -= 1;
+        right -= 1;
+        if !(0 <= left && left < right && right < self.columns) {
+            return;
+        }
+
+        self.left_margin = left;
+        self.right_margin = right;
+        self.home_in_margins();
+    }
+
+    /// Returns the left column of the scrolling region.
+    #[inline]
+    pub fn left_margin(&self) -> i32 {
+        self.left_margin
+    }
+
+    /// Returns the right column of the scrolling region.
+    #[inline]
+    pub fn right_margin(&self) -> i32 {
+        self.right_margin
+    }
+
+    /// Whether the left or right margin was set, the scrolling region doesn't cover entire lines.
+    #[inline]
+    pub fn has_left_right_margins(&self) -> bool {
+        self.left_margin > 0 || self.right_margin < self.columns - 1
     }
 
     /// Returns the top line of the scrolling region.
@@ -325,11 +499,13 @@ impl Screen {
         self.bottom_margin
     }
 
-    /// Resets the scrolling margins back to the top and bottom lines of the screen.
+    /// Resets the scrolling margins back to the edges of the screen.
     #[inline]
     pub fn set_default_margins(&mut self) {
         self.top_margin = 0;
         self.bottom_margin = self.lines - 1;
+        self.left_margin = 0;
+        self.right_margin = self.columns - 1;
     }
 
     /// Moves the cursor down one line, if the MODE_NewLine mode
@@ -356,7 +532,10 @@ impl Screen {
     /// Equivalent to NextLine() if the MODE_NewLine flag is set or index() otherwise.
     pub fn index(&mut self) {
         if self.cursor_y == self.bottom_margin {
-            self.scroll_up(1);
+            // The region doesn't scroll when the cursor was outside the left and right margins.
+            if self.cursor_in_left_right_margins() {
+                self.scroll_up(1);
+            }
         } else if self.cursor_y < self.lines - 1 {
             self.cursor_y += 1;
         }
@@ -367,16 +546,24 @@ impl Screen {
     /// region is scrolled down by one line instead.
     pub fn reverse_index(&mut self) {
         if self.cursor_y == self.top_margin {
-            self.inner_scroll_down(self.top_margin, 1);
+            if self.cursor_in_left_right_margins() {
+                self.scroll_down(1);
+            }
         } else if self.cursor_y > 0 {
             self.cursor_y -= 1;
         }
     }
 
     /// Scroll the scrolling region of the screen up by @p n lines.
-    /// The scrolling region is initially the whole screen, but can be changed using setMargins().
+    /// The scrolling region is initially the whole screen, but can be changed using setMargins()
+    /// and setLeftRightMargins().
     pub fn scroll_up(&mut self, n: i32) {
         let n = if n == 0 { 1 } else { n };
+        if self.has_left_right_margins() {
+            // The partial lines never go into the history.
+            self.scroll_columns(self.top_margin, -n);
+            return;
+        }
         if self.top_margin == 0 {
             self.add_history_line();
         }
@@ -384,17 +571,26 @@ impl Screen {
     }
 
     /// Scroll the scrolling region of the screen down by @p n lines.
-    /// The scrolling region is initially the whole screen, but can be changed using setMargins().
+    /// The scrolling region is initially the whole screen, but can be changed using setMargins()
+    /// and setLeftRightMargins().
     pub fn scroll_down(&mut self, n: i32) {
         let n = if n == 0 { 1 } else { n };
+        if self.has_left_right_margins() {
+            self.scroll_columns(self.top_margin, n);
+            return;
+        }
         self.inner_scroll_down(self.top_margin, n);
     }
 
-    /// Moves the cursor to the beginning of the current line.
-    /// Equivalent to setCursorX(0).
+    /// Moves the cursor to the beginning of the current line, which was the left margin
+    /// if the cursor was inside the margins.
     #[inline]
     pub fn to_start_of_line(&mut self) {
-        self.cursor_x = 0;
+        self.cursor_x = if self.cursor_x >= self.left_margin {
+            self.left_margin
+        } else {
+            0
+        };
     }
 
     ///  Moves the cursor one column to the left and erases the character at the new cursor position.
@@ -417,9 +613,10 @@ impl Screen {
     /// Moves the cursor @p n tab-stops to the right.
     pub fn tab(&mut self, n: i32) {
         let mut n = if n == 0 { 1 } else { n };
-        while n > 0 && self.cursor_x < self.columns - 1 {
+        let last_column = self.line_columns(self.cursor_y) - 1;
+        while n > 0 && self.cursor_x < last_column {
             self.cursor_right(1);
-            while self.cursor_x < self.columns - 1 && !self.tab_stops[self.cursor_x as usize] {
+            while self.cursor_x < last_column && !self.tab_stops[self.cursor_x as usize] {
                 self.cursor_right(1)
             }
             n -= 1;
@@ -460,6 +657,23 @@ impl Screen {
     pub fn delete_chars(&mut self, n: i32) {
         let mut n = if n == 0 { 1 } else { n };
 
+        if self.has_left_right_margins() {
+            // Only the characters between the cursor and right margin were shifted.
+            if !self.cursor_in_left_right_margins() {
+                return;
+            }
+            let (y, right) = (self.cursor_y, self.right_margin);
+            for x in self.cursor_x..=right {
+                let c = if x + n <= right {
+                    self.character_at(x + n, y)
+                } else {
+                    Character::default()
+                };
+                self.set_character_at(x, y, c);
+            }
+            return;
+        }
+
         // if cursor is beyond the end of the line there is nothing to do
         if self.cursor_x >= self.screen_lines[self.cursor_y as usize].len() as i32 {
             return;
@@ -483,6 +697,23 @@ impl Screen {
     pub fn insert_chars(&mut self, n: i32) {
         let n = if n == 0 { 1 } else { n };
 
+        if self.has_left_right_margins() {
+            // The characters pushed beyond the right margin were lost.
+            if !self.cursor_in_left_right_margins() {
+                return;
+            }
+            let (y, right) = (self.cursor_y, self.right_margin);
+            for x in (self.cursor_x..=right).rev() {
+                let c = if x - n >= self.cursor_x {
+                    self.character_at(x - n, y)
+                } else {
+                    Character::default()
+                };
+                self.set_character_at(x, y, c);
+            }
+            return;
+        }
+
         if self.screen_lines[self.cursor_y as usize].len() < self.cursor_x as usize {
             self.screen_lines[self.cursor_y as usize]
                 .resize(self.cursor_x as usize, Character::default())
@@ -511,6 +742,9 @@ impl Screen {
         //
         // So, a "normal" program should always use REP immediately after a visible
         // character (those other than escape sequences). So, lastDrawnChar can be safely used.
+        if self.last_drawn_char == 0 {
+            return;
+        }
         for _ in 0..count {
             self.display_character(self.last_drawn_char)
         }
@@ -521,6 +755,12 @@ impl Screen {
     /// If @p n is 0 then one line is removed.
     pub fn delete_lines(&mut self, n: i32) {
         let n = if n == 0 { 1 } else { n };
+        if self.has_left_right_margins() {
+            if self.cursor_in_left_right_margins() {
+                self.scroll_columns(self.cursor_y, -n);
+            }
+            return;
+        }
         self.inner_scroll_up(self.cursor_y, n);
     }
 
@@ -529,9 +769,149 @@ impl Screen {
     /// If @p n is 0 then one line is inserted.
     pub fn insert_lines(&mut self, n: i32) {
         let n = if n == 0 { 1 } else { n };
+        if self.has_left_right_margins() {
+            if self.cursor_in_left_right_margins() {
+                self.scroll_columns(self.cursor_y, n);
+            }
+            return;
+        }
         self.inner_scroll_down(self.cursor_y, n);
     }
 
+    ///////////////////////////////////////////////////////////////////////////////////////////
+    ////////////////// Rectangular area operations (VT400).
+    //
+    // The coordinates of area were 1-based like the cursor position and relative to the
+    // margins in origin mode, 0 means the edge of the screen (or the margins).
+    ///////////////////////////////////////////////////////////////////////////////////////////
+
+    /// Copies the rectangular area to the position of (`dest_top`, `dest_left`), the part
+    /// beyond the screen was clipped (DECCRA).
+    pub fn copy_rect(
+        &mut self,
+        top: i32,
+        left: i32,
+        bottom: i32,
+        right: i32,
+        dest_top: i32,
+        dest_left: i32,
+    ) {
+        let (top, left, bottom, right) = match self.rect_area(top, left, bottom, right) {
+            Some(area) => area,
+            None => return,
+        };
+        let (dest_top, dest_left, max_bottom, max_right) =
+            match self.rect_area(dest_top, dest_left, 0, 0) {
+                Some(area) => area,
+                None => return,
+            };
+        let height = (bottom - top).min(max_bottom - dest_top) + 1;
+        let width = (right - left).min(max_right - dest_left) + 1;
+
+        // Copy the source first since the areas may overlap.
+        let source: Vec<Vec<Character>> = (0..height)
+            .map(|dy| {
+                (0..width)
+                    .map(|dx| self.character_at(left + dx, top + dy))
+                    .collect()
+            })
+            .collect();
+
+        self.check_selection(
+            self.loc(dest_left, dest_top),
+            self.loc(dest_left + width - 1, dest_top + height - 1),
+        );
+        for (dy, line) in source.into_iter().enumerate() {
+            for (dx, c) in line.into_iter().enumerate() {
+                self.set_character_at(dest_left + dx as i32, dest_top + dy as i32, c);
+            }
+        }
+    }
+
+    /// Fills the rectangular area with the character `c` in the current rendition (DECFRA),
+    /// only the printable characters of GL and GR were allowed.
+    pub fn fill_rect(&mut self, c: wchar_t, top: i32, left: i32, bottom: i32, right: i32) {
+        if !(32..=126).contains(&c) && !(160..=255).contains(&c) {
+            return;
+        }
+        let (top, left, bottom, right) = match self.rect_area(top, left, bottom, right) {
+            Some(area) => area,
+            None => return,
+        };
+
+        let mut fill_ch = Character::new(
+            c,
+            self.effective_foreground,
+            self.effective_background,
+            self.effective_rendition,
+        );
+        fill_ch.underline_color = self.cursor_underline_color;
+
+        self.remove_rect_image_placements(top, left, bottom, right);
+        self.check_selection(self.loc(left, top), self.loc(right, bottom));
+        for y in top..=bottom {
+            for x in left..=right {
+                self.set_character_at(x, y, fill_ch);
+            }
+        }
+    }
+
+    /// Erases the rectangular area (DECERA).
+    ///
+    /// The selective erase (DECSERA) only erases the characters and keeps the renditions,
+    /// all the characters were erasable since the protection attribute (DECSCA) was not supported.
+    pub fn erase_rect(&mut self, top: i32, left: i32, bottom: i32, right: i32, selective: bool) {
+        let (top, left, bottom, right) = match self.rect_area(top, left, bottom, right) {
+            Some(area) => area,
+            None => return,
+        };
+
+        if !selective {
+            for y in top..=bottom {
+                self.clear_image(self.loc(left, y), self.loc(right, y), b' ');
+            }
+            return;
+        }
+
+        self.check_selection(self.loc(left, top), self.loc(right, bottom));
+        for y in top..=bottom {
+            let end = (right + 1).min(self.screen_lines[y as usize].len() as i32);
+            for x in left..end {
+                self.screen_lines[y as usize][x as usize]
+                    .character_union
+                    .set_data(wch!(' '));
+            }
+        }
+    }
+
+    /// Changes (DECCARA) or reverses (DECRARA) the renditions of characters in the
+    /// rectangular area, the `attributes` were the SGR parameters of bold(1), underline(4),
+    /// blink(5), reverse(7) and invisible(8), and the ones to reset them (0, 22, 24, 25, 27, 28).
+    pub fn change_rect_rendition(
+        &mut self,
+        top: i32,
+        left: i32,
+        bottom: i32,
+        right: i32,
+        attributes: &[i32],
+        reverse: bool,
+    ) {
+        let (top, left, bottom, right) = match self.rect_area(top, left, bottom, right) {
+            Some(area) => area,
+            None => return,
+        };
+
+        for y in top..=bottom {
+            for x in left..=right {
+                let mut c = self.character_at(x, y);
+                for attribute in attributes {
+                    c.rendition = Self::rect_rendition(c.rendition, *attribute, reverse);
+                }
+                self.set_character_at(x, y, c);
+            }
+        }
+    }
+
     /// Clears all the tab stops.
     pub fn clear_tab_stops(&mut self) {
         for i in 0..self.columns as usize {
@@ -548,7 +928,6 @@ impl Screen {
     }
 
     /// Resets (clears) the specified screen @p mode.
-    #[allow(clippy::single_match)]
     pub fn reset_mode(&mut self, mode: usize) {
         self.current_modes[mode] = false;
         match mode {
@@ -556,6 +935,10 @@ impl Screen {
                 self.cursor_x = 0;
                 self.cursor_y = 0;
             }
+            MODE_LEFT_RIGHT_MARGIN => {
+                self.left_margin = 0;
+                self.right_margin = self.columns - 1;
+            }
             _ => {}
         }
     }
@@ -566,7 +949,7 @@ impl Screen {
         self.current_modes[mode] = true;
         match mode {
             MODE_ORIGIN => {
-                self.cursor_x = 0;
+                self.cursor_x = self.left_margin;
                 self.cursor_y = self.top_margin;
             }
             _ => {}
@@ -596,6 +979,7 @@ impl Screen {
         self.saved_state.rendition = self.cursor_rendition;
         self.saved_state.foreground = self.cursor_foreground;
         self.saved_state.background = self.cursor_background;
+        self.saved_state.underline_color = self.cursor_underline_color;
     }
 
     /// Restores the position and appearance of the cursor.  
@@ -606,6 +990,7 @@ impl Screen {
         self.cursor_rendition = self.saved_state.rendition;
         self.cursor_foreground = self.saved_state.foreground;
         self.cursor_background = self.saved_state.background;
+        self.cursor_underline_color = self.saved_state.underline_color;
         self.update_effective_rendition();
     }
 
@@ -728,9 +1113,62 @@ impl Screen {
         self.set_foreground_color(COLOR_SPACE_DEFAULT, DEFAULT_FORE_COLOR);
         self.set_background_color(COLOR_SPACE_DEFAULT, DEFAULT_BACK_COLOR);
         self.cursor_rendition = DEFAULT_RENDITION;
+        self.cursor_underline_color = CharacterColor::empty();
         self.update_effective_rendition();
     }
 
+    /// Sets the underline style of cursor, [`UnderlineStyle::None`] disables the underline.
+    pub fn set_underline_style(&mut self, style: UnderlineStyle) {
+        self.cursor_rendition &= !(RE_UNDERLINE | RE_UNDERLINE_STYLE);
+        self.cursor_rendition |= style.rendition();
+        self.update_effective_rendition();
+    }
+
+    /// Sets the cursor's underline color.
+    /// @param space The color space used by the @p color argument, the undefined color space
+    /// means the underline was drawn with the foreground color.
+    pub fn set_underline_color(&mut self, space: u8, color: u32) {
+        self.cursor_underline_color = CharacterColor::new(space, color);
+    }
+
+    /// Sets the hyperlink of characters displayed after this call, which was
+    /// registered in [`HyperlinkTable`](crate::tools::hyperlink::HyperlinkTable).
+    /// [`NO_HYPERLINK`] ends the current hyperlink.
+    #[inline]
+    pub fn set_hyperlink(&mut self, hyperlink: u32) {
+        self.cursor_hyperlink = hyperlink;
+    }
+
+    /// Returns the hyperlink id of characters to display.
+    #[inline]
+    pub fn hyperlink(&self) -> u32 {
+        self.cursor_hyperlink
+    }
+
+    /// Returns the rendition flags of characters to display.
+    #[inline]
+    pub fn cursor_rendition(&self) -> wchar_t {
+        self.cursor_rendition
+    }
+
+    /// Returns the foreground color of characters to display.
+    #[inline]
+    pub fn cursor_foreground(&self) -> CharacterColor {
+        self.cursor_foreground
+    }
+
+    /// Returns the background color of characters to display.
+    #[inline]
+    pub fn cursor_background(&self) -> CharacterColor {
+        self.cursor_background
+    }
+
+    /// Returns the underline color of characters to display, undefined means the foreground color.
+    #[inline]
+    pub fn cursor_underline_color(&self) -> CharacterColor {
+        self.cursor_underline_color
+    }
+
     /// Returns the column which the cursor is positioned at.
     pub fn get_cursor_x(&self) -> i32 {
         self.cursor_x
@@ -786,8 +1224,12 @@ impl Screen {
         self.reset_mode(MODE_SCREEN);
         self.reset_mode(MODE_NEWLINE);
 
+        self.reset_mode(MODE_GRAPHEME_CLUSTER);
+        self.save_mode(MODE_GRAPHEME_CLUSTER);
+
         self.top_margin = 0;
         self.bottom_margin = self.lines - 1;
+        self.cursor_hyperlink = NO_HYPERLINK;
 
         self.set_default_margins();
         self.save_cursor();
@@ -806,20 +1248,162 @@ impl Screen {
     /// If the MODE_Insert screen mode is currently enabled then the character
     /// is inserted at the current cursor position, otherwise it will replace the
     /// character already at the current cursor position.
+    ///
+    /// The character continues the grapheme cluster of last displayed character was
+    /// joined to it, see [`Screen::join_last_cluster`].
+    ///
+    /// The UTF-16 surrogate pair (the wide characters on windows) was displayed as one
+    /// character, the unpaired surrogates were dropped.
     pub fn display_character(&mut self, c: wchar_t) {
-        let mut w = wcwidth(c);
+        if is_high_surrogate(c) {
+            self.high_surrogate = c;
+            return;
+        }
+        let high = std::mem::take(&mut self.high_surrogate);
+        if is_low_surrogate(c) {
+            if high != 0 {
+                self.display_code_point(&[high, c]);
+            }
+            return;
+        }
+        self.display_code_point(&[c]);
+    }
+
+    /// Displays the code point `c`, which may be a surrogate pair.
+    fn display_code_point(&mut self, c: &[wchar_t]) {
+        if self.join_last_cluster(c) {
+            return;
+        }
+
+        let w = code_point(c).map_or(0, |ch| self.char_width.code_point_width(ch as u32));
         if w <= 0 {
             return;
         }
+        let &[ch] = c else {
+            // The surrogate pair doesn't fit in one cell, store it as extended char.
+            let table = ExtendedCharTable::instance();
+            let length = c.len() as wchar_t;
+            self.collect_extended_chars();
+            let data = table.create_extended_char(c, length);
+            self.put_character(data, w);
+
+            let (x, y) = (self.last_cluster_x as usize, self.last_cluster_y as usize);
+            self.screen_lines[y][x].rendition |= RE_EXTEND_CHAR;
+            self.last_cluster.clear();
+            self.last_cluster.extend_from_slice(c);
+            // REP only repeats the characters fit in one cell.
+            self.last_drawn_char = 0;
+            return;
+        };
+        self.put_character(ch, w);
+    }
+
+    /// Displays the run of printable characters, same as [`Screen::display_character`]
+    /// for each of them.
+    ///
+    /// The printable ASCII character following another one never extends its grapheme
+    /// cluster, so the runs of them were written without the segmentation, see
+    /// [`Screen::put_ascii_run`].
+    pub fn display_characters(&mut self, chars: &[wchar_t]) {
+        let mut i = 0;
+        while i < chars.len() {
+            let run = chars[i..]
+                .iter()
+                .take_while(|&&c| (0x20..0x7f).contains(&c) && self.char_width.width(c) == 1)
+                .count();
+            let follows_ascii = self
+                .last_cluster
+                .last()
+                .map_or(true, |last| (0x20..0x7f).contains(last));
+
+            if run > 0 && follows_ascii && !self.get_mode(MODE_INSERT) {
+                self.put_ascii_run(&chars[i..i + run]);
+                i += run;
+            } else {
+                self.display_character(chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    /// The right bound of the text at cursor position, the text wraps at the right margin
+    /// if the cursor was inside the margins, or at the middle of double width line.
+    fn cursor_right_bound(&self) -> i32 {
+        let right = if self.cursor_x <= self.right_margin + 1 {
+            self.right_margin + 1
+        } else {
+            self.columns
+        };
+        right.min(self.line_columns(self.cursor_y))
+    }
 
-        if self.cursor_x + w > self.columns {
+    /// Moves the cursor to the next line if the character occupying `w` cells didn't fit
+    /// before the right bound and the wrap mode was set, otherwise keeps it at the bound.
+    ///
+    /// @return the right bound of the cursor line.
+    fn wrap_cursor(&mut self, w: i32) -> i32 {
+        let right = self.cursor_right_bound();
+        if self.cursor_x + w > right {
             if self.get_mode(MODE_WRAP) {
-                self.line_properties[self.cursor_y as usize] |= LINE_WRAPPED;
+                if !self.has_left_right_margins() {
+                    self.line_properties[self.cursor_y as usize] |= LINE_WRAPPED;
+                }
                 self.next_line();
+                return self.cursor_right_bound();
             } else {
-                self.cursor_x = self.columns - w;
+                self.cursor_x = right - w;
+            }
+        }
+        right
+    }
+
+    /// Writes the run of single width ASCII characters at the cursor position, the line
+    /// was resized and the selection was checked once for each segment before the wrap.
+    ///
+    /// The insert mode was not supported, the caller writes the characters one by one.
+    fn put_ascii_run(&mut self, mut run: &[wchar_t]) {
+        while !run.is_empty() {
+            let right = self.wrap_cursor(1);
+            let n = ((right - self.cursor_x).max(1) as usize).min(run.len());
+
+            let x = self.cursor_x as usize;
+            let y = self.cursor_y as usize;
+            if self.screen_lines[y].len() < x + n {
+                self.screen_lines[y].resize(x + n, Character::default());
             }
+
+            // check if selection is still valid.
+            let from = self.loc(self.cursor_x, self.cursor_y);
+            let to = self.loc(self.cursor_x + n as i32 - 1, self.cursor_y);
+            self.check_selection(from, to);
+
+            for (current_char, &c) in self.screen_lines[y][x..x + n].iter_mut().zip(run) {
+                current_char.character_union.set_data(c);
+                current_char.foreground_color = self.effective_foreground;
+                current_char.background_color = self.effective_background;
+                current_char.rendition = self.effective_rendition;
+                current_char.hyperlink = self.cursor_hyperlink;
+                current_char.underline_color = self.cursor_underline_color;
+            }
+
+            let c = run[n - 1];
+            self.last_drawn_char = c;
+
+            self.last_cluster.clear();
+            self.last_cluster.push(c);
+            self.last_cluster_data = c;
+            self.last_cluster_x = self.cursor_x + n as i32 - 1;
+            self.last_cluster_y = self.cursor_y;
+            self.last_cluster_width = 1;
+
+            self.cursor_x += n as i32;
+            run = &run[n..];
         }
+    }
+
+    /// Writes the character `c` occupying `w` cells at the cursor position.
+    fn put_character(&mut self, c: wchar_t, mut w: i32) {
+        self.wrap_cursor(w);
 
         // ensure current line vector has enough elements.
         let size = self.screen_lines[self.cursor_y as usize].len();
@@ -843,9 +1427,18 @@ impl Screen {
         current_char.foreground_color = self.effective_foreground;
         current_char.background_color = self.effective_background;
         current_char.rendition = self.effective_rendition;
+        current_char.hyperlink = self.cursor_hyperlink;
+        current_char.underline_color = self.cursor_underline_color;
 
         self.last_drawn_char = c;
 
+        self.last_cluster.clear();
+        self.last_cluster.push(c);
+        self.last_cluster_data = c;
+        self.last_cluster_x = self.cursor_x;
+        self.last_cluster_y = self.cursor_y;
+        self.last_cluster_width = w;
+
         let mut i = 0;
         let new_cursor_x = self.cursor_x + w;
         w -= 1;
@@ -862,26 +1455,129 @@ impl Screen {
             ch.foreground_color = self.effective_foreground;
             ch.background_color = self.effective_background;
             ch.rendition = self.effective_rendition;
+            ch.hyperlink = self.cursor_hyperlink;
+            ch.underline_color = self.cursor_underline_color;
 
             w -= 1;
         }
         self.cursor_x = new_cursor_x;
     }
 
+    /// Sets the width options of characters, only affects the characters displayed afterwards.
+    #[inline]
+    pub fn set_char_width(&mut self, char_width: CharWidth) {
+        self.char_width = char_width
+    }
+
+    /// Limits the number of the newest history lines rewrapped on column changes, the older
+    /// lines were kept at the columns they were written. `None` (the default) rewraps the
+    /// whole history, which may be slow for the large history.
+    #[inline]
+    pub fn set_reflow_history_lines(&mut self, max_lines: Option<usize>) {
+        self.reflow_history_lines = max_lines
+    }
+
+    #[inline]
+    pub fn char_width(&self) -> &CharWidth {
+        &self.char_width
+    }
+
     /// Do composition with last shown character.
     pub fn compose(&mut self, _compose: String) {}
 
+    /// Joins the character @p c to the grapheme cluster of last displayed character, if the
+    /// cursor was still behind the cluster and there was no cluster boundary between them.
+    ///
+    /// The multi code points cluster was stored in the extended char table, and the cell was
+    /// widened if the cluster became wider (e.g. the emoji presentation selector). The
+    /// extended char of the shorter cluster was removed from the table if nothing else
+    /// referenced it.
+    ///
+    /// @return true if the character was consumed.
+    fn join_last_cluster(&mut self, c: &[wchar_t]) -> bool {
+        if self.last_cluster.is_empty()
+            || self.cursor_y != self.last_cluster_y
+            || self.cursor_x != self.last_cluster_x + self.last_cluster_width
+        {
+            return false;
+        }
+        let x = self.last_cluster_x as usize;
+        let y = self.last_cluster_y as usize;
+        // The cell has been overwritten or erased.
+        let data = self.screen_lines[y]
+            .get(x)
+            .map(|ch| ch.character_union.data());
+        if data != Some(self.last_cluster_data) {
+            return false;
+        }
+
+        let grapheme_cluster = self.get_mode(MODE_GRAPHEME_CLUSTER);
+        let joined = if grapheme_cluster {
+            extends_cluster(&self.last_cluster, c)
+        } else {
+            code_point(c).map_or(false, |ch| self.char_width.code_point_width(ch as u32) == 0)
+        };
+        if !joined {
+            return false;
+        }
+        if self.last_cluster.len() + c.len() > MAX_CLUSTER_LENGTH {
+            return true;
+        }
+
+        // The extended char of the shorter cluster may have been copied to other cells,
+        // it's left to the collection.
+        self.collect_extended_chars();
+        self.last_cluster.extend_from_slice(c);
+        let length = self.last_cluster.len() as wchar_t;
+        self.last_cluster_data =
+            ExtendedCharTable::instance().create_extended_char(&self.last_cluster, length);
+
+        let cluster = &mut self.screen_lines[y][x];
+        cluster.character_union.set_data(self.last_cluster_data);
+        cluster.rendition |= RE_EXTEND_CHAR;
+        let mut filler = *cluster;
+        filler.character_union.set_data(0);
+        filler.rendition &= !RE_EXTEND_CHAR;
+
+        if !grapheme_cluster {
+            return true;
+        }
+        let width = cluster_width(&self.last_cluster, &self.char_width);
+        if width <= self.last_cluster_width || x as i32 + width > self.columns {
+            return true;
+        }
+
+        let line = &mut self.screen_lines[y];
+        if line.len() < x + width as usize {
+            line.resize(x + width as usize, Character::default());
+        }
+        line[x + self.last_cluster_width as usize..x + width as usize].fill(filler);
+
+        self.last_cluster_width = width;
+        self.cursor_x = self.last_cluster_x + width;
+        true
+    }
+
     /// Resizes the image to a new fixed size of @p new_lines by @p new_columns.
     /// In the case that @p new_columns is smaller than the current number of
     /// columns, existing lines are not truncated.  This prevents characters from
     /// being lost if the terminal display is resized smaller and then larger again.
     ///
     /// The top and bottom margins are reset to the top and bottom of the new
-    /// screen size.  Tab stops are also reset and the current selection is cleared.
+    /// screen size.  Tab stops are also reset.
+    ///
+    /// The lines of screen and history were rewrapped if the columns changed, see [`Screen::reflow`].
     pub fn resize_image(&mut self, new_lines: i32, new_columns: i32) {
         if new_lines == self.lines && new_columns == self.columns {
             return;
         }
+        if self.has_marks() {
+            self.marks_changed();
+        }
+
+        if new_columns != self.columns {
+            self.reflow(new_columns);
+        }
 
         if self.cursor_y > new_lines - 1 {
             self.bottom_margin = self.lines - 1;
@@ -923,10 +1619,161 @@ impl Screen {
         self.cursor_x = self.cursor_x.min(self.columns - 1);
         self.cursor_y = self.cursor_y.min(self.lines - 1);
 
-        self.top_margin = 0;
-        self.bottom_margin = self.lines - 1;
+        self.set_default_margins();
         self.init_tab_stops();
-        self.clear_selection();
+
+        // Remove the images anchored on the truncated lines.
+        let screen_end = self.history.get_lines() + self.lines;
+        self.image_placements.retain(|p| p.line < screen_end);
+    }
+
+    /// Rewraps the lines of screen image and history to @p new_columns by the [`LINE_WRAPPED`]
+    /// property, the cursor, selection, semantic marks and images stay on the same logical
+    /// characters. The lines pushed out of the top of screen were moved into history.
+    fn reflow(&mut self, new_columns: i32) {
+        let old_columns = self.columns;
+        let old_history_lines = self.history.get_lines();
+
+        let history_reflow =
+            self.history
+                .reflow_lines(old_columns, new_columns, self.reflow_history_lines);
+        let history_lines = self.history.get_lines();
+
+        let lines = (0..self.lines as usize)
+            .map(|y| {
+                ReflowLine::new(
+                    std::mem::take(&mut self.screen_lines[y]),
+                    self.line_properties[y],
+                )
+            })
+            .collect();
+        let anchor = (self.cursor_x as usize, self.cursor_y as usize);
+        let mut screen_reflow = Reflow::new(
+            lines,
+            old_columns as usize,
+            new_columns as usize,
+            Some(anchor),
+        );
+        let (_, cursor_line) = screen_reflow.map(anchor.0, anchor.1).unwrap_or_default();
+        let mut new_lines = screen_reflow.take_lines();
+
+        // The empty lines below cursor don't push the lines out of screen.
+        while new_lines.len() > cursor_line + 1
+            && new_lines
+                .last()
+                .is_some_and(|line| line.cells.is_empty() && !line.is_wrapped())
+        {
+            new_lines.pop();
+        }
+
+        // Move the lines out of the top of screen into history.
+        let overflow = new_lines.len().saturating_sub(self.lines as usize);
+        for line in new_lines.drain(..overflow) {
+            if self.has_scroll() {
+                self.history.add_cells_list(line.cells);
+                self.history
+                    .add_line(line.is_wrapped(), line.properties & LINE_SEMANTIC_MARKS);
+            }
+        }
+        let (base, dropped) = if self.has_scroll() {
+            let dropped = history_lines + overflow as i32 - self.history.get_lines();
+            (history_lines - dropped, dropped)
+        } else {
+            (-(overflow as i32), 0)
+        };
+        self.dropped_lines += dropped;
+
+        // Maps the position include history lines to the new position.
+        let map = |column: i32, line: i32| -> Option<(i32, i32)> {
+            let (column, line) = if line < old_history_lines {
+                let (column, line) = match history_reflow.as_ref() {
+                    Some(reflow) => reflow.map(column as usize, line as usize)?,
+                    None => (column as usize, line as usize),
+                };
+                (column as i32, line as i32 - dropped)
+            } else {
+                let (column, line) =
+                    screen_reflow.map(column as usize, (line - old_history_lines) as usize)?;
+                (column as i32, base + line as i32)
+            };
+            (line >= 0).then_some((column.min(new_columns - 1), line))
+        };
+
+        // Cursor
+        let new_history_lines = self.history.get_lines();
+        let (cursor_x, cursor_y) =
+            map(self.cursor_x, self.cursor_y + old_history_lines).unwrap_or((0, new_history_lines));
+        self.cursor_x = cursor_x;
+        self.cursor_y = (cursor_y - new_history_lines).max(0);
+
+        // Selection, the block selection can't be reflowed.
+        if self.select_begin != -1 {
+            let map_loc = |loc: i32| {
+                map(loc % old_columns, loc / old_columns)
+                    .map(|(column, line)| line * new_columns + column)
+            };
+            match (
+                map_loc(self.select_begin),
+                map_loc(self.select_top_left),
+                map_loc(self.select_bottom_right),
+            ) {
+                (Some(begin), Some(top_left), Some(bottom_right)) if !self.block_selection_mode => {
+                    self.select_begin = begin;
+                    self.select_top_left = top_left;
+                    self.select_bottom_right = bottom_right;
+                }
+                _ => self.clear_selection(),
+            }
+        }
+
+        // Images
+        self.image_placements
+            .retain_mut(|p| match map(p.column, p.line) {
+                Some((column, line)) => {
+                    p.column = column;
+                    p.line = line;
+                    true
+                }
+                None => false,
+            });
+
+        // Semantic marks in history, only the reflowed lines and the lines moved from screen
+        // were scanned again.
+        let first_reflowed = history_reflow
+            .as_ref()
+            .map_or(old_history_lines, |reflow| reflow.first_line() as i32);
+        let rescan_from = if first_reflowed < old_history_lines {
+            map(0, first_reflowed).map_or(0, |(_, line)| line)
+        } else {
+            base.max(0)
+        };
+        self.marks_changed();
+        let marked_lines = std::mem::take(&mut self.history_marked_lines);
+        self.history_marked_lines = marked_lines
+            .into_iter()
+            .filter(|(line, _)| *line < first_reflowed)
+            .filter_map(|(line, marks)| map(0, line).map(|(_, line)| (line, marks)))
+            .chain(
+                (rescan_from..new_history_lines)
+                    .map(|line| (line, self.history.get_line_marks(line)))
+                    .filter(|(_, marks)| *marks != LINE_DEFAULT),
+            )
+            .collect();
+
+        // Screen image
+        let mut screen_lines =
+            vec![vec![Character::default(); new_columns as usize]; self.lines as usize + 1];
+        let mut line_properties = vec![LINE_DEFAULT; self.lines as usize + 1];
+        for (y, line) in new_lines.into_iter().enumerate() {
+            line_properties[y] = line.properties;
+            screen_lines[y] = line.cells;
+            screen_lines[y].resize(new_columns as usize, Character::default());
+        }
+        self.screen_lines = screen_lines;
+        self.line_properties = line_properties;
+
+        self.columns = new_columns;
+        self.last_cluster.clear();
     }
 
     /// Returns the current screen image.
@@ -998,6 +1845,7 @@ impl Screen {
             if self.history.is_wrapped_line(line) {
                 result[index] |= LINE_WRAPPED;
             }
+            result[index] |= self.history.get_line_marks(line);
             index += 1;
         }
 
@@ -1035,12 +1883,26 @@ impl Screen {
         copy_previous_scroll: Option<bool>,
     ) {
         let copy_previous_scroll = copy_previous_scroll.unwrap_or(true);
+        let old_history_lines = self.history.get_lines();
 
         if copy_previous_scroll {
             self.history = history_type.borrow().scroll(Some(self.history.clone()));
         } else {
             self.history = history_type.borrow().scroll(None);
         }
+
+        self.history_marked_lines = (0..self.history.get_lines())
+            .map(|line| (line, self.history.get_line_marks(line)))
+            .filter(|(_, marks)| *marks != LINE_DEFAULT)
+            .collect();
+        self.marks_changed();
+
+        // The oldest lines may be dropped when the history was shrunk or discarded.
+        let offset = self.history.get_lines() - old_history_lines;
+        self.image_placements.retain_mut(|p| {
+            p.line += offset;
+            p.line + p.lines > 0
+        });
     }
 
     /// Returns the type of storage used to keep lines in the history.
@@ -1242,6 +2104,9 @@ impl Screen {
     ///                   Double-height lines are formed of two lines containing<br>
     /// the same characters, with both having the LINE_DOUBLEHEIGHT attribute. This
     /// allows other parts of the code to work on the assumption that all lines are the same height.
+    /// The bottom one has the LINE_DOUBLE_HEIGHT_BOTTOM attribute as well.
+    ///
+    /// The characters in the right half of line were discarded when the line became double width.
     ///
     /// @param enable true to apply the attribute to the current line or false to remove it
     pub fn set_line_property(&mut self, property: LineProperty, enable: bool) {
@@ -1250,6 +2115,163 @@ impl Screen {
         } else {
             self.line_properties[self.cursor_y as usize] &= !property;
         }
+        if property & LINE_SEMANTIC_MARKS != 0 {
+            self.marks_changed();
+        }
+
+        if enable && property & LINE_DOUBLE_WIDTH != 0 {
+            let line_columns = self.line_columns(self.cursor_y);
+            let line = &mut self.screen_lines[self.cursor_y as usize];
+            line.iter_mut()
+                .skip(line_columns as usize)
+                .for_each(|c| *c = Character::default());
+            self.cursor_x = self.cursor_x.min(line_columns - 1);
+        }
+    }
+
+    /// Returns the number of columns available on the screen line @p line, the characters on
+    /// double width lines occupy two columns each, so only the left half of columns were usable.
+    #[inline]
+    pub fn line_columns(&self, line: i32) -> i32 {
+        match self.line_properties.get(line as usize) {
+            Some(property) if property & LINE_DOUBLE_WIDTH != 0 => (self.columns / 2).max(1),
+            _ => self.columns,
+        }
+    }
+
+    /// The generation of semantic marks, which was changed whenever the marked lines may have
+    /// changed, so the result of [`Screen::marked_lines`] can be cached until then.
+    ///
+    /// The generations were unique among the screens.
+    #[inline]
+    pub fn marks_generation(&self) -> u64 {
+        self.marks_generation
+    }
+
+    #[inline]
+    fn marks_changed(&mut self) {
+        static GENERATION: AtomicU64 = AtomicU64::new(0);
+        self.marks_generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    }
+
+    /// Whether there were semantic marks on the screen or in history.
+    fn has_marks(&self) -> bool {
+        !self.history_marked_lines.is_empty()
+            || self
+                .line_properties
+                .iter()
+                .any(|p| p & LINE_SEMANTIC_MARKS != 0)
+    }
+
+    /// Returns the indexes of lines (include the lines in history) which have the semantic `mark`
+    /// set by `OSC 133`, in ascending order.
+    ///
+    /// @param mark One of the semantic marks in [`LINE_SEMANTIC_MARKS`]
+    pub fn marked_lines(&self, mark: LineProperty) -> Vec<i32> {
+        let history_lines = self.history.get_lines();
+
+        self.history_marked_lines
+            .iter()
+            .filter(|(_, marks)| marks & mark != 0)
+            .map(|(line, _)| *line)
+            .chain(
+                (0..self.lines)
+                    .filter(|&y| self.line_properties[y as usize] & mark != 0)
+                    .map(|y| y + history_lines),
+            )
+            .collect()
+    }
+
+    /// Places the `image` at the cursor position, which covers `columns` x `lines` cells.
+    /// The cursor is moved to the line below the image, the screen was scrolled up if the
+    /// image exceeded the bottom margin.
+    pub fn add_image(&mut self, image: Rc<TerminalImage>, columns: i32, lines: i32) {
+        let placement = ImagePlacement::new(
+            image,
+            self.history.get_lines() + self.cursor_y,
+            self.cursor_x,
+            lines,
+            columns,
+        );
+
+        self.add_image_placement(placement);
+
+        for _ in 0..lines {
+            self.index();
+        }
+    }
+
+    /// Adds the image placement without moving the cursor.
+    ///
+    /// The placement of kitty graphics protocol replaces the existing one with the same
+    /// image id and placement id.
+    pub fn add_image_placement(&mut self, placement: ImagePlacement) {
+        if placement.image_id == 0 {
+            // The images completely covered by the new one will never be seen again.
+            self.image_placements
+                .retain(|p| p.image_id != 0 || !placement.covers(p));
+        } else if placement.placement_id != 0 {
+            self.image_placements.retain(|p| {
+                p.image_id != placement.image_id || p.placement_id != placement.placement_id
+            });
+        }
+        self.image_placements.push(placement);
+        self.evict_image_placements();
+    }
+
+    /// Sets the memory quota of the placed images, shared with the image store of kitty
+    /// graphics protocol.
+    pub fn set_image_quota(&mut self, quota: usize) {
+        self.image_quota = quota;
+        self.evict_image_placements();
+    }
+
+    /// Removes the oldest placements until the pixels of the placed images fit in the quota,
+    /// the newest placement was always kept. The image shared by placements was counted once.
+    fn evict_image_placements(&mut self) {
+        let used = |placements: &[ImagePlacement]| {
+            let mut images: Vec<&Rc<TerminalImage>> = vec![];
+            for placement in placements {
+                if !images
+                    .iter()
+                    .any(|image| Rc::ptr_eq(image, &placement.image))
+                {
+                    images.push(&placement.image);
+                }
+            }
+            images
+                .iter()
+                .map(|image| image.pixels().len())
+                .sum::<usize>()
+        };
+
+        while self.image_placements.len() > 1 && used(&self.image_placements) > self.image_quota {
+            self.image_placements.remove(0);
+        }
+    }
+
+    /// Removes the image placements matching the `predicate`, returns the removed ones.
+    pub fn remove_image_placements<P>(&mut self, mut predicate: P) -> Vec<ImagePlacement>
+    where
+        P: FnMut(&ImagePlacement) -> bool,
+    {
+        let mut removed = vec![];
+        self.image_placements.retain(|p| {
+            if predicate(p) {
+                removed.push(p.clone());
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    /// Returns the images placed on the screen or in history, the `line` of placements
+    /// include the lines in history.
+    #[inline]
+    pub fn image_placements(&self) -> &[ImagePlacement] {
+        &self.image_placements
     }
 
     /// Returns the number of lines that the image has been scrolled up or down by,
@@ -1403,6 +2425,14 @@ impl Screen {
             self.clear_selection()
         }
 
+        // Remove the images anchored in the cleared area.
+        let (lines, columns) = (self.lines, self.columns);
+        let history_lines = self.history.get_lines();
+        self.image_placements.retain(|p| {
+            let y = p.line - history_lines;
+            !(0..lines).contains(&y) || !(loca..=loce).contains(&(y * columns + p.column))
+        });
+
         let top_line = loca / self.columns;
         let bottom_line = loce / self.columns;
 
@@ -1418,8 +2448,6 @@ impl Screen {
         let is_default_ch = clear_ch == Character::default();
 
         for y in top_line..=bottom_line {
-            self.line_properties[y as usize] = 0;
-
             let end_col = if y == bottom_line {
                 loce % self.columns
             } else {
@@ -1431,6 +2459,17 @@ impl Screen {
                 0
             };
 
+            // Shells usually erase part of the prompt line when redrawing it,
+            // keep the semantic marks unless the entire line was cleared.
+            if start_col == 0 && end_col == self.columns - 1 {
+                if self.line_properties[y as usize] & LINE_SEMANTIC_MARKS != 0 {
+                    self.marks_changed();
+                }
+                self.line_properties[y as usize] = 0;
+            } else {
+                self.line_properties[y as usize] &= LINE_SEMANTIC_MARKS;
+            }
+
             let line = &mut self.screen_lines[y as usize];
 
             if is_default_ch && end_col == self.columns - 1 {
@@ -1456,6 +2495,9 @@ impl Screen {
         assert!(source_begin <= source_end);
 
         let lines = (source_end - source_begin) / self.columns as usize;
+        if self.has_marks() {
+            self.marks_changed();
+        }
 
         // move screen image and line properties:
         // the source and destination areas of the image may overlap,
@@ -1543,6 +2585,8 @@ impl Screen {
             n = self.bottom_margin + 1 - from;
         }
 
+        self.move_image_placements(from, self.bottom_margin, -n);
+
         self.scrolled_lines -= n;
         self.last_scolled_region = Rect::new(
             0,
@@ -1577,6 +2621,8 @@ impl Screen {
             n = self.bottom_margin - from;
         }
 
+        self.move_image_placements(from, self.bottom_margin, n);
+
         self.move_image(
             self.loc(0, from + n) as usize,
             self.loc(0, from) as usize,
@@ -1589,16 +2635,219 @@ impl Screen {
         );
     }
 
+    /// Whether the cursor was between the left and right margins, include the position
+    /// after the right margin where the next character wraps.
+    #[inline]
+    fn cursor_in_left_right_margins(&self) -> bool {
+        self.cursor_x >= self.left_margin && self.cursor_x <= self.right_margin + 1
+    }
+
+    /// Moves the cursor to the home position, which was the top left of margins in origin mode.
+    fn home_in_margins(&mut self) {
+        if self.get_mode(MODE_ORIGIN) {
+            self.cursor_x = self.left_margin;
+            self.cursor_y = self.top_margin;
+        } else {
+            self.cursor_x = 0;
+            self.cursor_y = 0;
+        }
+    }
+
+    /// Returns the character at column `x` of line `y`, the default character beyond the end of line.
+    #[inline]
+    fn character_at(&self, x: i32, y: i32) -> Character {
+        self.screen_lines[y as usize]
+            .get(x as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Sets the character at column `x` of line `y`, the line was extended if necessary.
+    #[inline]
+    fn set_character_at(&mut self, x: i32, y: i32, c: Character) {
+        let line = &mut self.screen_lines[y as usize];
+        if line.len() <= x as usize {
+            if c == Character::default() {
+                return;
+            }
+            line.resize(x as usize + 1, Character::default());
+        }
+        line[x as usize] = c;
+    }
+
+    /// Scrolls the lines from `from` to the bottom margin between the left and right margins
+    /// by `n` lines (negative for up), clearing the exposed lines.
+    fn scroll_columns(&mut self, from: i32, n: i32) {
+        let (left, right, bottom) = (self.left_margin, self.right_margin, self.bottom_margin);
+        if n == 0 || from > bottom {
+            return;
+        }
+        let n = n.clamp(from - bottom - 1, bottom - from + 1);
+
+        self.check_selection(self.loc(left, from), self.loc(right, bottom));
+
+        let history_lines = self.history.get_lines();
+        self.image_placements.retain_mut(|p| {
+            let y = p.line - history_lines;
+            if y < from || y > bottom || p.column < left || p.column > right {
+                return true;
+            }
+            p.line += n;
+            (from..=bottom).contains(&(y + n))
+        });
+
+        // Copy in the right order since the source and destination overlap.
+        let lines: Vec<i32> = if n < 0 {
+            (from..=bottom + n).collect()
+        } else {
+            (from + n..=bottom).rev().collect()
+        };
+        for y in lines {
+            for x in left..=right {
+                let c = self.character_at(x, y - n);
+                self.set_character_at(x, y, c);
+            }
+        }
+
+        let exposed = if n < 0 {
+            bottom + n + 1..=bottom
+        } else {
+            from..=from + n - 1
+        };
+        for y in exposed {
+            self.clear_image(self.loc(left, y), self.loc(right, y), b' ');
+        }
+    }
+
+    /// Returns the 0-based (top, left, bottom, right) of the rectangular area clamped to the
+    /// screen (or the margins in origin mode), `None` if the area was empty.
+    fn rect_area(
+        &self,
+        top: i32,
+        left: i32,
+        bottom: i32,
+        right: i32,
+    ) -> Option<(i32, i32, i32, i32)> {
+        let (min_y, min_x, max_y, max_x) = if self.get_mode(MODE_ORIGIN) {
+            (
+                self.top_margin,
+                self.left_margin,
+                self.bottom_margin,
+                self.right_margin,
+            )
+        } else {
+            (0, 0, self.lines - 1, self.columns - 1)
+        };
+        let coordinate = |v: i32, min: i32, max: i32, default: i32| {
+            if v <= 0 {
+                default
+            } else {
+                (v - 1 + min).min(max)
+            }
+        };
+
+        let top = coordinate(top, min_y, max_y, min_y);
+        let left = coordinate(left, min_x, max_x, min_x);
+        let bottom = coordinate(bottom, min_y, max_y, max_y);
+        let right = coordinate(right, min_x, max_x, max_x);
+        if top > bottom || left > right {
+            None
+        } else {
+            Some((top, left, bottom, right))
+        }
+    }
+
+    /// Removes the images anchored in the rectangular area.
+    fn remove_rect_image_placements(&mut self, top: i32, left: i32, bottom: i32, right: i32) {
+        let history_lines = self.history.get_lines();
+        self.image_placements.retain(|p| {
+            let y = p.line - history_lines;
+            !(top..=bottom).contains(&y) || !(left..=right).contains(&p.column)
+        });
+    }
+
+    /// Applies the SGR `attribute` of rectangular area operations to the `rendition`,
+    /// the attributes were toggled if `reverse`, the underline of any style was toggled as a whole.
+    fn rect_rendition(rendition: wchar_t, attribute: i32, reverse: bool) -> wchar_t {
+        const UNDERLINE: wchar_t = RE_UNDERLINE | RE_UNDERLINE_STYLE;
+        let flags = match attribute {
+            0 => RE_BOLD | UNDERLINE | RE_BLINK | RE_REVERSE | RE_CONCEAL,
+            1 | 22 => RE_BOLD,
+            4 | 24 => UNDERLINE,
+            5 | 25 => RE_BLINK,
+            7 | 27 => RE_REVERSE,
+            8 | 28 => RE_CONCEAL,
+            _ => return rendition,
+        };
+        let single_underline = UnderlineStyle::Single.rendition();
+
+        if reverse {
+            // Only the attributes to set were allowed by DECRARA.
+            if attribute >= 22 {
+                return rendition;
+            }
+            let mut toggled = rendition ^ (flags & !UNDERLINE);
+            if flags & UNDERLINE != 0 {
+                toggled = if rendition & RE_UNDERLINE != 0 {
+                    toggled & !UNDERLINE
+                } else {
+                    toggled | single_underline
+                };
+            }
+            toggled
+        } else if attribute == 0 || attribute >= 22 {
+            rendition & !flags
+        } else if flags & UNDERLINE != 0 {
+            (rendition & !UNDERLINE) | single_underline
+        } else {
+            rendition | flags
+        }
+    }
+
+    /// Moves the images anchored in the screen lines `from..=to` by `n` lines (negative for up),
+    /// the images moved out of the lines were removed, except those scrolled into the history.
+    fn move_image_placements(&mut self, from: i32, to: i32, n: i32) {
+        let history_lines = self.history.get_lines();
+        let into_history = from == 0 && n < 0 && self.has_scroll();
+
+        self.image_placements.retain_mut(|p| {
+            let y = p.line - history_lines;
+            if y < from || y > to {
+                return true;
+            }
+            p.line += n;
+
+            (from..=to).contains(&(y + n)) || (into_history && p.line + p.lines > 0)
+        });
+    }
+
     /// Add line to history buffer
     fn add_history_line(&mut self) {
         if self.has_scroll() {
             let old_history_lines = self.history.get_lines();
 
+            let marks = self.line_properties[0] & LINE_SEMANTIC_MARKS;
             self.history.add_cells_list(self.screen_lines[0].clone());
             self.history
-                .add_line(self.line_properties[0] & LINE_WRAPPED != 0);
+                .add_line(self.line_properties[0] & LINE_WRAPPED != 0, marks);
 
             let new_history_lines = self.history.get_lines();
+
+            // Keep track of the semantic marks in history, the indexes of all marked lines
+            // were shifted if the oldest line was dropped.
+            if new_history_lines == old_history_lines && self.has_marks() {
+                self.marks_changed();
+            }
+            if new_history_lines == old_history_lines {
+                self.history_marked_lines.retain_mut(|(line, _)| {
+                    *line -= 1;
+                    *line >= 0
+                });
+            }
+            if marks != LINE_DEFAULT && new_history_lines > 0 {
+                self.history_marked_lines
+                    .push((new_history_lines - 1, marks));
+            }
             let begin_is_tl = self.select_begin == self.select_top_left;
 
             // If the history is full, increment the count of dropped lines
@@ -1606,6 +2855,22 @@ impl Screen {
                 self.dropped_lines += 1;
             }
 
+            // Keep the images at their lines, the images in history were moved up
+            // if the oldest line was dropped, otherwise the start of screen moved down.
+            if new_history_lines == old_history_lines {
+                self.image_placements.retain_mut(|p| {
+                    if p.line < old_history_lines {
+                        p.line -= 1;
+                    }
+                    p.line + p.lines > 0
+                });
+            } else {
+                self.image_placements
+                    .iter_mut()
+                    .filter(|p| p.line >= old_history_lines)
+                    .for_each(|p| p.line += 1);
+            }
+
             // Adjust selection for the new point of reference
             if new_history_lines > old_history_lines && self.select_begin != -1 {
                 self.select_top_left += self.columns;
@@ -1809,3 +3074,370 @@ impl Screen {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::screen_window::ScreenWindow,
+        tools::character::{LINE_DOUBLE_HEIGHT, LINE_OUTPUT_START, LINE_PROMPT_START},
+    };
+    use std::ptr::NonNull;
+
+    fn write(screen: &mut Screen, text: &str) {
+        let chars: Vec<wchar_t> = text.chars().map(|c| c as wchar_t).collect();
+        screen.display_characters(&chars);
+    }
+
+    fn line_text(screen: &Screen, y: i32) -> String {
+        screen.screen_lines[y as usize]
+            .iter()
+            .map(|c| match c.character_union.data() {
+                0 => ' ',
+                data => char::from_u32(data as u32).unwrap_or(' '),
+            })
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn test_double_width_line_columns() {
+        let mut screen = Screen::new(5, 20);
+        screen.set_line_property(LINE_DOUBLE_WIDTH, true);
+        assert_eq!(screen.line_columns(0), 10);
+        assert_eq!(screen.line_columns(1), 20);
+        assert_eq!(screen.line_columns(5), 20);
+
+        // The cursor was clamped to the left half.
+        screen.set_cursor_x(15);
+        assert_eq!(screen.get_cursor_x(), 9);
+        screen.set_cursor_x(1);
+        screen.cursor_right(15);
+        assert_eq!(screen.get_cursor_x(), 9);
+
+        // The tab stops beyond the half were ignored.
+        screen.set_cursor_x(1);
+        screen.tab(1);
+        assert_eq!(screen.get_cursor_x(), 8);
+        screen.tab(1);
+        assert_eq!(screen.get_cursor_x(), 9);
+        screen.set_cursor_yx(2, 9);
+        screen.tab(1);
+        assert_eq!(screen.get_cursor_x(), 16);
+
+        // The text wraps at the half of line.
+        screen.set_cursor_yx(1, 1);
+        write(&mut screen, "abcdefghijkl");
+        assert_eq!(line_text(&screen, 0), "abcdefghij");
+        assert_eq!(line_text(&screen, 1), "kl");
+        assert_ne!(screen.line_properties[0] & LINE_WRAPPED, 0);
+
+        // Same for the characters not written as ASCII run.
+        screen.set_cursor_yx(1, 1);
+        write(&mut screen, "123456789\u{e9}x");
+        assert_eq!(line_text(&screen, 0), "123456789\u{e9}");
+        assert_eq!(line_text(&screen, 1), "xl");
+
+        // Stays at the half when the wrap mode was reset.
+        screen.reset_mode(MODE_WRAP);
+        screen.set_cursor_yx(1, 1);
+        write(&mut screen, "ABCDEFGHIJKL");
+        assert_eq!(line_text(&screen, 0), "ABCDEFGHIL");
+        assert_eq!(screen.get_cursor_x(), 10);
+        assert_eq!(screen.get_cursor_y(), 0);
+    }
+
+    #[test]
+    fn test_set_line_property_truncation() {
+        let mut screen = Screen::new(5, 20);
+        screen.set_cursor_yx(2, 1);
+        write(&mut screen, "0123456789abcdef");
+        assert_eq!(screen.get_cursor_x(), 16);
+
+        // The double height only line keeps the characters.
+        screen.set_line_property(LINE_DOUBLE_HEIGHT, true);
+        assert_eq!(line_text(&screen, 1), "0123456789abcdef");
+        assert_eq!(screen.get_cursor_x(), 16);
+
+        screen.set_line_property(LINE_DOUBLE_WIDTH, true);
+        assert_eq!(line_text(&screen, 1), "0123456789");
+        assert_eq!(screen.get_cursor_x(), 9);
+        assert!(screen.screen_lines[1][10..]
+            .iter()
+            .all(|c| *c == Character::default()));
+
+        // The discarded characters were not restored.
+        screen.set_line_property(LINE_DOUBLE_WIDTH | LINE_DOUBLE_HEIGHT, false);
+        assert_eq!(screen.line_properties[1], LINE_DEFAULT);
+        assert_eq!(line_text(&screen, 1), "0123456789");
+        assert_eq!(screen.get_cursor_x(), 9);
+    }
+
+    /// Fills the 6x10 screen with distinct characters.
+    fn fill(screen: &mut Screen) {
+        let lines = [
+            "0123456789",
+            "abcdefghij",
+            "ABCDEFGHIJ",
+            "klmnopqrst",
+            "KLMNOPQRST",
+            "uvwxyz!@#$",
+        ];
+        for (y, line) in lines.iter().enumerate() {
+            screen.set_cursor_yx(y as i32 + 1, 1);
+            write(screen, line);
+        }
+    }
+
+    fn lines_text(screen: &Screen) -> Vec<String> {
+        (0..screen.lines).map(|y| line_text(screen, y)).collect()
+    }
+
+    #[test]
+    fn test_left_right_margins() {
+        let mut screen = Screen::new(6, 10);
+        fill(&mut screen);
+
+        // DECSLRM was ignored without DECLRMM.
+        screen.set_left_right_margins(3, 6);
+        assert!(!screen.has_left_right_margins());
+
+        screen.set_mode(MODE_LEFT_RIGHT_MARGIN);
+        screen.set_cursor_yx(3, 3);
+        screen.set_left_right_margins(3, 6);
+        assert_eq!((screen.left_margin(), screen.right_margin()), (2, 5));
+        assert_eq!((screen.get_cursor_x(), screen.get_cursor_y()), (0, 0));
+        screen.set_left_right_margins(6, 3);
+        assert_eq!((screen.left_margin(), screen.right_margin()), (2, 5));
+
+        // Only the columns between the margins were scrolled.
+        screen.set_margins(2, 5);
+        screen.scroll_up(1);
+        assert_eq!(
+            lines_text(&screen),
+            [
+                "0123456789",
+                "abCDEFghij",
+                "ABmnopGHIJ",
+                "klMNOPqrst",
+                "KL    QRST",
+                "uvwxyz!@#$"
+            ]
+        );
+        screen.scroll_down(1);
+        assert_eq!(
+            lines_text(&screen),
+            [
+                "0123456789",
+                "ab    ghij",
+                "ABCDEFGHIJ",
+                "klmnopqrst",
+                "KLMNOPQRST",
+                "uvwxyz!@#$"
+            ]
+        );
+
+        // IL/DL scroll from the cursor line, only when the cursor was inside the margins.
+        screen.set_cursor_yx(3, 8);
+        screen.delete_lines(1);
+        assert_eq!(line_text(&screen, 2), "ABCDEFGHIJ");
+        screen.set_cursor_yx(3, 4);
+        screen.delete_lines(1);
+        assert_eq!(
+            lines_text(&screen)[2..5],
+            ["ABmnopGHIJ", "klMNOPqrst", "KL    QRST"]
+        );
+        screen.insert_lines(2);
+        assert_eq!(
+            lines_text(&screen)[2..5],
+            ["AB    GHIJ", "kl    qrst", "KLmnopQRST"]
+        );
+
+        // The text wraps at the right margin to the left margin, the line was not wrapped.
+        screen.set_cursor_yx(1, 3);
+        write(&mut screen, "WXYZ12");
+        assert_eq!(line_text(&screen, 0), "01WXYZ6789");
+        assert_eq!(line_text(&screen, 1), "ab12  ghij");
+        assert_eq!(screen.line_properties[0] & LINE_WRAPPED, 0);
+
+        screen.reset_mode(MODE_LEFT_RIGHT_MARGIN);
+        assert!(!screen.has_left_right_margins());
+    }
+
+    #[test]
+    fn test_copy_rect() {
+        let mut screen = Screen::new(6, 10);
+        fill(&mut screen);
+
+        screen.copy_rect(1, 1, 2, 3, 4, 5);
+        assert_eq!(line_text(&screen, 3), "klmn012rst");
+        assert_eq!(line_text(&screen, 4), "KLMNabcRST");
+
+        // The part beyond the screen was clipped.
+        screen.copy_rect(1, 1, 2, 4, 6, 9);
+        assert_eq!(line_text(&screen, 5), "uvwxyz!@01");
+
+        // The overlapped areas.
+        screen.copy_rect(2, 1, 2, 0, 2, 3);
+        assert_eq!(line_text(&screen, 1), "ababcdefgh");
+
+        // The empty area.
+        screen.copy_rect(3, 1, 2, 10, 1, 1);
+        assert_eq!(line_text(&screen, 0), "0123456789");
+
+        // Relative to the margins in origin mode, clipped at the bottom margin.
+        screen.set_margins(2, 5);
+        screen.set_mode(MODE_ORIGIN);
+        screen.copy_rect(1, 1, 1, 2, 4, 1);
+        assert_eq!(line_text(&screen, 4), "abMNabcRST");
+        screen.copy_rect(1, 1, 2, 2, 4, 3);
+        assert_eq!(line_text(&screen, 4), "abababcRST");
+        assert_eq!(line_text(&screen, 5), "uvwxyz!@01");
+    }
+
+    #[test]
+    fn test_fill_rect() {
+        let mut screen = Screen::new(6, 10);
+        fill(&mut screen);
+
+        screen.set_rendition(RE_BOLD);
+        screen.fill_rect('*' as wchar_t, 2, 2, 3, 4);
+        assert_eq!(line_text(&screen, 1), "a***efghij");
+        assert_eq!(line_text(&screen, 2), "A***EFGHIJ");
+        assert_ne!(screen.screen_lines[1][1].rendition & RE_BOLD, 0);
+        assert_eq!(screen.screen_lines[1][0].rendition & RE_BOLD, 0);
+
+        // Only the printable characters of GL and GR were allowed.
+        screen.fill_rect(0x7f, 1, 1, 0, 0);
+        screen.fill_rect('\n' as wchar_t, 1, 1, 0, 0);
+        assert_eq!(line_text(&screen, 0), "0123456789");
+        screen.fill_rect(0xe9, 1, 1, 1, 1);
+        assert_eq!(line_text(&screen, 0), "\u{e9}123456789");
+
+        // The area beyond the screen was clipped.
+        screen.fill_rect('#' as wchar_t, 5, 9, 100, 100);
+        assert_eq!(line_text(&screen, 4), "KLMNOPQR##");
+        assert_eq!(line_text(&screen, 5), "uvwxyz!@##");
+
+        // Relative to the margins in origin mode.
+        screen.set_margins(2, 4);
+        screen.set_mode(MODE_ORIGIN);
+        screen.fill_rect('+' as wchar_t, 0, 0, 0, 0);
+        assert_eq!(
+            lines_text(&screen),
+            [
+                "\u{e9}123456789",
+                "++++++++++",
+                "++++++++++",
+                "++++++++++",
+                "KLMNOPQR##",
+                "uvwxyz!@##"
+            ]
+        );
+        screen.set_mode(MODE_LEFT_RIGHT_MARGIN);
+        screen.set_left_right_margins(3, 6);
+        screen.fill_rect('-' as wchar_t, 1, 1, 1, 99);
+        assert_eq!(line_text(&screen, 1), "++----++++");
+    }
+
+    #[test]
+    fn test_erase_rect() {
+        let mut screen = Screen::new(6, 10);
+        screen.set_rendition(RE_BOLD);
+        fill(&mut screen);
+
+        // DECERA erases the renditions too.
+        screen.erase_rect(1, 1, 1, 3, false);
+        assert_eq!(line_text(&screen, 0), "   3456789");
+        assert_eq!(screen.screen_lines[0][0].rendition, DEFAULT_RENDITION);
+        assert_ne!(screen.screen_lines[0][3].rendition & RE_BOLD, 0);
+
+        // DECSERA keeps the renditions.
+        screen.erase_rect(2, 1, 2, 3, true);
+        assert_eq!(line_text(&screen, 1), "   defghij");
+        assert_ne!(screen.screen_lines[1][0].rendition & RE_BOLD, 0);
+
+        // The area beyond the screen was clipped.
+        screen.erase_rect(6, 9, 99, 99, false);
+        assert_eq!(line_text(&screen, 5), "uvwxyz!@");
+        screen.erase_rect(5, 10, 99, 99, true);
+        assert_eq!(line_text(&screen, 4), "KLMNOPQRS");
+
+        // The empty area.
+        screen.erase_rect(4, 1, 3, 10, false);
+        assert_eq!(line_text(&screen, 2), "ABCDEFGHIJ");
+        assert_eq!(line_text(&screen, 3), "klmnopqrst");
+    }
+
+    #[test]
+    fn test_change_rect_rendition() {
+        const UNDERLINE: wchar_t = RE_UNDERLINE | RE_UNDERLINE_STYLE;
+        let underline = UnderlineStyle::Single.rendition();
+        let mut screen = Screen::new(6, 10);
+        fill(&mut screen);
+        let rendition = |screen: &Screen, x: usize| screen.screen_lines[0][x].rendition;
+
+        // DECCARA sets and resets the attributes.
+        screen.change_rect_rendition(1, 1, 1, 5, &[1, 4], false);
+        assert_eq!(rendition(&screen, 4), RE_BOLD | underline);
+        assert_eq!(rendition(&screen, 5), DEFAULT_RENDITION);
+        screen.change_rect_rendition(1, 1, 1, 2, &[0], false);
+        assert_eq!(rendition(&screen, 1), DEFAULT_RENDITION);
+
+        // DECRARA toggles the attributes, the underline of any style as a whole.
+        screen.change_rect_rendition(1, 1, 1, 5, &[1, 4], true);
+        assert_eq!(rendition(&screen, 0), RE_BOLD | underline);
+        assert_eq!(rendition(&screen, 2), DEFAULT_RENDITION);
+        screen.screen_lines[0][2].rendition = UnderlineStyle::Curly.rendition();
+        screen.change_rect_rendition(1, 3, 1, 3, &[4], true);
+        assert_eq!(rendition(&screen, 2) & UNDERLINE, 0);
+
+        // The reset attributes were not allowed by DECRARA.
+        screen.change_rect_rendition(1, 1, 1, 5, &[22], true);
+        assert_eq!(rendition(&screen, 0), RE_BOLD | underline);
+        screen.change_rect_rendition(1, 1, 1, 1, &[22], false);
+        assert_eq!(rendition(&screen, 0), underline);
+
+        // The area beyond the screen was clipped.
+        screen.change_rect_rendition(2, 8, 99, 99, &[7], false);
+        for line in &screen.screen_lines[1..6] {
+            assert_eq!(line[6].rendition & RE_REVERSE, 0);
+            assert!(line[7..10].iter().all(|c| c.rendition & RE_REVERSE != 0));
+        }
+        assert_eq!(line_text(&screen, 5), "uvwxyz!@#$");
+    }
+
+    #[test]
+    fn test_prompt_navigation() {
+        let mut screen = Screen::new(6, 10);
+        screen.set_line_property(LINE_PROMPT_START, true);
+        write(&mut screen, "$ ls");
+        screen.next_line();
+        screen.set_line_property(LINE_OUTPUT_START, true);
+        write(&mut screen, "a");
+        screen.next_line();
+        write(&mut screen, "b");
+        screen.next_line();
+        screen.set_line_property(LINE_PROMPT_START, true);
+        write(&mut screen, "$ ");
+
+        let mut window = ScreenWindow::new();
+        window.set_screen(NonNull::new(screen.as_mut()));
+        window.set_window_lines(2);
+        window.scroll_to(2);
+
+        assert!(window.scroll_to_previous_prompt());
+        assert_eq!(window.current_line(), 0);
+        assert!(!window.scroll_to_previous_prompt());
+        assert!(window.scroll_to_next_prompt());
+        assert_eq!(window.current_line(), 3);
+
+        // The output ends before the next prompt.
+        assert!(window.select_last_command_output());
+        assert!(!screen.is_selected(0, 0));
+        assert!(screen.is_selected(0, 1));
+        assert!(screen.is_selected(9, 2));
+        assert!(!screen.is_selected(0, 3));
+    }
+}
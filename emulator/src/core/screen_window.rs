@@ -1,7 +1,8 @@
 #![allow(dead_code)]
 use super::screen::{bound, Screen};
 use crate::tools::{
-    character::{Character, LineProperty},
+    character::{Character, LineProperty, LINE_OUTPUT_START, LINE_PROMPT_START},
+    graphics::ImagePlacement,
     translators::Command,
 };
 use std::ptr::NonNull;
@@ -50,6 +51,8 @@ pub struct ScreenWindow {
     track_output: bool,
     /// count of lines which the window has been scrolled by since the last call to reset_scroll_count()
     scroll_count: i32,
+    /// @see set_frozen()
+    frozen: bool,
 }
 impl ObjectSubclass for ScreenWindow {
     const NAME: &'static str = "ScreenWindow";
@@ -96,6 +99,7 @@ impl ScreenWindow {
         object.current_line = 0;
         object.track_output = true;
         object.scroll_count = 0;
+        object.frozen = false;
 
         object
     }
@@ -115,15 +119,17 @@ impl ScreenWindow {
     /// Returns the image of characters which are currently visible through this
     /// window onto the screen.
     pub fn get_image(&mut self) -> &Vec<Character> {
-        // reallocate internal buffer if the window size has changed
+        // reallocate internal buffer if the window size has changed,
+        // the frozen image was taken again in the new size.
         let size = self.window_lines() * self.window_columns();
-        if self.window_buffer.is_none() || self.window_buffer_size != size {
+        let resized = self.window_buffer.is_none() || self.window_buffer_size != size;
+        if resized {
             self.window_buffer_size = size;
             self.window_buffer = Some(vec![Character::default(); size as usize]);
             self.buffer_needs_update = true;
         }
 
-        if !self.buffer_needs_update {
+        if !self.buffer_needs_update || (self.frozen && !resized) {
             return self.window_buffer.as_ref().unwrap();
         }
 
@@ -146,6 +152,22 @@ impl ScreenWindow {
         self.window_buffer.as_ref().unwrap()
     }
 
+    /// Freeze the image returned by get_image(), the image was kept unchanged until unfrozen,
+    /// except the window size was changed, then the image was taken again in the new size
+    /// and frozen, so it never has the stale size.
+    ///
+    /// Used by the synchronized update, so that the half-updated screen will not be displayed.
+    #[inline]
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen
+    }
+
+    /// Returns true if the image of window was frozen.
+    #[inline]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
     /// Returns the line attributes associated with the lines of characters which
     /// are currently visible through this window
     pub fn get_line_properties(&self) -> Vec<LineProperty> {
@@ -301,6 +323,23 @@ impl ScreenWindow {
         )
     }
 
+    /// Returns the images which were visible in this window, the `line` of placements
+    /// were relative to the top of the window.
+    pub fn image_placements(&self) -> Vec<ImagePlacement> {
+        let current_line = self.current_line();
+        let end_line = current_line + self.window_lines();
+
+        self.screen()
+            .image_placements()
+            .iter()
+            .filter(|p| p.line + p.lines > current_line && p.line < end_line)
+            .map(|p| ImagePlacement {
+                line: p.line - current_line,
+                ..p.clone()
+            })
+            .collect()
+    }
+
     /// Returns the position of the cursor within the window.
     #[inline]
     pub fn cursor_position(&self) -> Point {
@@ -361,6 +400,91 @@ impl ScreenWindow {
         self.track_output
     }
 
+    /// Scrolls the window so that the previous prompt (marked by `OSC 133;A`)
+    /// above the top of the window is at the top of the window.
+    ///
+    /// @return false if there is no previous prompt.
+    pub fn scroll_to_previous_prompt(&mut self) -> bool {
+        let current_line = self.current_line();
+        let prompt = self
+            .screen()
+            .marked_lines(LINE_PROMPT_START)
+            .into_iter()
+            .rev()
+            .find(|&line| line < current_line);
+
+        match prompt {
+            Some(line) => {
+                self.scroll_to_prompt(line);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Scrolls the window so that the next prompt (marked by `OSC 133;A`)
+    /// below the top of the window is at the top of the window.
+    ///
+    /// @return false if there is no next prompt.
+    pub fn scroll_to_next_prompt(&mut self) -> bool {
+        if self.at_end_of_output() {
+            return false;
+        }
+
+        let current_line = self.current_line();
+        let prompt = self
+            .screen()
+            .marked_lines(LINE_PROMPT_START)
+            .into_iter()
+            .find(|&line| line > current_line);
+
+        match prompt {
+            Some(line) => {
+                self.scroll_to_prompt(line);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Selects the output of the last command, which starts from the line marked by `OSC 133;C`
+    /// and ends before the next prompt, or ends at the cursor line if the command is still running.
+    ///
+    /// @return false if there is no output of last command.
+    pub fn select_last_command_output(&mut self) -> bool {
+        let output_start = match self.screen().marked_lines(LINE_OUTPUT_START).last() {
+            Some(line) => *line,
+            None => return false,
+        };
+
+        let cursor_line = self.screen().get_history_lines() + self.screen().get_cursor_y();
+        let output_end = self
+            .screen()
+            .marked_lines(LINE_PROMPT_START)
+            .into_iter()
+            .find(|&line| line > output_start)
+            .map_or(cursor_line, |line| line - 1);
+
+        if output_end < output_start {
+            return false;
+        }
+
+        let columns = self.window_columns();
+        let screen = unsafe { self.screen.as_mut().unwrap().as_mut() };
+        screen.set_selection_start(0, output_start, false);
+        screen.set_selection_end(columns, output_end);
+
+        if output_start < self.current_line() || output_end > self.end_window_line() {
+            self.scroll_to(output_start);
+            self.set_track_output(self.at_end_of_output());
+        }
+
+        self.buffer_needs_update = true;
+        emit!(self, selection_changed());
+        emit!(self, screen_window_output_changed());
+        true
+    }
+
     ///  Returns the text which is currently selected.
     ///
     /// @param preserve_line_breaks See Screen::selected_text()
@@ -369,6 +493,12 @@ impl ScreenWindow {
         self.screen().selected_text(preserve_line_break)
     }
 
+    fn scroll_to_prompt(&mut self, line: i32) {
+        self.scroll_to(line);
+        self.set_track_output(self.at_end_of_output());
+        emit!(self, screen_window_output_changed());
+    }
+
     #[inline]
     fn end_window_line(&self) -> i32 {
         (self.current_line() + self.window_lines() - 1).min(self.line_count() - 1)
@@ -5,16 +5,35 @@ use crate::pty::con_pty::ConPty;
 #[cfg(not(target_os = "windows"))]
 use crate::pty::posix_pty::PosixPty;
 
+pub use crate::tools::{
+    char_width::{CharWidth, WidthOverride},
+    clipboard::{ClipboardAccess, ClipboardPolicy},
+    progress::{Progress, ProgressState},
+};
 use crate::{
     core::terminal_view::TerminalViewSignals,
     emulation::{Emulation, VT102Emulation},
     pty::Pty,
-    tools::{event::KeyPressedEvent, history::HistoryType},
+    tools::{
+        clipboard::{encode_clipboard_response, ClipboardRequest},
+        codec::{TerminalCodec, DEFAULT_ENCODING},
+        dynamic_color::DynamicColor,
+        event::KeyPressedEvent,
+        history::HistoryType,
+        notification::NotificationRateLimiter,
+        working_directory::WorkingDirectory,
+    },
 };
 use cli::{constant::ProtocolType, session::SessionPropsId};
 use derivative::Derivative;
 use log::{debug, warn};
-use std::{cell::RefCell, ptr::NonNull, rc::Rc, time::SystemTime};
+use std::{
+    cell::RefCell,
+    path::PathBuf,
+    ptr::NonNull,
+    rc::Rc,
+    time::{Duration, Instant, SystemTime},
+};
 use tmui::{
     prelude::*,
     scroll_area::{ScrollArea, ScrollAreaExt},
@@ -25,10 +44,16 @@ use tmui::{
         namespace::{ExitStatus, Orientation},
         nonnull_mut, nonnull_ref,
         object::{ObjectImpl, ObjectSubclass},
-        signals, Object,
+        signals,
+        timer::Timer,
+        Object,
     },
 };
 
+/// The terminal was resized after the view size has not changed for this duration, so the
+/// lines were reflowed once when dragging the window border.
+const RESIZE_DEBOUNCE: u64 = 60;
+
 /// Session represents an open tab used to bridge emulation and pty process.
 #[extends(Object)]
 pub struct Session {
@@ -61,6 +86,35 @@ pub struct Session {
     has_dark_background: bool,
     modified_background: Color,
 
+    /// The policy of clipboard accessing by `OSC 52` escape sequence.
+    clipboard_policy: ClipboardPolicy,
+    /// The clipboard request waiting for the user to answer, with its id.
+    pending_clipboard_request: Option<(u64, ClipboardRequest)>,
+    /// The id of the last clipboard request asked the user.
+    clipboard_request_id: u64,
+
+    /// The position on view where the context menu of filter actions was requested.
+    filter_actions_position: Point,
+
+    /// Whether the terminal program was allowed to resize the terminal (`CSI 8 ; lines ; columns t`).
+    allow_resize_request: bool,
+
+    /// The exit code of last command reported by shell integration (`OSC 133;D`).
+    last_exit_code: Option<i32>,
+
+    /// The current working directory reported by shell (`OSC 7`).
+    working_directory: Option<WorkingDirectory>,
+
+    /// Whether the desktop notifications (`OSC 9/777/99`) of this session were muted.
+    notifications_muted: bool,
+    notification_limiter: NotificationRateLimiter,
+
+    /// The progress reported by the terminal program (`OSC 9;4`).
+    progress: Progress,
+
+    /// Delays the terminal resizing until the view size settles.
+    resize_timer: Timer,
+
     // Zmodem
     zmodem_busy: bool,
     // zmodem_proc: Process
@@ -129,13 +183,61 @@ pub trait SessionSignal: ActionExt {
         /// @param url: [`String`]
         open_url_request();
 
+        /// Emitted when the terminal program requests to access the clipboard
+        /// while the [`ClipboardAccess::Ask`] policy was configured,
+        /// answer it by [`Session::answer_clipboard_request()`].
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param [`u64`] the id of request to answer.
+        /// @param [`bool`] true if the program requests to read the clipboard, false to write.
+        clipboard_access_request();
+
+        /// Emitted when the user right clicks on a hotspot of view (e.g. a link) which has
+        /// actions, the actions can be triggered by [`Session::activate_filter_action()`].
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param [`Point`] the global position to show the context menu.
+        filter_actions_request();
+
+        /// Emitted when the shell integration (`OSC 133;D`) reports that a command has finished,
+        /// the tab can show a failure badge when the exit code is not zero.
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param exit_code: [`i32`]
+        command_finished();
+
+        /// Emitted when the shell reports its current working directory by `OSC 7`.
+        ///
+        /// @param host: [`String`] empty if the shell did not report the host name.
+        /// @param path: [`String`]
+        cwd_changed();
+
+        /// Emitted when the terminal program requests the desktop notification by
+        /// `OSC 9/777/99`, the notifications were rate limited and dropped when the
+        /// session was muted.
+        ///
+        /// @param id: [`SessionPropsId`]
+        /// @param title: [`String`] may be empty.
+        /// @param body: [`String`]
+        notification_received();
+
+        /// Emitted when the progress reported by `OSC 9;4` has changed, it was reset to
+        /// [`ProgressState::None`] when the terminal process exits.
+        ///
+        /// @param id: [`SessionPropsId`]
+        /// @param state: [`u8`] see [`ProgressState`]
+        /// @param percent: [`u8`]
+        progress_changed();
+
         /// Detected the zmodem.
         zmodem_detected();
 
         /// Emitted when the terminal process requests a change
-        /// in the size of the terminal window.
+        /// in the size of the terminal window, only if it was allowed by
+        /// [`Session::set_allow_resize_request()`].
         ///
-        /// @param size: [`Size`] The requested window size in terms of lines and columns.
+        /// @param id: [`SessionPropsId`]
+        /// @param size: [`Size`] The requested window size in terms of columns(width) and lines(height).
         resize_request();
 
         /// Emitted when a profile change command is received from the terminal.
@@ -172,6 +274,13 @@ impl Session {
         session.session_id = id;
         session.protocol_type = protocol_type;
         let emulation = VT102Emulation::new(None).wrap();
+        session.resize_timer.set_single_shot(true);
+        connect!(
+            session.resize_timer,
+            timeout(),
+            session,
+            update_terminal_size()
+        );
         connect!(
             emulation,
             title_changed(),
@@ -179,6 +288,60 @@ impl Session {
             set_user_title(i32, String)
         );
         connect!(emulation, state_set(), session, activate_state_set(i32));
+        connect!(
+            emulation,
+            clipboard_write_request(),
+            session,
+            on_clipboard_write_request(u8, String)
+        );
+        connect!(
+            emulation,
+            clipboard_read_request(),
+            session,
+            on_clipboard_read_request(u8)
+        );
+        connect!(
+            emulation,
+            command_finished(),
+            session,
+            on_command_finished(i32)
+        );
+        connect!(
+            emulation,
+            working_directory_changed(),
+            session,
+            on_working_directory_changed(String, String)
+        );
+        connect!(
+            emulation,
+            notification_request(),
+            session,
+            on_notification_request(String, String)
+        );
+        connect!(
+            emulation,
+            progress_changed(),
+            session,
+            on_progress_changed(u8, u8)
+        );
+        connect!(
+            emulation,
+            color_query_request(),
+            session,
+            on_color_query_request(i32)
+        );
+        connect!(
+            emulation,
+            color_change_request(),
+            session,
+            on_color_change_request(i32, u32)
+        );
+        connect!(
+            emulation,
+            color_reset_request(),
+            session,
+            on_color_reset_request(i32)
+        );
         connect!(
             emulation,
             image_resize_request(),
@@ -211,7 +374,7 @@ impl Session {
                 done(SessionPropsId, ExitStatus)
             );
 
-            connect!(emulation, send_data(), shell_process, send_data(String));
+            connect!(emulation, send_data(), shell_process, send_data(Vec<u8>));
             connect!(
                 emulation,
                 use_utf8_request(),
@@ -277,11 +440,19 @@ impl Session {
     pub fn bind_view_to_emulation(&mut self) {
         let terminal_view = nonnull_mut!(self.view);
         connect!(self, finished(), terminal_view, terminate(SessionPropsId));
+        connect!(terminal_view, open_url_request(), self, open_url(String));
+        connect!(
+            terminal_view,
+            configure_request(),
+            self,
+            on_configure_request(Point)
+        );
 
         let emulation = self.emulation_mut();
 
         terminal_view.set_uses_mouse(emulation.program_use_mouse());
         terminal_view.set_bracketed_paste_mode(emulation.program_bracketed_paste_mode());
+        emulation.set_font_metrics(terminal_view.font_height(), terminal_view.font_width());
 
         // Connect `TerminalView`'s signal to emulation:
         connect!(
@@ -290,6 +461,18 @@ impl Session {
             emulation,
             send_key_event(KeyPressedEvent, bool)
         );
+        connect!(
+            terminal_view,
+            key_released_signal(),
+            emulation,
+            send_key_release_event(KeyPressedEvent)
+        );
+        connect!(
+            terminal_view,
+            term_lost_focus(),
+            emulation,
+            release_pressed_keys()
+        );
         connect!(
             terminal_view,
             control_insert_detected(),
@@ -308,12 +491,24 @@ impl Session {
             emulation,
             send_mouse_event(i32, i32, i32, u8)
         );
+        connect!(
+            terminal_view,
+            mouse_pixel_signal(),
+            emulation,
+            send_mouse_pixel_event(i32, i32, i32, u8)
+        );
         connect!(
             terminal_view,
             send_string_to_emulation(),
             emulation,
             send_string(String, i32)
         );
+        connect!(
+            terminal_view,
+            changed_font_metrics_signal(),
+            emulation,
+            set_font_metrics(f32, f32)
+        );
 
         // allow emulation to notify view when the foreground process
         // indicates whether or not it is interested in mouse signals:
@@ -388,6 +583,130 @@ impl Session {
         self.view_mut().set_blinking_cursor(blink)
     }
 
+    /// Sets the policy of clipboard accessing by `OSC 52` escape sequence.
+    #[inline]
+    pub fn set_clipboard_policy(&mut self, policy: ClipboardPolicy) {
+        self.clipboard_policy = policy
+    }
+    #[inline]
+    pub fn clipboard_policy(&self) -> ClipboardPolicy {
+        self.clipboard_policy
+    }
+
+    /// Sets whether the terminal program was allowed to resize the terminal by
+    /// `CSI 8 ; lines ; columns t`, disallowed by default.
+    #[inline]
+    pub fn set_allow_resize_request(&mut self, allow: bool) {
+        self.allow_resize_request = allow
+    }
+    #[inline]
+    pub fn allow_resize_request(&self) -> bool {
+        self.allow_resize_request
+    }
+
+    /// Sets the width options of characters, e.g. treat the East Asian ambiguous width
+    /// characters as wide.
+    #[inline]
+    pub fn set_char_width(&mut self, char_width: CharWidth) {
+        self.emulation_mut().set_char_width(char_width)
+    }
+
+    /// Limits the number of the newest history lines rewrapped when the columns of
+    /// terminal changed, `None` (the default) rewraps the whole history.
+    #[inline]
+    pub fn set_reflow_history_lines(&mut self, max_lines: Option<usize>) {
+        self.emulation_mut().set_reflow_history_lines(max_lines)
+    }
+
+    /// Sets the character encoding of session, e.g. `GBK` or `Big5`.
+    ///
+    /// @return false if the encoding was unknown.
+    pub fn set_encoding(&mut self, encoding: &str) -> bool {
+        if self.shell_process.as_ref().is_some_and(|p| p.utf8_only()) {
+            if TerminalCodec::new(encoding).is_none() {
+                return false;
+            }
+            debug!(
+                "[Session::set_encoding] The pty of session {} was UTF-8 only, encoding `{}` ignored.",
+                self.session_id, encoding
+            );
+            return self.emulation_mut().set_encoding(DEFAULT_ENCODING);
+        }
+        self.emulation_mut().set_encoding(encoding)
+    }
+
+    /// Returns the canonical name of character encoding of session.
+    #[inline]
+    pub fn encoding(&self) -> &'static str {
+        self.emulation().encoding()
+    }
+
+    /// Mute or unmute the desktop notifications requested by terminal program.
+    #[inline]
+    pub fn set_notifications_muted(&mut self, muted: bool) {
+        self.notifications_muted = muted
+    }
+    #[inline]
+    pub fn notifications_muted(&self) -> bool {
+        self.notifications_muted
+    }
+
+    /// Returns the progress reported by the terminal program.
+    #[inline]
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    /// Returns the exit code of last command reported by shell integration,
+    /// `None` if the shell integration is not enabled.
+    #[inline]
+    pub fn last_exit_code(&self) -> Option<i32> {
+        self.last_exit_code
+    }
+
+    /// Sets the directory which the shell process starts in,
+    /// should be called before [`Session::start_shell_process()`].
+    #[inline]
+    pub fn set_initial_working_dir(&mut self, dir: &str) {
+        self.initial_working_dir = dir.to_string()
+    }
+
+    /// Returns the current working directory reported by shell (`OSC 7`),
+    /// `None` if the shell has not reported it yet.
+    #[inline]
+    pub fn working_directory(&self) -> Option<&WorkingDirectory> {
+        self.working_directory.as_ref()
+    }
+
+    /// Returns the current working directory of local shell, which can be used
+    /// to start a new local session in the same directory.
+    ///
+    /// Falls back to the initial working directory if the shell has not reported it, or
+    /// reported the directory of another host (e.g. ssh in the local shell), `None` if the
+    /// session was connected to a remote host.
+    pub fn local_working_directory(&self) -> Option<PathBuf> {
+        if self.is_remote() {
+            return None;
+        }
+        match self.working_directory.as_ref() {
+            Some(dir) if dir.is_local() => Some(dir.local_path()),
+            _ if !self.initial_working_dir.is_empty() => {
+                Some(PathBuf::from(&self.initial_working_dir))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the current working directory on the remote host (e.g. ssh) tracked by `OSC 7`,
+    /// `None` if the session was local or the shell has not reported it.
+    #[inline]
+    pub fn remote_working_directory(&self) -> Option<&str> {
+        if !self.is_remote() {
+            return None;
+        }
+        self.working_directory.as_ref().map(|dir| dir.path.as_str())
+    }
+
     #[inline]
     pub fn get_protocol_type(&self) -> ProtocolType {
         self.protocol_type
@@ -400,6 +719,13 @@ impl Session {
 
     #[inline]
     pub fn start_shell_process(&mut self) {
+        if !self.initial_working_dir.is_empty() {
+            let dir = PathBuf::from(&self.initial_working_dir);
+            if let Some(shell_process) = self.shell_process.as_mut() {
+                shell_process.set_working_directory(dir);
+            }
+        }
+
         match self.protocol_type {
             ProtocolType::Cmd => {
                 #[cfg(target_os = "windows")]
@@ -433,6 +759,14 @@ impl Session {
     ///////////////////////////////////////////////////////////////////////////////////////////
     // private
     ///////////////////////////////////////////////////////////////////////////////////////////
+    #[inline]
+    fn is_remote(&self) -> bool {
+        matches!(
+            self.protocol_type,
+            ProtocolType::Ssh | ProtocolType::Mosh | ProtocolType::Telnet | ProtocolType::Rsh
+        )
+    }
+
     fn update_terminal_size(&mut self) {
         if self.shell_process.is_none() {
             return;
@@ -466,12 +800,48 @@ impl Session {
         }
     }
 
-    fn update_view_size(&mut self, size: Size) {
-        if size.width() <= 1 || size.height() <= 1 {
+    fn request_clipboard_access(&mut self, access: ClipboardAccess, request: ClipboardRequest) {
+        match access {
+            ClipboardAccess::Allow => self.perform_clipboard_request(request),
+            ClipboardAccess::Ask => {
+                // Only one request is asked at a time, the others are denied until answered.
+                if self.pending_clipboard_request.is_some() {
+                    warn!("The clipboard request is waiting for the user to answer, denied the new one.");
+                    return;
+                }
+                let read = matches!(request, ClipboardRequest::Read(_));
+                self.clipboard_request_id += 1;
+                let id = self.clipboard_request_id;
+                self.pending_clipboard_request = Some((id, request));
+                emit!(self, clipboard_access_request(self.session_id, id, read));
+            }
+            ClipboardAccess::Deny => {}
+        }
+    }
+
+    fn perform_clipboard_request(&mut self, request: ClipboardRequest) {
+        if self.view.is_none() {
             return;
         }
 
-        self.view_mut().set_size();
+        match request {
+            ClipboardRequest::Write(selection, text) => {
+                self.view_mut().set_clipboard_text(selection, text)
+            }
+            ClipboardRequest::Read(selection) => {
+                let mut text = self.view().clipboard_text(selection).unwrap_or_default();
+                if text.len() > self.clipboard_policy.size_limit {
+                    warn!(
+                        "The clipboard text exceeded the size limit, respond with empty text. size {}",
+                        text.len()
+                    );
+                    text.clear();
+                }
+
+                let response = encode_clipboard_response(selection, &text);
+                self.emulation().send_string(response, -1);
+            }
+        }
     }
 
     ///////////////////////////////////////////////////////////////////////////////////////////
@@ -483,18 +853,162 @@ impl Session {
 
     pub fn activate_state_set(&mut self, _state: i32) {}
 
+    /// Forward the url activated on view, see [`SessionSignal::open_url_request()`]
+    #[inline]
+    pub fn open_url(&mut self, url: String) {
+        emit!(self, open_url_request(url));
+    }
+
+    /// Asks to show the context menu when the user right clicked on a hotspot with actions.
+    pub fn on_configure_request(&mut self, position: Point) {
+        if self.view().filter_actions(position).is_empty() {
+            return;
+        }
+        self.filter_actions_position = position;
+        let global = self.view().map_to_global(&position);
+        emit!(self, filter_actions_request(self.session_id, global));
+    }
+
+    /// Triggers the @p action of the hotspot where the context menu was requested,
+    /// see [`SessionSignal::filter_actions_request()`].
+    #[inline]
+    pub fn activate_filter_action(&mut self, action: &str) {
+        let position = self.filter_actions_position;
+        self.view().activate_filter_action(position, action);
+    }
+
+    pub fn on_clipboard_write_request(&mut self, selection: u8, text: String) {
+        if text.len() > self.clipboard_policy.size_limit {
+            warn!(
+                "The clipboard text set by terminal program exceeded the size limit, ignored. size {}",
+                text.len()
+            );
+            return;
+        }
+
+        let request = ClipboardRequest::Write(selection.into(), text);
+        self.request_clipboard_access(self.clipboard_policy.write, request)
+    }
+
+    #[inline]
+    pub fn on_clipboard_read_request(&mut self, selection: u8) {
+        let request = ClipboardRequest::Read(selection.into());
+        self.request_clipboard_access(self.clipboard_policy.read, request)
+    }
+
+    /// Answer the pending clipboard request with `request_id`, see
+    /// [`SessionSignal::clipboard_access_request()`]
+    pub fn answer_clipboard_request(&mut self, request_id: u64, allow: bool) {
+        match self.pending_clipboard_request.take() {
+            Some((id, request)) if id == request_id => {
+                if allow {
+                    self.perform_clipboard_request(request)
+                }
+            }
+            pending => {
+                warn!(
+                    "The answered clipboard request {} is not pending.",
+                    request_id
+                );
+                self.pending_clipboard_request = pending;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn on_command_finished(&mut self, exit_code: i32) {
+        self.last_exit_code = Some(exit_code);
+        emit!(self, command_finished(self.session_id, exit_code));
+    }
+
+    pub fn on_working_directory_changed(&mut self, host: String, path: String) {
+        let dir = WorkingDirectory { host, path };
+        if self.working_directory.as_ref() == Some(&dir) {
+            return;
+        }
+        let WorkingDirectory { host, path } = dir.clone();
+        self.working_directory = Some(dir);
+        emit!(self, cwd_changed(host, path));
+    }
+
+    pub fn on_notification_request(&mut self, title: String, body: String) {
+        if self.notifications_muted {
+            return;
+        }
+        if !self.notification_limiter.allow(Instant::now()) {
+            warn!(
+                "The notifications of session {} exceeded the rate limit, dropped.",
+                self.session_id
+            );
+            return;
+        }
+        emit!(self, notification_received(self.session_id, title, body));
+    }
+
+    pub fn on_progress_changed(&mut self, state: u8, percent: u8) {
+        let progress = Progress {
+            state: state.into(),
+            percent,
+        };
+        if self.progress == progress {
+            return;
+        }
+        self.progress = progress;
+        emit!(self, progress_changed(self.session_id, state, percent));
+    }
+
+    /// Answer the color query from the color table of view.
+    pub fn on_color_query_request(&mut self, code: i32) {
+        if let Some(color) = DynamicColor::from_code(code) {
+            let value = self.view().dynamic_color(color);
+            let response = color.response((value.r, value.g, value.b));
+            self.emulation().send_string(response, -1);
+        }
+    }
+
+    /// The color was changed for this session only, the shared color scheme was not affected.
+    pub fn on_color_change_request(&mut self, code: i32, rgb: u32) {
+        if let Some(color) = DynamicColor::from_code(code) {
+            let [_, r, g, b] = rgb.to_be_bytes();
+            self.view_mut()
+                .set_dynamic_color(color, Color::rgb(r, g, b));
+        }
+    }
+
     #[inline]
+    pub fn on_color_reset_request(&mut self, code: i32) {
+        self.view_mut().reset_dynamic_color(code)
+    }
+
+    /// Forward the resize request of terminal program if it was allowed,
+    /// see [`SessionSignal::resize_request()`]
     pub fn on_emulation_size_change(&mut self, size: Size) {
-        self.update_view_size(size)
+        if self.accept_resize_request(size) {
+            emit!(self, resize_request(self.session_id, size));
+        }
+    }
+
+    /// Whether the resize request of terminal program was allowed and valid.
+    fn accept_resize_request(&self, size: Size) -> bool {
+        if !self.allow_resize_request {
+            debug!(
+                "The resize request of terminal program was not allowed, ignored. size {:?}",
+                size
+            );
+            return false;
+        }
+        size.width() >= 1 && size.height() >= 1
     }
 
     #[inline]
     pub fn on_view_size_change(&mut self, _width: i32, _height: i32) {
-        self.update_terminal_size()
+        self.resize_timer
+            .start(Duration::from_millis(RESIZE_DEBOUNCE));
     }
 
     #[inline]
     pub fn done(&mut self, id: SessionPropsId, _exit_status: ExitStatus) {
+        self.on_progress_changed(ProgressState::None as u8, 0);
         emit!(self, finished(id));
     }
 
@@ -510,3 +1024,26 @@ impl Session {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resize_request_permission() {
+        let mut session = Session::new(0, ProtocolType::Custom, None);
+        let size = Size::new(80, 24);
+
+        // Disallowed by default.
+        assert!(!session.allow_resize_request());
+        assert!(!session.accept_resize_request(size));
+
+        session.set_allow_resize_request(true);
+        assert!(session.accept_resize_request(size));
+        assert!(!session.accept_resize_request(Size::new(0, 24)));
+        assert!(!session.accept_resize_request(Size::new(80, 0)));
+
+        session.set_allow_resize_request(false);
+        assert!(!session.accept_resize_request(size));
+    }
+}
@@ -1,9 +1,12 @@
 #![allow(dead_code)]
-use std::cell::RefCell;
+use std::{cell::RefCell, path::PathBuf};
 
 use crate::{core::terminal_panel::TerminalPanelSignals, pty::Pty};
 
-use super::terminal_panel::TerminalPanel;
+use super::{
+    session::{CharWidth, ClipboardPolicy},
+    terminal_panel::TerminalPanel,
+};
 use cli::{constant::ProtocolType, scheme::ColorScheme, session::SessionPropsId};
 use derivative::Derivative;
 use log::warn;
@@ -36,9 +39,48 @@ pub trait TerminalEmulatorTrait: ActionExt {
     signals!(
         TerminalEmulator:
 
+        /// Emitted when the session was started, the tab of session can be shown.
+        session_started(SessionPropsId);
+
         session_finished(SessionPropsId);
 
         session_panel_finished(ObjectId);
+
+        /// Emitted when the session requests the desktop notification by `OSC 9/777/99`.
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param [`String`] The title, may be empty.
+        /// @param [`String`] The body.
+        session_notification(SessionPropsId, String, String);
+
+        /// Emitted when the progress reported by session (`OSC 9;4`) has changed.
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param [`u8`] The state, see [`ProgressState`](crate::core::session::ProgressState).
+        /// @param [`u8`] The progress in percent.
+        session_progress_changed(SessionPropsId, u8, u8);
+
+        /// Emitted when the command of session reported by shell integration (`OSC 133;D`)
+        /// has finished.
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param [`i32`] The exit code of command.
+        session_command_finished(SessionPropsId, i32);
+
+        /// Emitted when the session asks the user to allow the clipboard access (`OSC 52`),
+        /// answer it by [`TerminalEmulator::answer_clipboard_request()`].
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param [`u64`] the id of request to answer.
+        /// @param [`bool`] true if the program requests to read the clipboard, false to write.
+        session_clipboard_access_request(SessionPropsId, u64, bool);
+
+        /// Emitted when the user right clicks on a hotspot of session (e.g. a link),
+        /// trigger the chosen action by [`TerminalEmulator::activate_filter_action()`].
+        ///
+        /// @param [`SessionPropsId`]
+        /// @param [`Point`] the global position to show the context menu.
+        session_filter_actions_request(SessionPropsId, Point);
     );
 }
 impl TerminalEmulatorTrait for TerminalEmulator {}
@@ -79,9 +121,42 @@ impl TerminalEmulator {
         EMULATOR_ID.with(|e| *e.borrow())
     }
 
+    /// Start the session, local shell session will start in the working directory
+    /// of current focused session.
     #[inline]
     pub fn start_session(&mut self, id: SessionPropsId, protocol_type: ProtocolType) -> ObjectId {
-        self.start_session_inner(id, protocol_type, None)
+        let working_directory = match protocol_type {
+            ProtocolType::Cmd | ProtocolType::PowerShell => self
+                .cur_terminal_panel()
+                .and_then(|panel| panel.focused_session())
+                .and_then(|session| session.local_working_directory()),
+            _ => None,
+        };
+        self.start_session_inner(id, protocol_type, None, working_directory)
+    }
+
+    /// Duplicate the session with id `source`, the new session starts in
+    /// the current working directory of source session.
+    ///
+    /// Session with custom pty can not be duplicated.
+    pub fn duplicate_session(
+        &mut self,
+        source: SessionPropsId,
+        id: SessionPropsId,
+    ) -> Option<ObjectId> {
+        let (protocol_type, working_directory) = {
+            let session = self.find_session_panel(source)?.session(source)?;
+            (
+                session.get_protocol_type(),
+                session.local_working_directory(),
+            )
+        };
+        if protocol_type == ProtocolType::Custom {
+            warn!("[TerminalEmulator::duplicate_session] Session with custom pty can not be duplicated.");
+            return None;
+        }
+
+        Some(self.start_session_inner(id, protocol_type, None, working_directory))
     }
 
     #[inline]
@@ -90,7 +165,7 @@ impl TerminalEmulator {
         id: SessionPropsId,
         custom_pty: Box<dyn Pty>,
     ) -> ObjectId {
-        self.start_session_inner(id, ProtocolType::Custom, Some(custom_pty))
+        self.start_session_inner(id, ProtocolType::Custom, Some(custom_pty), None)
     }
 
     fn start_session_inner(
@@ -98,6 +173,7 @@ impl TerminalEmulator {
         id: SessionPropsId,
         protocol_type: ProtocolType,
         custom_pty: Option<Box<dyn Pty>>,
+        working_directory: Option<PathBuf>,
     ) -> ObjectId {
         let terminal_panel = TerminalPanel::new();
         connect!(
@@ -112,6 +188,36 @@ impl TerminalEmulator {
             self,
             handle_session_panel_finished(ObjectId)
         );
+        connect!(
+            terminal_panel,
+            session_notification(),
+            self,
+            handle_session_notification(SessionPropsId, String, String)
+        );
+        connect!(
+            terminal_panel,
+            session_progress_changed(),
+            self,
+            handle_session_progress_changed(SessionPropsId, u8, u8)
+        );
+        connect!(
+            terminal_panel,
+            session_command_finished(),
+            self,
+            handle_session_command_finished(SessionPropsId, i32)
+        );
+        connect!(
+            terminal_panel,
+            session_clipboard_access_request(),
+            self,
+            handle_session_clipboard_access_request(SessionPropsId, u64, bool)
+        );
+        connect!(
+            terminal_panel,
+            session_filter_actions_request(),
+            self,
+            handle_session_filter_actions_request(SessionPropsId, Point)
+        );
         self.session_id_map
             .entry(terminal_panel.id())
             .or_default()
@@ -126,9 +232,10 @@ impl TerminalEmulator {
         self.index_map.insert(panel_id, index);
 
         if let Some(terminal_panel) = self.cur_terminal_panel_mut() {
-            terminal_panel.create_session(id, protocol_type, custom_pty);
+            terminal_panel.create_session(id, protocol_type, custom_pty, working_directory);
             terminal_panel.set_session_focus(id);
         }
+        emit!(self, session_started(id));
 
         panel_id
     }
@@ -172,6 +279,99 @@ impl TerminalEmulator {
         }
     }
 
+    /// Sets the width options of characters of session.
+    #[inline]
+    pub fn set_char_width(&mut self, id: SessionPropsId, char_width: CharWidth) {
+        if let Some(terminal_panel) = self.find_session_panel(id) {
+            terminal_panel.set_char_width(id, char_width);
+        } else {
+            warn!(
+                "[TerminalEmulator::set_char_width] find session panel with session id {} is None.",
+                id
+            )
+        }
+    }
+
+    /// Sets the character encoding of session, e.g. `GBK` or `Big5`.
+    #[inline]
+    pub fn set_encoding(&mut self, id: SessionPropsId, encoding: &str) {
+        if let Some(terminal_panel) = self.find_session_panel(id) {
+            terminal_panel.set_encoding(id, encoding);
+        } else {
+            warn!(
+                "[TerminalEmulator::set_encoding] find session panel with session id {} is None.",
+                id
+            )
+        }
+    }
+
+    /// Sets whether the terminal program of session was allowed to resize the terminal.
+    #[inline]
+    pub fn set_allow_resize_request(&mut self, id: SessionPropsId, allow: bool) {
+        if let Some(terminal_panel) = self.find_session_panel(id) {
+            terminal_panel.set_allow_resize_request(id, allow);
+        } else {
+            warn!(
+                "[TerminalEmulator::set_allow_resize_request] find session panel with session id {} is None.",
+                id
+            )
+        }
+    }
+
+    /// Sets the policy of clipboard accessing by `OSC 52` of session.
+    #[inline]
+    pub fn set_clipboard_policy(&mut self, id: SessionPropsId, policy: ClipboardPolicy) {
+        if let Some(terminal_panel) = self.find_session_panel(id) {
+            terminal_panel.set_clipboard_policy(id, policy);
+        } else {
+            warn!(
+                "[TerminalEmulator::set_clipboard_policy] find session panel with session id {} is None.",
+                id
+            )
+        }
+    }
+
+    /// Answer the clipboard access request of session, see
+    /// [`TerminalEmulatorTrait::session_clipboard_access_request()`].
+    #[inline]
+    pub fn answer_clipboard_request(&mut self, id: SessionPropsId, request_id: u64, allow: bool) {
+        if let Some(terminal_panel) = self.find_session_panel(id) {
+            terminal_panel.answer_clipboard_request(id, request_id, allow);
+        } else {
+            warn!(
+                "[TerminalEmulator::answer_clipboard_request] find session panel with session id {} is None.",
+                id
+            )
+        }
+    }
+
+    /// Triggers the action of hotspot chosen from the context menu, see
+    /// [`TerminalEmulatorTrait::session_filter_actions_request()`].
+    #[inline]
+    pub fn activate_filter_action(&mut self, id: SessionPropsId, action: &str) {
+        if let Some(terminal_panel) = self.find_session_panel(id) {
+            terminal_panel.activate_filter_action(id, action);
+        } else {
+            warn!(
+                "[TerminalEmulator::activate_filter_action] find session panel with session id {} is None.",
+                id
+            )
+        }
+    }
+
+    /// Mute or unmute the desktop notifications of session.
+    #[inline]
+    pub fn set_notifications_muted(&mut self, id: SessionPropsId, muted: bool) {
+        if let Some(terminal_panel) = self.find_session_panel(id) {
+            terminal_panel.set_notifications_muted(id, muted);
+        } else {
+            warn!(
+                "[TerminalEmulator::set_notifications_muted] find session panel with session id {} is None.",
+                id
+            )
+        }
+    }
+
     #[inline]
     pub fn set_use_local_display(&mut self, id: SessionPropsId, use_local_display: bool) {
         if let Some(terminal_panel) = self.cur_terminal_panel_mut() {
@@ -223,6 +423,36 @@ impl TerminalEmulator {
         emit!(self, session_finished(id));
     }
 
+    #[inline]
+    fn handle_session_notification(&mut self, id: SessionPropsId, title: String, body: String) {
+        emit!(self, session_notification(id, title, body));
+    }
+
+    #[inline]
+    fn handle_session_progress_changed(&mut self, id: SessionPropsId, state: u8, percent: u8) {
+        emit!(self, session_progress_changed(id, state, percent));
+    }
+
+    #[inline]
+    fn handle_session_command_finished(&mut self, id: SessionPropsId, exit_code: i32) {
+        emit!(self, session_command_finished(id, exit_code));
+    }
+
+    #[inline]
+    fn handle_session_clipboard_access_request(
+        &mut self,
+        id: SessionPropsId,
+        request_id: u64,
+        read: bool,
+    ) {
+        emit!(self, session_clipboard_access_request(id, request_id, read));
+    }
+
+    #[inline]
+    fn handle_session_filter_actions_request(&mut self, id: SessionPropsId, position: Point) {
+        emit!(self, session_filter_actions_request(id, position));
+    }
+
     #[inline]
     fn handle_session_panel_finished(&mut self, id: ObjectId) {
         let idx = self.index_map.remove(&id).unwrap_or_else(|| {
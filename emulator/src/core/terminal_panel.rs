@@ -1,4 +1,4 @@
-use super::session::Session;
+use super::session::{CharWidth, ClipboardPolicy, Session};
 use crate::{
     config::Config,
     core::session::SessionSignal,
@@ -6,14 +6,14 @@ use crate::{
     pty::Pty,
     tools::{
         character_color::color_convert::ColorConvert, event::ToKeyPressedEvent,
-        history::HistoryTypeBuffer,
+        history::HistoryTypeBuffer, hyperlink::open_url,
     },
 };
 use cli::{constant::ProtocolType, scheme::ColorScheme, session::SessionPropsId};
 use derivative::Derivative;
 use log::warn;
 use nohash_hasher::IntMap;
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
 use tlib::{close_handler, iter_executor, ptr_mut, signals};
 use tmui::{
     prelude::*,
@@ -32,6 +32,8 @@ use tmui::{
 pub struct TerminalPanel {
     /// All the terminal sessions.
     sessions: IntMap<SessionPropsId, Box<Session>>,
+    /// The session which has the focus.
+    focused_session: Option<SessionPropsId>,
 }
 
 impl ObjectSubclass for TerminalPanel {
@@ -58,6 +60,21 @@ pub trait TerminalPanelSignals: ActionExt {
 
         /// Emit when all session closed.
         finished(ObjectId);
+
+        /// Emit when the session received the desktop notification.
+        session_notification(SessionPropsId, String, String);
+
+        /// Emit when the progress of session has changed.
+        session_progress_changed(SessionPropsId, u8, u8);
+
+        /// Emit when the command of session has finished with the exit code.
+        session_command_finished(SessionPropsId, i32);
+
+        /// Emit when the session asks the user to allow the clipboard access.
+        session_clipboard_access_request(SessionPropsId, u64, bool);
+
+        /// Emit when the session asks to show the context menu of filter actions.
+        session_filter_actions_request(SessionPropsId, Point);
     }
 }
 impl TerminalPanelSignals for TerminalPanel {}
@@ -68,13 +85,18 @@ impl TerminalPanel {
         Self::new_alloc()
     }
 
+    /// Create the session, the shell process will start in `working_directory` if it was specified.
     pub fn create_session(
         &mut self,
         id: SessionPropsId,
         protocol_type: ProtocolType,
         custom_pty: Option<Box<dyn Pty>>,
+        working_directory: Option<PathBuf>,
     ) -> &mut Box<Session> {
         let mut session = Session::new(id, protocol_type, custom_pty);
+        if let Some(dir) = working_directory {
+            session.set_initial_working_dir(&dir.to_string_lossy());
+        }
         session.set_auto_close(true);
         session.set_history_type(Rc::new(RefCell::new(HistoryTypeBuffer::new(10000))));
         session.set_key_binding("");
@@ -92,6 +114,48 @@ impl TerminalPanel {
             self,
             handle_session_finished(SessionPropsId)
         );
+        connect!(
+            session,
+            notification_received(),
+            self,
+            handle_session_notification(SessionPropsId, String, String)
+        );
+        connect!(
+            session,
+            progress_changed(),
+            self,
+            handle_session_progress_changed(SessionPropsId, u8, u8)
+        );
+        connect!(
+            session,
+            command_finished(),
+            self,
+            handle_session_command_finished(SessionPropsId, i32)
+        );
+        connect!(
+            session,
+            resize_request(),
+            self,
+            handle_session_resize_request(SessionPropsId, Size)
+        );
+        connect!(
+            session,
+            clipboard_access_request(),
+            self,
+            handle_session_clipboard_access_request(SessionPropsId, u64, bool)
+        );
+        connect!(
+            session,
+            filter_actions_request(),
+            self,
+            handle_session_filter_actions_request(SessionPropsId, Point)
+        );
+        connect!(
+            session,
+            open_url_request(),
+            self,
+            handle_session_open_url(String)
+        );
 
         self.sessions.insert(id, session);
         self.sessions.get_mut(&id).unwrap()
@@ -123,6 +187,61 @@ impl TerminalPanel {
         }
     }
 
+    /// Sets whether the terminal program of session was allowed to resize the terminal.
+    #[inline]
+    pub fn set_allow_resize_request(&mut self, id: SessionPropsId, allow: bool) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.set_allow_resize_request(allow);
+        }
+    }
+
+    #[inline]
+    pub fn set_char_width(&mut self, id: SessionPropsId, char_width: CharWidth) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.set_char_width(char_width);
+        }
+    }
+
+    #[inline]
+    pub fn set_encoding(&mut self, id: SessionPropsId, encoding: &str) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            if !session.set_encoding(encoding) {
+                warn!(
+                    "[TerminalPanel::set_encoding] Unknown encoding `{}` of session {}.",
+                    encoding, id
+                )
+            }
+        }
+    }
+
+    #[inline]
+    pub fn set_clipboard_policy(&mut self, id: SessionPropsId, policy: ClipboardPolicy) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.set_clipboard_policy(policy);
+        }
+    }
+
+    #[inline]
+    pub fn answer_clipboard_request(&mut self, id: SessionPropsId, request_id: u64, allow: bool) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.answer_clipboard_request(request_id, allow);
+        }
+    }
+
+    #[inline]
+    pub fn activate_filter_action(&mut self, id: SessionPropsId, action: &str) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.activate_filter_action(action);
+        }
+    }
+
+    #[inline]
+    pub fn set_notifications_muted(&mut self, id: SessionPropsId, muted: bool) {
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session.set_notifications_muted(muted);
+        }
+    }
+
     #[inline]
     pub fn set_use_local_display(&mut self, id: SessionPropsId, use_local_display: bool) {
         if let Some(session) = self.sessions.get_mut(&id) {
@@ -150,9 +269,21 @@ impl TerminalPanel {
     pub fn set_session_focus(&mut self, session_id: SessionPropsId) {
         if let Some(session) = self.sessions.get_mut(&session_id) {
             session.view_mut().set_focus(true);
+            self.focused_session = Some(session_id);
         }
     }
 
+    #[inline]
+    pub fn session(&self, session_id: SessionPropsId) -> Option<&Session> {
+        self.sessions.get(&session_id).map(|s| s.as_ref())
+    }
+
+    /// Returns the session which has the focus.
+    #[inline]
+    pub fn focused_session(&self) -> Option<&Session> {
+        self.focused_session.and_then(|id| self.session(id))
+    }
+
     #[inline]
     pub fn close_session(&mut self, session_id: SessionPropsId) {
         if let Some(session) = self.sessions.get_mut(&session_id) {
@@ -169,6 +300,9 @@ impl TerminalPanel {
     fn handle_session_finished(&mut self, id: SessionPropsId) {
         let panel_id = self.id();
         if let Some(session) = self.sessions.remove(&id) {
+            if self.focused_session == Some(id) {
+                self.focused_session = None;
+            }
             self.remove_children(session.scrolled_view().id());
             emit!(self, session_finished(panel_id, id));
         }
@@ -177,6 +311,54 @@ impl TerminalPanel {
             emit!(self, finished(panel_id));
         }
     }
+
+    #[inline]
+    fn handle_session_notification(&mut self, id: SessionPropsId, title: String, body: String) {
+        emit!(self, session_notification(id, title, body));
+    }
+
+    #[inline]
+    fn handle_session_progress_changed(&mut self, id: SessionPropsId, state: u8, percent: u8) {
+        emit!(self, session_progress_changed(id, state, percent));
+    }
+
+    #[inline]
+    fn handle_session_command_finished(&mut self, id: SessionPropsId, exit_code: i32) {
+        emit!(self, session_command_finished(id, exit_code));
+    }
+
+    #[inline]
+    fn handle_session_clipboard_access_request(
+        &mut self,
+        id: SessionPropsId,
+        request_id: u64,
+        read: bool,
+    ) {
+        emit!(self, session_clipboard_access_request(id, request_id, read));
+    }
+
+    #[inline]
+    fn handle_session_filter_actions_request(&mut self, id: SessionPropsId, position: Point) {
+        emit!(self, session_filter_actions_request(id, position));
+    }
+
+    #[inline]
+    fn handle_session_open_url(&mut self, url: String) {
+        open_url(&url);
+    }
+
+    /// Resize the view to the size in characters requested by the terminal program,
+    /// ignored when the panel was split, the views have to share the space of panel.
+    fn handle_session_resize_request(&mut self, id: SessionPropsId, size: Size) {
+        if self.sessions.len() > 1 {
+            return;
+        }
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session
+                .view_mut()
+                .set_terminal_size(size.width(), size.height());
+        }
+    }
 }
 
 impl IterExecutor for TerminalPanel {
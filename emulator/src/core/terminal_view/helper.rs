@@ -2,16 +2,21 @@ use super::TerminalView;
 use crate::{
     core::{
         terminal_view::{
-            predefine::{REGULAR_EXPRESSION, TEXT_BLINK_DELAY},
-            TerminalViewSignals,
+            predefine::{PROMPT_MARKER_WIDTH, REGULAR_EXPRESSION, TEXT_BLINK_DELAY},
+            ScrollBarState, TerminalViewSignals,
         },
         uwchar_t,
     },
     tools::{
-        character::{Character, DEFAULT_RENDITION, LINE_DOUBLE_HEIGHT, LINE_WRAPPED, RE_BLINK},
+        character::{
+            Character, DEFAULT_RENDITION, LINE_DOUBLE_HEIGHT, LINE_DOUBLE_WIDTH, LINE_PROMPT_START,
+            LINE_WRAPPED, RE_BLINK,
+        },
         character_color::CharacterColor,
-        event::KeyPressedEvent,
+        event::{mouse_modifiers, KeyPressedEvent, MOUSE_MOVE},
         filter::{FilterChainImpl, HotSpotImpl},
+        graphics::ImagePlacement,
+        hyperlink::NO_HYPERLINK,
         system_ffi::string_width,
     },
 };
@@ -287,6 +292,44 @@ impl TerminalView {
         }
     }
 
+    /// Report the mouse event to the terminal program both in characters and pixels,
+    /// the emulation decides which one to use by the mouse mode.
+    ///
+    /// The motion was reported only when the position has changed.
+    pub(super) fn emit_mouse_event(
+        &mut self,
+        button: i32,
+        modifier: KeyboardModifier,
+        widget_point: FPoint,
+        event_type: u8,
+    ) {
+        let (char_line, char_column) = self.get_character_position(widget_point);
+        let scroll_bar = self.scroll_bar().unwrap();
+        let cell = Point::new(
+            char_column + 1,
+            char_line + 1 + scroll_bar.value() - scroll_bar.maximum(),
+        );
+
+        let content_rect = self.contents_rect_f(Some(Coordinate::Widget));
+        let pixel = Point::new(
+            (widget_point.x() - content_rect.left() - self.left_margin) as i32 + 1,
+            (widget_point.y() - content_rect.top() - self.top_margin) as i32 + 1,
+        );
+
+        let button = button | mouse_modifiers(modifier);
+        if event_type != MOUSE_MOVE || cell != self.last_mouse_cell {
+            emit!(self, mouse_signal(button, cell.x(), cell.y(), event_type));
+        }
+        if event_type != MOUSE_MOVE || pixel != self.last_mouse_pixel {
+            emit!(
+                self,
+                mouse_pixel_signal(button, pixel.x(), pixel.y(), event_type)
+            );
+        }
+        self.last_mouse_cell = cell;
+        self.last_mouse_pixel = pixel;
+    }
+
     /// maps a point on the widget to the position ( ie. line and column )
     /// of the character at that point.
     pub(super) fn get_character_position(&self, widget_point: FPoint) -> (i32, i32) {
@@ -301,12 +344,16 @@ impl TerminalView {
             line = self.used_lines - 1;
         }
 
-        let x = widget_point.x() + self.font_width / 2. - content_rect.left() - self.left_margin;
+        // The characters on double width line occupy two columns each.
+        let scale = self.column_scale(line);
+        let used_columns = self.used_columns / scale;
+        let x = (widget_point.x() - content_rect.left() - self.left_margin) / scale as f32
+            + self.font_width / 2.;
         if self.fixed_font {
             column = (x / self.font_width) as i32;
         } else {
             column = 0;
-            while column + 1 < self.used_columns && x > self.text_width(0, column + 1, line) {
+            while column + 1 < used_columns && x > self.text_width(0, column + 1, line) {
                 column += 1;
             }
         }
@@ -320,12 +367,22 @@ impl TerminalView {
         //
         // this is required so that the user can select characters in the right-most
         // column (or left-most for right-to-left input)
-        if column > self.used_columns {
-            column = self.used_columns;
+        if column > used_columns {
+            column = used_columns;
         }
         (line, column)
     }
 
+    /// The number of columns on the view occupied by one character of @p line,
+    /// 2 for the double width lines.
+    #[inline]
+    pub(super) fn column_scale(&self, line: i32) -> i32 {
+        match self.line_properties.get(line as usize) {
+            Some(property) if property & LINE_DOUBLE_WIDTH != 0 => 2,
+            _ => 1,
+        }
+    }
+
     pub(super) fn blink_text_event(&mut self) {
         if !self.allow_blinking_text {
             return;
@@ -703,9 +760,11 @@ impl TerminalView {
             return FRect::default();
         }
 
+        let cursor = self.cursor_position();
+        let scale = self.column_scale(cursor.y());
         FRect::new(
-            self.left_margin + self.font_width * self.cursor_position().x() as f32,
-            self.top_margin + self.font_height * self.cursor_position().y() as f32,
+            self.left_margin + self.font_width * (cursor.x() * scale) as f32,
+            self.top_margin + self.font_height * cursor.y() as f32,
             self.font_width * preedit_length as f32,
             self.font_height,
         )
@@ -943,7 +1002,14 @@ impl TerminalView {
     /// redraws the cursor.
     #[inline]
     pub(super) fn update_cursor(&mut self) {
-        let rect = FRect::from_point_size(self.cursor_position().into(), Size::new(1, 1).into());
+        let cursor = self.cursor_position();
+        let scale = self.column_scale(cursor.y());
+        let rect = FRect::new(
+            (cursor.x() * scale) as f32,
+            cursor.y() as f32,
+            scale as f32,
+            1.,
+        );
         let cursor_rect = self.image_to_widget(&rect);
         self.update_rect(CoordRect::new(cursor_rect, Coordinate::Widget));
     }
@@ -956,6 +1022,33 @@ impl TerminalView {
         rect.set_height(self.lines as f32 * (self.font_height + self.line_spacing as f32));
         rect
     }
+
+    /// The area to paint the prompt markers, alongside the scroll bar.
+    pub(super) fn prompt_markers_rect(&self) -> FRect {
+        let rect = self.contents_rect_f(Some(Coordinate::Widget));
+        let x = match self.scroll_bar_location {
+            ScrollBarState::ScrollBarLeft => rect.left(),
+            _ => rect.right() - PROMPT_MARKER_WIDTH,
+        };
+        FRect::new(x, rect.top(), PROMPT_MARKER_WIDTH, rect.height())
+    }
+
+    /// The area covered by the image at its natural size, offset within the anchor cell.
+    pub(super) fn image_placement_rect(&self, placement: &ImagePlacement) -> FRect {
+        let tl = self.contents_rect(Some(Coordinate::Widget)).top_left();
+        FRect::new(
+            self.left_margin
+                + tl.x() as f32
+                + self.font_width * placement.column as f32
+                + placement.x_offset as f32,
+            self.top_margin
+                + tl.y() as f32
+                + self.font_height * placement.line as f32
+                + placement.y_offset as f32,
+            placement.image.width() as f32,
+            placement.image.height() as f32,
+        )
+    }
 }
 
 ////////////////////////////////////// Slots. //////////////////////////////////////
@@ -987,6 +1080,29 @@ impl TerminalView {
 
         self.set_scroll(screen_window.current_line(), screen_window.line_count());
 
+        // The prompt markers should be repainted when the prompts or the total lines changed.
+        let line_count = screen_window.line_count();
+        // The marked lines were collected again only if the marks of screen were changed.
+        let marks_generation = screen_window.screen().marks_generation();
+        let mut prompt_markers_changed = false;
+        if marks_generation != self.prompt_markers_generation {
+            let prompt_markers = screen_window.screen().marked_lines(LINE_PROMPT_START);
+            prompt_markers_changed = prompt_markers != self.prompt_markers;
+            self.prompt_markers = prompt_markers;
+            self.prompt_markers_generation = marks_generation;
+        }
+        prompt_markers_changed |=
+            !self.prompt_markers.is_empty() && line_count != self.prompt_markers_line_count;
+        self.prompt_markers_line_count = line_count;
+
+        // The images should be repainted when they were placed, removed or scrolled.
+        let image_placements = screen_window.image_placements();
+        let image_placements_changed = image_placements != self.image_placements;
+        let old_image_placements = std::mem::replace(&mut self.image_placements, image_placements);
+        if image_placements_changed {
+            self.prune_image_cache();
+        }
+
         // Skip the mutable reference borrow check.
         let image = ptr_mut!(self.image.as_mut().unwrap() as *mut Vec<Character>);
         let new_img = screen_window.get_image();
@@ -1109,8 +1225,7 @@ impl TerminalView {
             }
 
             // both the top and bottom halves of double height _lines must always be
-            // redrawn although both top and bottom halves contain the same characters,
-            // only the top one is actually drawn.
+            // redrawn, each of them draws a part of the double height characters.
             if self.line_properties.len() > y as usize {
                 update_line =
                     update_line || (self.line_properties[y as usize] & LINE_DOUBLE_HEIGHT != 0);
@@ -1160,6 +1275,19 @@ impl TerminalView {
 
         dirty_region.or(&self.input_method_data.previous_preedit_rect);
 
+        if prompt_markers_changed {
+            dirty_region.or(&self.prompt_markers_rect());
+        }
+
+        if image_placements_changed {
+            for placement in old_image_placements
+                .iter()
+                .chain(self.image_placements.iter())
+            {
+                dirty_region.or(&self.image_placement_rect(placement));
+            }
+        }
+
         // update the parts of the view which have changed
         if dirty_region.width() > 0. && dirty_region.height() > 0. {
             dirty_region.set_width(dirty_region.width().ceil());
@@ -1193,7 +1321,26 @@ impl TerminalView {
             return;
         }
 
-        self.line_properties = self.screen_window().unwrap().get_line_properties();
+        let line_properties = self.screen_window().unwrap().get_line_properties();
+        let old_line_properties = std::mem::replace(&mut self.line_properties, line_properties);
+
+        // The lines whose size changed should be repainted, even the characters were unchanged.
+        let tl = self.contents_rect(Some(Coordinate::Widget)).top_left();
+        for (y, (old, new)) in old_line_properties
+            .iter()
+            .zip(self.line_properties.iter())
+            .enumerate()
+        {
+            if (old ^ new) & (LINE_DOUBLE_WIDTH | LINE_DOUBLE_HEIGHT) != 0 {
+                let rect = FRect::new(
+                    self.left_margin + tl.x() as f32,
+                    self.top_margin + tl.y() as f32 + self.font_height * y as f32,
+                    self.font_width * self.used_columns as f32,
+                    self.font_height,
+                );
+                self.update_rect(CoordRect::new(rect, Coordinate::Widget));
+            }
+        }
     }
 
     pub(super) fn scroll_bar_position_changed(&mut self, val: i32) {
@@ -1228,6 +1375,29 @@ impl TerminalView {
         self.colors_inverted = !self.colors_inverted;
         self.update();
     }
+
+    /// Returns the hotspots represent the same link with `spot`, include itself.
+    ///
+    /// Hotspots of the same hyperlink(declared by `OSC 8`) may be split into
+    /// several parts, they should be highlighted together.
+    pub(super) fn linked_hotspots(
+        &self,
+        spot: &Rc<Box<dyn HotSpotImpl>>,
+    ) -> Vec<Rc<Box<dyn HotSpotImpl>>> {
+        if spot.hyperlink() == NO_HYPERLINK {
+            return vec![spot.clone()];
+        }
+
+        self.filter_chain
+            .hotspots()
+            .into_iter()
+            .filter(|s| s.hyperlink() == spot.hyperlink())
+            .collect()
+    }
+
+    pub(super) fn hotspot_activated(&mut self, url: String, _from_context_menu: bool) {
+        emit!(self, open_url_request(url));
+    }
 }
 
 #[derive(Default)]
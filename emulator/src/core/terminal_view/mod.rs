@@ -15,13 +15,19 @@ use self::{
 use super::screen_window::{ScreenWindow, ScreenWindowSignals};
 use crate::tools::{
     character::{Character, ExtendedCharTable, LineProperty},
-    character_color::{ColorEntry, DEFAULT_BACK_COLOR, DEFAULT_FORE_COLOR, TABLE_COLORS},
+    character_color::{
+        CharacterColor, ColorEntry, BASE_COLORS, DEFAULT_BACK_COLOR, DEFAULT_FORE_COLOR,
+        TABLE_COLORS,
+    },
+    clipboard::ClipboardSelection,
+    dynamic_color::DynamicColor,
     event::KeyPressedEvent,
     filter::{FilterChainImpl, TerminalImageFilterChain},
+    graphics::{ImagePlacement, TerminalImage},
 };
 use cli::session::SessionPropsId;
 use derivative::Derivative;
-use std::{ptr::NonNull, sync::atomic::Ordering, time::Duration};
+use std::{ptr::NonNull, rc::Rc, sync::atomic::Ordering, time::Duration};
 use tlib::global_watch;
 use tmui::{
     application,
@@ -32,6 +38,7 @@ use tmui::{
     opti::tracker::Tracker,
     prelude::*,
     scroll_bar::ScrollBar,
+    skia_safe::Image,
     system::System,
     tlib::{
         connect, emit,
@@ -96,7 +103,11 @@ pub struct TerminalView {
 
     line_properties: Vec<LineProperty>,
 
-    color_table: [ColorEntry; TABLE_COLORS],
+    /// The color table, followed by the colors `16..=255` of 256 colors palette.
+    color_table: Vec<ColorEntry>,
+    /// The color table set by color scheme, colors changed by terminal program
+    /// were restored from it.
+    scheme_color_table: [ColorEntry; TABLE_COLORS],
     random_seed: u32,
 
     resizing: bool,
@@ -106,6 +117,9 @@ pub struct TerminalView {
     bidi_enable: bool,
     #[derivative(Default(value = "true"))]
     mouse_marks: bool,
+    /// The last mouse position reported to the terminal program, in characters and pixels.
+    last_mouse_cell: Point,
+    last_mouse_pixel: Point,
     bracketed_paste_mode: bool,
     disable_bracketed_paste_mode: bool,
 
@@ -125,6 +139,16 @@ pub struct TerminalView {
 
     scroll_bar: Option<NonNull<ScrollBar>>,
     scroll_bar_location: ScrollBarState,
+    // The lines of prompts marked by `OSC 133;A`, painted as markers along the scroll bar.
+    prompt_markers: Vec<i32>,
+    // The total lines when the prompt markers were updated.
+    prompt_markers_line_count: i32,
+    // The generation of semantic marks of screen when the prompt markers were updated.
+    prompt_markers_generation: u64,
+    // The images visible in the view, the lines were relative to the top of the view.
+    image_placements: Vec<ImagePlacement>,
+    // The images converted for painting once, dropped when they were no longer placed.
+    image_cache: Vec<(Rc<TerminalImage>, Image)>,
     #[derivative(Default(value = "\":@-./_~\".to_string()"))]
     word_characters: String,
     bell_mode: BellMode,
@@ -172,6 +196,7 @@ pub struct TerminalView {
 
     cursor_shape: KeyboardCursorShape,
     cursor_color: Color,
+    scheme_cursor_color: Color,
 
     motion_after_pasting: MotionAfterPasting,
     confirm_multiline_paste: bool,
@@ -222,6 +247,11 @@ impl WidgetImpl for TerminalView {
         self.handle_key_pressed(event)
     }
 
+    #[inline]
+    fn on_key_released(&mut self, event: &KeyEvent) {
+        self.handle_key_released(event)
+    }
+
     #[inline]
     fn on_mouse_wheel(&mut self, event: &MouseEvent) {
         self.handle_mouse_wheel(event)
@@ -280,6 +310,8 @@ impl WidgetImpl for TerminalView {
         if !self.cursor_blinking {
             self.blink_cursor_event()
         }
+
+        emit!(self, term_lost_focus());
     }
 
     fn on_visibility_changed(&mut self, visible: bool) {
@@ -304,15 +336,27 @@ pub trait TerminalViewSignals: ActionExt {
         /// @param [`bool`] from paste.
         key_pressed_signal(KeyPressedEvent, bool);
 
+        /// Emitted when the user releases a key whilst the terminal widget has focus.
+        ///
+        /// @param [`KeyEvent`] key event.
+        key_released_signal(KeyPressedEvent);
+
         /// A mouse event occurred.
-        /// @param [`i32`] button: The mouse button (0 for left button, 1 for middle button, 2
-        /// for right button, 3 for release) <br>
+        /// @param [`i32`] button: The xterm button code (0 for left button, 1 for middle button, 2
+        /// for right button, 3 for no button, 64/65 for wheel up/down), with the modifier bits <br>
         /// @param [`i32`] column: The character column where the event occurred <br>
         /// @param [`i32`] row: The character row where the event occurred <br>
-        /// @param [`u8`] type: The type of event.  0 for a mouse press / release or 1 for
-        /// mouse motion
+        /// @param [`u8`] type: The type of event.  0 for a mouse press, 1 for mouse motion
+        /// or 2 for mouse release
         mouse_signal(i32, i32, i32, u8);
 
+        /// Same as [`mouse_signal()`], but the position was in pixels relative to the content area.
+        /// @param [`i32`] button <br>
+        /// @param [`i32`] x <br>
+        /// @param [`i32`] y <br>
+        /// @param [`u8`] type
+        mouse_pixel_signal(i32, i32, i32, u8);
+
         changed_font_metrics_signal(f32, f32);
         changed_content_size_signal(i32, i32);
 
@@ -347,6 +391,11 @@ pub trait TerminalViewSignals: ActionExt {
 
         control_insert_detected();
         shift_insert_detected();
+
+        /// Emitted when the user activates a link hotspot, by ctrl-click or the context menu.
+        ///
+        /// @param [`String`] the url of link.
+        open_url_request(String);
     );
 }
 impl TerminalViewSignals for TerminalView {}
@@ -390,13 +439,83 @@ impl TerminalView {
     /// Sets the terminal color palette used by the view.
     #[inline]
     pub fn set_color_table(&mut self, table: &[ColorEntry]) {
-        self.color_table[..TABLE_COLORS].copy_from_slice(&table[..TABLE_COLORS]);
+        let table = &table[..TABLE_COLORS];
+        self.color_table.clear();
+        self.color_table.extend_from_slice(table);
+        self.color_table
+            .extend((16..=u8::MAX).map(|index| {
+                ColorEntry::new(CharacterColor::color_256(index, table), false, None)
+            }));
+        self.scheme_color_table[..TABLE_COLORS].copy_from_slice(&table[..TABLE_COLORS]);
 
         self.set_background_color(self.color_table[DEFAULT_BACK_COLOR as usize].color);
 
         self.update();
     }
 
+    /// Returns the color which can be queried by terminal program.
+    pub fn dynamic_color(&self, color: DynamicColor) -> Color {
+        match color {
+            DynamicColor::Palette(index) => CharacterColor::color_256(index, &self.color_table),
+            DynamicColor::Foreground => self.color_table[DEFAULT_FORE_COLOR as usize].color,
+            DynamicColor::Background => self.color_table[DEFAULT_BACK_COLOR as usize].color,
+            DynamicColor::Cursor => {
+                if self.cursor_color.valid {
+                    self.cursor_color
+                } else {
+                    self.color_table[DEFAULT_FORE_COLOR as usize].color
+                }
+            }
+        }
+    }
+    /// Changes the color by terminal program, only affects this view, the color scheme
+    /// was not changed.
+    ///
+    /// All the 256 colors of palette can be changed.
+    pub fn set_dynamic_color(&mut self, color: DynamicColor, value: Color) {
+        match color {
+            DynamicColor::Palette(index) => {
+                self.color_table[palette_table_index(index)].color = value;
+                self.update();
+            }
+            DynamicColor::Foreground => self.set_foreground_color(value),
+            DynamicColor::Background => self.set_background_color(value),
+            DynamicColor::Cursor => {
+                self.cursor_color = value;
+                self.update();
+            }
+        }
+    }
+    /// Restores the color changed by terminal program to the one of color scheme.
+    ///
+    /// @param [`code`] The code of [`DynamicColor`], or [`DynamicColor::ALL_PALETTE`].
+    pub fn reset_dynamic_color(&mut self, code: i32) {
+        if code == DynamicColor::ALL_PALETTE {
+            for index in 0..=u8::MAX {
+                self.reset_dynamic_color(DynamicColor::Palette(index).code());
+            }
+            return;
+        }
+
+        match DynamicColor::from_code(code) {
+            Some(DynamicColor::Palette(index)) => {
+                // The colors after system colors were calculated, not in the scheme table.
+                self.color_table[palette_table_index(index)].color =
+                    CharacterColor::color_256(index, &self.scheme_color_table);
+                self.update();
+            }
+            Some(DynamicColor::Foreground) => self
+                .set_foreground_color(self.scheme_color_table[DEFAULT_FORE_COLOR as usize].color),
+            Some(DynamicColor::Background) => self
+                .set_background_color(self.scheme_color_table[DEFAULT_BACK_COLOR as usize].color),
+            Some(DynamicColor::Cursor) => {
+                self.cursor_color = self.scheme_cursor_color;
+                self.update();
+            }
+            None => {}
+        }
+    }
+
     /// Sets the seed used to generate random colors for the view
     /// (in color schemes that support them).
     #[inline]
@@ -454,8 +573,26 @@ impl TerminalView {
 
     /// Returns a list of menu actions created by the filters for the content
     /// at the given @p position.
-    pub fn filter_actions(&self, _position: Point) -> Vec<Action> {
-        todo!()
+    pub fn filter_actions(&self, position: Point) -> Vec<Action> {
+        let (char_line, char_column) =
+            self.get_character_position(FPoint::new(position.x() as f32, position.y() as f32));
+
+        self.filter_chain
+            .hotspot_at(char_line, char_column)
+            .map(|spot| spot.actions())
+            .unwrap_or_default()
+    }
+
+    /// Triggers the @p action of the hotspot at the given @p position, the action was
+    /// one of [`FilterObject::ACTION_OPEN`](crate::tools::filter::FilterObject::ACTION_OPEN)
+    /// and [`FilterObject::ACTION_COPY`](crate::tools::filter::FilterObject::ACTION_COPY).
+    pub fn activate_filter_action(&self, position: Point, action: &str) {
+        let (char_line, char_column) =
+            self.get_character_position(FPoint::new(position.x() as f32, position.y() as f32));
+
+        if let Some(spot) = self.filter_chain.hotspot_at(char_line, char_column) {
+            spot.activate(action);
+        }
     }
 
     /// Returns true if the cursor is set to blink or false otherwise.
@@ -570,6 +707,7 @@ impl TerminalView {
         } else {
             self.cursor_color = color
         }
+        self.scheme_cursor_color = self.cursor_color;
     }
     /// Returns the color of the keyboard cursor, or an invalid color if the
     /// keyboard cursor color is set to change according to the foreground color of
@@ -624,6 +762,17 @@ impl TerminalView {
             self.resize(Some(self.size.width()), Some(self.size.height()));
         }
     }
+    /// Resize the view to display `columns` x `lines` characters, the lines and columns
+    /// were updated when the view was resized.
+    pub fn set_terminal_size(&mut self, columns: i32, lines: i32) {
+        let horizontal_margin = 2 * self.left_margin as i32;
+        let vertical_margin = 2 * self.top_margin as i32;
+
+        self.resize(
+            Some(horizontal_margin + (columns.max(1) * self.font_width.ceil() as i32)),
+            Some(vertical_margin + (lines.max(1) * self.font_height.ceil() as i32)),
+        );
+    }
     pub fn set_fixed_size(&mut self, cols: i32, lins: i32) {
         self.is_fixed_size = true;
 
@@ -676,7 +825,19 @@ impl TerminalView {
     }
 
     pub fn copy_selection(&mut self, t: String) {
-        System::clipboard().set_text(t, ClipboardLevel::Os);
+        self.set_clipboard_text(ClipboardSelection::Clipboard, t)
+    }
+
+    /// Sets the text of the clipboard specified by `selection`.
+    #[inline]
+    pub fn set_clipboard_text(&mut self, selection: ClipboardSelection, text: String) {
+        System::clipboard().set_text(text, selection.level());
+    }
+
+    /// Returns the text of the clipboard specified by `selection`.
+    #[inline]
+    pub fn clipboard_text(&self, selection: ClipboardSelection) -> Option<String> {
+        System::clipboard().text(selection.level())
     }
 
     /// Specify whether line chars should be drawn by ourselves or left to
@@ -970,3 +1131,13 @@ impl GlobalWatchImpl for TerminalView {
         }
     }
 }
+
+/// Get the index in color table of the colors in 256 colors palette.
+#[inline]
+fn palette_table_index(index: u8) -> usize {
+    match index {
+        0..=7 => index as usize + 2,
+        8..=15 => index as usize - 8 + 2 + BASE_COLORS,
+        _ => index as usize - 16 + TABLE_COLORS,
+    }
+}
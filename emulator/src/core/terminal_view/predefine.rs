@@ -13,6 +13,9 @@ pub(super) static TEXT_BLINK_DELAY: AtomicU64 = AtomicU64::new(500);
 
 pub(super) const LTR_OVERRIDE_CHAR: wchar_t = 0x202D;
 
+pub(super) const PROMPT_MARKER_WIDTH: f32 = 4.;
+pub(super) const PROMPT_MARKER_HEIGHT: f32 = 2.;
+
 pub(super) const LINE_CHARS: [u32; 128] = [
     0x00007c00, 0x000fffe0, 0x00421084, 0x00e739ce, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
     0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00427000, 0x004e7380, 0x00e77800, 0x00ef7bc0,
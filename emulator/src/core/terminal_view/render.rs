@@ -1,21 +1,28 @@
 use super::{
-    helper::LineEncode::*, predefine::LINE_CHARS, FilterChainImpl, KeyboardCursorShape,
-    TerminalView,
+    helper::LineEncode::*,
+    predefine::{LINE_CHARS, PROMPT_MARKER_HEIGHT},
+    FilterChainImpl, KeyboardCursorShape, TerminalView,
 };
 use crate::{
     core::uwchar_t,
     tools::{
         character::{
-            Character, ExtendedCharTable, LINE_DOUBLE_HEIGHT, LINE_DOUBLE_WIDTH, RE_BLINK, RE_BOLD,
-            RE_CONCEAL, RE_CURSOR, RE_EXTEND_CHAR, RE_ITALIC, RE_OVERLINE, RE_STRIKEOUT,
-            RE_UNDERLINE,
+            Character, ExtendedCharTable, UnderlineStyle, LINE_DEFAULT, LINE_DOUBLE_HEIGHT,
+            LINE_DOUBLE_HEIGHT_BOTTOM, LINE_DOUBLE_WIDTH, RE_BLINK, RE_BOLD, RE_CONCEAL, RE_CURSOR,
+            RE_EXTEND_CHAR, RE_ITALIC, RE_OVERLINE, RE_STRIKEOUT,
         },
         filter::{HotSpotImpl, HotSpotType},
+        graphics::TerminalImage,
     },
 };
 use libc::wchar_t;
 use std::rc::Rc;
-use tmui::{cursor::Cursor, graphics::painter::Painter, prelude::*, skia_safe::Matrix};
+use tmui::{
+    cursor::Cursor,
+    graphics::painter::Painter,
+    prelude::*,
+    skia_safe::{images, AlphaType, ClipOp, ColorType, Data, Image, ImageInfo, Matrix},
+};
 use wchar::wch;
 use widestring::WideString;
 
@@ -42,6 +49,21 @@ impl TerminalView {
 
         let mut y = luy;
         while y <= rly {
+            let line_property = self
+                .line_properties
+                .get(y as usize)
+                .copied()
+                .unwrap_or(LINE_DEFAULT);
+            let line_double_width = line_property & LINE_DOUBLE_WIDTH != 0;
+            let line_double_height = line_property & LINE_DOUBLE_HEIGHT != 0;
+
+            // The characters on double width line occupy two columns each.
+            let (lux, rlx) = if line_double_width {
+                (lux / 2, (rlx / 2).min((self.used_columns / 2 - 1).max(0)))
+            } else {
+                (lux, rlx)
+            };
+
             let mut c = self.image()[self.loc(lux, y) as usize]
                 .character_union
                 .data();
@@ -61,12 +83,16 @@ impl TerminalView {
                 if self.image()[self.loc(x, y) as usize].rendition & RE_EXTEND_CHAR != 0 {
                     // sequence of characters
                     let mut extended_char_length = 0 as wchar_t;
+                    // The sequence evicted from the table was drawn as replacement character.
                     let chars = ExtendedCharTable::instance()
                         .lookup_extended_char(
                             self.image()[self.loc(x, y) as usize].character_union.data(),
                             &mut extended_char_length,
                         )
-                        .unwrap();
+                        .unwrap_or_else(|| {
+                            extended_char_length = 1;
+                            vec![0xFFFD as wchar_t]
+                        });
 
                     for c in chars.iter().take(extended_char_length as usize) {
                         assert!(p < buffer_size);
@@ -92,12 +118,16 @@ impl TerminalView {
                 let current_foreground = img.foreground_color;
                 let current_background = img.background_color;
                 let current_rendition = img.rendition;
+                let current_underline_color = img.underline_color;
 
+                // The grapheme cluster in extended char was drawn alone.
                 let mut img = &self.image()[self.loc(x + len, y) as usize];
                 while x + len <= rlx
+                    && current_rendition & RE_EXTEND_CHAR == 0
                     && img.foreground_color == current_foreground
                     && img.background_color == current_background
                     && img.rendition == current_rendition
+                    && img.underline_color == current_underline_color
                     && (self.image()[self.image_size.min(self.loc(x + len, y) + 1) as usize]
                         .character_union
                         .data()
@@ -137,18 +167,36 @@ impl TerminalView {
 
                 // Create a text scaling matrix for double width and double height lines.
                 let mut text_scale = Matrix::new_identity();
-
-                if y < self.line_properties.len() as i32 {
-                    if self.line_properties[y as usize] & LINE_DOUBLE_WIDTH != 0 {
-                        text_scale.set_scale_x(2.);
-                    }
-                    if self.line_properties[y as usize] & LINE_DOUBLE_HEIGHT != 0 {
-                        text_scale.set_scale_y(2.);
-                    }
+                if line_double_width {
+                    text_scale.set_scale_x(2.);
+                }
+                if line_double_height {
+                    text_scale.set_scale_y(2.);
                 }
 
                 // calculate the area in which the text will be drawn
                 let mut text_area = self.calculate_text_area(tlx, tly, x, y, len);
+                if line_double_width {
+                    text_area.offset(text_area.left() - self.left_margin - tlx as f32, 0.);
+                }
+
+                // Both halves of double height line contain the same characters drawn in
+                // double height, the top half shows the upper part of them, and the bottom
+                // half shows the lower part by drawing from the line above.
+                painter.save();
+                if line_double_height {
+                    let clip = FRect::new(
+                        text_area.left(),
+                        text_area.top(),
+                        text_area.width() * text_scale.scale_x(),
+                        self.font_height,
+                    );
+                    painter.clip_rect(clip, ClipOp::Intersect);
+
+                    if line_property & LINE_DOUBLE_HEIGHT_BOTTOM != 0 {
+                        text_area.offset(0., -self.font_height);
+                    }
+                }
 
                 // move the calculated area to take account of scaling applied to the
                 // painter. the position of the area from the origin (0,0) is scaled by
@@ -177,17 +225,7 @@ impl TerminalView {
 
                 // reset back to single-width, single-height lines.
                 painter.set_transform(text_scale.invert().unwrap(), true);
-
-                if y < self.line_properties.len() as i32 - 1 {
-                    // double-height lines are represented by two adjacent lines
-                    // containing the same characters
-                    // both lines will have the LINE_DOUBLEHEIGHT attribute.
-                    // If the current line has the LINE_DOUBLEHEIGHT attribute,
-                    // we can therefore skip the next line
-                    if self.line_properties[y as usize] & LINE_DOUBLE_HEIGHT != 0 {
-                        y += 1;
-                    }
-                }
+                painter.restore();
 
                 x += len - 1;
                 x += 1;
@@ -327,7 +365,7 @@ impl TerminalView {
 
         // Setup bold, underline, intalic, strkeout and overline
         let use_bold = style.rendition & RE_BOLD != 0 && self.bold_intense;
-        let use_underline = style.rendition & RE_UNDERLINE != 0;
+        let underline_style = style.underline_style();
         let use_italic = style.rendition & RE_ITALIC != 0;
         let use_strike_out = style.rendition & RE_STRIKEOUT != 0;
         let use_overline = style.rendition & RE_OVERLINE != 0;
@@ -382,11 +420,6 @@ impl TerminalView {
                     false,
                 );
 
-                if use_underline {
-                    let y = rect.bottom() - 0.5;
-                    painter.draw_line_f(rect.left(), y, rect.right(), y)
-                }
-
                 if use_strike_out {
                     let y = (rect.top() + rect.bottom()) / 2.;
                     painter.draw_line_f(rect.left(), y, rect.right(), y)
@@ -396,6 +429,62 @@ impl TerminalView {
                     let y = rect.top() + 0.5;
                     painter.draw_line_f(rect.left(), y, rect.right(), y)
                 }
+
+                if underline_style != UnderlineStyle::None {
+                    // The underline color set by `SGR 58`, or the same as text.
+                    if style.underline_color.is_valid() {
+                        painter.set_color(style.underline_color.color(&self.color_table));
+                    }
+                    self.draw_underline(painter, rect, underline_style);
+                }
+            }
+        }
+    }
+
+    /// draws the underline of text fragment in the given style.
+    pub(super) fn draw_underline(&self, painter: &mut Painter, rect: FRect, style: UnderlineStyle) {
+        let (left, right) = (rect.left(), rect.right());
+        let y = rect.bottom() - 0.5;
+
+        match style {
+            UnderlineStyle::None => {}
+            UnderlineStyle::Single => painter.draw_line_f(left, y, right, y),
+            UnderlineStyle::Double => {
+                painter.draw_line_f(left, y, right, y);
+                painter.draw_line_f(left, y - 2., right, y - 2.);
+            }
+            UnderlineStyle::Curly => {
+                // Approximate the wave by the zigzag lines, a period was half of the font width.
+                let step = (self.font_width / 4.).max(1.);
+                let amplitude = 1.;
+                let mut x = left;
+                let mut up = true;
+                while x < right {
+                    let next = (x + step).min(right);
+                    let (y1, y2) = if up {
+                        (y, y - amplitude * 2.)
+                    } else {
+                        (y - amplitude * 2., y)
+                    };
+                    painter.draw_line_f(x, y1, next, y2);
+                    x = next;
+                    up = !up;
+                }
+            }
+            UnderlineStyle::Dotted | UnderlineStyle::Dashed => {
+                let (dash, gap) = if style == UnderlineStyle::Dotted {
+                    (1., 1.)
+                } else {
+                    (
+                        (self.font_width / 2.).max(2.),
+                        (self.font_width / 4.).max(1.),
+                    )
+                };
+                let mut x = left;
+                while x < right {
+                    painter.draw_line_f(x, y, (x + dash).min(right), y);
+                    x += dash + gap;
+                }
             }
         }
     }
@@ -447,6 +536,67 @@ impl TerminalView {
         // TODO
     }
 
+    /// Paints the images placed on the screen (e.g. sixel graphics), clipped to the visible region.
+    /// The images were ordered by z-index among themselves, but always drawn over the text.
+    pub(super) fn paint_images(&mut self, painter: &mut Painter) {
+        if self.image_placements.is_empty() {
+            return;
+        }
+
+        let clip = self.terminal_rect();
+        painter.save();
+        painter.clip_rect(clip, ClipOp::Intersect);
+
+        let mut order: Vec<usize> = (0..self.image_placements.len()).collect();
+        order.sort_by_key(|&i| self.image_placements[i].z_index);
+
+        for i in order {
+            let rect = self.image_placement_rect(&self.image_placements[i]);
+            let image = self.image_placements[i].image.clone();
+            if let Some(image) = self.cached_image(&image) {
+                painter.draw_image(&image, rect.left(), rect.top());
+            }
+        }
+
+        painter.restore();
+    }
+
+    /// Returns the image converted from `image` for painting, the conversion was done once
+    /// for each image while it was placed.
+    pub(super) fn cached_image(&mut self, image: &Rc<TerminalImage>) -> Option<Image> {
+        if let Some((_, cached)) = self
+            .image_cache
+            .iter()
+            .find(|(placed, _)| Rc::ptr_eq(placed, image))
+        {
+            return Some(cached.clone());
+        }
+
+        let info = ImageInfo::new(
+            (image.width() as i32, image.height() as i32),
+            ColorType::RGBA8888,
+            AlphaType::Unpremul,
+            None,
+        );
+        let converted = images::raster_from_data(
+            &info,
+            Data::new_copy(image.pixels()),
+            image.width() as usize * 4,
+        )?;
+        self.image_cache.push((image.clone(), converted.clone()));
+        Some(converted)
+    }
+
+    /// Drops the converted images which were no longer placed.
+    pub(super) fn prune_image_cache(&mut self) {
+        let placements = &self.image_placements;
+        self.image_cache.retain(|(image, _)| {
+            placements
+                .iter()
+                .any(|placement| Rc::ptr_eq(&placement.image, image))
+        });
+    }
+
     pub(super) fn paint_filters(&mut self, painter: &mut Painter) {
         let cursor_pos = self.map_to_widget_f(&Cursor::position().into());
 
@@ -464,7 +614,10 @@ impl TerminalView {
             let mut region = FRegion::default();
 
             if spot.type_() == HotSpotType::Link {
-                self.calc_hotspot_link_region(spot, &mut region)
+                // Hotspots of the same hyperlink should be underlined together.
+                for linked in self.linked_hotspots(spot).iter() {
+                    self.calc_hotspot_link_region(linked, &mut region)
+                }
             }
 
             for line in spot.start_line()..=spot.end_line() {
@@ -473,6 +626,25 @@ impl TerminalView {
         }
     }
 
+    /// Paints the markers of prompts (marked by `OSC 133;A`) alongside the scroll bar,
+    /// which indicate the positions of prompts in the whole output.
+    pub(super) fn paint_prompt_markers(&mut self, painter: &mut Painter) {
+        if self.prompt_markers.is_empty() || self.prompt_markers_line_count <= 0 {
+            return;
+        }
+
+        let rect = self.prompt_markers_rect();
+        let line_count = self.prompt_markers_line_count as f32;
+
+        for line in self.prompt_markers.iter() {
+            let y = rect.top() + (rect.height() - PROMPT_MARKER_HEIGHT) * *line as f32 / line_count;
+            painter.fill_rect(
+                FRect::new(rect.left(), y, rect.width(), PROMPT_MARKER_HEIGHT),
+                Color::rgba(120, 160, 255, 200),
+            );
+        }
+    }
+
     pub(super) fn paint_hotspot_each_line(
         &self,
         line: i32,
@@ -3,11 +3,14 @@ use crate::{
     tools::{
         character::LINE_WRAPPED,
         character_color::BASE_COLOR_TABLE,
-        event::ToKeyPressedEvent,
-        filter::{FilterChainImpl, HotSpotType},
+        event::{
+            ToKeyPressedEvent, MOUSE_LEFT_BUTTON, MOUSE_MIDDLE_BUTTON, MOUSE_MOVE, MOUSE_NO_BUTTON,
+            MOUSE_PRESS, MOUSE_RELEASE, MOUSE_RIGHT_BUTTON, MOUSE_WHEEL_DOWN, MOUSE_WHEEL_UP,
+        },
+        filter::{hyperlink_filter::HyperlinkFilter, FilterChainImpl, FilterObject, HotSpotType},
     },
 };
-use std::time::Duration;
+use std::{cell::RefCell, ptr::NonNull, rc::Rc, time::Duration};
 use tlib::namespace::KeyCode;
 use tmui::{
     application::cursor_blinking_time,
@@ -35,6 +38,21 @@ impl TerminalView {
 
         self.extended_char_table.initialize();
 
+        // Connect the hotspots once they were created, rather than on every image update.
+        let view = NonNull::from(&mut *self);
+        let mut hyperlink_filter = HyperlinkFilter::new();
+        hyperlink_filter.set_on_hotspot_created(move |filter_object| {
+            let view = unsafe { &mut *view.as_ptr() };
+            connect!(
+                filter_object,
+                action_filter_activated(),
+                view,
+                hotspot_activated(String, bool)
+            );
+        });
+        self.filter_chain
+            .add_filter(Rc::new(RefCell::new(hyperlink_filter)));
+
         self.set_color_table(&BASE_COLOR_TABLE);
         self.set_mouse_tracking(true);
 
@@ -94,7 +112,9 @@ impl TerminalView {
         }
 
         // self.draw_input_method_preedit_string(&mut painter, &self.preddit_rect());
+        self.paint_images(painter);
         self.paint_filters(painter);
+        self.paint_prompt_markers(painter);
     }
 
     pub(super) fn handle_key_pressed(&mut self, event: &KeyEvent) {
@@ -133,6 +153,10 @@ impl TerminalView {
         );
     }
 
+    pub(super) fn handle_key_released(&mut self, event: &KeyEvent) {
+        emit!(self, key_released_signal(event.to_key_pressed_event()));
+    }
+
     pub(super) fn handle_mouse_wheel(&mut self, event: &MouseEvent) {
         if event.delta().y() == 0 {
             return;
@@ -140,15 +164,29 @@ impl TerminalView {
         if self.screen_window.is_none() {
             return;
         }
-        if self.screen_window().unwrap().screen().get_history_lines() == 0 {
+
+        // if the terminal program is interested in mouse events
+        // then report the wheel as button 4/5.
+        if !self.mouse_marks {
+            let button = if event.delta().y() > 0 {
+                MOUSE_WHEEL_UP
+            } else {
+                MOUSE_WHEEL_DOWN
+            };
+            self.emit_mouse_event(
+                button,
+                event.modifier(),
+                event.position().into(),
+                MOUSE_PRESS,
+            );
             return;
         }
 
-        // if the terminal program is not interested mouse events
-        // then send the event to the ScrollBar.
-        if self.mouse_marks {
-            self.scroll_bar_mut().unwrap().on_mouse_wheel(event)
+        // otherwise send the event to the ScrollBar.
+        if self.screen_window().unwrap().screen().get_history_lines() == 0 {
+            return;
         }
+        self.scroll_bar_mut().unwrap().on_mouse_wheel(event)
     }
 
     pub(super) fn handle_mouse_released(&mut self, event: &MouseEvent) {
@@ -158,12 +196,12 @@ impl TerminalView {
 
         self.act_sel = 0;
 
-        let (char_line, char_column) = self.get_character_position(event.position().into());
+        let report = !self.mouse_marks && !event.modifier().has(KeyboardModifier::ShiftModifier);
         if event.mouse_button() == MouseButton::LeftButton {
             if self.drag_info.state == DragState::DiPending {
                 self.screen_window_mut().unwrap().clear_selection();
-            } else if self.drag_info.state == DragState::DiDragging {
-                if self.act_sel > 1 {
+            } else {
+                if self.drag_info.state == DragState::DiDragging && self.act_sel > 1 {
                     self.copy_selection(
                         self.screen_window()
                             .unwrap()
@@ -171,43 +209,34 @@ impl TerminalView {
                     );
                 }
 
-                if !self.mouse_marks && !event.modifier().has(KeyboardModifier::ShiftModifier) {
-                    let scroll_bar = self.scroll_bar().unwrap();
-                    emit!(
-                        self,
-                        mouse_signal(
-                            0,
-                            char_column + 1,
-                            char_line + 1 + scroll_bar.value() - scroll_bar.maximum(),
-                            2u8
-                        )
-                    )
+                if report {
+                    self.emit_mouse_event(
+                        MOUSE_LEFT_BUTTON,
+                        event.modifier(),
+                        event.position().into(),
+                        MOUSE_RELEASE,
+                    );
                 }
             }
 
             self.drag_info.state = DragState::DiNone;
         } // end: event.mouse_button() == MouseButton::LeftButton
 
-        if !self.mouse_marks
-            && !event.modifier().has(KeyboardModifier::ShiftModifier)
+        if report
             && (event.mouse_button() == MouseButton::RightButton
                 || event.mouse_button() == MouseButton::MiddleButton)
         {
-            let scroll_bar = self.scroll_bar().unwrap();
             let button = if event.mouse_button() == MouseButton::MiddleButton {
-                1
+                MOUSE_MIDDLE_BUTTON
             } else {
-                2
+                MOUSE_RIGHT_BUTTON
             };
 
-            emit!(
-                self,
-                mouse_signal(
-                    button,
-                    char_column + 1,
-                    char_line + 1 + scroll_bar.value() - scroll_bar.maximum(),
-                    2u8
-                )
+            self.emit_mouse_event(
+                button,
+                event.modifier(),
+                event.position().into(),
+                MOUSE_RELEASE,
             );
         }
     }
@@ -218,48 +247,52 @@ impl TerminalView {
         let spot = self.filter_chain().hotspot_at(char_line, char_column);
         if let Some(spot) = spot {
             if spot.type_() == HotSpotType::Link {
+                let linked_hotspots = self.linked_hotspots(&spot);
                 let mut previous_hotspot_area = self
                     .mouse_over_hotspot_area
                     .replace(CoordRegion::new())
                     .unwrap();
                 let mouse_over_hotspot_area = self.mouse_over_hotspot_area.as_mut().unwrap();
 
-                let mut r = FRect::default();
+                // Hotspots of the same hyperlink should be highlighted together.
+                for spot in linked_hotspots.iter() {
+                    let mut r = FRect::default();
 
-                if spot.start_line() == spot.end_line() {
-                    r.set_coords(
-                        spot.start_column() as f32 * self.font_width + self.left_margin,
-                        spot.start_line() as f32 * self.font_height + self.top_margin,
-                        spot.end_column() as f32 * self.font_width + self.left_margin,
-                        (spot.end_line() + 1) as f32 * self.font_height - 1. + self.top_margin,
-                    );
-                    mouse_over_hotspot_area.add_rect(CoordRect::new(r, Coordinate::Widget));
-                } else {
-                    r.set_coords(
-                        spot.start_column() as f32 * self.font_width + self.left_margin,
-                        spot.start_line() as f32 * self.font_height + self.top_margin,
-                        self.columns as f32 * self.font_width - 1. + self.left_margin,
-                        (spot.start_line() + 1) as f32 * self.font_height + self.top_margin,
-                    );
-                    mouse_over_hotspot_area.add_rect(CoordRect::new(r, Coordinate::Widget));
+                    if spot.start_line() == spot.end_line() {
+                        r.set_coords(
+                            spot.start_column() as f32 * self.font_width + self.left_margin,
+                            spot.start_line() as f32 * self.font_height + self.top_margin,
+                            spot.end_column() as f32 * self.font_width + self.left_margin,
+                            (spot.end_line() + 1) as f32 * self.font_height - 1. + self.top_margin,
+                        );
+                        mouse_over_hotspot_area.add_rect(CoordRect::new(r, Coordinate::Widget));
+                    } else {
+                        r.set_coords(
+                            spot.start_column() as f32 * self.font_width + self.left_margin,
+                            spot.start_line() as f32 * self.font_height + self.top_margin,
+                            self.columns as f32 * self.font_width - 1. + self.left_margin,
+                            (spot.start_line() + 1) as f32 * self.font_height + self.top_margin,
+                        );
+                        mouse_over_hotspot_area.add_rect(CoordRect::new(r, Coordinate::Widget));
+
+                        for line in spot.start_line() + 1..spot.end_line() {
+                            r.set_coords(
+                                self.left_margin,
+                                line as f32 * self.font_height + self.top_margin,
+                                self.columns as f32 * self.font_width + self.left_margin,
+                                (line + 1) as f32 * self.font_height + self.top_margin,
+                            );
+                            mouse_over_hotspot_area.add_rect(CoordRect::new(r, Coordinate::Widget));
+                        }
 
-                    for line in spot.start_line() + 1..spot.end_line() {
                         r.set_coords(
                             self.left_margin,
-                            line as f32 * self.font_height + self.top_margin,
-                            self.columns as f32 * self.font_width + self.left_margin,
-                            (line + 1) as f32 * self.font_height + self.top_margin,
+                            spot.end_line() as f32 * self.font_height + self.top_margin,
+                            spot.end_column() as f32 * self.font_width + self.left_margin,
+                            (spot.end_line() + 1) as f32 * self.font_height + self.top_margin,
                         );
                         mouse_over_hotspot_area.add_rect(CoordRect::new(r, Coordinate::Widget));
                     }
-
-                    r.set_coords(
-                        self.left_margin,
-                        spot.end_line() as f32 * self.font_height + self.top_margin,
-                        spot.end_column() as f32 * self.font_width + self.left_margin,
-                        (spot.end_line() + 1) as f32 * self.font_height + self.top_margin,
-                    );
-                    mouse_over_hotspot_area.add_rect(CoordRect::new(r, Coordinate::Widget));
                 }
 
                 // update
@@ -274,36 +307,34 @@ impl TerminalView {
             self.update_region(&mouse_over_hotspot_area);
         }
 
-        if !event.mouse_button().has(MouseButton::LeftButton) {
-            return;
-        }
-
+        // Motion without button pressed was reported too, the emulation decides
+        // whether to send it by the mouse mode.
         if !self.mouse_marks && !event.modifier().has(KeyboardModifier::ShiftModifier) {
-            let mut button = 3;
+            let mut button = MOUSE_NO_BUTTON;
             let mouse_button = event.mouse_button();
             if mouse_button.has(MouseButton::LeftButton) {
-                button = 0;
+                button = MOUSE_LEFT_BUTTON;
             }
             if mouse_button.has(MouseButton::MiddleButton) {
-                button = 1;
+                button = MOUSE_MIDDLE_BUTTON;
             }
             if mouse_button.has(MouseButton::RightButton) {
-                button = 2;
+                button = MOUSE_RIGHT_BUTTON;
             }
 
-            let scroll_bar = self.scroll_bar().unwrap();
-            emit!(
-                self,
-                mouse_signal(
-                    button,
-                    char_column + 1,
-                    char_line + 1 + scroll_bar.value() - scroll_bar.maximum(),
-                    1u8
-                )
+            self.emit_mouse_event(
+                button,
+                event.modifier(),
+                event.position().into(),
+                MOUSE_MOVE,
             );
             return;
         }
 
+        if !event.mouse_button().has(MouseButton::LeftButton) {
+            return;
+        }
+
         if self.drag_info.state == DragState::DiPending {
             let distance = 10;
             let pos = event.position();
@@ -370,36 +401,30 @@ impl TerminalView {
                     self.i_pnt_sel = pos;
                     self.act_sel = 1;
                 } else {
-                    let scroll_bar = self.scroll_bar().unwrap();
-                    emit!(
-                        self,
-                        mouse_signal(
-                            0,
-                            char_column + 1,
-                            char_line + 1 + scroll_bar.value() - scroll_bar.maximum(),
-                            0u8
-                        )
-                    )
+                    self.emit_mouse_event(
+                        MOUSE_LEFT_BUTTON,
+                        modifier,
+                        evt.position().into(),
+                        MOUSE_PRESS,
+                    );
                 }
 
-                let spot = self.filter_chain.hotspot_at(char_line, char_column);
-                if let Some(spot) = spot {
-                    spot.activate("click-action");
+                if modifier.has(KeyboardModifier::ControlModifier) {
+                    let spot = self.filter_chain.hotspot_at(char_line, char_column);
+                    if let Some(spot) = spot {
+                        spot.activate(FilterObject::ACTION_CLICK);
+                    }
                 }
             }
         } else if evt.mouse_button() == MouseButton::MiddleButton {
             if self.mouse_marks || modifier.has(KeyboardModifier::ShiftModifier) {
                 self.emit_selection(true, modifier.has(KeyboardModifier::ControlModifier));
             } else {
-                let scroll_bar = self.scroll_bar().unwrap();
-                emit!(
-                    self,
-                    mouse_signal(
-                        1,
-                        char_column + 1,
-                        char_line + 1 + scroll_bar.value() - scroll_bar.maximum(),
-                        0u8
-                    )
+                self.emit_mouse_event(
+                    MOUSE_MIDDLE_BUTTON,
+                    modifier,
+                    evt.position().into(),
+                    MOUSE_PRESS,
                 );
             }
         } else if evt.mouse_button() == MouseButton::RightButton {
@@ -407,15 +432,11 @@ impl TerminalView {
                 let pos: Point = evt.position().into();
                 emit!(self, configure_request(pos));
             } else {
-                let scroll_bar = self.scroll_bar().unwrap();
-                emit!(
-                    self,
-                    mouse_signal(
-                        2,
-                        char_column + 1,
-                        char_line + 1 + scroll_bar.value() - scroll_bar.maximum(),
-                        0u8
-                    )
+                self.emit_mouse_event(
+                    MOUSE_RIGHT_BUTTON,
+                    modifier,
+                    evt.position().into(),
+                    MOUSE_PRESS,
                 );
             }
         }
@@ -434,15 +455,11 @@ impl TerminalView {
         let pos = Point::new(char_column, char_line);
 
         if !self.mouse_marks && !modifier.has(KeyboardModifier::ShiftModifier) {
-            let scroll_bar = self.scroll_bar().unwrap();
-            emit!(
-                self,
-                mouse_signal(
-                    0,
-                    pos.x() + 1,
-                    pos.y() + 1 + scroll_bar.value() - scroll_bar.maximum(),
-                    0u8
-                )
+            self.emit_mouse_event(
+                MOUSE_LEFT_BUTTON,
+                modifier,
+                evt.position().into(),
+                MOUSE_PRESS,
             );
             return;
         }
@@ -7,7 +7,6 @@ use data_sender::DataSender;
 use libc::wchar_t;
 use local_display::LocalDisplay;
 pub use vt102_emulation::*;
-use widestring::WideString;
 
 use crate::{
     core::{
@@ -15,6 +14,8 @@ use crate::{
         screen_window::{ScreenWindow, ScreenWindowSignals},
     },
     tools::{
+        char_width::CharWidth,
+        codec::{extend_wide, TerminalCodec, DEFAULT_ENCODING},
         event::KeyPressedEvent,
         history::HistoryType,
         terminal_character_decoder::TerminalCharacterDecoder,
@@ -36,6 +37,9 @@ use wchar::wch;
 
 const BULK_TIMEOUT1: u64 = 10;
 const BULK_TIMEOUT2: u64 = 40;
+/// The maximum duration of synchronized update, the screen will be flushed when
+/// the program never ends the update.
+const SYNC_UPDATE_TIMEOUT: u64 = 150;
 
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -53,6 +57,7 @@ pub enum EmulationState {
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum EmulationCodec {
+    /// The character encoding of session, see [`Emulation::set_encoding()`].
     LocalCodec = 0,
     Utf8Codec = 1,
 }
@@ -87,6 +92,20 @@ pub struct BaseEmulation {
     bracket_paste_mode: bool,
     bulk_timer1: Timer,
     bulk_timer2: Timer,
+    /// Synchronized update (DEC mode 2026), the views will not be updated until it ended.
+    synchronized_update: bool,
+    sync_update_timer: Timer,
+    /// The size of character cells in pixels, used to place the images.
+    #[derivative(Default(value = "8."))]
+    cell_width: f32,
+    #[derivative(Default(value = "16."))]
+    cell_height: f32,
+    /// The character encoding of session.
+    #[derivative(Default(value = "DEFAULT_ENCODING.to_string()"))]
+    encoding: String,
+    /// The codec currently used to convert the data between terminal program, switched
+    /// to UTF-8 temporarily by `ESC % G`.
+    codec: TerminalCodec,
 }
 impl ObjectSubclass for BaseEmulation {
     const NAME: &'static str = "BaseEmulation";
@@ -100,8 +119,8 @@ pub trait EmulationSignal: ActionExt {
 
         /// Emitted when a buffer of data is ready to send to the standard input of the terminal.
         ///
-        /// @param data The String buffer of data ready to be sent <br>
-        send_data(&str);
+        /// @param data The buffer of data ready to be sent, encoded in the character encoding of session. <br>
+        send_data(Vec<u8>);
 
         ///  Requests that sending of input to the emulation from the terminal process be suspended or resumed.
         ///
@@ -195,6 +214,57 @@ pub trait EmulationSignal: ActionExt {
         /// @param blinkingCursorEnabled Whether to enable blinking or not
         cursor_changed(u8, bool);
 
+        /// Emitted when the terminal program requests to set the clipboard by `OSC 52` escape sequence.
+        ///
+        /// @param [`u8`] The [`ClipboardSelection`](crate::tools::clipboard::ClipboardSelection) to set. <br>
+        /// @param [`String`] The decoded text.
+        clipboard_write_request(u8, String);
+
+        /// Emitted when the terminal program queries the clipboard by `OSC 52` escape sequence.
+        ///
+        /// @param [`u8`] The [`ClipboardSelection`](crate::tools::clipboard::ClipboardSelection) to query.
+        clipboard_read_request(u8);
+
+        /// Emitted when the shell reports the command was finished by `OSC 133;D;<exit_code>`.
+        ///
+        /// @param [`i32`] The exit code of the command.
+        command_finished(i32);
+
+        /// Emitted when the shell reports its current working directory by `OSC 7;file://host/path`.
+        ///
+        /// @param [`String`] The host name, empty if it was not reported.
+        /// @param [`String`] The decoded path of the working directory.
+        working_directory_changed(String, String);
+
+        /// Emitted when the terminal program requests the desktop notification by `OSC 9/777/99`.
+        ///
+        /// @param [`String`] The title, may be empty.
+        /// @param [`String`] The body.
+        notification_request(String, String);
+
+        /// Emitted when the terminal program reports the progress by `OSC 9;4;st;pr`.
+        ///
+        /// @param [`u8`] The state, see [`ProgressState`](crate::tools::progress::ProgressState).
+        /// @param [`u8`] The progress in percent.
+        progress_changed(u8, u8);
+
+        /// Emitted when the terminal program queries the color by `OSC 4/10/11/12` with `?`.
+        ///
+        /// @param [`i32`] The code of [`DynamicColor`](crate::tools::dynamic_color::DynamicColor) to query.
+        color_query_request(i32);
+
+        /// Emitted when the terminal program changes the color by `OSC 4/10/11/12`.
+        ///
+        /// @param [`i32`] The code of [`DynamicColor`](crate::tools::dynamic_color::DynamicColor) to change.
+        /// @param [`u32`] The new color packed as `0xRRGGBB`.
+        color_change_request(i32, u32);
+
+        /// Emitted when the terminal program resets the color by `OSC 104/110/111/112`.
+        ///
+        /// @param [`i32`] The code of [`DynamicColor`](crate::tools::dynamic_color::DynamicColor) to reset,
+        /// or [`DynamicColor::ALL_PALETTE`](crate::tools::dynamic_color::DynamicColor::ALL_PALETTE).
+        color_reset_request(i32);
+
         handle_command_from_keyboard(Command);
 
         output_from_keypress_event();
@@ -256,6 +326,26 @@ pub trait Emulation: 'static + EmulationSignal + ActionExt {
     /// @see set_key_bindings()
     fn keyboard_layout(&self) -> String;
 
+    /// Sets the width options of characters, applied to both of the normal and alternate screens.
+    fn set_char_width(&mut self, char_width: CharWidth);
+
+    /// Limits the number of the newest history lines rewrapped on column changes,
+    /// `None` for all the lines, see [`Screen::set_reflow_history_lines()`].
+    fn set_reflow_history_lines(&mut self, max_lines: Option<usize>);
+
+    /// Sets the character encoding of session used to decode the received data and encode
+    /// the sent data, e.g. `UTF-8`, `GBK`, `Big5`, `Shift_JIS`, `EUC-KR`, `ISO-8859-2`.
+    ///
+    /// @return false if the encoding was unknown, the encoding was unchanged.
+    fn set_encoding(&mut self, encoding: &str) -> bool;
+
+    /// Returns the canonical name of the current character encoding.
+    fn encoding(&self) -> &'static str;
+
+    /// Switch between the character encoding of session and UTF-8, requested by
+    /// `ESC % @` and `ESC % G`.
+    fn set_codec(&mut self, codec: EmulationCodec);
+
     /// Copies the current image into the history and clears the screen.
     fn clear_entire_screen(&mut self);
 
@@ -288,6 +378,9 @@ pub trait Emulation: 'static + EmulationSignal + ActionExt {
     /// Change the size of the emulation's image.
     fn set_image_size(&mut self, lines: i32, columns: i32);
 
+    /// Change the size of character cells in pixels, emitted by the view when the font changed.
+    fn set_font_metrics(&mut self, height: f32, width: f32);
+
     /// Interprets a sequence of characters and sends the result to the terminal.
     /// This is equivalent to calling sendKeyEvent() for each character in @p text in succession.
     fn send_text(&self, text: String);
@@ -296,10 +389,22 @@ pub trait Emulation: 'static + EmulationSignal + ActionExt {
     /// the resulting character stream.
     fn send_key_event(&mut self, event: KeyPressedEvent, from_paste: bool);
 
+    /// Interprets a key release event, only reported to the terminal program when it
+    /// enabled the report event types flag of kitty keyboard protocol.
+    fn send_key_release_event(&mut self, event: KeyPressedEvent);
+
+    /// Forgets the pressed keys when the view lost focus, their release events would
+    /// never arrive, so the next press of them was not reported as repeat.
+    fn release_pressed_keys(&mut self);
+
     /// Converts information about a mouse event into an xterm-compatible escape
     /// sequence and emits the character sequence via sendData()
     fn send_mouse_event(&self, buttons: i32, column: i32, line: i32, event_type: u8);
 
+    /// Same as [`Emulation::send_mouse_event()`], but the position was in pixels,
+    /// only reported when the terminal program enabled the SGR-Pixels (1016) mode.
+    fn send_mouse_pixel_event(&self, buttons: i32, x: i32, y: i32, event_type: u8);
+
     /// Sends a string of characters to the foreground terminal process.
     ///
     /// @param string The characters to send. <br>
@@ -364,12 +469,72 @@ impl BaseEmulation {
         wrapper.init();
         wrapper
     }
+
+    /// Begin or end the synchronized update, the images of screen windows were frozen
+    /// during the update.
+    ///
+    /// The update will be forced to end after [`SYNC_UPDATE_TIMEOUT`] milliseconds.
+    pub fn set_synchronized_update(&mut self, synchronized: bool) {
+        if self.synchronized_update == synchronized {
+            return;
+        }
+        self.synchronized_update = synchronized;
+
+        if synchronized {
+            self.sync_update_timer.set_single_shot(true);
+            self.sync_update_timer
+                .start(Duration::from_millis(SYNC_UPDATE_TIMEOUT));
+        } else {
+            self.sync_update_timer.stop();
+        }
+
+        for window in self.windows.iter_mut() {
+            window.set_frozen(synchronized);
+        }
+    }
+
+    #[inline]
+    pub fn synchronized_update(&self) -> bool {
+        self.synchronized_update
+    }
+
+    /// Returns the size of character cells in pixels: (width, height).
+    #[inline]
+    pub fn cell_size(&self) -> (f32, f32) {
+        (self.cell_width, self.cell_height)
+    }
+
+    /// Decode the data received from terminal program by the current codec to the wide
+    /// characters appended to `text`, the data from local display was always UTF-8.
+    pub fn decode(&mut self, buffer: &[u8], data_sender: DataSender, text: &mut Vec<wchar_t>) {
+        match data_sender {
+            DataSender::Pty => self.codec.decode_wide(buffer, text),
+            DataSender::LocalDisplay => extend_wide(text, &String::from_utf8_lossy(buffer)),
+        }
+    }
+
+    /// Encode the data sent to terminal program by the current codec.
+    #[inline]
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        self.codec.encode(text)
+    }
+
+    fn sync_update_timeout(&mut self) {
+        self.set_synchronized_update(false);
+        self.show_bulk();
+    }
 }
 
 impl Emulation for BaseEmulation {
     fn init(&mut self) {
         connect!(self.bulk_timer1, timeout(), self, show_bulk());
         connect!(self.bulk_timer2, timeout(), self, show_bulk());
+        connect!(
+            self.sync_update_timer,
+            timeout(),
+            self,
+            sync_update_timeout()
+        );
 
         connect!(
             self,
@@ -390,6 +555,7 @@ impl Emulation for BaseEmulation {
         let mut window = ScreenWindow::new();
 
         window.set_screen(self.current_screen);
+        window.set_frozen(self.synchronized_update);
 
         let window_ptr = NonNull::new(window.as_mut() as *mut ScreenWindow);
         self.windows.push(window);
@@ -505,6 +671,39 @@ impl Emulation for BaseEmulation {
         }
     }
 
+    fn set_char_width(&mut self, char_width: CharWidth) {
+        self.screen[0].set_char_width(char_width.clone());
+        self.screen[1].set_char_width(char_width);
+    }
+
+    fn set_reflow_history_lines(&mut self, max_lines: Option<usize>) {
+        self.screen[0].set_reflow_history_lines(max_lines);
+        self.screen[1].set_reflow_history_lines(max_lines);
+    }
+
+    fn set_encoding(&mut self, encoding: &str) -> bool {
+        match TerminalCodec::new(encoding) {
+            Some(codec) => {
+                self.encoding = encoding.to_string();
+                self.codec = codec;
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[inline]
+    fn encoding(&self) -> &'static str {
+        self.codec.name()
+    }
+
+    fn set_codec(&mut self, codec: EmulationCodec) {
+        self.codec = match codec {
+            EmulationCodec::LocalCodec => TerminalCodec::new(&self.encoding).unwrap_or_default(),
+            EmulationCodec::Utf8Codec => TerminalCodec::utf8(),
+        };
+    }
+
     fn clear_entire_screen(&mut self) {
         unsafe {
             self.current_screen
@@ -592,22 +791,43 @@ impl Emulation for BaseEmulation {
         self.direct_update();
     }
 
-    fn send_text(&self, _: String) {
-        // Default implementation does nothing.
+    fn set_font_metrics(&mut self, height: f32, width: f32) {
+        if height > 0. && width > 0. {
+            self.cell_width = width;
+            self.cell_height = height;
+        }
+    }
+
+    fn send_text(&self, text: String) {
+        if !text.is_empty() {
+            emit!(self, send_data(self.codec.encode(&text)));
+        }
     }
 
     fn send_key_event(&mut self, event: KeyPressedEvent, _from_paste: bool) {
         emit!(self, state_set(EmulationState::NotifyNormal as i32));
 
         if !event.text().is_empty() {
-            emit!(self, send_data(event.text()));
+            emit!(self, send_data(self.codec.encode(event.text())));
         }
     }
 
+    fn send_key_release_event(&mut self, _: KeyPressedEvent) {
+        // Default implementation does nothing.
+    }
+
+    fn release_pressed_keys(&mut self) {
+        // Default implementation does nothing.
+    }
+
     fn send_mouse_event(&self, _: i32, _: i32, _: i32, _: u8) {
         // Default implementation does nothing.
     }
 
+    fn send_mouse_pixel_event(&self, _: i32, _: i32, _: i32, _: u8) {
+        // Default implementation does nothing.
+    }
+
     fn send_string(&self, _: String, _: i32) {
         // Default implementation does nothing.
     }
@@ -615,14 +835,12 @@ impl Emulation for BaseEmulation {
     fn receive_data(&mut self, buffer: &[u8], len: i32, data_sender: DataSender) {
         emit!(self, state_set(EmulationState::NotifyActivity as i32));
 
-        let utf8_text = String::from_utf8(buffer.to_vec())
-            .expect("`Emulation` receive_data() parse utf-8 string failed.");
-        let utf16_text = WideString::from_str(&utf8_text);
+        let mut text = vec![];
+        self.decode(&buffer[..len as usize], data_sender, &mut text);
 
         // Send characters to terminal emulator
-        let text_slice = utf16_text.as_slice();
-        for &ts in text_slice.iter() {
-            self.receive_char(ts as wchar_t);
+        for c in text {
+            self.receive_char(c);
         }
 
         // Look for z-modem indicator
@@ -630,7 +848,7 @@ impl Emulation for BaseEmulation {
             #[allow(clippy::char_lit_as_u8)]
             if buffer[i] == '\u{0030}' as u8
                 && len as usize - i - 1 > 3
-                && String::from_utf8(buffer[i + 1..i + 4].to_vec()).unwrap() == "B00"
+                && buffer[i + 1..i + 4] == *b"B00"
             {
                 emit!(self, zmodem_detected());
             }
@@ -652,6 +870,11 @@ impl Emulation for BaseEmulation {
         self.bulk_timer1.stop();
         self.bulk_timer2.stop();
 
+        // The changes will be flushed when the synchronized update ended.
+        if self.synchronized_update {
+            return;
+        }
+
         emit!(self, output_changed());
 
         let current_screen = unsafe { self.current_screen.as_mut().unwrap().as_mut() };
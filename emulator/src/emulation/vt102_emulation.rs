@@ -1,46 +1,73 @@
 #![allow(unused_macros)]
-use super::{data_sender::DataSender, BaseEmulation, Emulation};
+use super::{data_sender::DataSender, BaseEmulation, Emulation, EmulationCodec};
 use crate::{
     core::{
         screen::{
-            MODES_SCREEN, MODE_CURSOR, MODE_INSERT, MODE_NEWLINE, MODE_ORIGIN, MODE_SCREEN,
-            MODE_WRAP,
+            Screen, MODES_SCREEN, MODE_CURSOR, MODE_GRAPHEME_CLUSTER, MODE_INSERT,
+            MODE_LEFT_RIGHT_MARGIN, MODE_NEWLINE, MODE_ORIGIN, MODE_SCREEN, MODE_WRAP,
         },
         screen_window::ScreenWindow,
         terminal_view::KeyboardCursorShape,
-        uwchar_t,
     },
     emulation::{EmulationSignal, EmulationState},
     tools::{
+        char_width::CharWidth,
         character::{
-            LINE_DOUBLE_HEIGHT, LINE_DOUBLE_WIDTH, RE_BLINK, RE_BOLD, RE_CONCEAL, RE_FAINT,
-            RE_ITALIC, RE_OVERLINE, RE_REVERSE, RE_STRIKEOUT, RE_UNDERLINE,
+            UnderlineStyle, LINE_COMMAND_FINISHED, LINE_COMMAND_START, LINE_DOUBLE_HEIGHT,
+            LINE_DOUBLE_HEIGHT_BOTTOM, LINE_DOUBLE_WIDTH, LINE_OUTPUT_START, LINE_PROMPT_START,
+            RE_BLINK, RE_BOLD, RE_CONCEAL, RE_FAINT, RE_ITALIC, RE_OVERLINE, RE_REVERSE,
+            RE_STRIKEOUT,
         },
         character_color::{
-            COLOR_SPACE_256, COLOR_SPACE_DEFAULT, COLOR_SPACE_RGB, COLOR_SPACE_SYSTEM,
-            VT100_GRAPHICS,
+            CharacterColor, COLOR_SPACE_256, COLOR_SPACE_DEFAULT, COLOR_SPACE_RGB,
+            COLOR_SPACE_SYSTEM, COLOR_SPACE_UNDEFINED,
         },
-        event::KeyPressedEvent,
+        charset::Charset,
+        clipboard::{decode_clipboard_data, ClipboardSelection},
+        dynamic_color::{parse_color_spec, DynamicColor},
+        event::{
+            KeyPressedEvent, MOUSE_MODIFIERS, MOUSE_MOTION, MOUSE_MOVE, MOUSE_NO_BUTTON,
+            MOUSE_RELEASE, MOUSE_WHEEL_UP,
+        },
+        graphics::{ImagePlacement, TerminalImage},
         history::HistoryType,
+        hyperlink::{Hyperlink, HyperlinkTable, NO_HYPERLINK},
+        kitty_graphics::{graphics_response, GraphicsCommand, KittyImageStore},
+        kitty_keyboard::{
+            encode_kitty_key, kitty_modifiers, KeyEventType, KeyboardFlagsStack, KittyKey,
+        },
+        notification::{KittyNotifications, Notification},
+        progress::Progress,
+        sixel::{decode_sixel, SIXEL_COLOR_REGISTERS, SIXEL_MAX_GEOMETRY},
         terminal_character_decoder::TerminalCharacterDecoder,
-        translators::{Command, KeyboardTranslatorManager, State, CTRL_MODIFIER},
+        terminfo::xtgettcap_response,
+        translators::{Command, KeyboardTranslatorManager, ModifyOtherKeys, State, CTRL_MODIFIER},
+        vt52_keyboard::encode_vt52_key,
+        working_directory::WorkingDirectory,
     },
 };
-use std::{cell::RefCell, collections::HashMap, ptr::NonNull, rc::Rc};
+use log::warn;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ptr::NonNull,
+    rc::Rc,
+    time::Duration,
+};
 use tmui::{
     clipboard::ClipboardLevel,
     prelude::*,
     system::System,
     tlib::{
-        emit,
+        connect, emit,
         figure::Size,
         impl_as_any,
         namespace::{AsNumeric, KeyCode, KeyboardModifier},
         nonnull_ref,
+        timer::Timer,
     },
 };
 use wchar::{wch, wchar_t};
-use widestring::WideString;
 
 /// Processing the incoming byte stream.
 /// --------------------------------------------------------------------
@@ -85,6 +112,11 @@ use widestring::WideString;
 /// - CSI_PS_SP  - Escape codes of the form <ESC>'['     {Pn} ';' ... {Space} C
 /// - CSI_PR     - Escape codes of the form <ESC>'[' '?' {Pn} ';' ...  C
 /// - CSI_PE     - Escape codes of the form <ESC>'[' '!' {Pn} ';' ...  C
+/// - CSI_PG     - Escape codes of the form <ESC>'[' '>' {Pn} ';' ...  C
+/// - CSI_PQ     - Escape codes of the form <ESC>'[' '=' {Pn} ';' {Pn} C
+/// - CSI_PL     - Escape codes of the form <ESC>'[' '<' {Pn} ';' {Pn} C
+/// - CSI_PD     - Escape codes of the form <ESC>'[' '?' {Pn} '$' C
+/// - CSI_DL     - Escape codes of the form <ESC>'['     {Pn} ';' ... '$' C
 /// - VT52       - VT52 escape codes
 ///              - <ESC><Chr>
 ///              - <ESC>'Y'{Pc}{Pc}
@@ -164,10 +196,41 @@ macro_rules! ty_csi_pe {
         ty_construct!(10, $a, 0)
     };
 }
+macro_rules! ty_csi_pq {
+    ( $a:expr ) => {
+        ty_construct!(12, $a, 0)
+    };
+}
+macro_rules! ty_csi_pl {
+    ( $a:expr ) => {
+        ty_construct!(13, $a, 0)
+    };
+}
+macro_rules! ty_csi_pd {
+    ( $a:expr ) => {
+        ty_construct!(14, $a, 0)
+    };
+}
+macro_rules! ty_csi_dl {
+    ( $a:expr ) => {
+        ty_construct!(15, $a, 0)
+    };
+}
 
 const MAX_ARGUMENT: usize = 4096;
-const MAX_TOKEN_LENGTH: usize = 256;
+/// The maximum number of intermediate characters, the sequences with more were ignored.
+const MAX_INTERMEDIATES: usize = 2;
 const MAXARGS: usize = 15;
+/// The maximum length of the text part of OSC sequence, the exceeded part will be dropped.
+const MAX_OSC_LENGTH: usize = 1 << 20;
+/// The maximum length of the data part of DCS sequence, which may carry the large images.
+const MAX_DCS_LENGTH: usize = 1 << 26;
+/// The maximum length of the data part of APC sequence, which carries the kitty graphics commands.
+const MAX_APC_LENGTH: usize = 1 << 26;
+/// The maximum depth of the title stack of XTWINOPS `CSI 22 t`, same as xterm.
+const MAX_TITLE_STACK_DEPTH: usize = 10;
+/// The title updates in this duration (milliseconds) were buffered into one update.
+const TITLE_UPDATE_DELAY: u64 = 20;
 
 /// Mode #1.
 const MODE_APP_SCREEN: usize = MODES_SCREEN;
@@ -189,6 +252,8 @@ const MODE_MOUSE_1005: usize = MODES_SCREEN + 7;
 const MODE_MOUSE_1006: usize = MODES_SCREEN + 8;
 /// Urxvt-style extended coordinates.
 const MODE_MOUSE_1015: usize = MODES_SCREEN + 9;
+/// Xterm-style extended coordinates in pixels.
+const MODE_MOUSE_1016: usize = MODES_SCREEN + 14;
 /// Use US Ascii for character sets G0-G3 (DECANM).
 const MODE_ANSI: usize = MODES_SCREEN + 10;
 /// 80 <-> 132 column mode switch (DECCOLM).
@@ -198,22 +263,56 @@ const MODE_ALLOW_132_COLUMNS: usize = MODES_SCREEN + 12;
 /// Xterm-style bracketed paste mode.
 const MODE_BRACKETD_PASTE: usize = MODES_SCREEN + 13;
 /// The total size of modes.
-const MODE_TOTAL: usize = MODES_SCREEN + 14;
+const MODE_TOTAL: usize = MODES_SCREEN + 15;
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 struct CharCodes {
-    /// Coding information.
-    charset: [u8; 4],
-    /// Actual charset.
-    current_charset: i32,
-    /// Some VT100 tricks.
-    graphic: bool,
-    /// Some VT100 tricks.
-    pound: bool,
-    /// Saved graphic.
-    saved_graphic: bool,
-    /// Saved pound.
-    saved_pound: bool,
+    /// The character sets designated to G0-G3.
+    charset: [Charset; 4],
+    /// The set invoked into GL by the locking shifts SI/SO/LS2/LS3.
+    current_charset: usize,
+    /// The set invoked into GR by the locking shifts LS1R/LS2R/LS3R,
+    /// `None` if the GR characters were kept as ISO Latin-1.
+    current_gr_charset: Option<usize>,
+    /// The set invoked by the single shifts SS2/SS3 for the next character only.
+    single_shift: Option<usize>,
+}
+
+/// The states of DEC ANSI parser, see https://vt100.net/emu/dec_ansi_parser
+///
+/// The control characters were executed in all states except the strings, so they have no
+/// transitions here.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+enum ParserState {
+    /// The printable characters were displayed.
+    #[default]
+    Ground,
+    /// After `ESC`.
+    Escape,
+    /// After `ESC` and the intermediate characters 0x20..=0x2F.
+    EscapeIntermediate,
+    /// After `CSI`, the private marker `<`, `=`, `>` or `?` was accepted here.
+    CsiEntry,
+    /// Collecting the parameters of CSI.
+    CsiParam,
+    /// After the intermediate characters of CSI.
+    CsiIntermediate,
+    /// The malformed CSI was ignored until its final character.
+    CsiIgnore,
+    /// The text of OSC `ESC ]`, terminated by `BEL` or `ST`.
+    OscString,
+    /// The text of DCS `ESC P`, terminated by `ST`.
+    DcsPassthrough,
+    /// The text of APC `ESC _`, terminated by `ST`.
+    ApcString,
+    /// The text of SOS `ESC X` and PM `ESC ^`, which was ignored until `ST`.
+    SosPmString,
+    /// After `ESC` in VT52 mode.
+    Vt52Escape,
+    /// After VT52 `ESC Y`, the line of direct cursor address.
+    Vt52CursorLine,
+    /// After VT52 `ESC Y Pl`, the column of direct cursor address.
+    Vt52CursorColumn,
 }
 
 #[derive(Default)]
@@ -232,22 +331,61 @@ struct TerminalState {
 /// http://rtfm.etla.org/xterm/ctlseq.html for a description of xterm's escape sequences.
 pub struct VT102Emulation {
     emulation: Option<Box<BaseEmulation>>,
-    token_buffer: [wchar_t; MAX_TOKEN_LENGTH],
-    token_buffer_pos: usize,
+    parser_state: ParserState,
+    /// The private marker `<`, `=`, `>` or `?` of CSI, 0 if there was none.
+    private_marker: wchar_t,
+    /// The intermediate characters 0x20..=0x2F of ESC and CSI.
+    intermediates: [wchar_t; MAX_INTERMEDIATES],
+    /// The number of intermediate characters, may exceed [`MAX_INTERMEDIATES`].
+    intermediate_count: usize,
+    /// Whether the `ESC` was received in the string of OSC, DCS, APC, SOS or PM,
+    /// it was the string terminator `ESC \` if followed by `\`.
+    string_escape: bool,
+    /// The text part of Xpe (aka OSC) escape sequence.
+    osc_text: String,
+    /// Whether the `osc_text` exceeded [`MAX_OSC_LENGTH`], the truncated sequence will be discarded.
+    osc_text_overflow: bool,
+    /// The text part of DCS escape sequence "ESC P", include the parameters and data.
+    dcs_text: String,
+    /// Whether the `dcs_text` exceeded [`MAX_DCS_LENGTH`], the truncated sequence will be discarded.
+    dcs_text_overflow: bool,
+    /// The text part of APC escape sequence "ESC _".
+    apc_text: String,
+    /// Whether the `apc_text` exceeded [`MAX_APC_LENGTH`], the truncated sequence will be discarded.
+    apc_text_overflow: bool,
     argv: [i32; MAXARGS],
     argc: i32,
-    cc: wchar_t,
-    prev_cc: wchar_t,
-    /// Set of flags for each of the ASCII characters which indicates what category they fall into
-    /// (printable character, control, digit etc.) for the purposes of decoding terminal output
-    char_class: [i32; 256],
+    /// The bit `i` was set if `argv[i]` was a sub-parameter, which was separated by colon.
+    sub_params: u16,
     charset: [CharCodes; 2],
+    /// The charset state of ANSI mode, saved when entering the VT52 mode.
+    ansi_charset: [CharCodes; 2],
+    /// The charset state saved by DECSC, indexed by main(0) and alternate(1) screen.
+    saved_charset: [CharCodes; 2],
     current_modes: TerminalState,
     saved_modes: TerminalState,
     pending_title_updates: HashMap<i32, String>,
+    title_update_timer: Timer,
+    /// The current icon title(0) and window title(1).
+    titles: [String; 2],
+    /// The titles saved by XTWINOPS `CSI 22 ; Ps t`, `None` if the title was not saved.
+    title_stack: Vec<[Option<String>; 2]>,
     report_focus_event: bool,
+    /// The enhancement flags of kitty keyboard protocol, indexed by main(0) and alternate(1) screen.
+    keyboard_flags: [KeyboardFlagsStack; 2],
+    /// The keys being pressed, to distinguish the repeat events from press events.
+    pressed_keys: HashSet<u32>,
+    /// The xterm's modifyOtherKeys level, set by `CSI > 4 ; level m`.
+    modify_other_keys: ModifyOtherKeys,
+    /// The cursor style set by DECSCUSR `CSI Ps SP q`, 0 for the default style.
+    cursor_style: u8,
+    /// The images transmitted by kitty graphics protocol.
+    image_store: KittyImageStore,
+    /// The chunked notifications of `OSC 99`.
+    kitty_notifications: KittyNotifications,
+    /// The wide characters decoded from the data received, kept to reuse the allocation.
+    decoded_text: Vec<wchar_t>,
     signal_source: Option<ObjectId>,
-    // TODO: Add timer: title_update_timer
 }
 impl_as_any!(VT102Emulation);
 impl ObjectOperation for VT102Emulation {
@@ -291,18 +429,37 @@ impl Default for VT102Emulation {
     fn default() -> Self {
         Self {
             emulation: Default::default(),
-            token_buffer: [0; MAX_TOKEN_LENGTH],
-            token_buffer_pos: Default::default(),
+            parser_state: Default::default(),
+            private_marker: Default::default(),
+            intermediates: Default::default(),
+            intermediate_count: Default::default(),
+            string_escape: Default::default(),
+            osc_text: Default::default(),
+            osc_text_overflow: Default::default(),
+            dcs_text: Default::default(),
+            dcs_text_overflow: Default::default(),
+            apc_text: Default::default(),
+            apc_text_overflow: Default::default(),
             argv: [0; MAXARGS],
             argc: Default::default(),
-            cc: Default::default(),
-            prev_cc: Default::default(),
-            char_class: [0; 256],
+            sub_params: Default::default(),
             charset: Default::default(),
+            ansi_charset: Default::default(),
+            saved_charset: Default::default(),
             current_modes: Default::default(),
             saved_modes: Default::default(),
             pending_title_updates: Default::default(),
+            title_update_timer: Default::default(),
+            titles: Default::default(),
+            title_stack: Default::default(),
             report_focus_event: Default::default(),
+            keyboard_flags: Default::default(),
+            pressed_keys: Default::default(),
+            modify_other_keys: Default::default(),
+            cursor_style: Default::default(),
+            image_store: Default::default(),
+            kitty_notifications: Default::default(),
+            decoded_text: Default::default(),
             signal_source: None,
         }
     }
@@ -323,22 +480,6 @@ impl Reflect for VT102Emulation {
     }
 }
 
-//////////////////////// Character Class flags used while decoding
-/// Control character
-const CTL: i32 = 1;
-/// Printable character
-const CHR: i32 = 2;
-/// The character used in control sequnce
-const CPN: i32 = 4;
-/// Numberic digit
-const DIG: i32 = 8;
-/// Special characters in "()+*%"
-const SCS: i32 = 16;
-/// Special characters in "()+*#[]%"
-const GRP: i32 = 32;
-/// Character('t') which indicates end of window resize (escape sequence '\e[8;<row>;<col>t')
-const CPS: i32 = 64;
-
 impl VT102Emulation {
     /// Constructer to create a new Emulation.
     #[allow(clippy::field_reassign_with_default)]
@@ -347,9 +488,15 @@ impl VT102Emulation {
         let mut vt102_emulation: VT102Emulation = Default::default();
         vt102_emulation.emulation = Some(base_emulation);
 
-        vt102_emulation.init_tokenizer();
+        vt102_emulation.reset_tokenizer();
         vt102_emulation.reset();
 
+        // The sixel images and kitty placements on screens share the quota of image store.
+        let quota = vt102_emulation.image_store.quota();
+        for screen in vt102_emulation.emulation_mut().screen.iter_mut() {
+            screen.set_image_quota(quota);
+        }
+
         vt102_emulation
     }
 
@@ -369,43 +516,44 @@ impl VT102Emulation {
         self.emulation.as_deref_mut().unwrap()
     }
 
-    //////////////////////////////////////////////////////// Private function
-    fn init_tokenizer(&mut self) {
-        for i in 0..256 {
-            self.char_class[i] = 0;
-        }
-        for i in 0..32 {
-            self.char_class[i] |= CTL;
-        }
-        for i in 32..256 {
-            self.char_class[i] |= CHR;
-        }
-        for s in "@ABCDEFGHILMPSTXZbcdfry".as_bytes().iter() {
-            self.char_class[*s as usize] |= CPN;
-        }
-        for s in "t".as_bytes().iter() {
-            self.char_class[*s as usize] |= CPS;
-        }
-        for s in "0123456789".as_bytes().iter() {
-            self.char_class[*s as usize] |= DIG;
-        }
-        for s in "()+*%".as_bytes().iter() {
-            self.char_class[*s as usize] |= SCS;
-        }
-        for s in "()+*#[]%".as_bytes().iter() {
-            self.char_class[*s as usize] |= GRP;
+    #[inline]
+    fn current_screen_mut(&mut self) -> &mut Screen {
+        unsafe {
+            self.emulation_mut()
+                .current_screen
+                .as_mut()
+                .unwrap()
+                .as_mut()
         }
+    }
 
-        self.reset_tokenizer();
+    /// The kitty keyboard protocol flags stack of current screen.
+    #[inline]
+    fn keyboard_flags(&self) -> &KeyboardFlagsStack {
+        &self.keyboard_flags[self.get_mode(MODE_APP_SCREEN) as usize]
+    }
+    #[inline]
+    fn keyboard_flags_mut(&mut self) -> &mut KeyboardFlagsStack {
+        let idx = self.get_mode(MODE_APP_SCREEN) as usize;
+        &mut self.keyboard_flags[idx]
     }
 
+    //////////////////////////////////////////////////////// Private function
     fn reset_tokenizer(&mut self) {
-        self.token_buffer_pos = 0;
+        self.parser_state = ParserState::Ground;
+        self.private_marker = 0;
+        self.intermediate_count = 0;
+        self.string_escape = false;
+        self.osc_text.clear();
+        self.osc_text_overflow = false;
+        self.dcs_text.clear();
+        self.dcs_text_overflow = false;
+        self.apc_text.clear();
+        self.apc_text_overflow = false;
         self.argc = 0;
         self.argv[0] = 0;
         self.argv[1] = 0;
-        self.cc = 0;
-        self.prev_cc = 0;
+        self.sub_params = 0;
     }
 
     #[allow(clippy::if_same_then_else)]
@@ -532,9 +680,26 @@ impl VT102Emulation {
             self.reset();
         //////////////////////////////////////////////////////////////
         } else if token == ty_esc!('n') {
+            // LS2
             self.use_charset(2);
         } else if token == ty_esc!('o') {
+            // LS3
             self.use_charset(3);
+        } else if token == ty_esc!('~') {
+            // LS1R
+            self.use_gr_charset(1);
+        } else if token == ty_esc!('}') {
+            // LS2R
+            self.use_gr_charset(2);
+        } else if token == ty_esc!('|') {
+            // LS3R
+            self.use_gr_charset(3);
+        } else if token == ty_esc!('N') {
+            // SS2
+            self.charset().single_shift = Some(2);
+        } else if token == ty_esc!('O') {
+            // SS3
+            self.charset().single_shift = Some(3);
         } else if token == ty_esc!('7') {
             self.save_cursor();
         } else if token == ty_esc!('8') {
@@ -548,76 +713,59 @@ impl VT102Emulation {
             // VT100
             self.set_mode(MODE_ANSI);
         //////////////////////////////////////////////////////////////
-        } else if token == ty_esc_cs!('(', '0') {
-            // VT100
-            self.set_charset(0, b'0');
-        } else if token == ty_esc_cs!('(', 'A') {
-            // VT100
-            self.set_charset(0, b'A');
-        } else if token == ty_esc_cs!('(', 'B') {
-            // VT100
-            self.set_charset(0, b'B');
-        //////////////////////////////////////////////////////////////
-        } else if token == ty_esc_cs!(')', '0') {
-            // VT100
-            self.set_charset(1, b'0');
-        } else if token == ty_esc_cs!(')', 'A') {
-            // VT100
-            self.set_charset(1, b'A');
-        } else if token == ty_esc_cs!(')', 'B') {
-            // VT100
-            self.set_charset(1, b'B');
-        //////////////////////////////////////////////////////////////
-        } else if token == ty_esc_cs!('*', '0') {
-            // VT100
-            self.set_charset(2, b'0');
-        } else if token == ty_esc_cs!('*', 'A') {
-            // VT100
-            self.set_charset(2, b'A');
-        } else if token == ty_esc_cs!('*', 'B') {
-            // VT100
-            self.set_charset(2, b'B');
-        //////////////////////////////////////////////////////////////
-        } else if token == ty_esc_cs!('+', '0') {
-            // VT100
-            self.set_charset(3, b'0');
-        } else if token == ty_esc_cs!('+', 'A') {
-            // VT100
-            self.set_charset(3, b'A');
-        } else if token == ty_esc_cs!('+', 'B') {
-            // VT100
-            self.set_charset(3, b'B');
+        } else if let Some((n, charset)) = Self::designation(token) {
+            // VT100, designate G0-G3
+            self.set_charset(n, charset);
         //////////////////////////////////////////////////////////////
         } else if token == ty_esc_cs!('%', 'G') {
             // Linux
-            // TODO: setCodec(UTF8Codec)
+            self.emulation_mut().set_codec(EmulationCodec::Utf8Codec);
         } else if token == ty_esc_cs!('%', '@') {
             // Linux
-            // TODO: setCodec(LocaleCodec)
+            self.emulation_mut().set_codec(EmulationCodec::LocalCodec);
             //////////////////////////////////////////////////////////////
         } else if token == ty_esc_de!('3') {
             // Double height line, top half.
             current_screen.set_line_property(LINE_DOUBLE_WIDTH, true);
             current_screen.set_line_property(LINE_DOUBLE_HEIGHT, true);
+            current_screen.set_line_property(LINE_DOUBLE_HEIGHT_BOTTOM, false);
         } else if token == ty_esc_de!('4') {
             // Double height line, bottom half.
             current_screen.set_line_property(LINE_DOUBLE_WIDTH, true);
             current_screen.set_line_property(LINE_DOUBLE_HEIGHT, true);
+            current_screen.set_line_property(LINE_DOUBLE_HEIGHT_BOTTOM, true);
         } else if token == ty_esc_de!('5') {
             // Single width, single height line.
             current_screen.set_line_property(LINE_DOUBLE_WIDTH, false);
             current_screen.set_line_property(LINE_DOUBLE_HEIGHT, false);
+            current_screen.set_line_property(LINE_DOUBLE_HEIGHT_BOTTOM, false);
         } else if token == ty_esc_de!('6') {
             // Double width, single height line.
             current_screen.set_line_property(LINE_DOUBLE_WIDTH, true);
             current_screen.set_line_property(LINE_DOUBLE_HEIGHT, false);
+            current_screen.set_line_property(LINE_DOUBLE_HEIGHT_BOTTOM, false);
         } else if token == ty_esc_de!('8') {
             current_screen.help_align();
         //////////////////////////////////////////////////////////////
         } else if token == ty_csi_ps!('t', 8) {
             // resize = \e[8;<row>;<col>t
-            self.set_image_size(p as i32, q);
-            emit!(self, image_resize_request(Size::new(q, p as i32)));
+            let size = self.resize_request_size(p as i32, q);
+            emit!(self, image_resize_request(size));
+        } else if token == ty_csi_ps!('t', 14) {
+            // XTerm: report text area size in pixels
+            self.request_window_attribute(14);
+        } else if token == ty_csi_ps!('t', 16) {
+            // XTerm: report character cell size in pixels
+            self.request_window_attribute(16);
+        } else if token == ty_csi_ps!('t', 18) {
+            // XTerm: report text area size in characters
+            self.request_window_attribute(18);
+        } else if token == ty_csi_ps!('t', 22) {
+            // XTerm: push title to stack
+            self.push_title(p as i32);
+        } else if token == ty_csi_ps!('t', 23) {
+            // XTerm: pop title from stack
+            self.pop_title(p as i32);
         //////////////////////////////////////////////////////////////
         } else if token == ty_csi_ps!('t', 28) {
             // change tab text color : \e[28;<color>t  color: 0-16,777,215
@@ -670,8 +818,13 @@ impl VT102Emulation {
             // VT100
             current_screen.set_rendition(RE_ITALIC);
         } else if token == ty_csi_ps!('m', 4) {
-            // VT100
-            current_screen.set_rendition(RE_UNDERLINE);
+            // VT100, the style was specified by the sub-parameter of `4:x` when `p` was set.
+            let style = if p == 0 {
+                UnderlineStyle::Single
+            } else {
+                UnderlineStyle::from_sgr(q)
+            };
+            current_screen.set_underline_style(style);
         } else if token == ty_csi_ps!('m', 5) {
             // VT100
             current_screen.set_rendition(RE_BLINK);
@@ -693,7 +846,7 @@ impl VT102Emulation {
             // IGNORED: mapping related
             // Linux
         } else if token == ty_csi_ps!('m', 21) {
-            current_screen.reset_rendition(RE_BOLD);
+            current_screen.set_underline_style(UnderlineStyle::Double);
         } else if token == ty_csi_ps!('m', 22) {
             current_screen.reset_rendition(RE_BOLD);
             current_screen.reset_rendition(RE_FAINT);
@@ -701,7 +854,7 @@ impl VT102Emulation {
             // VT100
             current_screen.reset_rendition(RE_ITALIC);
         } else if token == ty_csi_ps!('m', 24) {
-            current_screen.reset_rendition(RE_UNDERLINE);
+            current_screen.set_underline_style(UnderlineStyle::None);
         } else if token == ty_csi_ps!('m', 25) {
             current_screen.reset_rendition(RE_BLINK);
         } else if token == ty_csi_ps!('m', 27) {
@@ -712,6 +865,10 @@ impl VT102Emulation {
             current_screen.reset_rendition(RE_STRIKEOUT);
         } else if token == ty_csi_ps!('m', 55) {
             current_screen.reset_rendition(RE_OVERLINE);
+        } else if token == ty_csi_ps!('m', 58) {
+            current_screen.set_underline_color(p as u8, q as u32);
+        } else if token == ty_csi_ps!('m', 59) {
+            current_screen.set_underline_color(COLOR_SPACE_UNDEFINED, 0);
         //////////////////////////////////////////////////////////////
         } else if token == ty_csi_ps!('m', 30) {
             current_screen.set_foreground_color(COLOR_SPACE_SYSTEM, 0);
@@ -817,41 +974,9 @@ impl VT102Emulation {
             // VT100
             self.report_terminal_params(3);
         //////////////////////////////////////////////////////////////
-        } else if token == ty_csi_ps_sp!('q', 0) {
-            emit!(
-                self,
-                cursor_changed(KeyboardCursorShape::BlockCursor as u8, true)
-            );
-        } else if token == ty_csi_ps_sp!('q', 1) {
-            emit!(
-                self,
-                cursor_changed(KeyboardCursorShape::BlockCursor as u8, true)
-            );
-        } else if token == ty_csi_ps_sp!('q', 2) {
-            emit!(
-                self,
-                cursor_changed(KeyboardCursorShape::BlockCursor as u8, false)
-            );
-        } else if token == ty_csi_ps_sp!('q', 3) {
-            emit!(
-                self,
-                cursor_changed(KeyboardCursorShape::UnderlineCursor as u8, true)
-            );
-        } else if token == ty_csi_ps_sp!('q', 4) {
-            emit!(
-                self,
-                cursor_changed(KeyboardCursorShape::UnderlineCursor as u8, false)
-            );
-        } else if token == ty_csi_ps_sp!('q', 5) {
-            emit!(
-                self,
-                cursor_changed(KeyboardCursorShape::IBeamCursor as u8, true)
-            );
-        } else if token == ty_csi_ps_sp!('q', 6) {
-            emit!(
-                self,
-                cursor_changed(KeyboardCursorShape::IBeamCursor as u8, false)
-            );
+        } else if token == ty_csi_ps_sp!('q', p as i32) {
+            // DECSCUSR: set cursor style
+            self.set_cursor_style(p as u8);
         //////////////////////////////////////////////////////////////
         } else if token == ty_csi_pn!('@') {
             current_screen.insert_chars(p as i32);
@@ -909,6 +1034,10 @@ impl VT102Emulation {
         } else if token == ty_csi_pn!('r') {
             // VT100
             self.set_margins(p as i32, q);
+        } else if token == ty_csi_pn!('s') {
+            // DECSLRM, only in the left right margin mode
+            // VT420
+            self.set_left_right_margins(p as i32, q);
         } else if token == ty_csi_pn!('y') {
             // IGNORED: Confidence test.
             // VT100
@@ -924,7 +1053,7 @@ impl VT102Emulation {
         //////////////////////////////////////////////////////////////
         } else if token == ty_csi_pr!('l', 2) {
             // Vt100
-            self.reset_mode(MODE_ANSI);
+            self.enter_vt52_mode();
         //////////////////////////////////////////////////////////////
         } else if token == ty_csi_pr!('h', 3) {
             self.set_mode(MODE_132_COLUMNS);
@@ -1059,6 +1188,20 @@ impl VT102Emulation {
             // IGNORED: DECBKM
             // XTerm
             //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_pr!('h', 69) {
+            // DECLRMM: left right margin mode
+            // VT420
+            self.set_mode(MODE_LEFT_RIGHT_MARGIN);
+        } else if token == ty_csi_pr!('l', 69) {
+            // VT420
+            self.reset_mode(MODE_LEFT_RIGHT_MARGIN);
+        } else if token == ty_csi_pr!('s', 69) {
+            // VT420
+            self.save_mode(MODE_LEFT_RIGHT_MARGIN);
+        } else if token == ty_csi_pr!('r', 69) {
+            // VT420
+            self.restore_mode(MODE_LEFT_RIGHT_MARGIN);
+            //////////////////////////////////////////////////////////////
             // XTerm defines the following modes:
             // SET_VT200_MOUSE             1000
             // SET_VT200_HIGHLIGHT_MOUSE   1001
@@ -1067,10 +1210,9 @@ impl VT102Emulation {
             //
             // Note about mouse modes:
             // There are four mouse modes which xterm-compatible terminals can support
-            // - 1000,1001,1002,1003 Konsole currently supports mode 1000 (basic mouse
-            // press and release) and mode 1002 (dragging the mouse).
-            // TODO:  Implementation of mouse modes 1001 (something called hilight
-            // tracking) and 1003 (a slight variation on dragging the mouse)
+            // - 1000,1001,1002,1003 We support mode 1000 (basic mouse press and release),
+            // mode 1002 (dragging the mouse) and mode 1003 (any mouse motion).
+            // Mode 1001 (something called hilight tracking) is ignored.
         } else if token == ty_csi_pr!('h', 1000) {
             // XTerm
             self.set_mode(MODE_MOUSE_1000);
@@ -1167,6 +1309,19 @@ impl VT102Emulation {
             // XTerm
             self.restore_mode(MODE_MOUSE_1015);
         //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_pr!('h', 1016) {
+            // XTerm
+            self.set_mode(MODE_MOUSE_1016);
+        } else if token == ty_csi_pr!('l', 1016) {
+            // XTerm
+            self.reset_mode(MODE_MOUSE_1016);
+        } else if token == ty_csi_pr!('s', 1016) {
+            // XTerm
+            self.save_mode(MODE_MOUSE_1016);
+        } else if token == ty_csi_pr!('r', 1016) {
+            // XTerm
+            self.restore_mode(MODE_MOUSE_1016);
+        //////////////////////////////////////////////////////////////
         } else if token == ty_csi_pr!('r', 1034) {
             // IGNORED: 8bitinput activation
             // XTerm
@@ -1210,6 +1365,78 @@ impl VT102Emulation {
             self.reset_mode(MODE_APP_SCREEN);
             self.restore_cursor();
         //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_pr!('h', 2026) {
+            // Synchronized output
+            self.emulation_mut().set_synchronized_update(true);
+        } else if token == ty_csi_pr!('l', 2026) {
+            // Synchronized output
+            self.emulation_mut().set_synchronized_update(false);
+        } else if token == ty_csi_pr!('h', 2027) {
+            // Grapheme cluster
+            self.set_mode(MODE_GRAPHEME_CLUSTER);
+        } else if token == ty_csi_pr!('l', 2027) {
+            // Grapheme cluster
+            self.reset_mode(MODE_GRAPHEME_CLUSTER);
+        } else if token == ty_csi_pr!('s', 2027) {
+            // Grapheme cluster
+            self.save_mode(MODE_GRAPHEME_CLUSTER);
+        } else if token == ty_csi_pr!('r', 2027) {
+            // Grapheme cluster
+            self.restore_mode(MODE_GRAPHEME_CLUSTER);
+        //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_dl!('v') {
+            // DECCRA: copy rectangular area, the pages were ignored.
+            // VT400
+            current_screen.copy_rect(
+                self.argument(0),
+                self.argument(1),
+                self.argument(2),
+                self.argument(3),
+                self.argument(5),
+                self.argument(6),
+            );
+        } else if token == ty_csi_dl!('x') {
+            // DECFRA: fill rectangular area
+            // VT400
+            current_screen.fill_rect(
+                p,
+                self.argument(1),
+                self.argument(2),
+                self.argument(3),
+                self.argument(4),
+            );
+        } else if token == ty_csi_dl!('z') || token == ty_csi_dl!('{') {
+            // DECERA: erase rectangular area -or- DECSERA: selective erase rectangular area
+            // VT400
+            current_screen.erase_rect(
+                self.argument(0),
+                self.argument(1),
+                self.argument(2),
+                self.argument(3),
+                token == ty_csi_dl!('{'),
+            );
+        } else if token == ty_csi_dl!('r') || token == ty_csi_dl!('t') {
+            // DECCARA: change attributes -or- DECRARA: reverse attributes in rectangular area
+            // VT400
+            let attributes: Vec<i32> = (4..=(self.argc as usize).max(4))
+                .map(|i| self.argument(i))
+                .collect();
+            current_screen.change_rect_rendition(
+                self.argument(0),
+                self.argument(1),
+                self.argument(2),
+                self.argument(3),
+                &attributes,
+                token == ty_csi_dl!('t'),
+            );
+        //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_dl!('p') {
+            // DECRQM: request ANSI mode
+            self.report_ansi_mode(p as i32);
+        } else if token == ty_csi_pd!('p') {
+            // DECRQM: request private mode
+            self.report_private_mode(p as i32);
+        //////////////////////////////////////////////////////////////
         } else if token == ty_csi_pr!('h', 2004) {
             // XTerm
             self.set_mode(MODE_BRACKETD_PASTE);
@@ -1226,7 +1453,6 @@ impl VT102Emulation {
         } else if token == ty_csi_pe!('p') {
             // IGNORED: reset
             //////////////////////////////////////////////////////////////
-            // FIXME: when changing between vt52 and ansi mode evtl do some resetting.
         } else if token == ty_vt52!('A') {
             // VT52
             current_screen.cursor_up(1);
@@ -1242,10 +1468,10 @@ impl VT102Emulation {
         //////////////////////////////////////////////////////////////
         } else if token == ty_vt52!('F') {
             // VT52
-            self.set_and_use_charset(0, '0' as i32);
+            self.set_and_use_charset(0, Charset::DecSpecialGraphics);
         } else if token == ty_vt52!('G') {
             // VT52
-            self.set_and_use_charset(0, 'B' as i32);
+            self.set_and_use_charset(0, Charset::Ascii);
         //////////////////////////////////////////////////////////////
         } else if token == ty_vt52!('H') {
             // VT52
@@ -1267,7 +1493,7 @@ impl VT102Emulation {
             self.report_terminal_type();
         } else if token == ty_vt52!('<') {
             // VT52
-            self.set_mode(MODE_ANSI);
+            self.leave_vt52_mode();
         } else if token == ty_vt52!('=') {
             // VT52
             self.set_mode(MODE_APP_KEY_PAD);
@@ -1277,6 +1503,43 @@ impl VT102Emulation {
         //////////////////////////////////////////////////////////////
         } else if token == ty_csi_pg!('c') {
             self.report_secondary_attributes();
+        } else if token == ty_csi_pg!('q') {
+            // XTVERSION
+            self.report_version();
+        //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_pg!('m') {
+            // XTerm: set modifyOtherKeys, other resources were ignored
+            if p == 4 {
+                self.modify_other_keys = ModifyOtherKeys::from_level(q);
+            }
+        } else if token == ty_csi_pg!('n') {
+            // XTerm: disable modifyOtherKeys
+            if p == 4 {
+                self.modify_other_keys = ModifyOtherKeys::Disabled;
+            }
+        } else if token == ty_csi_pr!('m', 4) {
+            // XTerm: query modifyOtherKeys
+            self.report_modify_other_keys();
+        //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_pg!('u') {
+            // Kitty keyboard protocol: push flags
+            self.keyboard_flags_mut().push(p as u8);
+        } else if token == ty_csi_pl!('u') {
+            // Kitty keyboard protocol: pop flags
+            self.keyboard_flags_mut().pop(p as usize);
+        } else if token == ty_csi_pq!('u') {
+            // Kitty keyboard protocol: set flags
+            self.keyboard_flags_mut().set(p as u8, q);
+        } else if token == ty_csi_pr!('u', 0) {
+            // Kitty keyboard protocol: query flags
+            self.report_keyboard_flags();
+        //////////////////////////////////////////////////////////////
+        } else if token == ty_csi_pr!('S', 1)
+            || token == ty_csi_pr!('S', 2)
+            || token == ty_csi_pr!('S', 3)
+        {
+            // XTerm: XTSMGRAPHICS, query the graphics attributes
+            self.report_graphics_attribute(q, p as i32);
         //////////////////////////////////////////////////////////////
         } else {
             self.report_decoding_error();
@@ -1286,193 +1549,941 @@ impl VT102Emulation {
     fn process_window_attribute_change(&mut self) {
         // Describes the window or terminal session attribute to change
         // See [`Session::user_title_change`] for possible values
-        let mut attribute_to_change = 0;
-        let mut i = 2;
-        loop {
-            if !(i < self.token_buffer_pos
-                && self.token_buffer[i] >= wch!('0')
-                && self.token_buffer[i] <= wch!('9'))
-            {
-                break;
-            }
-
-            attribute_to_change =
-                10 * attribute_to_change + (self.token_buffer[i] as i32 - wch!('0') as i32);
-            i += 1;
-        }
-
-        if self.token_buffer[i] != wch!(';') {
-            self.report_decoding_error();
+        //
+        // Note that as control characters in OSC text parts are ignored, and the
+        // ending delimiter 0x07 or "\e\\" was not added to `osc_text`.
+        let osc_text = std::mem::take(&mut self.osc_text);
+        if std::mem::take(&mut self.osc_text_overflow) {
+            warn!("The OSC escape sequence exceeded the maximum length, discarded.");
             return;
         }
-
-        // copy from the first char after ';', and skipping the ending delimiter
-        // 0x07 or 0x92. Note that as control characters in OSC text parts are
-        // ignored, only the second char in ST ("\e\\") is appended to tokenBuffer.
-        let mut new_value = WideString::new();
-        let slice: &[uwchar_t] = unsafe {
-            std::mem::transmute(&self.token_buffer[i + 1..(self.token_buffer_pos - i - 2).max(i + 1)])
+        let (attribute_to_change, new_value) = match osc_text.split_once(';') {
+            Some((attribute, value)) => match attribute.parse::<i32>() {
+                Ok(attribute) => (attribute, value),
+                Err(_) => {
+                    self.report_decoding_error();
+                    return;
+                }
+            },
+            // The reset forms like `OSC 104 ST` has no value.
+            None => match osc_text.parse::<i32>() {
+                Ok(attribute) => (attribute, ""),
+                Err(_) => {
+                    self.report_decoding_error();
+                    return;
+                }
+            },
         };
-        new_value.push_slice(slice);
 
-        self.pending_title_updates.insert(
-            attribute_to_change,
-            new_value
-                .to_string()
-                .expect("`wchar_tString` transmit to String failed."),
-        );
-        // TODO: Update title update timer
+        match attribute_to_change {
+            4 => self.process_palette_color(new_value),
+            7 => self.process_working_directory(new_value),
+            8 => self.process_hyperlink(new_value),
+            9 => match new_value.strip_prefix('4') {
+                Some(progress) if progress.is_empty() || progress.starts_with(';') => {
+                    self.process_progress(progress)
+                }
+                _ => self.process_notification(Notification::parse_osc9(new_value)),
+            },
+            10..=12 => self.process_default_color(attribute_to_change, new_value),
+            52 => self.process_clipboard(new_value),
+            99 => {
+                let notification = self.kitty_notifications.receive(new_value);
+                self.process_notification(notification)
+            }
+            104 | 110..=112 => self.process_color_reset(attribute_to_change, new_value),
+            133 => self.process_semantic_prompt(new_value),
+            777 => self.process_notification(Notification::parse_osc777(new_value)),
+            _ => self.set_title(attribute_to_change, new_value.to_string()),
+        }
     }
 
-    fn request_window_attribute(&self, _p: i32) {
-        // No implementation
+    /// Process the `OSC 7` current working directory: `ESC ] 7 ; file://host/path ST`
+    fn process_working_directory(&mut self, value: &str) {
+        if let Some(dir) = WorkingDirectory::parse(value) {
+            emit!(self, working_directory_changed(dir.host, dir.path));
+        } else {
+            self.report_decoding_error();
+        }
     }
 
-    fn add_to_current_token(&mut self, cc: wchar_t) {
-        let pos = self.token_buffer_pos;
-        self.token_buffer[pos] = cc;
-        self.token_buffer_pos = (pos + 1).min(MAX_TOKEN_LENGTH - 1);
+    /// Process the desktop notification of `OSC 9/777/99`, the rate limiting was done by session.
+    fn process_notification(&mut self, notification: Option<Notification>) {
+        if let Some(Notification { title, body }) = notification {
+            emit!(self, notification_request(title, body));
+        }
     }
 
-    fn add_digit(&mut self, digit: i32) {
-        if self.argv[self.argc as usize] < MAX_ARGUMENT as i32 {
-            self.argv[self.argc as usize] = 10 * self.argv[self.argc as usize] + digit;
+    /// Process the ConEmu's progress: `ESC ] 9 ; 4 ; st ; pr ST`
+    fn process_progress(&mut self, value: &str) {
+        if let Some(progress) = Progress::parse(value) {
+            emit!(
+                self,
+                progress_changed(progress.state as u8, progress.percent)
+            );
+        } else {
+            self.report_decoding_error();
         }
     }
 
-    fn add_argument(&mut self) {
-        self.argc = (self.argc + 1).min(MAXARGS as i32 - 1);
-        self.argv[self.argc as usize] = 0;
+    /// Process the `OSC 8` hyperlink: `ESC ] 8 ; params ; URI ST`,
+    /// the hyperlink with empty URI indicates the end of current hyperlink.
+    fn process_hyperlink(&mut self, value: &str) {
+        let hyperlink = Hyperlink::parse(value).map_or(NO_HYPERLINK, HyperlinkTable::register);
+        self.current_screen_mut().set_hyperlink(hyperlink);
     }
 
-    //////////////////////////////////////// VT100 Charset ////////////////////////////////////////
-    /*
-       The processing contains a VT100 specific code translation layer.
-       It's still in use and mainly responsible for the line drawing graphics.
+    /// Process the `OSC 4` palette color: `ESC ] 4 ; c ; spec [; c ; spec ...] ST`
+    fn process_palette_color(&mut self, value: &str) {
+        let mut params = value.split(';');
+        while let Some(index) = params.next() {
+            let (index, spec) = match (index.parse::<u8>(), params.next()) {
+                (Ok(index), Some(spec)) => (index, spec),
+                _ => {
+                    self.report_decoding_error();
+                    return;
+                }
+            };
+            self.process_dynamic_color(DynamicColor::Palette(index), spec);
+        }
+    }
 
-       These and some other glyphs are assigned to codes (0x5f-0xfe)
-       normally occupied by the latin letters. Since this codes also
-       appear within control sequences, the extra code conversion
-       does not permute with the tokenizer and is placed behind it
-       in the pipeline. It only applies to tokens, which represent
-       plain characters.
+    /// Process the `OSC 10/11/12` default colors: `ESC ] Ps ; spec [; spec ...] ST`,
+    /// the following specs apply to the next colors, e.g. `OSC 10 ; fg ; bg ST`.
+    fn process_default_color(&mut self, ps: i32, value: &str) {
+        for (i, spec) in value.split(';').enumerate() {
+            match DynamicColor::from_osc(ps + i as i32) {
+                Some(color) => self.process_dynamic_color(color, spec),
+                None => break,
+            }
+        }
+    }
 
-       This conversion it eventually continued in TerminalDisplay.C, since
-       it might involve VT100 enhanced fonts, which have these
-       particular glyphs allocated in (0x00-0x1f) in their code page.
-    */
-    fn charset(&mut self) -> &mut CharCodes {
-        unsafe {
-            self.charset
-                .get_mut(
-                    if self
-                        .emulation
-                        .as_ref()
-                        .unwrap()
-                        .current_screen
-                        .as_ref()
-                        .unwrap()
-                        .as_ref()
-                        .id()
-                        == self.emulation.as_ref().unwrap().screen[1].id()
-                    {
-                        1
-                    } else {
-                        0
-                    },
-                )
-                .unwrap()
+    fn process_dynamic_color(&mut self, color: DynamicColor, spec: &str) {
+        if spec == "?" {
+            emit!(self, color_query_request(color.code()));
+        } else if let Some((r, g, b)) = parse_color_spec(spec) {
+            let rgb = u32::from_be_bytes([0, r, g, b]);
+            emit!(self, color_change_request(color.code(), rgb));
+        } else {
+            self.report_decoding_error();
         }
     }
 
-    fn apply_charset(&mut self, c: wchar_t) -> wchar_t {
-        if self.charset().graphic && (0x5f..=0x7e).contains(&c) {
-            return VT100_GRAPHICS[(c - 0x5f) as usize];
+    /// Process the color reset:
+    ///
+    /// - `OSC 104 [; c ...] ST`: Reset the palette colors, all of them if no `c` was specified.
+    /// - `OSC 110/111/112 ST`: Reset the default foreground/background/cursor color.
+    fn process_color_reset(&mut self, ps: i32, value: &str) {
+        if ps != 104 {
+            if let Some(color) = DynamicColor::from_osc(ps - 100) {
+                emit!(self, color_reset_request(color.code()));
+            }
+            return;
+        }
+
+        if value.is_empty() {
+            emit!(self, color_reset_request(DynamicColor::ALL_PALETTE));
+            return;
         }
-        if self.charset().pound && c == wch!('#') {
-            return 0xa3;
+        for index in value.split(';') {
+            match index.parse::<u8>() {
+                Ok(index) => emit!(
+                    self,
+                    color_reset_request(DynamicColor::Palette(index).code())
+                ),
+                Err(_) => self.report_decoding_error(),
+            }
         }
-        c
     }
 
-    /// "Charset" related part of the emulation state. This configures the VT100 charset filter.
+    /// Process the `OSC 52` clipboard access: `ESC ] 52 ; Pc ; Pd ST`,
+    /// `Pd` is the base64 encoded text to set, or `?` to query the clipboard.
     ///
-    /// While most operation work on the current _screen, the following two are different.
-    fn reset_charset(&mut self, scrno: usize) {
-        self.charset[scrno].current_charset = 0;
-        self.charset[scrno]
-            .charset
-            .copy_from_slice("BBBB".as_bytes());
-        self.charset[scrno].saved_graphic = false;
-        self.charset[scrno].saved_pound = false;
-        self.charset[scrno].graphic = false;
-        self.charset[scrno].pound = false;
+    /// Whether the access was permitted is decided by the session.
+    fn process_clipboard(&mut self, value: &str) {
+        let (selector, data) = value.split_once(';').unwrap_or(("", value));
+        let selection = ClipboardSelection::from_selector(selector) as u8;
+
+        if data == "?" {
+            emit!(self, clipboard_read_request(selection));
+        } else if let Some(text) = decode_clipboard_data(data) {
+            emit!(self, clipboard_write_request(selection, text));
+        } else {
+            self.report_decoding_error();
+        }
     }
 
-    fn set_charset(&mut self, n: i32, cs: u8) {
-        self.charset[0].charset[(n & 3) as usize] = cs;
-        self.use_charset(self.charset[0].current_charset);
-        self.charset[1].charset[(n & 3) as usize] = cs;
-        self.use_charset(self.charset[1].current_charset);
-    }
+    /// Process the `OSC 133` semantic prompt marks of shell integration: `ESC ] 133 ; Ps [; options] ST`
+    ///
+    /// - `A`: Prompt start.
+    /// - `B`: Command start (the end of prompt).
+    /// - `C`: Command output start.
+    /// - `D [; exit_code]`: Command finished.
+    ///
+    /// The marks are stored as properties of the cursor line.
+    fn process_semantic_prompt(&mut self, value: &str) {
+        let mut params = value.split(';');
+        let mark = match params.next().unwrap_or_default() {
+            "A" => LINE_PROMPT_START,
+            "B" => LINE_COMMAND_START,
+            "C" => LINE_OUTPUT_START,
+            "D" => LINE_COMMAND_FINISHED,
+            _ => {
+                self.report_decoding_error();
+                return;
+            }
+        };
+        self.current_screen_mut().set_line_property(mark, true);
 
-    fn use_charset(&mut self, n: i32) {
-        let charset = self.charset();
-        charset.current_charset = n & 3;
-        charset.graphic = charset.charset[(n & 3) as usize] == b'0';
-        // This mode is obsolete.
-        charset.pound = charset.charset[(n & 3) as usize] == b'A';
+        if mark == LINE_COMMAND_FINISHED {
+            if let Some(exit_code) = params.next().and_then(|code| code.parse::<i32>().ok()) {
+                emit!(self, command_finished(exit_code));
+            }
+        }
     }
 
-    fn set_and_use_charset(&mut self, n: i32, cs: i32) {
-        self.charset().charset[(n & 3) as usize] = cs as u8;
-        self.use_charset(n & 3);
+    /// Encode the mouse event in the current mouse mode and send it to the terminal program.
+    ///
+    /// @param cb: The xterm button code with modifier bits, see [`MOUSE_NO_BUTTON`] etc.
+    /// @param cx, cy: The 1-based position in characters, or in pixels in 1016 mode.
+    fn report_mouse_event(&self, cb: i32, cx: i32, cy: i32, event_type: u8) {
+        if let Some(command) = self.encode_mouse_event(cb, cx, cy, event_type) {
+            let len = command.len() as i32;
+            self.send_string(command, len)
+        }
     }
 
-    fn save_cursor(&mut self) {
-        let charset = self.charset();
-        charset.saved_graphic = charset.graphic;
-        charset.saved_pound = charset.pound;
-        // we are not clear about these
-        // sa_charset = charsets[cScreen->_charset];
-        // sa_charset_num = cScreen->_charset;
-        unsafe {
-            self.emulation
-                .as_mut()
-                .unwrap()
-                .current_screen
-                .as_mut()
-                .unwrap()
-                .as_mut()
-                .save_cursor();
+    /// The report of [`Self::report_mouse_event`], `None` if the event shouldn't be reported.
+    fn encode_mouse_event(&self, mut cb: i32, cx: i32, cy: i32, event_type: u8) -> Option<String> {
+        if cx < 1 || cy < 1 {
+            return None;
+        }
+        if !self.get_mode(MODE_MOUSE_1000)
+            && !self.get_mode(MODE_MOUSE_1002)
+            && !self.get_mode(MODE_MOUSE_1003)
+        {
+            return None;
         }
-    }
 
-    fn restore_cursor(&mut self) {
-        let charset = self.charset();
-        charset.graphic = charset.saved_graphic;
-        charset.pound = charset.saved_pound;
-        unsafe {
-            self.emulation
-                .as_mut()
-                .unwrap()
-                .current_screen
-                .as_mut()
-                .unwrap()
-                .as_mut()
-                .resotre_cursor();
+        // Handle mouse motion:
+        // 1002 reports the motion only when a button was pressed, 1003 reports any motion.
+        // add 32 to signify motion event
+        if event_type == MOUSE_MOVE {
+            let button_pressed = cb & MOUSE_NO_BUTTON != MOUSE_NO_BUTTON;
+            if !self.get_mode(MODE_MOUSE_1003)
+                && !(self.get_mode(MODE_MOUSE_1002) && button_pressed)
+            {
+                return None;
+            }
+            cb += MOUSE_MOTION;
+        }
+
+        let sgr = self.get_mode(MODE_MOUSE_1006) || self.get_mode(MODE_MOUSE_1016);
+        if event_type == MOUSE_RELEASE {
+            // Mouse wheel has no release event.
+            if cb & MOUSE_WHEEL_UP != 0 {
+                return None;
+            }
+            // With the exception of the SGR modes, button release is encoded in cb.
+            if !sgr {
+                cb = MOUSE_NO_BUTTON | (cb & MOUSE_MODIFIERS);
+            }
+        }
+
+        // Check the extensions in decreasing order of preference. Encoding the
+        // release event above assumes that SGR modes come first.
+        if sgr {
+            Some(format!(
+                "\x1B[<{};{};{}{}",
+                cb,
+                cx,
+                cy,
+                if event_type == MOUSE_RELEASE {
+                    'm'
+                } else {
+                    'M'
+                }
+            ))
+        } else if self.get_mode(MODE_MOUSE_1015) {
+            Some(format!("\x1B[{};{};{}M", cb + 0x20, cx, cy))
+        } else if self.get_mode(MODE_MOUSE_1005) {
+            // Out of the range, the position can't be encoded.
+            if cx > 2015 || cy > 2015 {
+                return None;
+            }
+            // The xterm extension uses UTF-8 (up to 2 bytes) to encode
+            // coordinate+32, no matter what the locale is.
+            let coords: String = [
+                char::from_u32(cx as u32 + 0x20).unwrap(),
+                char::from_u32(cy as u32 + 0x20).unwrap(),
+            ]
+            .iter()
+            .collect();
+            Some(format!("\x1B[M{}{}", (cb as u8 + 0x20) as char, coords))
+        } else if cx <= 223 && cy <= 223 {
+            Some(format!(
+                "\x1B[M{}{}{}",
+                (cb as u8 + 0x20) as char,
+                (cx as u8 + 0x20) as char,
+                (cy as u8 + 0x20) as char
+            ))
+        } else {
+            None
         }
     }
 
-    fn set_margins(&mut self, top: i32, bottom: i32) {
-        self.emulation_mut().screen[0].set_margins(top, bottom);
-        self.emulation_mut().screen[1].set_margins(top, bottom);
+    /// Process the DCS escape sequence: `ESC P <params> <intermediates> <final> <data> ST`
+    fn process_device_control_string(&mut self) {
+        // Like OSC, the control characters in DCS were ignored, and the ending
+        // delimiter "\e\\" was not added to `dcs_text`.
+        let dcs_text = std::mem::take(&mut self.dcs_text);
+        if std::mem::take(&mut self.dcs_text_overflow) {
+            warn!("The DCS escape sequence exceeded the maximum length, discarded.");
+            return;
+        }
+
+        let params_end = dcs_text
+            .find(|c: char| !c.is_ascii_digit() && c != ';' && c != ':')
+            .unwrap_or(dcs_text.len());
+        let (params, rest) = dcs_text.split_at(params_end);
+        let intermediates_end = rest
+            .find(|c: char| !(' '..='/').contains(&c))
+            .unwrap_or(rest.len());
+        let (intermediates, rest) = rest.split_at(intermediates_end);
+        let mut chars = rest.chars();
+        let final_char = chars.next();
+        let data = chars.as_str();
+
+        match (intermediates, final_char) {
+            ("", Some('q')) => self.process_sixel(params, data),
+            // DECRQSS: request selection or setting
+            ("$", Some('q')) => self.report_control_function(data),
+            // XTGETTCAP: request termcap/terminfo capabilities
+            ("+", Some('q')) => self.send_string(xtgettcap_response(data), -1),
+            _ => warn!(
+                "Unsupported DCS escape sequence, intermediates: {:?}, final: {:?}",
+                intermediates, final_char
+            ),
+        }
     }
 
-    /// Set margins for all screens back to their defaults.
-    fn set_default_margins(&mut self) {
-        self.emulation_mut().screen[0].set_default_margins();
-        self.emulation_mut().screen[1].set_default_margins();
+    /// Process the sixel graphics: `ESC P P1 ; P2 ; P3 q <sixel data> ST`, the image was
+    /// placed at the cursor position, and the cursor was moved to the line below the image.
+    fn process_sixel(&mut self, params: &str, data: &str) {
+        let image = match decode_sixel(params, data) {
+            Some(image) => image,
+            None => return,
+        };
+
+        let (cell_width, cell_height) = self.emulation().cell_size();
+        let columns = (image.width() as f32 / cell_width).ceil() as i32;
+        let lines = (image.height() as f32 / cell_height).ceil() as i32;
+
+        self.current_screen_mut()
+            .add_image(Rc::new(image), columns, lines);
+    }
+
+    /// Process the APC escape sequence: `ESC _ <text> ST`, only the kitty graphics
+    /// commands `ESC _ G <control data> ; <payload> ST` were supported.
+    fn process_application_program_command(&mut self) {
+        let apc_text = std::mem::take(&mut self.apc_text);
+        if std::mem::take(&mut self.apc_text_overflow) {
+            warn!("The APC escape sequence exceeded the maximum length, discarded.");
+            return;
+        }
+
+        match apc_text.strip_prefix('G') {
+            Some(text) => self.process_kitty_graphics(text),
+            None => warn!("Unsupported APC escape sequence."),
+        }
+    }
+
+    /// Process the command of kitty graphics protocol,
+    /// see https://sw.kovidgoyal.net/kitty/graphics-protocol/
+    fn process_kitty_graphics(&mut self, text: &str) {
+        let command = match GraphicsCommand::parse(text) {
+            Some(command) => command,
+            None => {
+                warn!("Malformed kitty graphics command: {}", text);
+                return;
+            }
+        };
+
+        match command.action {
+            // The chunks after the first one may only carry the `m` key.
+            _ if self.image_store.is_loading() => self.process_kitty_transmission(command),
+            't' | 'T' | 'q' => self.process_kitty_transmission(command),
+            'p' => self.process_kitty_placement(command),
+            'd' => self.process_kitty_deletion(command),
+            _ => self.report_kitty_graphics(
+                &command,
+                command.image_id,
+                Err("EINVAL:unsupported action".to_string()),
+            ),
+        }
+    }
+
+    /// Transmits(`a=t`), transmits and displays(`a=T`), or queries(`a=q`) the image.
+    /// The query only checks whether the image could be loaded, nothing was stored.
+    fn process_kitty_transmission(&mut self, command: GraphicsCommand) {
+        let (command, result) = match self.image_store.receive(command) {
+            Some(received) => received,
+            None => return,
+        };
+        let image = match result {
+            Ok(image) => image,
+            Err(err) => {
+                self.report_kitty_graphics(&command, command.image_id, Err(err));
+                return;
+            }
+        };
+        if command.action == 'q' {
+            self.report_kitty_graphics(&command, command.image_id, Ok(()));
+            return;
+        }
+
+        let id = match command.image_id {
+            0 => self.image_store.allocate_id(),
+            id => id,
+        };
+        let image = self.image_store.insert(id, command.image_number, image);
+
+        let result = if command.action == 'T' {
+            self.place_kitty_image(&command, id, image)
+        } else {
+            Ok(())
+        };
+        self.report_kitty_graphics(&command, id, result);
+    }
+
+    /// Displays(`a=p`) the image transmitted previously, referred by image id or number.
+    fn process_kitty_placement(&mut self, command: GraphicsCommand) {
+        let id = match (command.image_id, command.image_number) {
+            (0, 0) => None,
+            (0, number) => self.image_store.id_of_number(number),
+            (id, _) => Some(id),
+        };
+
+        let result = match id.and_then(|id| self.image_store.get(id).map(|image| (id, image))) {
+            Some((id, image)) => self.place_kitty_image(&command, id, image),
+            None => Err("ENOENT:image not found".to_string()),
+        };
+        self.report_kitty_graphics(&command, id.unwrap_or(command.image_id), result);
+    }
+
+    /// Places the image at the cursor position.
+    ///
+    /// The source rectangle `x`, `y`, `w`, `h` was cropped from the image, and scaled to fit
+    /// `c` x `r` cells if specified. The cursor was moved to the right of the last line of
+    /// image, unless `C=1`.
+    fn place_kitty_image(
+        &mut self,
+        command: &GraphicsCommand,
+        id: u32,
+        image: Rc<TerminalImage>,
+    ) -> Result<(), String> {
+        let image = if command.x == 0 && command.y == 0 && command.w == 0 && command.h == 0 {
+            image
+        } else {
+            let w = if command.w == 0 {
+                image.width()
+            } else {
+                command.w
+            };
+            let h = if command.h == 0 {
+                image.height()
+            } else {
+                command.h
+            };
+            match image.cropped(command.x, command.y, w, h) {
+                Some(cropped) => Rc::new(cropped),
+                None => return Err("EINVAL:the source rectangle was empty".to_string()),
+            }
+        };
+
+        let (cell_width, cell_height) = self.emulation().cell_size();
+        let (x_offset, y_offset) = (command.x_offset as f32, command.y_offset as f32);
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let (width, height) = match (command.columns, command.rows) {
+            (0, 0) => (width, height),
+            (columns, 0) => {
+                let w = (columns as f32 * cell_width - x_offset).max(1.);
+                (w, height * w / width)
+            }
+            (0, rows) => {
+                let h = (rows as f32 * cell_height - y_offset).max(1.);
+                (width * h / height, h)
+            }
+            (columns, rows) => (
+                (columns as f32 * cell_width - x_offset).max(1.),
+                (rows as f32 * cell_height - y_offset).max(1.),
+            ),
+        };
+        let image = if width as u32 == image.width() && height as u32 == image.height() {
+            image
+        } else {
+            Rc::new(image.scaled(width as u32, height as u32))
+        };
+
+        let columns = ((width + x_offset) / cell_width).ceil().max(1.) as i32;
+        let lines = ((height + y_offset) / cell_height).ceil().max(1.) as i32;
+
+        let screen = self.current_screen_mut();
+        let mut placement = ImagePlacement::new(
+            image,
+            screen.get_history_lines() + screen.get_cursor_y(),
+            screen.get_cursor_x(),
+            lines,
+            columns,
+        );
+        placement.image_id = id;
+        placement.placement_id = command.placement_id;
+        placement.z_index = command.z_index;
+        placement.x_offset = command.x_offset;
+        placement.y_offset = command.y_offset;
+        screen.add_image_placement(placement);
+
+        if command.cursor_movement != 1 {
+            for _ in 1..lines {
+                screen.index();
+            }
+            screen.cursor_right(columns);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes(`a=d`) the image placements, the uppercase specifiers free the data of images
+    /// as well when they were no longer placed on both screens.
+    fn process_kitty_deletion(&mut self, command: GraphicsCommand) {
+        let number_id = self.image_store.id_of_number(command.image_number);
+        let screen = self.current_screen_mut();
+        let history_lines = screen.get_history_lines();
+        let screen_lines = screen.get_lines();
+        let cursor = (history_lines + screen.get_cursor_y(), screen.get_cursor_x());
+        // The cell coordinates of `x` and `y` were 1-based.
+        let (x, y) = (command.x as i32 - 1, history_lines + command.y as i32 - 1);
+        let matches_id = |p: &ImagePlacement, id: u32| {
+            p.image_id == id
+                && (command.placement_id == 0 || p.placement_id == command.placement_id)
+        };
+
+        let removed = screen.remove_image_placements(|p| match command.delete {
+            'a' | 'A' => {
+                p.image_id != 0
+                    && p.line + p.lines > history_lines
+                    && p.line < history_lines + screen_lines
+            }
+            'i' | 'I' => command.image_id != 0 && matches_id(p, command.image_id),
+            'n' | 'N' => number_id.map_or(false, |id| matches_id(p, id)),
+            'c' | 'C' => p.image_id != 0 && p.intersects(cursor.0, cursor.1),
+            'p' | 'P' => p.image_id != 0 && p.intersects(y, x),
+            'q' | 'Q' => p.image_id != 0 && p.intersects(y, x) && p.z_index == command.z_index,
+            'x' | 'X' => p.image_id != 0 && (p.column..p.column + p.columns).contains(&x),
+            'y' | 'Y' => p.image_id != 0 && (p.line..p.line + p.lines).contains(&y),
+            'z' | 'Z' => p.image_id != 0 && p.z_index == command.z_index,
+            'r' | 'R' => p.image_id != 0 && (command.x..=command.y).contains(&p.image_id),
+            _ => false,
+        });
+
+        if !command.delete.is_ascii_uppercase() {
+            return;
+        }
+        let mut ids: HashSet<u32> = removed.iter().map(|p| p.image_id).collect();
+        match command.delete {
+            'I' if command.image_id != 0 => {
+                ids.insert(command.image_id);
+            }
+            'N' => ids.extend(number_id),
+            'R' => ids.extend(
+                self.image_store
+                    .ids()
+                    .into_iter()
+                    .filter(|id| (command.x..=command.y).contains(id)),
+            ),
+            _ => {}
+        }
+
+        for id in ids {
+            let placed = self
+                .emulation()
+                .screen
+                .iter()
+                .any(|screen| screen.image_placements().iter().any(|p| p.image_id == id));
+            if !placed {
+                self.image_store.remove(id);
+            }
+        }
+    }
+
+    fn report_kitty_graphics(
+        &self,
+        command: &GraphicsCommand,
+        image_id: u32,
+        result: Result<(), String>,
+    ) {
+        if let Some(response) = graphics_response(command, image_id, &result) {
+            self.send_string(response, -1);
+        }
+    }
+
+    /// The size in characters requested by XTWINOPS `CSI 8 ; lines ; columns t`,
+    /// zero keeps the current lines or columns.
+    fn resize_request_size(&self, lines: i32, columns: i32) -> Size {
+        let screen = self.emulation().current_screen();
+        let lines = if lines == 0 {
+            screen.get_lines()
+        } else {
+            lines
+        };
+        let columns = if columns == 0 {
+            screen.get_columns()
+        } else {
+            columns
+        };
+        Size::new(columns, lines)
+    }
+
+    #[inline]
+    fn request_window_attribute(&self, p: i32) {
+        if let Some(response) = self.window_attribute_response(p) {
+            self.send_string(response, -1)
+        }
+    }
+
+    /// Response of XTWINOPS reports: `CSI 4 ; height ; width t` for the text area size in
+    /// pixels(14), `CSI 6 ; height ; width t` for the cell size in pixels(16) and
+    /// `CSI 8 ; lines ; columns t` for the text area size in characters(18).
+    fn window_attribute_response(&self, p: i32) -> Option<String> {
+        let screen = self.emulation().current_screen();
+        let (lines, columns) = (screen.get_lines(), screen.get_columns());
+        // Same as the view, the cells were aligned to the whole pixels.
+        let (cell_width, cell_height) = self.emulation().cell_size();
+        let (cell_width, cell_height) = (cell_width.ceil() as i32, cell_height.ceil() as i32);
+
+        let response = match p {
+            14 => format!(
+                "\u{001b}[4;{};{}t",
+                lines * cell_height,
+                columns * cell_width
+            ),
+            16 => format!("\u{001b}[6;{};{}t", cell_height, cell_width),
+            18 => format!("\u{001b}[8;{};{}t", lines, columns),
+            _ => return None,
+        };
+        Some(response)
+    }
+
+    /// Buffer the title update, the updates in [`TITLE_UPDATE_DELAY`] were emitted together,
+    /// see [`Self::update_title`].
+    fn set_title(&mut self, attribute: i32, title: String) {
+        match attribute {
+            0 => self.titles = [title.clone(), title.clone()],
+            1 => self.titles[0] = title.clone(),
+            2 => self.titles[1] = title.clone(),
+            _ => {}
+        }
+        self.pending_title_updates.insert(attribute, title);
+
+        self.title_update_timer.set_single_shot(true);
+        self.title_update_timer
+            .start(Duration::from_millis(TITLE_UPDATE_DELAY));
+    }
+
+    /// XTWINOPS `CSI 22 ; Ps t`: save the icon and window title(0), icon title(1)
+    /// or window title(2) on the stack.
+    fn push_title(&mut self, which: i32) {
+        if !(0..=2).contains(&which) {
+            return;
+        }
+        if self.title_stack.len() >= MAX_TITLE_STACK_DEPTH {
+            self.title_stack.remove(0);
+        }
+
+        let icon = (which != 2).then(|| self.titles[0].clone());
+        let window = (which != 1).then(|| self.titles[1].clone());
+        self.title_stack.push([icon, window]);
+    }
+
+    /// XTWINOPS `CSI 23 ; Ps t`: restore the titles saved by [`Self::push_title`],
+    /// `Ps` has the same meaning.
+    fn pop_title(&mut self, which: i32) {
+        if !(0..=2).contains(&which) {
+            return;
+        }
+        if let Some([icon, window]) = self.title_stack.pop() {
+            if let Some(icon) = icon.filter(|_| which != 2) {
+                self.set_title(1, icon);
+            }
+            if let Some(window) = window.filter(|_| which != 1) {
+                self.set_title(2, window);
+            }
+        }
+    }
+
+    fn add_intermediate(&mut self, cc: wchar_t) {
+        if self.intermediate_count < MAX_INTERMEDIATES {
+            self.intermediates[self.intermediate_count] = cc;
+        }
+        self.intermediate_count += 1;
+    }
+
+    /// The intermediate characters of ESC or CSI, `None` if there were too many.
+    #[inline]
+    fn intermediates(&self) -> Option<&[wchar_t]> {
+        self.intermediates.get(..self.intermediate_count)
+    }
+
+    fn add_to_osc_text(&mut self, cc: wchar_t) {
+        if self.osc_text.len() >= MAX_OSC_LENGTH {
+            self.osc_text_overflow = true;
+            return;
+        }
+        if let Some(c) = char::from_u32(cc as u32) {
+            self.osc_text.push(c);
+        }
+    }
+
+    fn add_to_dcs_text(&mut self, cc: wchar_t) {
+        if self.dcs_text.len() >= MAX_DCS_LENGTH {
+            self.dcs_text_overflow = true;
+            return;
+        }
+        if let Some(c) = char::from_u32(cc as u32) {
+            self.dcs_text.push(c);
+        }
+    }
+
+    fn add_to_apc_text(&mut self, cc: wchar_t) {
+        if self.apc_text.len() >= MAX_APC_LENGTH {
+            self.apc_text_overflow = true;
+            return;
+        }
+        if let Some(c) = char::from_u32(cc as u32) {
+            self.apc_text.push(c);
+        }
+    }
+
+    fn add_digit(&mut self, digit: i32) {
+        if self.argv[self.argc as usize] < MAX_ARGUMENT as i32 {
+            self.argv[self.argc as usize] = 10 * self.argv[self.argc as usize] + digit;
+        }
+    }
+
+    fn add_argument(&mut self) {
+        self.argc = (self.argc + 1).min(MAXARGS as i32 - 1);
+        self.argv[self.argc as usize] = 0;
+    }
+
+    /// Returns `argv[i]`, 0 if the argument was omitted.
+    #[inline]
+    fn argument(&self, i: usize) -> i32 {
+        if i <= self.argc as usize {
+            self.argv[i]
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn is_sub_param(&self, i: usize) -> bool {
+        i <= self.argc as usize && self.sub_params & (1 << i) != 0
+    }
+
+    /// The number of sub-parameters following `argv[i]`.
+    #[inline]
+    fn sub_param_count(&self, i: usize) -> usize {
+        let mut count = 0;
+        while self.is_sub_param(i + count + 1) {
+            count += 1;
+        }
+        count
+    }
+
+    /// Process the SGR parameter `argv[i]` with the colon separated sub-parameters:
+    ///
+    /// - `4:x`: The underline style.
+    /// - `38:2:[colorspace]:r:g:b`, `48:..` and `58:..`: The RGB color.
+    /// - `38:5:index`, `48:..` and `58:..`: The 256 color.
+    ///
+    /// The sub-parameters of other parameters were ignored.
+    fn process_sgr_sub_params(&mut self, i: usize, subs: usize) {
+        let ps = self.argv[i];
+        let sub = self.argv[i + 1..=i + subs].to_vec();
+        match ps {
+            4 => self.process_token(ty_csi_ps!('m', 4), 1, sub[0]),
+            38 | 48 | 58 => match sub[0] {
+                2 if subs >= 4 => {
+                    // The color space id was optional.
+                    let rgb = &sub[subs - 3..];
+                    let q = (rgb[0] << 16) | (rgb[1] << 8) | rgb[2];
+                    self.process_token(ty_csi_ps!('m', ps), COLOR_SPACE_RGB as wchar_t, q)
+                }
+                5 if subs >= 2 => {
+                    self.process_token(ty_csi_ps!('m', ps), COLOR_SPACE_256 as wchar_t, sub[1])
+                }
+                _ => {}
+            },
+            _ => self.process_token(ty_csi_ps!('m', ps), 0, 0),
+        }
+    }
+
+    //////////////////////////////////////// VT100 Charset ////////////////////////////////////////
+    /*
+       The processing contains a VT100 specific code translation layer.
+       It's still in use and mainly responsible for the line drawing graphics.
+
+       These and some other glyphs are assigned to codes (0x5f-0xfe)
+       normally occupied by the latin letters. Since this codes also
+       appear within control sequences, the extra code conversion
+       does not permute with the tokenizer and is placed behind it
+       in the pipeline. It only applies to tokens, which represent
+       plain characters.
+
+       This conversion it eventually continued in TerminalDisplay.C, since
+       it might involve VT100 enhanced fonts, which have these
+       particular glyphs allocated in (0x00-0x1f) in their code page.
+    */
+    fn charset_index(&self) -> usize {
+        let emulation = self.emulation.as_ref().unwrap();
+        unsafe {
+            if emulation.current_screen.as_ref().unwrap().as_ref().id() == emulation.screen[1].id()
+            {
+                1
+            } else {
+                0
+            }
+        }
+    }
+
+    #[inline]
+    fn charset(&mut self) -> &mut CharCodes {
+        let index = self.charset_index();
+        &mut self.charset[index]
+    }
+
+    fn apply_charset(&mut self, c: wchar_t) -> wchar_t {
+        let charset = self.charset();
+        let single_shift = charset.single_shift.take();
+        match c {
+            0x21..=0x7e => charset.charset[single_shift.unwrap_or(charset.current_charset)].map(c),
+            0xa1..=0xfe => match single_shift.or(charset.current_gr_charset) {
+                Some(n) => charset.charset[n].map(c - 0x80),
+                None => c,
+            },
+            _ => c,
+        }
+    }
+
+    /// Decode the designation `ESC ( ) * + F` of G0-G3 from the token.
+    ///
+    /// @return `None` if the token was not a designation, or the charset was unknown.
+    fn designation(token: i32) -> Option<(usize, Charset)> {
+        if token & 0xff != 3 {
+            return None;
+        }
+        let n = match ((token >> 8) & 0xff) as u8 {
+            b'(' => 0,
+            b')' => 1,
+            b'*' => 2,
+            b'+' => 3,
+            _ => return None,
+        };
+        Charset::from_designator(((token >> 16) & 0xffff) as wchar_t).map(|charset| (n, charset))
+    }
+
+    /// "Charset" related part of the emulation state. This configures the VT100 charset filter.
+    ///
+    /// While most operation work on the current _screen, the following two are different.
+    fn reset_charset(&mut self, scrno: usize) {
+        self.charset[scrno] = CharCodes::default();
+        self.saved_charset[scrno] = CharCodes::default();
+    }
+
+    fn set_charset(&mut self, n: usize, charset: Charset) {
+        self.charset[0].charset[n & 3] = charset;
+        self.charset[1].charset[n & 3] = charset;
+    }
+
+    fn use_charset(&mut self, n: usize) {
+        self.charset().current_charset = n & 3;
+    }
+
+    fn use_gr_charset(&mut self, n: usize) {
+        self.charset().current_gr_charset = Some(n & 3);
+    }
+
+    fn set_and_use_charset(&mut self, n: usize, charset: Charset) {
+        self.charset().charset[n & 3] = charset;
+        self.use_charset(n);
+    }
+
+    /// Enter the VT52 mode (DECANM reset), the charsets of ANSI mode were saved and reset,
+    /// the graphics mode of VT52 was invoked into G0.
+    fn enter_vt52_mode(&mut self) {
+        if !self.get_mode(MODE_ANSI) {
+            return;
+        }
+        self.ansi_charset = self.charset;
+        self.charset = Default::default();
+        self.reset_mode(MODE_ANSI);
+    }
+
+    /// Return to the ANSI mode by VT52 `ESC <`, the charsets of ANSI mode were restored.
+    fn leave_vt52_mode(&mut self) {
+        if self.get_mode(MODE_ANSI) {
+            return;
+        }
+        self.charset = self.ansi_charset;
+        self.set_mode(MODE_ANSI);
+    }
+
+    fn save_cursor(&mut self) {
+        let index = self.charset_index();
+        self.saved_charset[index] = self.charset[index];
+        self.saved_charset[index].single_shift = None;
+        unsafe {
+            self.emulation
+                .as_mut()
+                .unwrap()
+                .current_screen
+                .as_mut()
+                .unwrap()
+                .as_mut()
+                .save_cursor();
+        }
+    }
+
+    fn restore_cursor(&mut self) {
+        let index = self.charset_index();
+        self.charset[index] = self.saved_charset[index];
+        unsafe {
+            self.emulation
+                .as_mut()
+                .unwrap()
+                .current_screen
+                .as_mut()
+                .unwrap()
+                .as_mut()
+                .resotre_cursor();
+        }
+    }
+
+    fn set_margins(&mut self, top: i32, bottom: i32) {
+        self.emulation_mut().screen[0].set_margins(top, bottom);
+        self.emulation_mut().screen[1].set_margins(top, bottom);
+    }
+
+    fn set_left_right_margins(&mut self, left: i32, right: i32) {
+        self.emulation_mut().screen[0].set_left_right_margins(left, right);
+        self.emulation_mut().screen[1].set_left_right_margins(left, right);
+    }
+
+    /// Set margins for all screens back to their defaults.
+    fn set_default_margins(&mut self) {
+        self.emulation_mut().screen[0].set_default_margins();
+        self.emulation_mut().screen[1].set_default_margins();
     }
 
     //////////////////////////////////////// Modes operations ////////////////////////////////////////
@@ -1526,6 +2537,8 @@ impl VT102Emulation {
         self.save_mode(MODE_MOUSE_1006);
         self.reset_mode(MODE_MOUSE_1015);
         self.save_mode(MODE_MOUSE_1015);
+        self.reset_mode(MODE_MOUSE_1016);
+        self.save_mode(MODE_MOUSE_1016);
         self.reset_mode(MODE_BRACKETD_PASTE);
         self.save_mode(MODE_BRACKETD_PASTE);
 
@@ -1536,13 +2549,18 @@ impl VT102Emulation {
         self.reset_mode(MODE_APP_KEY_PAD);
         self.save_mode(MODE_APP_KEY_PAD);
         self.reset_mode(MODE_NEWLINE);
+        self.reset_mode(MODE_LEFT_RIGHT_MARGIN);
+        self.save_mode(MODE_LEFT_RIGHT_MARGIN);
+        self.reset_mode(MODE_GRAPHEME_CLUSTER);
+        self.save_mode(MODE_GRAPHEME_CLUSTER);
         self.set_mode(MODE_ANSI);
+
+        self.modify_other_keys = ModifyOtherKeys::Disabled;
+        self.emulation_mut().set_synchronized_update(false);
     }
 
     fn report_decoding_error(&self) {
-        if self.token_buffer_pos == 0
-            || self.token_buffer_pos == 1 && self.token_buffer[0] & 0xff >= 32
-        {}
+        // The unsupported or malformed sequences were ignored silently, like xterm.
     }
 
     fn report_terminal_type(&self) {
@@ -1550,8 +2568,8 @@ impl VT102Emulation {
         // Users Guide)) VT220:  ^[[?63;1;2;3;6;7;8c   (list deps on emul.
         // capabilities) VT100:  ^[[?1;2c VT101:  ^[[?1;0c VT102:  ^[[?6v
         if self.get_mode(MODE_ANSI) {
-            // VT100
-            self.send_string("\u{001b}[?1;2c".to_string(), -1)
+            // VT220, with sixel graphics(4) and ANSI color(22)
+            self.send_string("\u{001b}[?62;4;22c".to_string(), -1)
         } else {
             // VT52
             self.send_string("\u{001b}/Z".to_string(), -1)
@@ -1567,6 +2585,255 @@ impl VT102Emulation {
         }
     }
 
+    #[inline]
+    fn report_ansi_mode(&self, mode: i32) {
+        self.send_string(self.ansi_mode_response(mode), -1)
+    }
+
+    /// Response of DECRQM for ANSI modes: `CSI Ps ; Pm $ y`, see [`Self::private_mode_response`].
+    fn ansi_mode_response(&self, mode: i32) -> String {
+        let value = match mode {
+            // KAM: the keyboard can't be locked.
+            2 => 4,
+            4 => Self::mode_value(self.emulation().current_screen().get_mode(MODE_INSERT)),
+            // SRM: the local echo was never performed.
+            12 => 3,
+            20 => Self::mode_value(self.get_mode(MODE_NEWLINE)),
+            _ => 0,
+        };
+        format!("\u{001b}[{};{}$y", mode, value)
+    }
+
+    #[inline]
+    fn report_private_mode(&self, mode: i32) {
+        self.send_string(self.private_mode_response(mode), -1)
+    }
+
+    /// Response of DECRQM: `CSI ? Ps ; Pm $ y`, `Pm` was 0(not recognized), 1(set), 2(reset),
+    /// 3(permanently set) or 4(permanently reset).
+    fn private_mode_response(&self, mode: i32) -> String {
+        let screen = self.emulation().current_screen();
+        let value = match mode {
+            1 => Self::mode_value(self.get_mode(MODE_APP_CURSOR_KEY)),
+            2 => Self::mode_value(self.get_mode(MODE_ANSI)),
+            3 => Self::mode_value(self.get_mode(MODE_132_COLUMNS)),
+            5 => Self::mode_value(screen.get_mode(MODE_SCREEN)),
+            6 => Self::mode_value(screen.get_mode(MODE_ORIGIN)),
+            7 => Self::mode_value(screen.get_mode(MODE_WRAP)),
+            25 => Self::mode_value(self.get_mode(MODE_CURSOR)),
+            40 => Self::mode_value(self.get_mode(MODE_ALLOW_132_COLUMNS)),
+            47 | 1047 | 1049 => Self::mode_value(self.get_mode(MODE_APP_SCREEN)),
+            69 => Self::mode_value(self.get_mode(MODE_LEFT_RIGHT_MARGIN)),
+            1000 => Self::mode_value(self.get_mode(MODE_MOUSE_1000)),
+            1001 => Self::mode_value(self.get_mode(MODE_MOUSE_1001)),
+            1002 => Self::mode_value(self.get_mode(MODE_MOUSE_1002)),
+            1003 => Self::mode_value(self.get_mode(MODE_MOUSE_1003)),
+            1004 => Self::mode_value(self.report_focus_event),
+            1005 => Self::mode_value(self.get_mode(MODE_MOUSE_1005)),
+            1006 => Self::mode_value(self.get_mode(MODE_MOUSE_1006)),
+            1015 => Self::mode_value(self.get_mode(MODE_MOUSE_1015)),
+            1016 => Self::mode_value(self.get_mode(MODE_MOUSE_1016)),
+            2004 => Self::mode_value(self.get_mode(MODE_BRACKETD_PASTE)),
+            2026 => Self::mode_value(self.emulation().synchronized_update()),
+            2027 => Self::mode_value(self.get_mode(MODE_GRAPHEME_CLUSTER)),
+            // The autorepeat was always on.
+            8 => 3,
+            // The ignored modes: soft scrolling, interlace, more(1) fix and DECBKM.
+            4 | 9 | 41 | 67 => 4,
+            _ => 0,
+        };
+        format!("\u{001b}[?{};{}$y", mode, value)
+    }
+
+    /// The `Pm` of DECRQM response for the recognized mode.
+    #[inline]
+    fn mode_value(set: bool) -> i32 {
+        if set {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Response of DECRQSS: `DCS 1 $ r Pt ST` for the valid request `Pt` of SGR(`m`),
+    /// DECSTBM(`r`), DECSLRM(`s`) and DECSCUSR(` q`), otherwise `DCS 0 $ r ST`.
+    fn report_control_function(&self, request: &str) {
+        let screen = self.emulation().current_screen();
+        let reply = match request {
+            "m" => Some(format!("{}m", Self::sgr_parameters(screen))),
+            "r" => Some(format!(
+                "{};{}r",
+                screen.top_margin() + 1,
+                screen.bottom_margin() + 1
+            )),
+            "s" => Some(format!(
+                "{};{}s",
+                screen.left_margin() + 1,
+                screen.right_margin() + 1
+            )),
+            " q" => Some(format!("{} q", self.cursor_style.max(1))),
+            _ => None,
+        };
+
+        match reply {
+            Some(reply) => self.send_string(format!("\u{001b}P1$r{}\u{001b}\\", reply), -1),
+            None => self.send_string("\u{001b}P0$r\u{001b}\\".to_string(), -1),
+        }
+    }
+
+    /// Returns the SGR parameters which reproduce the current rendition and colors of `screen`.
+    fn sgr_parameters(screen: &Screen) -> String {
+        let mut params = vec!["0".to_string()];
+        let rendition = screen.cursor_rendition();
+        let flags = [
+            (RE_BOLD, "1"),
+            (RE_FAINT, "2"),
+            (RE_ITALIC, "3"),
+            (RE_BLINK, "5"),
+            (RE_REVERSE, "7"),
+            (RE_CONCEAL, "8"),
+            (RE_STRIKEOUT, "9"),
+            (RE_OVERLINE, "53"),
+        ];
+        for (flag, param) in flags {
+            if rendition & flag != 0 {
+                params.push(param.to_string());
+            }
+        }
+        match UnderlineStyle::from_rendition(rendition) {
+            UnderlineStyle::None => {}
+            UnderlineStyle::Single => params.push("4".to_string()),
+            style => params.push(format!("4:{}", style as u8)),
+        }
+
+        let color = |color: CharacterColor, base: u32, bright_base: u32, extended: u32| match color
+            .color_space
+        {
+            COLOR_SPACE_SYSTEM if color.v == 0 => Some(format!("{}", base + color.u as u32)),
+            COLOR_SPACE_SYSTEM => Some(format!("{}", bright_base + color.u as u32)),
+            COLOR_SPACE_256 => Some(format!("{};5;{}", extended, color.u)),
+            COLOR_SPACE_RGB => Some(format!(
+                "{};2;{};{};{}",
+                extended, color.u, color.v, color.w
+            )),
+            _ => None,
+        };
+        params.extend(color(screen.cursor_foreground(), 30, 90, 38));
+        params.extend(color(screen.cursor_background(), 40, 100, 48));
+        match screen.cursor_underline_color() {
+            // The underline color has no bright system colors.
+            c if c.color_space == COLOR_SPACE_SYSTEM => {
+                params.push(format!("58;5;{}", c.u as u32 + 8 * c.v as u32))
+            }
+            c => params.extend(color(c, 0, 0, 58)),
+        }
+
+        params.join(";")
+    }
+
+    /// Response of XTVERSION: `DCS > | termio(version) ST`.
+    fn report_version(&self) {
+        self.send_string(
+            format!("\u{001b}P>|termio({})\u{001b}\\", env!("CARGO_PKG_VERSION")),
+            -1,
+        )
+    }
+
+    /// Sets the cursor style by DECSCUSR `Ps`: 0 or 1(blinking block), 2(steady block),
+    /// 3(blinking underline), 4(steady underline), 5(blinking bar) or 6(steady bar).
+    fn set_cursor_style(&mut self, style: u8) {
+        let shape = match style {
+            0..=2 => KeyboardCursorShape::BlockCursor,
+            3 | 4 => KeyboardCursorShape::UnderlineCursor,
+            5 | 6 => KeyboardCursorShape::IBeamCursor,
+            _ => return,
+        };
+        let blinking = style == 0 || style % 2 == 1;
+
+        self.cursor_style = style;
+        emit!(self, cursor_changed(shape as u8, blinking));
+    }
+
+    /// Response of XTSMGRAPHICS: `CSI ? Pi ; Ps ; Pv S`, the item `Pi` was 1(color registers)
+    /// or 2(sixel geometry), `Ps` was 0(success), 1(error in Pi), 2(error in Pa) or 3(failure).
+    ///
+    /// @param action `Pa` of the request, 1(read), 2(reset), 3(set) or 4(read maximum).
+    fn report_graphics_attribute(&self, item: i32, action: i32) {
+        let reply = match (item, action) {
+            // The number of color registers was fixed.
+            (1, 1 | 2 | 4) => format!("1;0;{}", SIXEL_COLOR_REGISTERS),
+            (2, 1 | 2) => {
+                let screen = self.emulation().current_screen();
+                let (cell_width, cell_height) = self.emulation().cell_size();
+                let width = (screen.get_columns() as f32 * cell_width) as u32;
+                let height = (screen.get_lines() as f32 * cell_height) as u32;
+                format!(
+                    "2;0;{};{}",
+                    width.min(SIXEL_MAX_GEOMETRY),
+                    height.min(SIXEL_MAX_GEOMETRY)
+                )
+            }
+            (2, 4) => format!("2;0;{};{}", SIXEL_MAX_GEOMETRY, SIXEL_MAX_GEOMETRY),
+            // Changing the attributes was not supported.
+            (1 | 2, 3) => format!("{};3;0", item),
+            (1 | 2, _) => format!("{};2;0", item),
+            _ => format!("{};1;0", item),
+        };
+        self.send_string(format!("\u{001b}[?{}S", reply), -1)
+    }
+
+    fn report_modify_other_keys(&self) {
+        let level = self.modify_other_keys.level();
+        self.send_string(format!("\u{001b}[>4;{}m", level), -1)
+    }
+
+    fn report_keyboard_flags(&self) {
+        let flags = self.keyboard_flags().flags();
+        self.send_string(format!("\u{001b}[?{}u", flags), -1)
+    }
+
+    /// Send the key event encoded by kitty keyboard protocol.
+    ///
+    /// @return `false` if the protocol was not enabled or the key should be sent by the
+    /// legacy encoding(keyboard translator).
+    fn send_kitty_key_event(&self, event: &KeyPressedEvent, event_type: KeyEventType) -> bool {
+        let flags = self.keyboard_flags().flags();
+        if flags == 0 {
+            return false;
+        }
+        let key = match KittyKey::from_key_code(event.key_code()) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        let modifiers = kitty_modifiers(event.modifier());
+        match encode_kitty_key(key, modifiers, event_type, event.text(), flags) {
+            Some(sequence) => {
+                emit!(self, send_data(self.emulation().encode(&sequence)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Send the key event by the VT52 encoding, see [`encode_vt52_key`].
+    fn send_vt52_key_event(&self, event: &KeyPressedEvent) -> bool {
+        let modifiers = event.modifier();
+        match encode_vt52_key(
+            event.key_code(),
+            event.text(),
+            modifiers,
+            modifiers.has(KeyboardModifier::KeypadModifier),
+            self.get_mode(MODE_APP_KEY_PAD),
+        ) {
+            Some(sequence) => {
+                emit!(self, send_data(self.emulation().encode(sequence)));
+                true
+            }
+            None => false,
+        }
+    }
+
     fn report_status(&self) {
         self.send_string("\u{001b}[0n".to_string(), -1)
     }
@@ -1668,107 +2935,308 @@ impl VT102Emulation {
         self.pending_title_updates.clear();
     }
 }
+const BEL: wchar_t = 7;
+const CAN: wchar_t = 24;
+const SUB: wchar_t = 26;
 const ESC: wchar_t = 27;
 const DEL: wchar_t = 127;
+/// The 8-bit control sequence introducer, same as `ESC [`.
+const CSI: wchar_t = 0x9b;
+/// The 8-bit string terminator, same as `ESC \`.
+const ST: wchar_t = 0x9c;
+/// The final characters of `CSI Pn ; Pn F`, which has at most two numeric parameters.
+const CSI_PN_FINALS: &str = "@ABCDEFGHILMPSTXZbcdfry";
+/// The mark of the command execution finished, sent by the local display.
+const ZERO_WIDTH_SPACE: wchar_t = 0x200b;
 impl VT102Emulation {
-    /**
-       Ok, here comes the nasty part of the decoder.
-
-       Instead of keeping an explicit state, we deduce it from the
-       token scanned so far. It is then immediately combined with
-       the current character to form a scanning decision.
-
-       This is done by the following defines.
-
-       - p is the length of the token scanned so far.
-       - l (often p-1) is the position on which contents we base a decision.
-       - c is a character or a group of characters (taken from 'charClass').
-
-       Note that they need to applied in proper order
-       and thoes macros used in [`VT102Emulation::receive_char(&self, cc: wchar_t)`]]
-    */
+    // Here comes the parser, an explicit state machine of the DEC ANSI parser, the states
+    // were listed in `ParserState`.
+    //
+    // The complete sequences were dispatched as the tokens to `process_token()`, the runs of
+    // printable characters in the ground state were written to the screen in one call by
+    // `print()`, without walking through the state machine.
+
+    /// Whether the character was displayed directly in the ground state.
     #[inline]
-    fn lec(&self, p: usize, l: usize, c: wchar_t) -> bool {
-        self.token_buffer_pos == p && self.token_buffer[l] == c
+    fn is_printable(c: wchar_t) -> bool {
+        c >= 0x20 && c != DEL && !(0x80..0xa0).contains(&c) && c != ZERO_WIDTH_SPACE
     }
 
-    #[inline]
-    fn lun(&self) -> bool {
-        self.token_buffer_pos == 1 && self.cc >= 32
-    }
+    /// Display the printable characters translated by the current charsets.
+    fn print(&mut self, text: &[wchar_t]) {
+        let charset = self.charset();
+        let translated = charset.single_shift.is_some()
+            || charset.current_gr_charset.is_some()
+            || charset.charset[charset.current_charset] != Charset::Ascii;
 
-    #[inline]
-    fn les(&self, p: usize, l: usize, c: wchar_t) -> bool {
-        self.token_buffer_pos == p
-            && self.token_buffer[l] < 256
-            && self.char_class[self.token_buffer[l] as usize] & c as i32 == c as i32
+        if translated {
+            let text: Vec<wchar_t> = text.iter().map(|&c| self.apply_charset(c)).collect();
+            self.current_screen_mut().display_characters(&text);
+        } else {
+            self.current_screen_mut().display_characters(text);
+        }
     }
 
-    #[inline]
-    fn eec(&self, c: wchar_t) -> bool {
-        self.token_buffer_pos >= 3 && self.cc == c
-    }
+    /// `ESC {intermediates} F`, the `ESC [`, `ESC ]`, `ESC P`, `ESC _`, `ESC X` and `ESC ^`
+    /// enter the states of CSI and strings.
+    fn receive_escape_char(&mut self, cc: wchar_t) {
+        if (0x20..0x30).contains(&cc) {
+            self.add_intermediate(cc);
+            self.parser_state = ParserState::EscapeIntermediate;
+            return;
+        }
 
-    #[inline]
-    fn ees(&self, c: wchar_t) -> bool {
-        self.token_buffer_pos >= 3
-            && self.cc < 256
-            && self.char_class[self.cc as usize] & c as i32 == c as i32
-    }
+        if self.parser_state == ParserState::Escape {
+            let state = match cc {
+                wch!('[') => Some(ParserState::CsiEntry),
+                wch!(']') => Some(ParserState::OscString),
+                wch!('P') => Some(ParserState::DcsPassthrough),
+                wch!('_') => Some(ParserState::ApcString),
+                wch!('X') | wch!('^') => Some(ParserState::SosPmString),
+                _ => None,
+            };
+            if let Some(state) = state {
+                self.parser_state = state;
+                return;
+            }
+        }
 
-    #[inline]
-    fn eps(&self, c: wchar_t) -> bool {
-        self.token_buffer_pos >= 3
-            && self.token_buffer[2] != wch!('?')
-            && self.token_buffer[2] != wch!('!')
-            && self.token_buffer[2] != wch!('>')
-            && self.cc < 256
-            && self.char_class[self.cc as usize] & c as i32 == c as i32
+        if (0x30..DEL).contains(&cc) {
+            self.dispatch_esc(cc);
+        }
+        self.reset_tokenizer();
     }
 
-    #[inline]
-    fn epp(&self) -> bool {
-        self.token_buffer_pos >= 3 && self.token_buffer[2] == wch!('?')
+    fn dispatch_esc(&mut self, cc: wchar_t) {
+        let token = match self.intermediates() {
+            Some([]) => ty_esc!(cc),
+            Some(&[i]) if i == wch!('#') => ty_esc_de!(cc),
+            Some(&[i])
+                if matches!(i, wch!('(') | wch!(')') | wch!('*') | wch!('+') | wch!('%')) =>
+            {
+                ty_esc_cs!(i, cc)
+            }
+            _ => {
+                self.report_decoding_error();
+                return;
+            }
+        };
+        self.process_token(token, 0, 0);
     }
 
-    #[inline]
-    fn epe(&self) -> bool {
-        self.token_buffer_pos >= 3 && self.token_buffer[2] == wch!('!')
+    /// `CSI {private marker} {parameters} {intermediates} F`.
+    fn receive_csi_char(&mut self, cc: wchar_t) {
+        let state = self.parser_state;
+        match cc {
+            0x40..=0x7e => {
+                if state != ParserState::CsiIgnore {
+                    self.dispatch_csi(cc);
+                }
+                self.reset_tokenizer();
+            }
+            _ if state == ParserState::CsiIgnore => {}
+            0x30..=0x39 if state != ParserState::CsiIntermediate => {
+                self.add_digit((cc - wch!('0')) as i32);
+                self.parser_state = ParserState::CsiParam;
+            }
+            wch!(';') | wch!(':') if state != ParserState::CsiIntermediate => {
+                self.add_argument();
+                if cc == wch!(':') {
+                    self.sub_params |= 1 << self.argc;
+                } else {
+                    self.sub_params &= !(1 << self.argc);
+                }
+                self.parser_state = ParserState::CsiParam;
+            }
+            0x3c..=0x3f if state == ParserState::CsiEntry => {
+                self.private_marker = cc;
+                self.parser_state = ParserState::CsiParam;
+            }
+            0x20..=0x2f => {
+                self.add_intermediate(cc);
+                self.parser_state = ParserState::CsiIntermediate;
+            }
+            _ => self.parser_state = ParserState::CsiIgnore,
+        }
     }
 
-    #[inline]
-    fn egt(&self) -> bool {
-        self.token_buffer_pos >= 3 && self.token_buffer[2] == wch!('>')
+    fn dispatch_csi(&mut self, cc: wchar_t) {
+        let marker = self.private_marker;
+        match self.intermediates() {
+            Some([]) => {}
+            Some(&[i]) if i == wch!('$') => {
+                if marker == wch!('?') {
+                    self.process_token(ty_csi_pd!(cc), self.argv[0] as wchar_t, 0);
+                } else {
+                    self.process_token(ty_csi_dl!(cc), self.argv[0] as wchar_t, self.argv[1]);
+                }
+                return;
+            }
+            Some(&[i]) if i == wch!(' ') => {
+                self.process_token(ty_csi_ps_sp!(cc, self.argv[0]), self.argv[0] as wchar_t, 0);
+                return;
+            }
+            Some(&[i]) if i == wch!('!') => {
+                self.process_token(ty_csi_pe!(cc), 0, 0);
+                return;
+            }
+            _ => {
+                self.report_decoding_error();
+                return;
+            }
+        }
+
+        match marker {
+            0 if char::from_u32(cc as u32).is_some_and(|c| CSI_PN_FINALS.contains(c)) => {
+                self.process_token(ty_csi_pn!(cc), self.argv[0] as wchar_t, self.argv[1]);
+            }
+            // resize = \e[8;<row>;<col>t
+            0 if cc == wch!('t') => self.process_token(
+                ty_csi_ps!(cc, self.argv[0]),
+                self.argument(1) as wchar_t,
+                self.argument(2),
+            ),
+            wch!('=') => {
+                self.process_token(ty_csi_pq!(cc), self.argv[0] as wchar_t, self.argv[1]);
+            }
+            wch!('<') => {
+                self.process_token(ty_csi_pl!(cc), self.argv[0] as wchar_t, self.argv[1]);
+            }
+            _ => self.dispatch_csi_arguments(cc),
+        }
     }
 
-    #[inline]
-    fn esp(&self) -> bool {
-        self.token_buffer_pos >= 3 && self.token_buffer[2] == wch!(' ')
+    /// The CSI with the list of arguments, each of them was processed as an individual token.
+    fn dispatch_csi_arguments(&mut self, cc: wchar_t) {
+        let private = self.private_marker == wch!('?');
+        let mut i = 0usize;
+        loop {
+            if i > self.argc as usize {
+                break;
+            }
+            if private && cc == wch!('S') {
+                // spec. case for ESC[?Pi;Pa;PvS, processed once with the item and action.
+                self.process_token(
+                    ty_csi_pr!(cc, self.argv[0]),
+                    self.argv[1] as wchar_t,
+                    self.argv[0],
+                );
+                break;
+            } else if private {
+                self.process_token(ty_csi_pr!(cc, self.argv[i]), 0, 0);
+            } else if self.private_marker == wch!('>') {
+                // spec. case for ESC[>0c or ESC[>c, the sequences like ESC[>4;2m were
+                // processed once with the first two arguments.
+                self.process_token(ty_csi_pg!(cc), self.argv[0] as wchar_t, self.argv[1]);
+                break;
+            } else if cc == wch!('s') && self.get_mode(MODE_LEFT_RIGHT_MARGIN) {
+                // spec. case for ESC[Pl;Prs, which saves the cursor when the left right
+                // margin mode was reset.
+                self.process_token(ty_csi_pn!(cc), self.argv[0] as wchar_t, self.argv[1]);
+                break;
+            } else if cc == wch!('m') && self.is_sub_param(i + 1) {
+                // ESC[ ... 4:3 ... m -or- ESC[ ... 58:2::<red>:<green>:<blue> ... m
+                let subs = self.sub_param_count(i);
+                self.process_sgr_sub_params(i, subs);
+                i += subs;
+            } else if cc == wch!('m')
+                && self.argc - i as i32 >= 4
+                && (self.argv[i] == 38 || self.argv[i] == 48 || self.argv[i] == 58)
+                && self.argv[i + 1] == 2
+            {
+                // ESC[ ... 48;2;<red>;<green>;<blue> ... m -or- ESC[ ...
+                // 38;2;<red>;<green>;<blue> ... m
+                i += 2;
+                let q = (self.argv[i] << 16) | (self.argv[i + 1] << 8) | self.argv[i + 2];
+                self.process_token(
+                    ty_csi_ps!(cc, self.argv[i - 2]),
+                    COLOR_SPACE_RGB as wchar_t,
+                    q,
+                );
+                i += 2;
+            } else if cc == wch!('m')
+                && self.argc - i as i32 >= 2
+                && (self.argv[i] == 38 || self.argv[i] == 48 || self.argv[i] == 58)
+                && self.argv[i + 1] == 5
+            {
+                // ESC[ ... 48;5;<index> ... m -or- ESC[ ... 38;5;<index> ... m
+                i += 2;
+                self.process_token(
+                    ty_csi_ps!(cc, self.argv[i - 2]),
+                    COLOR_SPACE_256 as wchar_t,
+                    self.argv[i],
+                );
+            } else {
+                self.process_token(ty_csi_ps!(cc, self.argv[i]), 0, 0);
+            }
+
+            i += 1;
+        }
     }
 
-    #[inline]
-    fn xpe(&self) -> bool {
-        self.token_buffer_pos >= 2 && self.token_buffer[1] == wch!(']')
-    }
+    /// The text of OSC, DCS, APC, SOS and PM, terminated by `ST` (`ESC \`), or `BEL` for OSC.
+    ///
+    /// The control characters in the text were ignored, this matches what XTERM docs say.
+    fn receive_string_char(&mut self, cc: wchar_t) {
+        // The `ESC` not followed by `\` was ignored.
+        if std::mem::take(&mut self.string_escape) && cc == wch!('\\') {
+            self.dispatch_string();
+            return;
+        }
 
-    #[inline]
-    fn xte(&self) -> bool {
-        self.xpe() && (self.cc == 7 || (self.prev_cc == 27 && self.cc == 92))
+        match cc {
+            BEL if self.parser_state == ParserState::OscString => self.dispatch_string(),
+            ESC => self.string_escape = true,
+            ST => self.dispatch_string(),
+            CAN | SUB => self.reset_tokenizer(),
+            0..=0x1f => {}
+            _ => match self.parser_state {
+                ParserState::OscString => self.add_to_osc_text(cc),
+                ParserState::DcsPassthrough => self.add_to_dcs_text(cc),
+                ParserState::ApcString => self.add_to_apc_text(cc),
+                _ => {}
+            },
+        }
     }
 
-    #[inline]
-    fn ces(&self, c: wchar_t) -> bool {
-        self.cc < 256 && self.char_class[self.cc as usize] & c as i32 == c as i32 && !self.xte()
+    fn dispatch_string(&mut self) {
+        match self.parser_state {
+            ParserState::OscString => self.process_window_attribute_change(),
+            ParserState::DcsPassthrough => self.process_device_control_string(),
+            ParserState::ApcString => self.process_application_program_command(),
+            _ => {}
+        }
+        self.reset_tokenizer();
     }
 
-    #[inline]
-    fn cntl(&self, c: wchar_t) -> wchar_t {
-        c - wch!('@')
+    /// VT52 `ESC C`, and the direct cursor address `ESC Y Pl Pc`.
+    fn receive_vt52_char(&mut self, cc: wchar_t) {
+        match self.parser_state {
+            ParserState::Vt52Escape if cc == wch!('Y') => {
+                self.parser_state = ParserState::Vt52CursorLine;
+            }
+            ParserState::Vt52CursorLine => {
+                self.argv[0] = cc as i32;
+                self.parser_state = ParserState::Vt52CursorColumn;
+            }
+            ParserState::Vt52CursorColumn => {
+                self.process_token(ty_vt52!('Y'), self.argv[0] as wchar_t, cc as i32);
+                self.reset_tokenizer();
+            }
+            _ => {
+                if cc < DEL {
+                    self.process_token(ty_vt52!(cc), 0, 0);
+                }
+                self.reset_tokenizer();
+            }
+        }
     }
 }
 impl Emulation for VT102Emulation {
     fn init(&mut self) {
-        self.emulation_mut().init()
+        self.emulation_mut().init();
+
+        connect!(self.title_update_timer, timeout(), self, update_title());
     }
 
     fn create_window(&mut self) -> Option<NonNull<ScreenWindow>> {
@@ -1816,6 +3284,7 @@ impl Emulation for VT102Emulation {
                     KeyCode::KeyBackspace as u32,
                     KeyboardModifier::NoModifier,
                     Some(State::None),
+                    ModifyOtherKeys::Disabled,
                 )
         };
 
@@ -1835,6 +3304,22 @@ impl Emulation for VT102Emulation {
         self.emulation().keyboard_layout()
     }
 
+    fn set_char_width(&mut self, char_width: CharWidth) {
+        self.emulation_mut().set_char_width(char_width)
+    }
+
+    fn set_encoding(&mut self, encoding: &str) -> bool {
+        self.emulation_mut().set_encoding(encoding)
+    }
+
+    fn encoding(&self) -> &'static str {
+        self.emulation().encoding()
+    }
+
+    fn set_codec(&mut self, codec: EmulationCodec) {
+        self.emulation_mut().set_codec(codec)
+    }
+
     fn clear_entire_screen(&mut self) {
         self.emulation_mut().clear_entire_screen()
     }
@@ -1842,9 +3327,15 @@ impl Emulation for VT102Emulation {
     fn reset(&mut self) {
         self.reset_tokenizer();
         self.reset_modes();
+        self.keyboard_flags
+            .iter_mut()
+            .for_each(|flags| flags.clear());
+        self.pressed_keys.clear();
         self.reset_charset(0);
         self.emulation_mut().screen[0].reset(None);
         self.reset_charset(1);
+        self.image_store.clear();
+        self.kitty_notifications.clear();
 
         self.direct_update();
     }
@@ -1909,6 +3400,8 @@ impl Emulation for VT102Emulation {
             MODE_APP_SCREEN => {
                 self.emulation_mut().screen[0].clear_selection();
                 self.set_screen(0);
+                // The keyboard flags of alternate screen were dropped when leaving it.
+                self.keyboard_flags[1].clear();
             }
             _ => {}
         }
@@ -1919,200 +3412,59 @@ impl Emulation for VT102Emulation {
         }
     }
 
-    #[inline]
     fn receive_char(&mut self, cc: wchar_t) {
         if cc == DEL {
             return;
         }
-        self.cc = cc;
-
-        if self.ces(CTL as wchar_t) {
-            // ignore control characters in the text part of Xpe (aka OSC) "ESC]"
-            // escape sequences; this matches what XTERM docs say
-            if self.xpe() {
-                self.prev_cc = cc;
-                return;
-            }
 
-            // DEC HACK ALERT! Control Characters are allowed *within* esc sequences in
-            // VT100 This means, they do neither a reset_tokenizer() nor a push_to_token().
-            // Some of them, do of course. Guess this originates from a weakly layered
-            // handling of the X-on X-off protocol, which comes really below this level.
-            if cc == self.cntl(wch!('X')) || cc == self.cntl(wch!('Z')) || cc == ESC {
-                self.reset_tokenizer();
-            }
-            if cc != ESC {
-                self.process_token(ty_ctl!(cc + wch!('@')), 0, 0);
-                return;
-            }
+        if matches!(
+            self.parser_state,
+            ParserState::OscString
+                | ParserState::DcsPassthrough
+                | ParserState::ApcString
+                | ParserState::SosPmString
+        ) {
+            self.receive_string_char(cc);
+            return;
         }
 
-        // advance the state
-        self.add_to_current_token(cc);
-
-        if self.get_mode(MODE_ANSI) {
-            if self.lec(1, 0, ESC) {
-                return;
-            }
-            if self.lec(1, 0, ESC + 128) {
-                self.token_buffer[0] = ESC;
-                self.receive_char(wch!('['));
-                return;
-            }
-            if self.les(2, 1, GRP as wchar_t) {
-                return;
-            }
-            if self.xte() {
-                self.process_window_attribute_change();
-                self.reset_tokenizer();
-                return;
-            }
-            if self.xpe() {
-                self.prev_cc = cc;
-                return;
-            }
-            if self.lec(3, 2, wch!('?')) {
-                return;
-            }
-            if self.lec(3, 2, wch!('>')) {
-                return;
-            }
-            if self.lec(3, 2, wch!('!')) {
-                return;
-            }
-            if self.lun() {
-                let apply_charset = self.apply_charset(cc);
-                self.process_token(ty_chr!(), apply_charset, 0);
-                self.reset_tokenizer();
-                return;
-            }
-            if self.lec(2, 0, ESC as wchar_t) {
-                self.process_token(ty_esc!(self.token_buffer[1]), 0, 0);
-                self.reset_tokenizer();
-                return;
-            }
-            if self.lec(3, 1, SCS as wchar_t) {
-                self.process_token(ty_esc_cs!(self.token_buffer[1], self.token_buffer[2]), 0, 0);
-                self.reset_tokenizer();
-                return;
-            }
-            if self.lec(3, 1, wch!('#')) {
-                self.process_token(ty_esc_de!(self.token_buffer[2]), 0, 0);
-                self.reset_tokenizer();
-                return;
-            }
-            if self.eps(CPN as wchar_t) {
-                self.process_token(ty_csi_pn!(cc), self.argv[0] as wchar_t, self.argv[1]);
-                self.reset_tokenizer();
-                return;
-            }
-            if self.esp() {
-                return;
-            }
-            if self.lec(5, 4, wch!('q')) && self.token_buffer[3] == wch!(' ') {
-                self.process_token(ty_csi_ps_sp!(cc, self.argv[0]), self.argv[0] as wchar_t, 0);
-                self.reset_tokenizer();
-                return;
-            }
-
-            // resize = \e[8;<row>;<col>t
-            if self.eps(CPS as wchar_t) {
-                self.process_token(
-                    ty_csi_ps!(cc, self.argv[0]),
-                    self.argv[1] as wchar_t,
-                    self.argv[2],
-                );
-                self.reset_tokenizer();
-                return;
-            }
-
-            if self.epe() {
-                self.process_token(ty_csi_pe!(cc), 0, 0);
-                self.reset_tokenizer();
-                return;
-            }
-            if self.ees(DIG as wchar_t) {
-                self.add_digit((cc - wch!('0')) as i32);
-                return;
-            }
-            if self.eec(wch!(';')) || self.eec(wch!(':')) {
-                self.add_argument();
-                return;
-            }
-
-            let mut i = 0usize;
-            loop {
-                if i > self.argc as usize {
-                    break;
+        // DEC HACK ALERT! Control Characters are allowed *within* esc sequences in
+        // VT100, they were executed without changing the state, except CAN, SUB and ESC.
+        if cc < 0x20 {
+            match cc {
+                ESC => {
+                    self.reset_tokenizer();
+                    self.parser_state = if self.get_mode(MODE_ANSI) {
+                        ParserState::Escape
+                    } else {
+                        ParserState::Vt52Escape
+                    };
                 }
-                if self.epp() {
-                    self.process_token(ty_csi_pr!(cc, self.argv[i]), 0, 0);
-                } else if self.egt() {
-                    // spec. case for ESC]>0c or ESC]>c
-                    self.process_token(ty_csi_pg!(cc), 0, 0);
-                } else if cc == wch!('m')
-                    && self.argc - i as i32 >= 4
-                    && (self.argv[i] == 38 || self.argv[i] == 48)
-                    && self.argv[i + 1] == 2
-                {
-                    // ESC[ ... 48;2;<red>;<green>;<blue> ... m -or- ESC[ ...
-                    // 38;2;<red>;<green>;<blue> ... m
-                    i += 2;
-                    let q = (self.argv[i] << 16) | (self.argv[i + 1] << 8) | self.argv[i + 2];
-                    self.process_token(
-                        ty_csi_ps!(cc, self.argv[i - 2]),
-                        COLOR_SPACE_RGB as wchar_t,
-                        q,
-                    );
-                    i += 2;
-                } else if cc == wch!('m')
-                    && self.argc - i as i32 >= 2
-                    && (self.argv[i] == 38 || self.argv[i] == 48)
-                    && self.argv[i + 1] == 5
-                {
-                    // ESC[ ... 48;5;<index> ... m -or- ESC[ ... 38;5;<index> ... m
-                    i += 2;
-                    self.process_token(
-                        ty_csi_ps!(cc, self.argv[i - 2]),
-                        COLOR_SPACE_256 as wchar_t,
-                        self.argv[i],
-                    );
-                } else {
-                    self.process_token(ty_csi_ps!(cc, self.argv[i]), 0, 0);
+                CAN | SUB => {
+                    self.reset_tokenizer();
+                    self.process_token(ty_ctl!(cc + wch!('@')), 0, 0);
                 }
-
-                i += 1;
+                _ => self.process_token(ty_ctl!(cc + wch!('@')), 0, 0),
             }
+            return;
+        }
 
+        // The 8-bit CSI was the same as `ESC [`, the other C1 control characters were not
+        // recognized, since they may be a part of text in the other encodings.
+        if cc == CSI && self.get_mode(MODE_ANSI) {
             self.reset_tokenizer();
-        } else {
-            // VT52 Mode
-            if self.lec(1, 0, ESC as wchar_t) {
-                return;
-            }
-            if self.les(1, 0, CHR as wchar_t) {
-                self.process_token(ty_chr!(), self.token_buffer[0], 0);
-                self.reset_tokenizer();
-                return;
-            }
-            if self.lec(2, 1, wch!('Y')) {
-                return;
-            }
-            if self.lec(3, 1, wch!('Y')) {
-                return;
-            }
-            if self.token_buffer_pos < 4 {
-                self.process_token(ty_vt52!(self.token_buffer[1]), 0, 0);
-                self.reset_tokenizer();
-                return;
-            }
+            self.parser_state = ParserState::Escape;
+            self.receive_char(wch!('['));
+            return;
+        }
 
-            self.process_token(
-                ty_vt52!(self.token_buffer[1]),
-                self.token_buffer[2],
-                self.token_buffer[3] as i32,
-            );
-            self.reset_tokenizer();
+        match self.parser_state {
+            ParserState::Ground => self.print(&[cc]),
+            ParserState::Escape | ParserState::EscapeIntermediate => self.receive_escape_char(cc),
+            ParserState::Vt52Escape
+            | ParserState::Vt52CursorLine
+            | ParserState::Vt52CursorColumn => self.receive_vt52_char(cc),
+            _ => self.receive_csi_char(cc),
         }
     }
 
@@ -2130,6 +3482,11 @@ impl Emulation for VT102Emulation {
             .set_image_size(lines, columns)
     }
 
+    #[inline]
+    fn set_font_metrics(&mut self, height: f32, width: f32) {
+        self.emulation_mut().set_font_metrics(height, width)
+    }
+
     #[inline]
     fn send_text(&self, text: String) {
         self.emulation().send_text(text)
@@ -2164,11 +3521,30 @@ impl Emulation for VT102Emulation {
             }
         }
 
+        if !from_paste && !self.get_mode(MODE_ANSI) && self.send_vt52_key_event(&event) {
+            emit!(self, output_from_keypress_event());
+            return;
+        }
+
+        // The pasted text was sent as is, even when the kitty keyboard protocol enabled.
+        if !from_paste {
+            let event_type = if self.pressed_keys.insert(event.key_code().as_numeric()) {
+                KeyEventType::Press
+            } else {
+                KeyEventType::Repeat
+            };
+            if self.send_kitty_key_event(&event, event_type) {
+                emit!(self, output_from_keypress_event());
+                return;
+            }
+        }
+
         if self.emulation().key_translator.is_some() {
             let entry = nonnull_ref!(self.emulation().key_translator).find_entry(
                 event.key_code().as_numeric(),
                 modifiers,
                 Some(states),
+                self.modify_other_keys,
             );
 
             let mut text_to_send: Vec<u8> = vec![];
@@ -2240,7 +3616,7 @@ impl Emulation for VT102Emulation {
             }
 
             let text_to_send = String::from_utf8(text_to_send).unwrap();
-            emit!(self, send_data(text_to_send.as_str()));
+            emit!(self, send_data(self.emulation().encode(&text_to_send)));
 
             if self.emulation().use_local_display {
                 let text_to_send = self
@@ -2277,78 +3653,41 @@ is missing."#;
     }
 
     #[inline]
-    fn send_mouse_event(&self, mut cb: i32, cx: i32, cy: i32, event_type: u8) {
-        if cx < 1 || cy < 1 {
-            return;
-        }
-
-        // With the exception of the 1006 mode, button release is encoded in cb.
-        // Note that if multiple extensions are enabled, the 1006 is used, so it's
-        // okay to check for only that.
-        if event_type == 2 && !self.get_mode(MODE_MOUSE_1006) {
-            cb = 3;
-        }
+    fn send_key_release_event(&mut self, event: KeyPressedEvent) {
+        self.pressed_keys.remove(&event.key_code().as_numeric());
+        self.send_kitty_key_event(&event, KeyEventType::Release);
+    }
 
-        // normal buttons are passed as 0x20 + button,
-        // mouse wheel (buttons 4,5) as 0x5c + button
-        if cb >= 4 {
-            cb += 0x3c;
-        }
+    #[inline]
+    fn release_pressed_keys(&mut self) {
+        self.pressed_keys.clear();
+    }
 
-        // Handle mouse motion:
-        // add 32 to signify motion event
-        if (self.get_mode(MODE_MOUSE_1002) || self.get_mode(MODE_MOUSE_1003)) && event_type == 1 {
-            cb += 0x20;
+    #[inline]
+    fn send_mouse_event(&self, cb: i32, cx: i32, cy: i32, event_type: u8) {
+        // The position was reported in pixels by `send_mouse_pixel_event` in 1016 mode.
+        if !self.get_mode(MODE_MOUSE_1016) {
+            self.report_mouse_event(cb, cx, cy, event_type)
         }
+    }
 
-        let mut command = String::new();
-        // Check the extensions in decreasing order of preference. Encoding the
-        // release event above assumes that 1006 comes first.
-        if self.get_mode(MODE_MOUSE_1006) {
-            command = format!(
-                "\x1B[<{};{};{}{}",
-                cb,
-                cx,
-                cy,
-                if event_type == 2 { 'm' } else { 'M' }
-            )
-        } else if self.get_mode(MODE_MOUSE_1015) {
-            command = format!("\x1B[{};{};{}M", cb + 0x20, cx, cy)
-        } else if self.get_mode(MODE_MOUSE_1005) {
-            if cx <= 2015 && cy <= 2015 {
-                // The xterm extension uses UTF-8 (up to 2 bytes) to encode
-                // coordinate+32, no matter what the locale is.
-                let coords: String = [
-                    char::from_u32(cx as u32 + 0x20).unwrap(),
-                    char::from_u32(cy as u32 + 0x20).unwrap(),
-                ]
-                .iter()
-                .collect();
-                command = format!("\x1B[M{}{}", (cb as u8 + 0x20) as char, coords)
-            }
-        } else if cx <= 223 && cy <= 223 {
-            command = format!(
-                "\x1B[M{}{}{}",
-                (cb as u8 + 0x20) as char,
-                (cx as u8 + 0x20) as char,
-                (cy as u8 + 0x20) as char
-            )
+    #[inline]
+    fn send_mouse_pixel_event(&self, cb: i32, x: i32, y: i32, event_type: u8) {
+        if self.get_mode(MODE_MOUSE_1016) {
+            self.report_mouse_event(cb, x, y, event_type)
         }
-
-        let len = command.len() as i32;
-        self.send_string(command, len)
     }
 
     #[inline]
     fn send_string(&self, string: String, length: i32) {
         if length >= 0 {
-            emit!(self, send_data(string.as_str()));
+            emit!(self, send_data(self.emulation().encode(&string)));
         } else {
             let len = string.len() as i32;
             if len == 0 {
                 return;
             }
-            emit!(self, send_data(string.as_str()));
+            emit!(self, send_data(self.emulation().encode(&string)));
         }
     }
 
@@ -2358,31 +3697,44 @@ is missing."#;
         }
         emit!(self, state_set(EmulationState::NotifyActivity as i32));
 
-        let utf8_text = String::from_utf8(buffer.to_vec())
-            .expect("`Emulation` receive_data() parse utf-8 string failed.");
-        let utf16_text = WideString::from_str(&utf8_text);
+        // The buffer of decoded text was reused between the calls.
+        let mut text = std::mem::take(&mut self.decoded_text);
+        text.clear();
+        self.emulation_mut()
+            .decode(&buffer[..len as usize], data_sender, &mut text);
 
-        // Send characters to terminal emulator
-        let text_slice = utf16_text.as_slice();
+        // Send characters to terminal emulator, the runs of printable characters in the
+        // ground state were displayed in one call.
         let mut execution_finish = false;
-        for &ts in text_slice.iter() {
-            #[allow(clippy::useless_transmute)]
-            let end: uwchar_t = unsafe { std::mem::transmute(wch!('\u{200B}')) };
-
-            if ts == end {
+        let mut i = 0;
+        while i < text.len() {
+            let c = text[i];
+            if c == ZERO_WIDTH_SPACE {
                 execution_finish = true;
+                i += 1;
+                continue;
+            }
+
+            if self.parser_state == ParserState::Ground && Self::is_printable(c) {
+                let start = i;
+                while i < text.len() && Self::is_printable(text[i]) {
+                    i += 1;
+                }
+                self.print(&text[start..i]);
                 continue;
             }
 
-            self.receive_char(ts as wchar_t);
+            self.receive_char(c);
+            i += 1;
         }
+        self.decoded_text = text;
 
         // Look for z-modem indicator
         for i in 0..len as usize {
             #[allow(clippy::char_lit_as_u8)]
             if buffer[i] == '\u{0030}' as u8
                 && len as usize - i - 1 > 3
-                && std::str::from_utf8(&buffer[i + 1..i + 4]) == Ok("B00")
+                && buffer[i + 1..i + 4] == *b"B00"
             {
                 emit!(self, zmodem_detected());
             }
@@ -2501,3 +3853,434 @@ is missing."#;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{
+        character::Character,
+        event::{
+            MOUSE_CONTROL, MOUSE_LEFT_BUTTON, MOUSE_META, MOUSE_PRESS, MOUSE_RIGHT_BUTTON,
+            MOUSE_SHIFT, MOUSE_WHEEL_DOWN,
+        },
+    };
+
+    fn receive(emulation: &mut VT102Emulation, data: &str) {
+        emulation.receive_data(data.as_bytes(), data.len() as i32, DataSender::Pty);
+    }
+
+    fn line_text(emulation: &mut VT102Emulation, line: i32) -> String {
+        let screen = emulation.current_screen_mut();
+        let columns = screen.get_columns();
+        let line = line + screen.get_history_lines();
+        let mut image = vec![Character::default(); columns as usize];
+        screen.get_image(&mut image, columns, line, line);
+
+        // The cells after the wide characters were skipped.
+        image
+            .iter()
+            .filter(|c| c.character_union.data() != 0)
+            .map(|c| char::from_u32(c.character_union.data() as u32).unwrap_or(' '))
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    fn cell(emulation: &mut VT102Emulation, x: i32, y: i32) -> Character {
+        let screen = emulation.current_screen_mut();
+        let columns = screen.get_columns();
+        let line = y + screen.get_history_lines();
+        let mut image = vec![Character::default(); columns as usize];
+        screen.get_image(&mut image, columns, line, line);
+        image[x as usize]
+    }
+
+    fn cursor(emulation: &mut VT102Emulation) -> (i32, i32) {
+        let screen = emulation.current_screen_mut();
+        (screen.get_cursor_x(), screen.get_cursor_y())
+    }
+
+    #[test]
+    fn test_csi_params_and_intermediates() {
+        let mut emulation = VT102Emulation::new(None);
+
+        receive(&mut emulation, "\u{1b}[5;10");
+        assert_eq!(emulation.parser_state, ParserState::CsiParam);
+        assert_eq!(emulation.argc, 1);
+        assert_eq!(&emulation.argv[..2], &[5, 10]);
+
+        receive(&mut emulation, "H");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(cursor(&mut emulation), (9, 4));
+
+        // DECSCUSR with the intermediate `SP`.
+        receive(&mut emulation, "\u{1b}[4 ");
+        assert_eq!(emulation.parser_state, ParserState::CsiIntermediate);
+        assert_eq!(emulation.intermediate_count, 1);
+        receive(&mut emulation, "q");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(emulation.cursor_style, 4);
+
+        // The parameter after the intermediate was malformed.
+        receive(&mut emulation, "\u{1b}[ 1H");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(cursor(&mut emulation), (9, 4));
+    }
+
+    #[test]
+    fn test_osc_terminators() {
+        let mut emulation = VT102Emulation::new(None);
+
+        receive(&mut emulation, "\u{1b}]2;first\u{7}");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(emulation.titles[1], "first");
+
+        receive(&mut emulation, "\u{1b}]2;second\u{1b}");
+        assert_eq!(emulation.parser_state, ParserState::OscString);
+        receive(&mut emulation, "\\");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(emulation.titles[1], "second");
+        assert!(emulation.osc_text.is_empty());
+
+        // The `ESC` not followed by `\` was ignored in the string.
+        receive(&mut emulation, "\u{1b}]1;ic\u{1b}on\u{1b}\\");
+        assert_eq!(emulation.titles[0], "icon");
+    }
+
+    #[test]
+    fn test_dcs_and_apc() {
+        let mut emulation = VT102Emulation::new(None);
+
+        receive(&mut emulation, "\u{1b}P$q\u{7}m");
+        assert_eq!(emulation.parser_state, ParserState::DcsPassthrough);
+        // The `BEL` doesn't terminate the DCS.
+        assert_eq!(emulation.dcs_text, "$qm");
+        receive(&mut emulation, "\u{1b}\\");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert!(emulation.dcs_text.is_empty());
+
+        receive(&mut emulation, "\u{1b}_Ga=d");
+        assert_eq!(emulation.parser_state, ParserState::ApcString);
+        assert_eq!(emulation.apc_text, "Ga=d");
+        receive(&mut emulation, "\u{1b}\\x");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(line_text(&mut emulation, 0), "x");
+
+        // SOS and PM were ignored.
+        receive(
+            &mut emulation,
+            "\u{1b}^ignored\u{1b}\\\u{1b}Xignored\u{1b}\\y",
+        );
+        assert_eq!(line_text(&mut emulation, 0), "xy");
+    }
+
+    #[test]
+    fn test_can_sub_abort() {
+        let mut emulation = VT102Emulation::new(None);
+
+        // The aborted sequence was displayed as the error character.
+        receive(&mut emulation, "\u{1b}[5;5H\u{1b}[3\u{18}A");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(line_text(&mut emulation, 4), "    \u{2592}A");
+
+        // The strings were discarded silently.
+        receive(&mut emulation, "\u{1b}]2;title\u{1a}B");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert!(emulation.titles[1].is_empty());
+        assert_eq!(line_text(&mut emulation, 4), "    \u{2592}AB");
+
+        receive(&mut emulation, "\u{1b}P$q\u{18}C");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(line_text(&mut emulation, 4), "    \u{2592}ABC");
+    }
+
+    #[test]
+    fn test_c1_controls() {
+        let mut emulation = VT102Emulation::new(None);
+
+        // CSI
+        receive(&mut emulation, "\u{9b}3;4H");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(cursor(&mut emulation), (3, 2));
+
+        // CSI started a new sequence in the CSI.
+        receive(&mut emulation, "\u{1b}[2\u{9b}1;1H");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(cursor(&mut emulation), (0, 0));
+
+        // The other C1 control characters were not recognized.
+        receive(&mut emulation, "\u{9d}2;title\u{9c}");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_ne!(emulation.titles[1], "title");
+        receive(&mut emulation, "\u{90}");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+
+        // OSC terminated by ST.
+        receive(&mut emulation, "\u{1b}]2;title\u{9c}");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(emulation.titles[1], "title");
+    }
+
+    #[test]
+    fn test_vt52_cursor_address() {
+        let mut emulation = VT102Emulation::new(None);
+
+        receive(&mut emulation, "\u{1b}[?2l");
+        assert!(!emulation.get_mode(MODE_ANSI));
+
+        receive(&mut emulation, "\u{1b}Y");
+        assert_eq!(emulation.parser_state, ParserState::Vt52CursorLine);
+        receive(&mut emulation, "%");
+        assert_eq!(emulation.parser_state, ParserState::Vt52CursorColumn);
+        receive(&mut emulation, "'");
+        assert_eq!(emulation.parser_state, ParserState::Ground);
+        assert_eq!(cursor(&mut emulation), (7, 5));
+
+        // Back to ANSI mode by `ESC <`.
+        receive(&mut emulation, "\u{1b}<");
+        assert!(emulation.get_mode(MODE_ANSI));
+    }
+
+    #[test]
+    fn test_split_printable_run() {
+        let mut emulation = VT102Emulation::new(None);
+
+        receive(&mut emulation, "ab");
+        receive(&mut emulation, "cd\u{1b}");
+        receive(&mut emulation, "[");
+        receive(&mut emulation, "2Cef");
+        assert_eq!(line_text(&mut emulation, 0), "abcd  ef");
+        assert_eq!(cursor(&mut emulation), (8, 0));
+
+        // The multibyte sequence split across the calls.
+        let bytes = "中g".as_bytes();
+        emulation.receive_data(&bytes[..2], 2, DataSender::Pty);
+        emulation.receive_data(&bytes[2..], bytes.len() as i32 - 2, DataSender::Pty);
+        assert_eq!(line_text(&mut emulation, 0), "abcd  ef中g");
+        assert_eq!(cursor(&mut emulation), (11, 0));
+    }
+
+    #[test]
+    fn test_semantic_prompt_marks() {
+        let mut emulation = VT102Emulation::new(None);
+
+        receive(&mut emulation, "\u{1b}]133;A\u{7}$ \u{1b}]133;B\u{7}ls\r\n");
+        receive(
+            &mut emulation,
+            "\u{1b}]133;C\u{7}a\r\nb\r\n\u{1b}]133;D;1\u{7}",
+        );
+        receive(&mut emulation, "\u{1b}]133;A\u{1b}\\$ ");
+        // The unknown mark was ignored.
+        receive(&mut emulation, "\u{1b}]133;Z\u{7}");
+
+        let screen = emulation.current_screen_mut();
+        assert_eq!(screen.marked_lines(LINE_PROMPT_START), [0, 3]);
+        assert_eq!(screen.marked_lines(LINE_COMMAND_START), [0]);
+        assert_eq!(screen.marked_lines(LINE_OUTPUT_START), [1]);
+        assert_eq!(screen.marked_lines(LINE_COMMAND_FINISHED), [3]);
+        assert_eq!(line_text(&mut emulation, 0), "$ ls");
+        assert_eq!(line_text(&mut emulation, 3), "$");
+    }
+
+    #[test]
+    fn test_mouse_encodings() {
+        // The empty report if the event wasn't reported.
+        let report = |emulation: &VT102Emulation, cb, x, y, event_type| {
+            emulation
+                .encode_mouse_event(cb, x, y, event_type)
+                .unwrap_or_default()
+        };
+        let mut emulation = VT102Emulation::new(None);
+        let left = MOUSE_LEFT_BUTTON;
+
+        // No mouse tracking mode.
+        assert_eq!(report(&emulation, left, 1, 1, MOUSE_PRESS), "");
+
+        // 1000 reports the press and release, the release was encoded in the button code.
+        receive(&mut emulation, "\u{1b}[?1000h");
+        assert_eq!(report(&emulation, left, 1, 2, MOUSE_PRESS), "\u{1b}[M !\"");
+        assert_eq!(report(&emulation, left, 1, 1, MOUSE_MOVE), "");
+        let shift_left = left | MOUSE_SHIFT;
+        assert_eq!(
+            report(&emulation, shift_left, 1, 1, MOUSE_RELEASE),
+            "\u{1b}[M'!!"
+        );
+        assert_eq!(report(&emulation, left, 0, 1, MOUSE_PRESS), "");
+        assert_eq!(report(&emulation, left, 224, 1, MOUSE_PRESS), "");
+
+        // The wheel has no release, the modifier bits were added to the button code.
+        let control_wheel = MOUSE_WHEEL_DOWN | MOUSE_CONTROL;
+        assert_eq!(
+            report(&emulation, control_wheel, 1, 1, MOUSE_PRESS),
+            "\u{1b}[Mq!!"
+        );
+        assert_eq!(report(&emulation, MOUSE_WHEEL_UP, 1, 1, MOUSE_RELEASE), "");
+
+        // 1002 reports the motion only with a button pressed, 1003 reports any motion.
+        receive(&mut emulation, "\u{1b}[?1002h");
+        assert_eq!(report(&emulation, left, 1, 1, MOUSE_MOVE), "\u{1b}[M@!!");
+        assert_eq!(report(&emulation, MOUSE_NO_BUTTON, 1, 1, MOUSE_MOVE), "");
+        receive(&mut emulation, "\u{1b}[?1003h");
+        assert_eq!(
+            report(&emulation, MOUSE_NO_BUTTON, 1, 1, MOUSE_MOVE),
+            "\u{1b}[MC!!"
+        );
+
+        // 1006 reports the release by the final character with the button.
+        receive(&mut emulation, "\u{1b}[?1006h");
+        let meta_left = left | MOUSE_META;
+        assert_eq!(
+            report(&emulation, meta_left, 10, 300, MOUSE_PRESS),
+            "\u{1b}[<8;10;300M"
+        );
+        assert_eq!(
+            report(&emulation, meta_left, 10, 300, MOUSE_RELEASE),
+            "\u{1b}[<8;10;300m"
+        );
+        let shift_motion = MOUSE_NO_BUTTON | MOUSE_SHIFT;
+        assert_eq!(
+            report(&emulation, shift_motion, 5, 6, MOUSE_MOVE),
+            "\u{1b}[<39;5;6M"
+        );
+        assert_eq!(
+            report(&emulation, MOUSE_WHEEL_DOWN, 1, 1, MOUSE_PRESS),
+            "\u{1b}[<65;1;1M"
+        );
+        assert_eq!(
+            report(&emulation, MOUSE_WHEEL_DOWN, 1, 1, MOUSE_RELEASE),
+            ""
+        );
+
+        // 1016 is the same as 1006 with the position in pixels.
+        receive(&mut emulation, "\u{1b}[?1006l\u{1b}[?1016h");
+        assert_eq!(
+            report(&emulation, MOUSE_RIGHT_BUTTON, 640, 480, MOUSE_RELEASE),
+            "\u{1b}[<2;640;480m"
+        );
+    }
+
+    #[test]
+    fn test_synchronized_update() {
+        let mut emulation = VT102Emulation::new(None);
+        let window = emulation.create_window().unwrap();
+        let frozen = || unsafe { window.as_ref() }.is_frozen();
+        assert_eq!(emulation.private_mode_response(2026), "\u{1b}[?2026;2$y");
+
+        // The window image was frozen until the update ended.
+        receive(&mut emulation, "\u{1b}[?2026h");
+        assert!(frozen());
+        assert_eq!(emulation.private_mode_response(2026), "\u{1b}[?2026;1$y");
+        receive(&mut emulation, "\u{1b}[?2026l");
+        assert!(!frozen());
+        assert_eq!(emulation.private_mode_response(2026), "\u{1b}[?2026;2$y");
+
+        // RIS ended the update.
+        receive(&mut emulation, "\u{1b}[?2026h\u{1b}c");
+        assert!(!frozen());
+        assert_eq!(emulation.private_mode_response(2026), "\u{1b}[?2026;2$y");
+
+        // The permanent and unrecognized modes.
+        assert_eq!(emulation.private_mode_response(8), "\u{1b}[?8;3$y");
+        assert_eq!(emulation.private_mode_response(9999), "\u{1b}[?9999;0$y");
+        receive(&mut emulation, "\u{1b}[4h");
+        assert_eq!(emulation.ansi_mode_response(4), "\u{1b}[4;1$y");
+        assert_eq!(emulation.ansi_mode_response(2), "\u{1b}[2;4$y");
+    }
+
+    #[test]
+    fn test_sgr_underline() {
+        let mut emulation = VT102Emulation::new(None);
+
+        receive(
+            &mut emulation,
+            "\u{1b}[1;4:3ma\u{1b}[21mb\u{1b}[24mc\u{1b}[4m",
+        );
+        receive(
+            &mut emulation,
+            "\u{1b}[58:2::1:2:3md\u{1b}[58;5;100me\u{1b}[58;2;4;5;6mf",
+        );
+        receive(
+            &mut emulation,
+            "\u{1b}[59mg\u{1b}[4:0mh\u{1b}[4;58;5;1m\u{1b}[0mi",
+        );
+
+        let styles = [
+            UnderlineStyle::Curly,
+            UnderlineStyle::Double,
+            UnderlineStyle::None,
+            UnderlineStyle::Single,
+            UnderlineStyle::Single,
+            UnderlineStyle::Single,
+            UnderlineStyle::Single,
+            UnderlineStyle::None,
+            UnderlineStyle::None,
+        ];
+        let colors = [
+            CharacterColor::empty(),
+            CharacterColor::empty(),
+            CharacterColor::empty(),
+            CharacterColor::new(COLOR_SPACE_RGB, 0x010203),
+            CharacterColor::new(COLOR_SPACE_256, 100),
+            CharacterColor::new(COLOR_SPACE_RGB, 0x040506),
+            CharacterColor::empty(),
+            CharacterColor::empty(),
+            CharacterColor::empty(),
+        ];
+        for (x, (style, color)) in styles.into_iter().zip(colors).enumerate() {
+            let cell = cell(&mut emulation, x as i32, 0);
+            assert_eq!(cell.underline_style(), style, "column {}", x);
+            assert_eq!(cell.underline_color, color, "column {}", x);
+        }
+        // The other parameters in the same sequence were applied.
+        assert_ne!(cell(&mut emulation, 0, 0).rendition & RE_BOLD, 0);
+        assert_eq!(cell(&mut emulation, 8, 0).rendition & RE_BOLD, 0);
+    }
+
+    #[test]
+    fn test_xtwinops() {
+        let mut emulation = VT102Emulation::new(None);
+        emulation.set_image_size(24, 80);
+        // The cells were aligned to the whole pixels.
+        emulation.set_font_metrics(16.5, 8.2);
+
+        assert_eq!(
+            emulation.window_attribute_response(14).unwrap(),
+            "\u{1b}[4;408;720t"
+        );
+        assert_eq!(
+            emulation.window_attribute_response(16).unwrap(),
+            "\u{1b}[6;17;9t"
+        );
+        assert_eq!(
+            emulation.window_attribute_response(18).unwrap(),
+            "\u{1b}[8;24;80t"
+        );
+        assert_eq!(emulation.window_attribute_response(19), None);
+
+        // Zero keeps the current lines or columns.
+        assert_eq!(emulation.resize_request_size(30, 100), Size::new(100, 30));
+        assert_eq!(emulation.resize_request_size(0, 100), Size::new(100, 24));
+        assert_eq!(emulation.resize_request_size(30, 0), Size::new(80, 30));
+
+        // Push the window title only, the icon title wasn't restored.
+        receive(&mut emulation, "\u{1b}]0;first\u{7}\u{1b}[22;2t");
+        receive(&mut emulation, "\u{1b}]0;second\u{7}\u{1b}[23;0t");
+        assert_eq!(emulation.titles, ["second", "first"]);
+
+        // Push both titles, and pop them twice.
+        receive(
+            &mut emulation,
+            "\u{1b}[22;0t\u{1b}]1;icon\u{7}\u{1b}]2;window\u{7}",
+        );
+        assert_eq!(emulation.titles, ["icon", "window"]);
+        receive(&mut emulation, "\u{1b}[23;0t\u{1b}[23;0t");
+        assert_eq!(emulation.titles, ["second", "first"]);
+        assert!(emulation.title_stack.is_empty());
+
+        // The stack depth was limited.
+        for _ in 0..MAX_TITLE_STACK_DEPTH + 1 {
+            receive(&mut emulation, "\u{1b}[22t");
+        }
+        assert_eq!(emulation.title_stack.len(), MAX_TITLE_STACK_DEPTH);
+    }
+}
@@ -4,4 +4,4 @@ pub mod pty;
 
 mod asset;
 mod config;
-mod tools;
+pub mod tools;
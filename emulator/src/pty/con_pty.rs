@@ -5,6 +5,7 @@ use super::{
     },
     Pty, PtySignals,
 };
+use crate::tools::working_directory::cmd_quote;
 use cli::session::SessionPropsId;
 use derivative::Derivative;
 use log::warn;
@@ -83,6 +84,18 @@ impl Pty for ConPty {
             });
         };
 
+        // `start_sub_process` can not specify the directory of sub process,
+        // so start it through `cmd.exe` which changes the directory first.
+        if self.working_directory.as_path() != std::env::current_dir().unwrap_or_default() {
+            match cmd_quote(&self.working_directory) {
+                Some(dir) => cmd = format!("cmd.exe /D /S /C \"cd /d {} && {}\"", dir, cmd),
+                None => warn!(
+                    "[ConPty::start] Invalid working directory `{}`, ignored.",
+                    self.working_directory.display()
+                ),
+            }
+        }
+
         let running = self.running.clone();
         let closed = self.closed.clone();
         thread::spawn(move || {
@@ -157,13 +170,19 @@ impl Pty for ConPty {
         self.utf8_mode = on;
     }
 
+    /// The `ConPTY` converts between the console code page and UTF-8 itself.
+    #[inline]
+    fn utf8_only(&self) -> bool {
+        true
+    }
+
     #[inline]
     fn set_timeout(&mut self, timeout: u32) {
         self.timeout = timeout
     }
 
     #[inline]
-    fn send_data(&mut self, data: String) {
+    fn send_data(&mut self, data: Vec<u8>) {
         if !self.is_running() {
             return;
         }
@@ -171,7 +190,9 @@ impl Pty for ConPty {
             warn!("The `ConPTY` is not writeable.");
             return;
         }
-        write_data(self.fd, &data);
+        // The `ConPTY` was always in UTF-8 mode, the session never encodes the data to it
+        // in other encodings, see `Pty::utf8_only()`.
+        write_data(self.fd, &String::from_utf8_lossy(&data));
     }
 
     #[inline]
@@ -61,13 +61,19 @@ pub trait Pty: PtySignals {
     /// Put the pty into UTF-8 mode on systems which support it.
     fn set_utf8_mode(&mut self, on: bool);
 
+    /// Whether the pty only accepts and produces UTF-8, the character encoding of session
+    /// was not applied to it.
+    fn utf8_only(&self) -> bool {
+        false
+    }
+
     /// Set the timeout of pty.
     fn set_timeout(&mut self, timeout: u32);
 
     /// Sends data to the process currently controlling the teletype.
     ///
-    /// @param data: the data to send.
-    fn send_data(&mut self, data: String);
+    /// @param data: the data to send, encoded in the character encoding of session.
+    fn send_data(&mut self, data: Vec<u8>);
 
     /// Read data from the process.
     fn read_data(&mut self) -> Vec<u8>;
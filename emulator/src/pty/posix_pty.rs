@@ -1,4 +1,5 @@
 use super::{Pty, PtySignals};
+use crate::tools::working_directory::sh_quote;
 use cli::session::SessionPropsId;
 use pty::prelude::Fork;
 use std::{
@@ -55,6 +56,11 @@ impl Pty for PosixPty {
             envs.push('\n');
         });
 
+        // Start the program in the working directory if it was specified.
+        if std::env::current_dir().is_ok_and(|dir| dir != self.working_directory) {
+            envs.push_str(&format!("cd -- {}\n", sh_quote(&self.working_directory)));
+        }
+
         let mut program = program.to_string();
         program.push_str(&args);
         program.push(' ');
@@ -122,7 +128,7 @@ impl Pty for PosixPty {
     }
 
     #[inline]
-    fn send_data(&mut self, data: String) {
+    fn send_data(&mut self, data: Vec<u8>) {
         todo!()
     }
 
@@ -0,0 +1,184 @@
+#![allow(dead_code)]
+use cli::session::cfg::WidthCfg;
+use log::warn;
+use unicode_width::UnicodeWidthChar;
+use wchar::wchar_t;
+
+/// The custom width of code points in range `start..=end`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WidthOverride {
+    pub start: u32,
+    pub end: u32,
+    pub width: u8,
+}
+
+impl WidthOverride {
+    /// Parse the hexadecimal code point range, e.g. `2500-257F`, `U+E0B0` or `U+1F300-U+1F5FF`.
+    ///
+    /// @return `None` if the range was malformed, or the width was greater than 2.
+    pub fn parse(range: &str, width: u8) -> Option<Self> {
+        if width > 2 {
+            return None;
+        }
+        let code_point = |text: &str| {
+            let text = text.trim();
+            let text = text
+                .strip_prefix("U+")
+                .or_else(|| text.strip_prefix("u+"))
+                .unwrap_or(text);
+            u32::from_str_radix(text, 16).ok()
+        };
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (code_point(start)?, code_point(end)?),
+            None => {
+                let code_point = code_point(range)?;
+                (code_point, code_point)
+            }
+        };
+        if start > end {
+            return None;
+        }
+
+        Some(Self { start, end, width })
+    }
+
+    #[inline]
+    pub fn contains(&self, code_point: u32) -> bool {
+        (self.start..=self.end).contains(&code_point)
+    }
+}
+
+/// Computes the number of cells occupied by the characters.
+///
+/// The East Asian ambiguous width characters (box drawing, circled digits...) were single
+/// width by default, or double width when `ambiguous_wide` was set, to agree with the CJK
+/// legacy programs. The overrides take precedence, and the latter one wins if they were
+/// overlapped, so the width can be kept in agreement with the `wcwidth` of remote host.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct CharWidth {
+    ambiguous_wide: bool,
+    overrides: Vec<WidthOverride>,
+}
+
+impl CharWidth {
+    #[inline]
+    pub fn new(ambiguous_wide: bool, overrides: Vec<WidthOverride>) -> Self {
+        Self {
+            ambiguous_wide,
+            overrides,
+        }
+    }
+
+    #[inline]
+    pub fn ambiguous_wide(&self) -> bool {
+        self.ambiguous_wide
+    }
+
+    #[inline]
+    pub fn overrides(&self) -> &[WidthOverride] {
+        &self.overrides
+    }
+
+    /// The width of character `c`, 0 for the control and zero width characters.
+    #[inline]
+    pub fn width(&self, c: wchar_t) -> i32 {
+        self.code_point_width(c as u32)
+    }
+
+    /// The width of `code_point`, which may exceed the range of `wchar_t` on windows.
+    pub fn code_point_width(&self, code_point: u32) -> i32 {
+        if let Some(width_override) = self
+            .overrides
+            .iter()
+            .rev()
+            .find(|width_override| width_override.contains(code_point))
+        {
+            return width_override.width as i32;
+        }
+
+        let Some(c) = char::from_u32(code_point) else {
+            return 0;
+        };
+        let width = if self.ambiguous_wide {
+            c.width_cjk()
+        } else {
+            c.width()
+        };
+        width.unwrap_or(0) as i32
+    }
+}
+
+impl From<&WidthCfg> for CharWidth {
+    fn from(cfg: &WidthCfg) -> Self {
+        let overrides = cfg
+            .overrides()
+            .iter()
+            .filter_map(|(range, width)| {
+                let width_override = WidthOverride::parse(range, *width);
+                if width_override.is_none() {
+                    warn!(
+                        "[CharWidth::from] Invalid width override `{}: {}`, ignored.",
+                        range, width
+                    );
+                }
+                width_override
+            })
+            .collect();
+
+        Self::new(cfg.ambiguous_wide(), overrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_width_override() {
+        assert_eq!(
+            WidthOverride::parse("2500-257F", 2),
+            Some(WidthOverride {
+                start: 0x2500,
+                end: 0x257F,
+                width: 2
+            })
+        );
+        assert_eq!(
+            WidthOverride::parse("U+E0B0", 1),
+            Some(WidthOverride {
+                start: 0xE0B0,
+                end: 0xE0B0,
+                width: 1
+            })
+        );
+        assert!(WidthOverride::parse("257F-2500", 1).is_none());
+        assert!(WidthOverride::parse("xyz", 1).is_none());
+        assert!(WidthOverride::parse("2500", 3).is_none());
+    }
+
+    #[test]
+    fn test_char_width() {
+        let narrow = CharWidth::default();
+        assert_eq!(narrow.width('a' as wchar_t), 1);
+        assert_eq!(narrow.width('\u{4E2D}' as wchar_t), 2);
+        // Box drawing and circled digits were ambiguous.
+        assert_eq!(narrow.width('\u{2500}' as wchar_t), 1);
+        assert_eq!(narrow.width('\u{2460}' as wchar_t), 1);
+
+        let wide = CharWidth::new(
+            true,
+            vec![
+                WidthOverride::parse("2500-257F", 1).unwrap(),
+                WidthOverride::parse("2502", 2).unwrap(),
+                WidthOverride::parse("E0B0", 2).unwrap(),
+            ],
+        );
+        assert_eq!(wide.width('a' as wchar_t), 1);
+        assert_eq!(wide.width('\u{2460}' as wchar_t), 2);
+        assert_eq!(wide.width('\u{2500}' as wchar_t), 1);
+        assert_eq!(wide.width('\u{2502}' as wchar_t), 2);
+        assert_eq!(wide.width('\u{E0B0}' as wchar_t), 2);
+        assert_eq!(wide.width('\u{0301}' as wchar_t), 0);
+    }
+}
@@ -2,16 +2,19 @@
 use lazy_static::lazy_static;
 use libc::wchar_t;
 use std::{
-    cell::RefCell,
-    collections::HashMap,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
     ptr::null_mut,
     sync::atomic::{AtomicPtr, Ordering},
 };
 use wchar::wch;
 
-use super::character_color::{
-    CharacterColor, ColorEntry, FontWeight, BASE_COLORS, COLOR_SPACE_DEFAULT, COLOR_SPACE_SYSTEM,
-    DEFAULT_BACK_COLOR, DEFAULT_FORE_COLOR,
+use super::{
+    character_color::{
+        CharacterColor, ColorEntry, FontWeight, BASE_COLORS, COLOR_SPACE_DEFAULT,
+        COLOR_SPACE_SYSTEM, DEFAULT_BACK_COLOR, DEFAULT_FORE_COLOR,
+    },
+    hyperlink::NO_HYPERLINK,
 };
 pub type LineProperty = u8;
 
@@ -19,6 +22,17 @@ pub const LINE_DEFAULT: u8 = 0;
 pub const LINE_WRAPPED: u8 = 1 << 0;
 pub const LINE_DOUBLE_WIDTH: u8 = 1 << 1;
 pub const LINE_DOUBLE_HEIGHT: u8 = 1 << 2;
+// Semantic marks of shell integration, set by the `OSC 133` escape sequence.
+pub const LINE_PROMPT_START: u8 = 1 << 3;
+pub const LINE_COMMAND_START: u8 = 1 << 4;
+pub const LINE_OUTPUT_START: u8 = 1 << 5;
+pub const LINE_COMMAND_FINISHED: u8 = 1 << 6;
+pub const LINE_SEMANTIC_MARKS: u8 =
+    LINE_PROMPT_START | LINE_COMMAND_START | LINE_OUTPUT_START | LINE_COMMAND_FINISHED;
+// Set with `LINE_DOUBLE_HEIGHT` on the bottom half of double height line (DECDHL).
+// It takes the last free bit of `LineProperty`, widen the type (and the line flags stored
+// by the history files) before adding another property.
+pub const LINE_DOUBLE_HEIGHT_BOTTOM: u8 = 1 << 7;
 
 pub const DEFAULT_RENDITION: wchar_t = 0;
 pub const RE_BOLD: wchar_t = 1 << 0;
@@ -33,6 +47,59 @@ pub const RE_FAINT: wchar_t = 1 << 7;
 pub const RE_STRIKEOUT: wchar_t = 1 << 8;
 pub const RE_CONCEAL: wchar_t = 1 << 9;
 pub const RE_OVERLINE: wchar_t = 1 << 10;
+/// The style of underline, only valid when [`RE_UNDERLINE`] was set, see [`UnderlineStyle`].
+pub const RE_UNDERLINE_STYLE: wchar_t = 0b111 << UNDERLINE_STYLE_SHIFT;
+const UNDERLINE_STYLE_SHIFT: wchar_t = 11;
+
+/// The underline styles set by `SGR 4:x`, `SGR 4` and `SGR 21` were the single and double underline.
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum UnderlineStyle {
+    #[default]
+    None = 0,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+impl UnderlineStyle {
+    /// Get the style from the sub-parameter of `SGR 4:x`, unknown styles were treated as single.
+    #[inline]
+    pub fn from_sgr(x: i32) -> Self {
+        match x {
+            0 => Self::None,
+            2 => Self::Double,
+            3 => Self::Curly,
+            4 => Self::Dotted,
+            5 => Self::Dashed,
+            _ => Self::Single,
+        }
+    }
+
+    #[inline]
+    pub fn from_rendition(rendition: wchar_t) -> Self {
+        if rendition & RE_UNDERLINE == 0 {
+            return Self::None;
+        }
+        match (rendition & RE_UNDERLINE_STYLE) >> UNDERLINE_STYLE_SHIFT {
+            2 => Self::Double,
+            3 => Self::Curly,
+            4 => Self::Dotted,
+            5 => Self::Dashed,
+            _ => Self::Single,
+        }
+    }
+
+    /// Get the rendition flags of the style, including [`RE_UNDERLINE`].
+    #[inline]
+    pub fn rendition(&self) -> wchar_t {
+        match self {
+            Self::None => 0,
+            _ => RE_UNDERLINE | ((*self as wchar_t) << UNDERLINE_STYLE_SHIFT),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum CharacterUnion {
@@ -96,6 +163,11 @@ pub struct Character {
     pub foreground_color: CharacterColor,
     /// The color used to draw this character's background. */
     pub background_color: CharacterColor,
+    /// The id of hyperlink this character belongs to, can be used to look up the hyperlink
+    /// in [`HyperlinkTable`](super::hyperlink::HyperlinkTable).
+    pub hyperlink: u32,
+    /// The color of underline set by `SGR 58`, the foreground color was used when it was invalid.
+    pub underline_color: CharacterColor,
 }
 
 impl Default for Character {
@@ -105,6 +177,8 @@ impl Default for Character {
             rendition: DEFAULT_RENDITION,
             foreground_color: CharacterColor::new(COLOR_SPACE_DEFAULT, DEFAULT_FORE_COLOR),
             background_color: CharacterColor::new(COLOR_SPACE_DEFAULT, DEFAULT_BACK_COLOR),
+            hyperlink: NO_HYPERLINK,
+            underline_color: CharacterColor::empty(),
         }
     }
 }
@@ -116,6 +190,8 @@ impl Character {
             rendition: r,
             foreground_color: f,
             background_color: b,
+            hyperlink: NO_HYPERLINK,
+            underline_color: CharacterColor::empty(),
         }
     }
 
@@ -169,26 +245,59 @@ impl Character {
         }
     }
 
-    /// returns true if the format (color, rendition flag) of the compared
+    /// returns true if the format (color, rendition flag, hyperlink) of the compared
     /// characters is equal
     #[inline]
     pub fn equals_format(&self, other: &Character) -> bool {
         self.background_color == other.background_color
             && self.foreground_color == other.foreground_color
             && self.rendition == other.rendition
+            && self.hyperlink == other.hyperlink
+            && self.underline_color == other.underline_color
+    }
+
+    /// Returns the style of underline.
+    #[inline]
+    pub fn underline_style(&self) -> UnderlineStyle {
+        UnderlineStyle::from_rendition(self.rendition)
+    }
+
+    /// Returns true if this character is a part of hyperlink.
+    #[inline]
+    pub fn is_hyperlink(&self) -> bool {
+        self.hyperlink != NO_HYPERLINK
     }
 }
 
+/// The number of sequences in [`ExtendedCharTable`] which triggers the first collection
+/// of the sequences no longer referenced by any character.
+pub const EXTENDED_CHARS_COLLECTION_THRESHOLD: usize = 4096;
+
 /// A table which stores sequences of unicode characters, referenced
 /// by hash keys.  The hash key itself is the same size as a unicode
 /// character ( ushort ) so that it can occupy the same space in a structure.
-#[derive(Debug, Default)]
+///
+/// Sequences are never evicted on their own, the owner of the characters collects the
+/// unused ones with [`ExtendedCharTable::collect_unused`] once
+/// [`ExtendedCharTable::needs_collection`] returns true.
+#[derive(Debug)]
 pub struct ExtendedCharTable(
     /// internal, maps hash keys to character sequence buffers.  The first wchar_t
     /// in each value is the length of the buffer, followed by the ushorts in the buffer themselves.
     RefCell<HashMap<wchar_t, Vec<wchar_t>>>,
+    /// The number of sequences which triggers the next collection.
+    Cell<usize>,
 );
 
+impl Default for ExtendedCharTable {
+    fn default() -> Self {
+        Self(
+            RefCell::default(),
+            Cell::new(EXTENDED_CHARS_COLLECTION_THRESHOLD),
+        )
+    }
+}
+
 impl ExtendedCharTable {
     pub fn instance<'a>() -> &'a Self {
         unsafe { INSTANCE.load(Ordering::SeqCst).as_ref().unwrap() }
@@ -214,7 +323,7 @@ impl ExtendedCharTable {
             if self.extended_char_match(hash, unicode_points, length) {
                 return hash;
             } else {
-                hash += 1;
+                hash = hash.wrapping_add(1);
             }
         }
 
@@ -228,12 +337,28 @@ impl ExtendedCharTable {
         hash
     }
 
+    /// Returns true if the table has grown enough since the last collection.
+    #[inline]
+    pub fn needs_collection(&self) -> bool {
+        self.0.borrow().len() >= self.1.get()
+    }
+
+    /// Removes the sequences whose hash is not in @p used, which must contain the hash of
+    /// every character still referencing the table.
+    pub fn collect_unused(&self, used: &HashSet<wchar_t>) {
+        let mut map = self.0.borrow_mut();
+        map.retain(|hash, _| used.contains(hash));
+        // Collect again once the table doubled, so the cost is amortized over the creations.
+        self.1
+            .set(EXTENDED_CHARS_COLLECTION_THRESHOLD.max(map.len() * 2));
+    }
+
     /// Looks up and returns a pointer to a sequence of unicode characters which was added to the table using createExtendedChar().
     ///
     /// @param hash The hash key returned by createExtendedChar()
     /// @param length This variable is set to the length of the character sequence.
     ///
-    /// @return A unicode character sequence of size @p length.
+    /// @return A unicode character sequence of size @p length, `None` if it was collected.
     pub fn lookup_extended_char(
         &self,
         hash: wchar_t,
@@ -258,7 +383,7 @@ impl ExtendedCharTable {
     fn extended_char_hash(&self, unicode_points: &[wchar_t], length: wchar_t) -> wchar_t {
         let mut hash = 0 as wchar_t;
         for &up in unicode_points.iter().take(length as usize) {
-            hash = 31 * hash + up;
+            hash = hash.wrapping_mul(31).wrapping_add(up);
         }
         hash
     }
@@ -296,3 +421,50 @@ impl ExtendedCharTable {
 lazy_static! {
     static ref INSTANCE: AtomicPtr<ExtendedCharTable> = AtomicPtr::new(null_mut());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_char_table() {
+        let table = ExtendedCharTable::default();
+        let cluster = ['e' as wchar_t, 0x301];
+        let hash = table.create_extended_char(&cluster, 2);
+        assert_eq!(table.create_extended_char(&cluster, 2), hash);
+
+        let mut len = 0;
+        assert_eq!(
+            table.lookup_extended_char(hash, &mut len),
+            Some(cluster.to_vec())
+        );
+        assert_eq!(len, 2);
+    }
+
+    #[test]
+    fn test_extended_char_collection() {
+        let table = ExtendedCharTable::default();
+        let used = ['a' as wchar_t, 0x301];
+        let unused = ['b' as wchar_t, 0x301];
+        let used_hash = table.create_extended_char(&used, 2);
+        let unused_hash = table.create_extended_char(&unused, 2);
+
+        // Sequences are kept however many are created until collected.
+        for i in 0..2 * EXTENDED_CHARS_COLLECTION_THRESHOLD as wchar_t {
+            table.create_extended_char(&[0x4E00 + i, 0x301], 2);
+        }
+        let mut len = 0;
+        assert!(table.lookup_extended_char(unused_hash, &mut len).is_some());
+        assert!(table.needs_collection());
+
+        table.collect_unused(&HashSet::from([used_hash]));
+        assert!(!table.needs_collection());
+        assert_eq!(
+            table.lookup_extended_char(used_hash, &mut len),
+            Some(used.to_vec())
+        );
+        assert_eq!(table.lookup_extended_char(unused_hash, &mut len), None);
+        assert_eq!(len, 0);
+        assert_eq!(table.create_extended_char(&used, 2), used_hash);
+    }
+}
@@ -238,6 +238,11 @@ impl CharacterColor {
         }
         u -= 8;
 
+        // 16..255: Changed by terminal program, appended to the color table.
+        if let Some(entry) = palette.get(TABLE_COLORS + u as usize) {
+            return entry.color;
+        }
+
         // 16..231: 6x6x6 rgb color cube
         if u < 216 {
             return Color::rgb(
@@ -268,6 +273,8 @@ impl CharacterColor {
 pub const BASE_COLORS: usize = 10;
 pub const INTENSITIES: usize = 2;
 pub const TABLE_COLORS: usize = INTENSITIES * BASE_COLORS;
+/// The colors `16..=255` of 256 colors palette, may be appended to the color table.
+pub const EXTENDED_COLORS: usize = 240;
 
 pub const DEFAULT_FORE_COLOR: u32 = 0;
 pub const DEFAULT_BACK_COLOR: u32 = 1;
@@ -588,4 +595,30 @@ mod tests {
         assert!(c1 == c2);
         assert!(c1 != c3);
     }
+
+    #[test]
+    fn test_color_256() {
+        let color: (i32, i32, i32) = CharacterColor::color_256(1, &BASE_COLOR_TABLE).into();
+        assert_eq!(color, (0xC5, 0x0F, 0x1F));
+        let color: (i32, i32, i32) = CharacterColor::color_256(196, &BASE_COLOR_TABLE).into();
+        assert_eq!(color, (0xFF, 0x00, 0x00));
+        let color: (i32, i32, i32) = CharacterColor::color_256(232, &BASE_COLOR_TABLE).into();
+        assert_eq!(color, (0x08, 0x08, 0x08));
+
+        // The extended colors appended to the color table take precedence.
+        let mut palette = BASE_COLOR_TABLE.to_vec();
+        palette.extend((16..=u8::MAX).map(|index| {
+            ColorEntry::new(
+                CharacterColor::color_256(index, &BASE_COLOR_TABLE),
+                false,
+                None,
+            )
+        }));
+        assert_eq!(palette.len(), TABLE_COLORS + EXTENDED_COLORS);
+        palette[TABLE_COLORS + 196 - 16].color = Color::rgb(1, 2, 3);
+        let color: (i32, i32, i32) = CharacterColor::color_256(196, &palette).into();
+        assert_eq!(color, (1, 2, 3));
+        let color: (i32, i32, i32) = CharacterColor::color_256(255, &palette).into();
+        assert_eq!(color, (0xEE, 0xEE, 0xEE));
+    }
 }
\ No newline at end of file
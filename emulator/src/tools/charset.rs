@@ -0,0 +1,177 @@
+#![allow(dead_code)]
+use super::character_color::VT100_GRAPHICS;
+use wchar::wchar_t;
+
+/// The positions of characters replaced by the national replacement character sets.
+const NRCS_POSITIONS: [u8; 12] = *b"#@[\\]^_`{|}~";
+
+const BRITISH: [char; 12] = ['£', '@', '[', '\\', ']', '^', '_', '`', '{', '|', '}', '~'];
+const DUTCH: [char; 12] = ['£', '¾', 'ĳ', '½', '|', '^', '_', '`', '¨', 'ƒ', '¼', '´'];
+const FINNISH: [char; 12] = ['#', '@', 'Ä', 'Ö', 'Å', 'Ü', '_', 'é', 'ä', 'ö', 'å', 'ü'];
+const FRENCH: [char; 12] = ['£', 'à', '°', 'ç', '§', '^', '_', '`', 'é', 'ù', 'è', '¨'];
+const FRENCH_CANADIAN: [char; 12] = ['#', 'à', 'â', 'ç', 'ê', 'î', '_', 'ô', 'é', 'ù', 'è', 'û'];
+const GERMAN: [char; 12] = ['#', '§', 'Ä', 'Ö', 'Ü', '^', '_', '`', 'ä', 'ö', 'ü', 'ß'];
+const ITALIAN: [char; 12] = ['£', '§', '°', 'ç', 'é', '^', '_', 'ù', 'à', 'ò', 'è', 'ì'];
+const NORWEGIAN_DANISH: [char; 12] = ['#', 'Ä', 'Æ', 'Ø', 'Å', 'Ü', '_', 'ä', 'æ', 'ø', 'å', 'ü'];
+const SPANISH: [char; 12] = ['£', '§', '¡', 'Ñ', '¿', '^', '_', '`', '°', 'ñ', 'ç', '~'];
+const SWEDISH: [char; 12] = ['#', 'É', 'Ä', 'Ö', 'Å', 'Ü', '_', 'é', 'ä', 'ö', 'å', 'ü'];
+const SWISS: [char; 12] = ['ù', 'à', 'é', 'ç', 'ê', 'î', 'è', 'ô', 'ä', 'ö', 'ü', 'û'];
+
+/// The DEC Technical character set of 0x21..=0x7E, 0 for the undefined characters.
+const DEC_TECHNICAL: [u16; 94] = [
+    0x23B7, 0x250C, 0x2500, 0x2320, 0x2321, 0x2502, 0x23A1, //
+    0x23A3, 0x23A4, 0x23A6, 0x239B, 0x239D, 0x239E, 0x23A0, 0x23A8, //
+    0x23AC, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, //
+    0x0000, 0x0000, 0x0000, 0x0000, 0x2264, 0x2260, 0x2265, 0x222B, //
+    0x2234, 0x221D, 0x221E, 0x00F7, 0x0394, 0x2207, 0x03A6, 0x0393, //
+    0x223C, 0x2243, 0x0398, 0x00D7, 0x039B, 0x21D4, 0x21D2, 0x2261, //
+    0x03A0, 0x03A8, 0x0000, 0x03A3, 0x0000, 0x0000, 0x221A, 0x03A9, //
+    0x039E, 0x03A5, 0x2282, 0x2283, 0x2229, 0x222A, 0x2227, 0x2228, //
+    0x00AC, 0x03B1, 0x03B2, 0x03C7, 0x03B4, 0x03B5, 0x03C6, 0x03B3, //
+    0x03B7, 0x03B9, 0x03B8, 0x03BA, 0x03BB, 0x0000, 0x03BD, 0x2202, //
+    0x03C0, 0x03C8, 0x03C1, 0x03C3, 0x03C4, 0x0000, 0x0192, 0x03C9, //
+    0x03BE, 0x03C5, 0x03B6, 0x2190, 0x2191, 0x2192, 0x2193,
+];
+
+/// The 94-character sets designated to G0-G3 by ISO 2022 `ESC ( ) * + F`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Charset {
+    #[default]
+    Ascii,
+    DecSpecialGraphics,
+    DecSupplemental,
+    DecTechnical,
+    British,
+    Dutch,
+    Finnish,
+    French,
+    FrenchCanadian,
+    German,
+    Italian,
+    NorwegianDanish,
+    Spanish,
+    Swedish,
+    Swiss,
+}
+
+impl Charset {
+    /// The charset of the final character `F` of designation `ESC ( F`.
+    ///
+    /// @return `None` if the charset was unknown.
+    pub fn from_designator(designator: wchar_t) -> Option<Self> {
+        let charset = match char::from_u32(designator as u32)? {
+            'B' => Self::Ascii,
+            '0' => Self::DecSpecialGraphics,
+            '<' => Self::DecSupplemental,
+            '>' => Self::DecTechnical,
+            'A' => Self::British,
+            '4' => Self::Dutch,
+            'C' | '5' => Self::Finnish,
+            'R' | 'f' => Self::French,
+            'Q' | '9' => Self::FrenchCanadian,
+            'K' => Self::German,
+            'Y' => Self::Italian,
+            'E' | '6' | '`' => Self::NorwegianDanish,
+            'Z' => Self::Spanish,
+            'H' | '7' => Self::Swedish,
+            '=' => Self::Swiss,
+            _ => return None,
+        };
+        Some(charset)
+    }
+
+    /// Translate the character `c` in the range of GL (0x21..=0x7E) by the charset,
+    /// the others were returned unchanged.
+    pub fn map(self, c: wchar_t) -> wchar_t {
+        if !(0x21..=0x7e).contains(&c) {
+            return c;
+        }
+
+        let nrcs = match self {
+            Self::Ascii => return c,
+            Self::DecSpecialGraphics => {
+                return if c >= 0x5f {
+                    VT100_GRAPHICS[(c - 0x5f) as usize]
+                } else {
+                    c
+                };
+            }
+            Self::DecSupplemental => {
+                // Same as the upper half of ISO Latin-1, except the following.
+                return match c + 0x80 {
+                    0xA8 => 0xA4,
+                    0xD7 => 0x152,
+                    0xDD => 0x178,
+                    0xF7 => 0x153,
+                    0xFD => 0xFF,
+                    c => c,
+                };
+            }
+            Self::DecTechnical => {
+                return match DEC_TECHNICAL[(c - 0x21) as usize] {
+                    0 => c,
+                    mapped => mapped as wchar_t,
+                };
+            }
+            Self::British => &BRITISH,
+            Self::Dutch => &DUTCH,
+            Self::Finnish => &FINNISH,
+            Self::French => &FRENCH,
+            Self::FrenchCanadian => &FRENCH_CANADIAN,
+            Self::German => &GERMAN,
+            Self::Italian => &ITALIAN,
+            Self::NorwegianDanish => &NORWEGIAN_DANISH,
+            Self::Spanish => &SPANISH,
+            Self::Swedish => &SWEDISH,
+            Self::Swiss => &SWISS,
+        };
+
+        match NRCS_POSITIONS.iter().position(|&p| p as wchar_t == c) {
+            Some(i) => nrcs[i] as wchar_t,
+            None => c,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(charset: Charset, c: char) -> char {
+        char::from_u32(charset.map(c as wchar_t) as u32).unwrap()
+    }
+
+    #[test]
+    fn test_charset_map() {
+        assert_eq!(map(Charset::Ascii, '#'), '#');
+        assert_eq!(map(Charset::British, '#'), '£');
+        assert_eq!(map(Charset::British, 'a'), 'a');
+        assert_eq!(map(Charset::DecSpecialGraphics, 'q'), '─');
+        assert_eq!(map(Charset::DecSpecialGraphics, 'A'), 'A');
+        assert_eq!(map(Charset::DecSupplemental, '\''), '§');
+        assert_eq!(map(Charset::DecSupplemental, 'W'), 'Œ');
+        assert_eq!(map(Charset::DecTechnical, 'a'), 'α');
+        assert_eq!(map(Charset::DecTechnical, '1'), '1');
+        assert_eq!(map(Charset::German, '['), 'Ä');
+        assert_eq!(map(Charset::German, '~'), 'ß');
+        assert_eq!(map(Charset::Swiss, '#'), 'ù');
+        assert_eq!(map(Charset::German, ' '), ' ');
+    }
+
+    #[test]
+    fn test_charset_designator() {
+        assert_eq!(
+            Charset::from_designator('0' as wchar_t),
+            Some(Charset::DecSpecialGraphics)
+        );
+        assert_eq!(
+            Charset::from_designator('5' as wchar_t),
+            Some(Charset::Finnish)
+        );
+        assert_eq!(
+            Charset::from_designator('B' as wchar_t),
+            Some(Charset::Ascii)
+        );
+        assert!(Charset::from_designator('1' as wchar_t).is_none());
+    }
+}
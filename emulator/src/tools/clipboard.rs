@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+use cli::session::cfg::{ClipboardAccessCfg, ClipboardCfg};
+use tmui::clipboard::ClipboardLevel;
+
+/// The default maximum bytes of text can be set/read by the `OSC 52` escape sequence.
+pub const DEFAULT_CLIPBOARD_SIZE_LIMIT: usize = 1 << 19;
+
+/// The selection targeted by the `OSC 52` escape sequence:
+///
+/// `ESC ] 52 ; Pc ; Pd ST`
+///
+/// `Pc` is a list of selection characters, `c` for the clipboard, `p` and `s` for the primary selection,
+/// empty or the others were treated as clipboard.
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ClipboardSelection {
+    #[default]
+    Clipboard = 0,
+    Primary,
+}
+impl From<u8> for ClipboardSelection {
+    #[inline]
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Primary,
+            _ => Self::Clipboard,
+        }
+    }
+}
+impl ClipboardSelection {
+    /// Parse the selection from the `Pc` parameter of `OSC 52`.
+    pub fn from_selector(selector: &str) -> Self {
+        for c in selector.chars() {
+            match c {
+                'c' => return Self::Clipboard,
+                'p' | 's' => return Self::Primary,
+                _ => {}
+            }
+        }
+        Self::Clipboard
+    }
+
+    #[inline]
+    pub fn selector(&self) -> char {
+        match self {
+            Self::Clipboard => 'c',
+            Self::Primary => 'p',
+        }
+    }
+
+    /// The primary selection was kept inside the application.
+    #[inline]
+    pub fn level(&self) -> ClipboardLevel {
+        match self {
+            Self::Clipboard => ClipboardLevel::Os,
+            Self::Primary => ClipboardLevel::Application,
+        }
+    }
+}
+
+/// Decides how the clipboard access requested by terminal program was handled.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ClipboardAccess {
+    /// Perform the access directly.
+    Allow,
+    /// Ask the user before performing the access.
+    Ask,
+    /// Ignore the access.
+    #[default]
+    Deny,
+}
+
+/// The policy of clipboard accessing through the `OSC 52` escape sequence, configured per session.
+///
+/// By default, terminal program was allowed to write the clipboard but not to read it,
+/// so that a hostile remote can not silently read the clipboard.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ClipboardPolicy {
+    /// The access of setting the clipboard.
+    pub write: ClipboardAccess,
+    /// The access of querying the clipboard.
+    pub read: ClipboardAccess,
+    /// The maximum bytes of text can be set to or read from the clipboard,
+    /// requests exceeding the limit will be ignored.
+    pub size_limit: usize,
+}
+impl Default for ClipboardPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            write: ClipboardAccess::Allow,
+            read: ClipboardAccess::Deny,
+            size_limit: DEFAULT_CLIPBOARD_SIZE_LIMIT,
+        }
+    }
+}
+
+impl From<ClipboardAccessCfg> for ClipboardAccess {
+    #[inline]
+    fn from(cfg: ClipboardAccessCfg) -> Self {
+        match cfg {
+            ClipboardAccessCfg::Allow => Self::Allow,
+            ClipboardAccessCfg::Ask => Self::Ask,
+            ClipboardAccessCfg::Deny => Self::Deny,
+        }
+    }
+}
+
+impl From<ClipboardCfg> for ClipboardPolicy {
+    #[inline]
+    fn from(cfg: ClipboardCfg) -> Self {
+        Self {
+            write: cfg.write().into(),
+            read: cfg.read().into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The clipboard request made by terminal program, waiting for the user to answer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ClipboardRequest {
+    Write(ClipboardSelection, String),
+    Read(ClipboardSelection),
+}
+
+/// Decode the `Pd` parameter of `OSC 52`.
+///
+/// @return `None` if `Pd` was not valid base64 encoded utf-8 text.
+pub fn decode_clipboard_data(data: &str) -> Option<String> {
+    let bytes = base64::decode(data).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Build the `OSC 52` response of clipboard query.
+pub fn encode_clipboard_response(selection: ClipboardSelection, text: &str) -> String {
+    format!(
+        "\u{1b}]52;{};{}\u{1b}\\",
+        selection.selector(),
+        base64::encode(text)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector() {
+        assert_eq!(
+            ClipboardSelection::from_selector(""),
+            ClipboardSelection::Clipboard
+        );
+        assert_eq!(
+            ClipboardSelection::from_selector("c"),
+            ClipboardSelection::Clipboard
+        );
+        assert_eq!(
+            ClipboardSelection::from_selector("p"),
+            ClipboardSelection::Primary
+        );
+        assert_eq!(
+            ClipboardSelection::from_selector("s0"),
+            ClipboardSelection::Primary
+        );
+        assert_eq!(
+            ClipboardSelection::from_selector("0c"),
+            ClipboardSelection::Clipboard
+        );
+        assert_eq!(
+            ClipboardSelection::from(ClipboardSelection::Primary as u8),
+            ClipboardSelection::Primary
+        );
+    }
+
+    #[test]
+    fn test_codec() {
+        assert_eq!(decode_clipboard_data("aGVsbG8=").unwrap(), "hello");
+        assert!(decode_clipboard_data("not base64!").is_none());
+        assert_eq!(
+            encode_clipboard_response(ClipboardSelection::Clipboard, "hello"),
+            "\u{1b}]52;c;aGVsbG8=\u{1b}\\"
+        );
+    }
+}
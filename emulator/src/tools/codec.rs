@@ -0,0 +1,200 @@
+#![allow(dead_code)]
+use encoding::{
+    all::UTF_8, label::encoding_from_whatwg_label, EncoderTrap, Encoding, EncodingRef, RawDecoder,
+    StringWriter,
+};
+use libc::wchar_t;
+use std::fmt::Debug;
+
+pub const DEFAULT_ENCODING: &str = "UTF-8";
+
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+/// Converts the bytes between terminal program and the characters of terminal, in the
+/// character encoding of session, e.g. `UTF-8`, `GBK`, `Big5`, `Shift_JIS`, `EUC-KR`
+/// or `ISO-8859-2`.
+///
+/// The decoder was stateful, the multibyte sequence split across the reads was kept
+/// until the following bytes arrived.
+pub struct TerminalCodec {
+    encoding: EncodingRef,
+    decoder: Box<dyn RawDecoder>,
+}
+
+impl TerminalCodec {
+    /// Create the codec by the label of encoding (case insensitive), e.g. `gbk` or `Big5`.
+    ///
+    /// @return `None` if the encoding was unknown.
+    pub fn new(label: &str) -> Option<Self> {
+        let encoding = encoding_from_whatwg_label(label.trim())?;
+        Some(Self {
+            encoding,
+            decoder: encoding.raw_decoder(),
+        })
+    }
+
+    #[inline]
+    pub fn utf8() -> Self {
+        Self {
+            encoding: UTF_8,
+            decoder: UTF_8.raw_decoder(),
+        }
+    }
+
+    /// The canonical name of encoding.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.encoding.whatwg_name().unwrap_or(self.encoding.name())
+    }
+
+    #[inline]
+    pub fn is_utf8(&self) -> bool {
+        self.name() == UTF_8.name()
+    }
+
+    /// Decode the bytes received from terminal program, the malformed sequences were replaced
+    /// by `U+FFFD`, the incomplete sequence at the end was kept to the next call.
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        let mut text = String::with_capacity(bytes.len());
+        self.decode_into(bytes, &mut text);
+        text
+    }
+
+    /// Decode the bytes received from terminal program to the wide characters appended to
+    /// `text`, same as [`TerminalCodec::decode`] without the intermediate string.
+    #[inline]
+    pub fn decode_wide(&mut self, bytes: &[u8], text: &mut Vec<wchar_t>) {
+        self.decode_into(bytes, &mut WideWriter(text))
+    }
+
+    fn decode_into(&mut self, bytes: &[u8], text: &mut dyn StringWriter) {
+        let mut remaining = bytes;
+
+        loop {
+            let (processed, error) = self.decoder.raw_feed(remaining, text);
+            match error {
+                Some(error) => {
+                    text.write_char(REPLACEMENT_CHARACTER);
+                    let upto = (error.upto.max(0) as usize).clamp(processed, remaining.len());
+                    remaining = &remaining[upto..];
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Encode the text sent to terminal program, the unmappable characters were replaced by `?`.
+    #[inline]
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        if self.is_utf8() {
+            return text.as_bytes().to_vec();
+        }
+        self.encoding
+            .encode(text, EncoderTrap::Replace)
+            .unwrap_or_else(|_| text.as_bytes().to_vec())
+    }
+
+    /// Drop the incomplete sequence kept by decoder.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.decoder = self.encoding.raw_decoder()
+    }
+}
+
+/// Append the characters of `text` to the wide characters, they were UTF-16 code units
+/// on Windows.
+pub fn extend_wide(wide: &mut Vec<wchar_t>, text: &str) {
+    #[cfg(not(windows))]
+    wide.extend(text.chars().map(|c| c as wchar_t));
+    #[cfg(windows)]
+    wide.extend(text.encode_utf16().map(|c| c as wchar_t));
+}
+
+struct WideWriter<'a>(&'a mut Vec<wchar_t>);
+
+impl StringWriter for WideWriter<'_> {
+    #[inline]
+    fn writer_hint(&mut self, expectedlen: usize) {
+        self.0.reserve(expectedlen)
+    }
+
+    #[inline]
+    fn write_char(&mut self, c: char) {
+        extend_wide(self.0, c.encode_utf8(&mut [0; 4]))
+    }
+
+    #[inline]
+    fn write_str(&mut self, s: &str) {
+        extend_wide(self.0, s)
+    }
+}
+
+impl Default for TerminalCodec {
+    #[inline]
+    fn default() -> Self {
+        Self::utf8()
+    }
+}
+
+impl Debug for TerminalCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TerminalCodec")
+            .field("encoding", &self.name())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_split_sequence() {
+        // "中文" in GBK, split in the middle of the second character.
+        let mut codec = TerminalCodec::new("GBK").unwrap();
+        let mut text = codec.decode(&[0xD6, 0xD0, 0xCE]);
+        text.push_str(&codec.decode(&[0xC4, b'a']));
+        assert_eq!(text, "中文a");
+
+        let mut codec = TerminalCodec::utf8();
+        let mut text = codec.decode(&[0xE4, 0xB8]);
+        text.push_str(&codec.decode(&[0xAD]));
+        assert_eq!(text, "中");
+    }
+
+    #[test]
+    fn test_decode_wide() {
+        let mut codec = TerminalCodec::new("GBK").unwrap();
+        let mut text = vec![];
+        codec.decode_wide(&[b'a', 0xD6, 0xD0, 0xCE], &mut text);
+        codec.decode_wide(&[0xC4, 0xFF], &mut text);
+
+        let mut expected = vec![];
+        extend_wide(&mut expected, "a中文\u{FFFD}");
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        let mut codec = TerminalCodec::utf8();
+        assert_eq!(codec.decode(&[b'a', 0xFF, b'b']), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn test_encode() {
+        let codec = TerminalCodec::new("big5").unwrap();
+        // "中" in Big5.
+        assert_eq!(
+            codec.encode("\u{1b}[A中"),
+            vec![0x1B, b'[', b'A', 0xA4, 0xA4]
+        );
+
+        let codec = TerminalCodec::new("Shift_JIS").unwrap();
+        assert_eq!(codec.encode("日"), vec![0x93, 0xFA]);
+
+        let codec = TerminalCodec::new("iso-8859-2").unwrap();
+        assert_eq!(codec.encode("ł中"), vec![0xB3, b'?']);
+
+        assert!(TerminalCodec::new("unknown").is_none());
+    }
+}
@@ -0,0 +1,150 @@
+#![allow(dead_code)]
+
+/// The color which can be queried or changed by terminal program through the escape sequences:
+///
+/// - `OSC 4 ; c ; spec ST`: Change the palette color `c`, `spec` was `?` to query the color.
+/// - `OSC 10/11/12 ; spec ST`: Change the default foreground/background/cursor color.
+/// - `OSC 104 [; c] ST`: Reset the palette color `c`, or all the palette colors if `c` was omitted.
+/// - `OSC 110/111/112 ST`: Reset the default foreground/background/cursor color.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DynamicColor {
+    /// The 256 colors palette.
+    Palette(u8),
+    Foreground,
+    Background,
+    Cursor,
+}
+
+impl DynamicColor {
+    /// The code of all the palette colors, only used by the reset request.
+    pub const ALL_PALETTE: i32 = -1;
+
+    /// Convert to the code passed by signals, `0..=255` represents the palette colors.
+    #[inline]
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::Palette(idx) => *idx as i32,
+            Self::Foreground => 256,
+            Self::Background => 257,
+            Self::Cursor => 258,
+        }
+    }
+
+    #[inline]
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0..=255 => Some(Self::Palette(code as u8)),
+            256 => Some(Self::Foreground),
+            257 => Some(Self::Background),
+            258 => Some(Self::Cursor),
+            _ => None,
+        }
+    }
+
+    /// Get the default color changed by `OSC 10/11/12`.
+    #[inline]
+    pub fn from_osc(ps: i32) -> Option<Self> {
+        match ps {
+            10 => Some(Self::Foreground),
+            11 => Some(Self::Background),
+            12 => Some(Self::Cursor),
+            _ => None,
+        }
+    }
+
+    /// Build the response of color query, the color was reported in the form of `rgb:rrrr/gggg/bbbb`.
+    pub fn response(&self, (r, g, b): (u8, u8, u8)) -> String {
+        let spec = format!(
+            "rgb:{:02x}{:02x}/{:02x}{:02x}/{:02x}{:02x}",
+            r, r, g, g, b, b
+        );
+        match self {
+            Self::Palette(idx) => format!("\u{1b}]4;{};{}\u{1b}\\", idx, spec),
+            Self::Foreground => format!("\u{1b}]10;{}\u{1b}\\", spec),
+            Self::Background => format!("\u{1b}]11;{}\u{1b}\\", spec),
+            Self::Cursor => format!("\u{1b}]12;{}\u{1b}\\", spec),
+        }
+    }
+}
+
+/// Parse the color specification of XParseColor, supports the forms:
+///
+/// - `rgb:r/g/b`, each component has 1 to 4 hex digits.
+/// - `#rgb`, `#rrggbb`, `#rrrgggbbb` and `#rrrrggggbbbb`.
+///
+/// @return `None` if the specification was not valid.
+pub fn parse_color_spec(spec: &str) -> Option<(u8, u8, u8)> {
+    if let Some(rgb) = spec.strip_prefix("rgb:") {
+        let mut components = rgb.split('/');
+        let r = parse_component(components.next()?)?;
+        let g = parse_component(components.next()?)?;
+        let b = parse_component(components.next()?)?;
+        if components.next().is_some() {
+            return None;
+        }
+        return Some((r, g, b));
+    }
+
+    let hex = spec.strip_prefix('#')?;
+    if hex.is_empty() || hex.len() % 3 != 0 || hex.len() > 12 {
+        return None;
+    }
+    let len = hex.len() / 3;
+    // The digits of `#` form were the most significant bits of the component.
+    let component = |i: usize| -> Option<u8> {
+        let digits = hex.get(i * len..(i + 1) * len)?;
+        let value = u16::from_str_radix(digits, 16).ok()?;
+        Some((value << (16 - len * 4) >> 8) as u8)
+    };
+    Some((component(0)?, component(1)?, component(2)?))
+}
+
+/// Parse the component of `rgb:` form, the value was scaled to 8 bits.
+fn parse_component(digits: &str) -> Option<u8> {
+    if digits.is_empty() || digits.len() > 4 {
+        return None;
+    }
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let max = (1u32 << (digits.len() * 4)) - 1;
+    Some(((value * 255 + max / 2) / max) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color_spec() {
+        assert_eq!(parse_color_spec("rgb:ff/80/00"), Some((0xff, 0x80, 0x00)));
+        assert_eq!(parse_color_spec("rgb:f/8/0"), Some((0xff, 0x88, 0x00)));
+        assert_eq!(
+            parse_color_spec("rgb:ffff/8080/0000"),
+            Some((0xff, 0x80, 0x00))
+        );
+        assert_eq!(parse_color_spec("#ff8000"), Some((0xff, 0x80, 0x00)));
+        assert_eq!(parse_color_spec("#f80"), Some((0xf0, 0x80, 0x00)));
+        assert_eq!(parse_color_spec("#ffff80800000"), Some((0xff, 0x80, 0x00)));
+        assert_eq!(parse_color_spec("rgb:ff/80"), None);
+        assert_eq!(parse_color_spec("rgb:ff/80/00/00"), None);
+        assert_eq!(parse_color_spec("rgb:fffff/80/00"), None);
+        assert_eq!(parse_color_spec("#ff80"), None);
+        assert_eq!(parse_color_spec("red"), None);
+    }
+
+    #[test]
+    fn test_response() {
+        assert_eq!(
+            DynamicColor::Background.response((0x0a, 0x0b, 0x0c)),
+            "\u{1b}]11;rgb:0a0a/0b0b/0c0c\u{1b}\\"
+        );
+        assert_eq!(
+            DynamicColor::Palette(1).response((0xff, 0, 0)),
+            "\u{1b}]4;1;rgb:ffff/0000/0000\u{1b}\\"
+        );
+        assert_eq!(
+            DynamicColor::from_code(DynamicColor::Cursor.code()),
+            Some(DynamicColor::Cursor)
+        );
+        assert_eq!(DynamicColor::from_code(DynamicColor::ALL_PALETTE), None);
+    }
+}
@@ -1,3 +1,4 @@
+use super::translators::CTRL_MODIFIER;
 use tmui::{
     prelude::{FromBytes, StaticType, ToBytes, ToValue},
     tlib::{
@@ -123,6 +124,46 @@ impl ToKeyPressedEvent for KeyEvent {
     }
 }
 
+////////////////////////////////////////////////////////////////////
+//////////////////// Mouse
+////////////////////////////////////////////////////////////////////
+/// The xterm button code of mouse event reported to the terminal program.
+pub const MOUSE_LEFT_BUTTON: i32 = 0;
+pub const MOUSE_MIDDLE_BUTTON: i32 = 1;
+pub const MOUSE_RIGHT_BUTTON: i32 = 2;
+/// Button released, or mouse moved without any button pressed.
+pub const MOUSE_NO_BUTTON: i32 = 3;
+pub const MOUSE_WHEEL_UP: i32 = 64;
+pub const MOUSE_WHEEL_DOWN: i32 = 65;
+
+/// The modifier bits of xterm button code.
+pub const MOUSE_SHIFT: i32 = 4;
+pub const MOUSE_META: i32 = 8;
+pub const MOUSE_CONTROL: i32 = 16;
+pub const MOUSE_MODIFIERS: i32 = MOUSE_SHIFT | MOUSE_META | MOUSE_CONTROL;
+/// Added to the button code when the mouse was moved.
+pub const MOUSE_MOTION: i32 = 32;
+
+/// The type of mouse event reported to the terminal program.
+pub const MOUSE_PRESS: u8 = 0;
+pub const MOUSE_MOVE: u8 = 1;
+pub const MOUSE_RELEASE: u8 = 2;
+
+/// Get the modifier bits of xterm button code.
+pub fn mouse_modifiers(modifier: KeyboardModifier) -> i32 {
+    let mut bits = 0;
+    if modifier.has(KeyboardModifier::ShiftModifier) {
+        bits |= MOUSE_SHIFT;
+    }
+    if modifier.has(KeyboardModifier::AltModifier) {
+        bits |= MOUSE_META;
+    }
+    if modifier.has(CTRL_MODIFIER) {
+        bits |= MOUSE_CONTROL;
+    }
+    bits
+}
+
 #[cfg(test)]
 mod tests {
     use tmui::tlib::events::EventType;
@@ -174,6 +174,10 @@ impl TerminalImageFilterChain {
         let mut line_stream = TextStream::new(buffer_ref_mut.deref_mut());
         decoder.begin(&mut line_stream);
 
+        for filter in self.filter_chain.borrow_mut().iter_mut() {
+            filter.borrow_mut().set_image(image, lines, columns);
+        }
+
         for i in 0..lines as usize {
             self.line_positions
                 .borrow_mut()
@@ -0,0 +1,259 @@
+use regex::Regex;
+use std::{cell::RefCell, ptr::NonNull, rc::Rc};
+use tmui::prelude::*;
+use tmui::{
+    clipboard::ClipboardLevel,
+    system::System,
+    tlib::object::{ObjectImpl, ObjectSubclass},
+};
+
+use super::{
+    BaseFilter, BaseFilterImpl, Filter, FilterObject, HotSpot, HotSpotConstructer, HotSpotImpl,
+    HotSpotType,
+};
+use crate::tools::{
+    character::Character,
+    hyperlink::{HyperlinkTable, NO_HYPERLINK},
+};
+
+/// Hotspot represents the area of characters belonging to a `OSC 8` hyperlink.
+///
+/// A hyperlink may be split into several hotspots (e.g. the hyperlink was wrapped
+/// with trailing blanks), hotspots with the same [`HotSpotImpl::hyperlink()`]
+/// should be treated as a whole.
+#[extends(Object)]
+pub struct HyperlinkFilterHotSpot {
+    hotspot: Box<HotSpot>,
+    hyperlink: u32,
+    link_object: RefCell<FilterObject>,
+}
+impl ObjectSubclass for HyperlinkFilterHotSpot {
+    const NAME: &'static str = "HyperlinkFilterHotSpot";
+}
+impl ObjectImpl for HyperlinkFilterHotSpot {}
+
+impl HyperlinkFilterHotSpot {
+    #[inline]
+    pub fn set_hyperlink(&mut self, hyperlink: u32) {
+        self.hyperlink = hyperlink
+    }
+
+    /// Returns the uri of the hyperlink.
+    #[inline]
+    pub fn uri(&self) -> Option<String> {
+        HyperlinkTable::uri(self.hyperlink)
+    }
+}
+impl HotSpotConstructer for HyperlinkFilterHotSpot {
+    fn new(start_line: i32, start_column: i32, end_line: i32, end_column: i32) -> Box<Self> {
+        let mut hotspot: Box<HyperlinkFilterHotSpot> = Object::new(&[]);
+
+        hotspot.hotspot = HotSpot::new(start_line, start_column, end_line, end_column);
+        hotspot.set_type(HotSpotType::Link);
+
+        let ptr = hotspot.as_mut() as *mut HyperlinkFilterHotSpot as *mut dyn HotSpotImpl;
+        hotspot.link_object.borrow_mut().set_filter(ptr);
+
+        hotspot
+    }
+}
+impl HotSpotImpl for HyperlinkFilterHotSpot {
+    fn initialize(&self) {
+        self.link_object.borrow().activate();
+    }
+
+    #[inline]
+    fn start_line(&self) -> i32 {
+        self.hotspot.start_line()
+    }
+
+    #[inline]
+    fn end_line(&self) -> i32 {
+        self.hotspot.end_line()
+    }
+
+    #[inline]
+    fn start_column(&self) -> i32 {
+        self.hotspot.start_column()
+    }
+
+    #[inline]
+    fn end_column(&self) -> i32 {
+        self.hotspot.end_column()
+    }
+
+    #[inline]
+    fn type_(&self) -> HotSpotType {
+        self.hotspot.type_()
+    }
+
+    fn activate(&self, action: &str) {
+        let uri = match self.uri() {
+            Some(uri) => uri,
+            None => return,
+        };
+
+        if action == FilterObject::ACTION_COPY {
+            System::clipboard().set_text(uri, ClipboardLevel::Os);
+            return;
+        }
+
+        if action.is_empty()
+            || action == FilterObject::ACTION_OPEN
+            || action == FilterObject::ACTION_CLICK
+        {
+            self.link_object
+                .borrow()
+                .emit_activated(uri, action != FilterObject::ACTION_CLICK);
+        }
+    }
+
+    #[inline]
+    fn set_type(&mut self, type_: HotSpotType) {
+        self.hotspot.set_type(type_)
+    }
+
+    #[inline]
+    fn hyperlink(&self) -> u32 {
+        self.hyperlink
+    }
+
+    #[inline]
+    fn filter_object(&self) -> Option<NonNull<FilterObject>> {
+        NonNull::new(self.link_object.as_ptr())
+    }
+
+    fn actions(&self) -> Vec<Action> {
+        let open_action = Action::with_param(
+            self.link_object.borrow().action_open(),
+            vec!["Open link".to_value()],
+        );
+        let copy_action = Action::with_param(
+            self.link_object.borrow().action_copy(),
+            vec!["Copy link address".to_value()],
+        );
+        vec![open_action, copy_action]
+    }
+}
+
+/// A filter which finds the hyperlinks declared by `OSC 8` escape sequence,
+/// unlike the other filters, it processes the hyperlink id of characters in
+/// terminal image rather than the plain text.
+pub struct HyperlinkFilter {
+    filter: BaseFilter,
+    /// The hyperlink id of each character in terminal image.
+    hyperlinks: Vec<u32>,
+    columns: i32,
+    /// Called with the [`FilterObject`] of each hotspot once it was created.
+    on_hotspot_created: Option<Box<dyn Fn(&mut FilterObject)>>,
+}
+impl HyperlinkFilter {
+    pub fn new() -> Self {
+        Self {
+            filter: BaseFilter::new(),
+            hyperlinks: vec![],
+            columns: 0,
+            on_hotspot_created: None,
+        }
+    }
+
+    /// Sets the function called with the [`FilterObject`] of each hotspot once it was
+    /// created, to connect the `action_filter_activated` signal of it.
+    #[inline]
+    pub fn set_on_hotspot_created(&mut self, f: impl Fn(&mut FilterObject) + 'static) {
+        self.on_hotspot_created = Some(Box::new(f))
+    }
+}
+impl BaseFilterImpl for HyperlinkFilter {
+    fn add_hotspot(&mut self, hotspot: Box<dyn HotSpotImpl>) -> &dyn HotSpotImpl {
+        let end_line = hotspot.end_line();
+        self.filter.add_hotspot(hotspot);
+
+        // The base filter indexes the hotspot by the lines before its end line, but the
+        // hyperlinks mostly start and end on the same line, index the end line as well.
+        let spot = self.filter.hostspots_list.last().unwrap().clone();
+        self.filter.hotspots.entry(end_line).or_default().push(spot);
+        &***self.filter.hostspots_list.last().unwrap()
+    }
+
+    fn get_line_column(&self, position: i32) -> (i32, i32) {
+        (position / self.columns, position % self.columns)
+    }
+}
+impl Filter for HyperlinkFilter {
+    fn process(&mut self, _: &Regex) {
+        if self.columns <= 0 {
+            return;
+        }
+
+        // Each run of characters with the same hyperlink id becomes a hotspot,
+        // the run continues to the next line when the line was filled up,
+        // which means hyperlinks across wrapped lines are still treated as one hotspot.
+        let mut pos = 0usize;
+        while pos < self.hyperlinks.len() {
+            let hyperlink = self.hyperlinks[pos];
+            if hyperlink == NO_HYPERLINK {
+                pos += 1;
+                continue;
+            }
+
+            let start = pos;
+            while pos < self.hyperlinks.len() && self.hyperlinks[pos] == hyperlink {
+                pos += 1;
+            }
+
+            let (start_line, start_column) = self.get_line_column(start as i32);
+            let (end_line, end_column) = self.get_line_column(pos as i32 - 1);
+
+            let mut spot =
+                HyperlinkFilterHotSpot::new(start_line, start_column, end_line, end_column + 1);
+            spot.set_hyperlink(hyperlink);
+
+            let spot_ref = self.add_hotspot(spot);
+            spot_ref.initialize();
+
+            let filter_object = spot_ref.filter_object();
+            if let (Some(f), Some(mut filter_object)) = (&self.on_hotspot_created, filter_object) {
+                f(unsafe { filter_object.as_mut() });
+            }
+        }
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        self.filter.reset()
+    }
+
+    #[inline]
+    fn hotspot_at(&self, line: i32, column: i32) -> Option<Rc<Box<dyn HotSpotImpl>>> {
+        self.filter.hotspot_at(line, column)
+    }
+
+    #[inline]
+    fn hotspots(&self) -> &Vec<Rc<Box<dyn HotSpotImpl>>> {
+        self.filter.hotspots()
+    }
+
+    #[inline]
+    fn hotspots_at_line(&self, line: i32) -> Option<&Vec<Rc<Box<dyn HotSpotImpl>>>> {
+        self.filter.hotspots_at_line(line)
+    }
+
+    #[inline]
+    fn set_buffer(&mut self, buffer: Rc<RefCell<String>>, line_positions: Rc<RefCell<Vec<i32>>>) {
+        self.filter.set_buffer(buffer, line_positions)
+    }
+
+    #[inline]
+    fn buffer(&mut self) -> Rc<RefCell<String>> {
+        self.filter.buffer()
+    }
+
+    fn set_image(&mut self, image: &[Character], lines: i32, columns: i32) {
+        let size = (lines * columns).max(0) as usize;
+        self.columns = columns;
+        self.hyperlinks.clear();
+        self.hyperlinks
+            .extend(image.iter().take(size).map(|c| c.hyperlink));
+    }
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 pub mod filter_chain;
+pub mod hyperlink_filter;
 pub mod regex_filter;
 pub mod url_filter;
 
@@ -8,6 +9,8 @@ use tmui::tlib::emit;
 use tmui::tlib::object::{ObjectImpl, ObjectSubclass};
 use tmui::tlib::{signal, signals};
 
+use crate::tools::character::Character;
+use crate::tools::hyperlink::NO_HYPERLINK;
 use crate::tools::system_ffi::string_width;
 use lazy_static::__Deref;
 use lazy_static::lazy_static;
@@ -95,6 +98,20 @@ pub trait HotSpotImpl {
 
     /// Sets the type of a hotspot.  This should only be set once
     fn set_type(&mut self, type_: HotSpotType);
+
+    /// Returns the hyperlink id of the hotspot, hotspots with the same hyperlink id
+    /// represent the same link and should be treated as a whole.
+    #[inline]
+    fn hyperlink(&self) -> u32 {
+        NO_HYPERLINK
+    }
+
+    /// Returns the [`FilterObject`] which emits the `action_filter_activated` signal
+    /// when the hotspot was activated.
+    #[inline]
+    fn filter_object(&self) -> Option<NonNull<FilterObject>> {
+        None
+    }
 }
 pub fn unsafe_as_hotspot_ref<T: HotSpotImpl>(hotspot: &mut dyn HotSpotImpl) -> &mut T {
     unsafe { &mut *(hotspot as *mut dyn HotSpotImpl as *mut T) }
@@ -215,6 +232,11 @@ pub trait Filter {
 
     /// Get the buffer of filter
     fn buffer(&mut self) -> Rc<RefCell<String>>;
+
+    /// Set the terminal image, for the filters processing the attributes of characters
+    /// rather than the plain text.
+    #[inline]
+    fn set_image(&mut self, _image: &[Character], _lines: i32, _columns: i32) {}
 }
 /// Judge whether two filters are the same
 fn filter_equals(one: Rc<RefCell<dyn Filter>>, other: Rc<RefCell<dyn Filter>>) -> bool {
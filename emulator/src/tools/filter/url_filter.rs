@@ -1,5 +1,5 @@
 use regex::Regex;
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, ptr::NonNull, rc::Rc};
 use tmui::prelude::*;
 use tmui::tlib::object::{ObjectImpl, ObjectSubclass};
 
@@ -134,6 +134,11 @@ impl HotSpotImpl for UrlFilterHotSpot {
         self.hotspot.set_type(type_)
     }
 
+    #[inline]
+    fn filter_object(&self) -> Option<NonNull<FilterObject>> {
+        NonNull::new(self.url_object.as_ptr())
+    }
+
     fn actions(&self) -> Vec<Action> {
         let mut list = vec![];
         let kind = self.url_type();
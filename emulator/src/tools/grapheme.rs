@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+use super::char_width::CharWidth;
+use unicode_segmentation::GraphemeCursor;
+use wchar::wchar_t;
+
+/// The maximum number of code points in one grapheme cluster, the exceeded code points
+/// were dropped, so the "zalgo" text can't grow the extended char table unboundedly.
+pub const MAX_CLUSTER_LENGTH: usize = 32;
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+const TEXT_PRESENTATION_SELECTOR: char = '\u{FE0E}';
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{FE0F}';
+
+/// Returns true if there was no extended grapheme cluster boundary between `cluster`
+/// and the following character `c`, it means `c` should be joined to the cluster.
+///
+/// The character `c` was one code point, which may be a UTF-16 surrogate pair.
+pub fn extends_cluster(cluster: &[wchar_t], c: &[wchar_t]) -> bool {
+    let Some(c) = code_point(c) else {
+        return false;
+    };
+    let mut text = String::with_capacity(cluster.len() * 4 + c.len_utf8());
+    for ch in decode(cluster) {
+        match ch {
+            Some(ch) => text.push(ch),
+            None => return false,
+        }
+    }
+    if text.is_empty() {
+        return false;
+    }
+
+    let offset = text.len();
+    text.push(c);
+    let mut cursor = GraphemeCursor::new(offset, text.len(), true);
+    matches!(cursor.is_boundary(&text, 0), Ok(false))
+}
+
+/// The number of cells occupied by the grapheme cluster:
+/// - The width of the first code point computed by `char_width` was the base width.
+/// - The emoji presentation selector (VS16) widens the cluster to 2 cells, the text
+///   presentation selector (VS15) narrows it to 1 cell.
+/// - The pair of regional indicators (flag) occupies 2 cells.
+/// - The code points after zero width joiner were the parts of the same emoji, they
+///   have no extra width.
+pub fn cluster_width(cluster: &[wchar_t], char_width: &CharWidth) -> i32 {
+    let mut chars = decode(cluster).flatten();
+    let Some(first) = chars.next() else {
+        return 0;
+    };
+    let mut width = char_width.code_point_width(first as u32);
+
+    let mut joined = false;
+    for c in chars {
+        match c {
+            ZERO_WIDTH_JOINER => joined = true,
+            EMOJI_PRESENTATION_SELECTOR if !joined => width = 2,
+            TEXT_PRESENTATION_SELECTOR if !joined => width = 1,
+            c if is_regional_indicator(c) && is_regional_indicator(first) => width = 2,
+            _ => {}
+        }
+    }
+    width
+}
+
+#[inline]
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+#[inline]
+pub fn is_high_surrogate(c: wchar_t) -> bool {
+    (0xD800..0xDC00).contains(&(c as u32))
+}
+
+#[inline]
+pub fn is_low_surrogate(c: wchar_t) -> bool {
+    (0xDC00..0xE000).contains(&(c as u32))
+}
+
+/// Returns the first code point of the wide characters, `None` if it was invalid.
+#[inline]
+pub fn code_point(c: &[wchar_t]) -> Option<char> {
+    decode(c).next().flatten()
+}
+
+/// Decodes the code points of wide characters, the UTF-16 surrogate pairs (the wide
+/// characters on windows) were combined, `None` for the unpaired surrogates.
+fn decode(chars: &[wchar_t]) -> impl Iterator<Item = Option<char>> + '_ {
+    let mut chars = chars.iter().map(|&c| c as u32).peekable();
+    std::iter::from_fn(move || {
+        let c = chars.next()?;
+        if !is_high_surrogate(c as wchar_t) {
+            return Some(char::from_u32(c));
+        }
+        match chars.peek() {
+            Some(&low) if is_low_surrogate(low as wchar_t) => {
+                chars.next();
+                Some(char::from_u32(
+                    0x10000 + ((c - 0xD800) << 10) + (low - 0xDC00),
+                ))
+            }
+            _ => Some(None),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(text: &str) -> Vec<wchar_t> {
+        text.chars().map(|c| c as wchar_t).collect()
+    }
+
+    fn utf16(text: &str) -> Vec<wchar_t> {
+        text.encode_utf16().map(|c| c as wchar_t).collect()
+    }
+
+    /// Feeds the characters of `text` one by one, returns the clusters.
+    fn segment(text: &str) -> Vec<Vec<wchar_t>> {
+        let mut clusters: Vec<Vec<wchar_t>> = vec![];
+        for c in points(text) {
+            match clusters.last_mut() {
+                Some(cluster) if extends_cluster(cluster, &[c]) => cluster.push(c),
+                _ => clusters.push(vec![c]),
+            }
+        }
+        clusters
+    }
+
+    #[test]
+    fn test_extends_cluster() {
+        // e + combining acute accent.
+        assert_eq!(segment("e\u{0301}x").len(), 2);
+        // Family: man ZWJ woman ZWJ girl.
+        assert_eq!(
+            segment("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}").len(),
+            1
+        );
+        // Two flags.
+        assert_eq!(segment("\u{1F1E8}\u{1F1F3}\u{1F1FA}\u{1F1F8}").len(), 2);
+        // Thumbs up with skin tone modifier.
+        assert_eq!(segment("\u{1F44D}\u{1F3FD}").len(), 1);
+        // Thai: ko kai + mai ek, then sara am.
+        assert_eq!(segment("\u{0E01}\u{0E48}\u{0E33}").len(), 1);
+        assert_eq!(segment("ab").len(), 2);
+        assert!(!extends_cluster(&[], &[0x0301]));
+    }
+
+    #[test]
+    fn test_surrogates() {
+        // Thumbs up with skin tone modifier, both were surrogate pairs.
+        let thumbs_up = utf16("\u{1F44D}");
+        let skin_tone = utf16("\u{1F3FD}");
+        assert!(is_high_surrogate(thumbs_up[0]));
+        assert!(is_low_surrogate(thumbs_up[1]));
+        assert!(extends_cluster(&thumbs_up, &skin_tone));
+        assert!(!extends_cluster(&thumbs_up, &utf16("a")));
+        assert_eq!(code_point(&thumbs_up), Some('\u{1F44D}'));
+
+        let char_width = CharWidth::default();
+        assert_eq!(cluster_width(&utf16("\u{1F44D}\u{1F3FD}"), &char_width), 2);
+        assert_eq!(cluster_width(&utf16("\u{1F1E8}\u{1F1F3}"), &char_width), 2);
+        assert_eq!(
+            cluster_width(&utf16("\u{1F468}\u{200D}\u{1F469}"), &char_width),
+            2
+        );
+
+        // The unpaired surrogate was invalid.
+        assert_eq!(code_point(&thumbs_up[..1]), None);
+        assert!(!extends_cluster(&thumbs_up[..1], &[0x0301]));
+    }
+
+    #[test]
+    fn test_cluster_width() {
+        let char_width = CharWidth::default();
+        assert_eq!(cluster_width(&points("e\u{0301}"), &char_width), 1);
+        assert_eq!(cluster_width(&points("\u{2764}\u{FE0F}"), &char_width), 2);
+        assert_eq!(cluster_width(&points("\u{231A}\u{FE0E}"), &char_width), 1);
+        assert_eq!(cluster_width(&points("\u{1F1E8}\u{1F1F3}"), &char_width), 2);
+        assert_eq!(cluster_width(&points("\u{1F44D}\u{1F3FD}"), &char_width), 2);
+        assert_eq!(
+            cluster_width(
+                &points("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+                &char_width
+            ),
+            2
+        );
+        assert_eq!(cluster_width(&points("\u{4E2D}"), &char_width), 2);
+        assert_eq!(cluster_width(&[], &char_width), 0);
+    }
+}
@@ -0,0 +1,174 @@
+#![allow(dead_code)]
+use std::rc::Rc;
+
+/// The image displayed in terminal (e.g. decoded from sixel graphics), the pixels were
+/// stored in 8-bit RGBA format row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl TerminalImage {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Returns the RGBA components of pixel at (`x`, `y`).
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let idx = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[idx],
+            self.pixels[idx + 1],
+            self.pixels[idx + 2],
+            self.pixels[idx + 3],
+        ]
+    }
+
+    /// Returns the image of the area (`x`, `y`, `width`, `height`) clamped to this image,
+    /// `None` if the area was empty.
+    pub fn cropped(&self, x: u32, y: u32, width: u32, height: u32) -> Option<TerminalImage> {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for row in y..y + height {
+            let start = ((row * self.width + x) * 4) as usize;
+            pixels.extend_from_slice(&self.pixels[start..start + (width * 4) as usize]);
+        }
+        Some(TerminalImage::new(width, height, pixels))
+    }
+
+    /// Returns the image scaled to `width` x `height` by the nearest neighbor.
+    pub fn scaled(&self, width: u32, height: u32) -> TerminalImage {
+        let (width, height) = (width.max(1), height.max(1));
+        if width == self.width && height == self.height {
+            return self.clone();
+        }
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let sy = (y as u64 * self.height as u64 / height as u64) as u32;
+            for x in 0..width {
+                let sx = (x as u64 * self.width as u64 / width as u64) as u32;
+                pixels.extend_from_slice(&self.pixel(sx, sy));
+            }
+        }
+        TerminalImage::new(width, height, pixels)
+    }
+}
+
+/// The image placed on the screen, anchored to the cell at `column` of `line`
+/// (include the lines in history), covers `columns` x `lines` cells.
+///
+/// The placements scroll with the text, and go into the history as well.
+#[derive(Debug, Clone)]
+pub struct ImagePlacement {
+    pub image: Rc<TerminalImage>,
+    pub line: i32,
+    pub column: i32,
+    pub lines: i32,
+    pub columns: i32,
+    /// The image id and placement id of kitty graphics protocol, 0 for the others.
+    pub image_id: u32,
+    pub placement_id: u32,
+    /// The images with larger z-index were drawn over the others.
+    pub z_index: i32,
+    /// The offset in pixels of the image within the anchor cell.
+    pub x_offset: u32,
+    pub y_offset: u32,
+}
+
+impl ImagePlacement {
+    #[inline]
+    pub fn new(image: Rc<TerminalImage>, line: i32, column: i32, lines: i32, columns: i32) -> Self {
+        Self {
+            image,
+            line,
+            column,
+            lines,
+            columns,
+            image_id: 0,
+            placement_id: 0,
+            z_index: 0,
+            x_offset: 0,
+            y_offset: 0,
+        }
+    }
+
+    /// Whether the placement covers the cell at `column` of `line`.
+    #[inline]
+    pub fn intersects(&self, line: i32, column: i32) -> bool {
+        (self.line..self.line + self.lines).contains(&line)
+            && (self.column..self.column + self.columns).contains(&column)
+    }
+
+    /// Whether the cells covered by `other` were all covered by this placement.
+    #[inline]
+    pub fn covers(&self, other: &ImagePlacement) -> bool {
+        self.line <= other.line
+            && self.column <= other.column
+            && self.line + self.lines >= other.line + other.lines
+            && self.column + self.columns >= other.column + other.columns
+    }
+}
+
+impl PartialEq for ImagePlacement {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.image, &other.image)
+            && self.line == other.line
+            && self.column == other.column
+            && self.lines == other.lines
+            && self.columns == other.columns
+            && self.z_index == other.z_index
+            && self.x_offset == other.x_offset
+            && self.y_offset == other.y_offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_transform() {
+        let pixels = (0..16u8).flat_map(|i| [i, i, i, 255]).collect();
+        let image = TerminalImage::new(4, 4, pixels);
+
+        let cropped = image.cropped(1, 2, 8, 1).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (3, 1));
+        assert_eq!(cropped.pixel(0, 0), [9, 9, 9, 255]);
+        assert!(image.cropped(4, 0, 1, 1).is_none());
+
+        let scaled = image.scaled(2, 8);
+        assert_eq!((scaled.width(), scaled.height()), (2, 8));
+        assert_eq!(scaled.pixel(1, 0), [2, 2, 2, 255]);
+        assert_eq!(scaled.pixel(1, 7), [14, 14, 14, 255]);
+    }
+}
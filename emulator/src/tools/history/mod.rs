@@ -12,7 +12,10 @@ pub use scroll_file::*;
 pub use scroll_none::*;
 use tmui::tlib::global::SemanticExt;
 
-use super::character::Character;
+use super::{
+    character::{Character, LineProperty, LINE_DEFAULT, LINE_SEMANTIC_MARKS, LINE_WRAPPED},
+    reflow::{Reflow, ReflowLine},
+};
 use std::{cell::RefCell, rc::Rc};
 
 const MAP_THRESHOLD: i32 = -1000;
@@ -34,6 +37,10 @@ pub trait HistoryScroll: Sized + 'static {
     fn get_line_len(&mut self, lineno: i32) -> i32;
     fn get_cells(&mut self, lineno: i32, colno: i32, count: i32, res: &mut [Character]);
     fn is_wrapped_line(&mut self, lineno: i32) -> bool;
+    /// Returns the semantic marks of line set by `OSC 133`, see [`LINE_SEMANTIC_MARKS`](crate::tools::character::LINE_SEMANTIC_MARKS).
+    fn get_line_marks(&mut self, _lineno: i32) -> LineProperty {
+        LINE_DEFAULT
+    }
 
     ///  backward compatibility (obsolete)
     fn get_cell(&mut self, lineno: i32, colno: i32) -> Character {
@@ -48,11 +55,79 @@ pub trait HistoryScroll: Sized + 'static {
         self.add_cells(&list, list.len() as i32);
     }
 
-    fn add_line(&mut self, previous_wrapped: bool);
+    fn add_line(&mut self, previous_wrapped: bool, marks: LineProperty);
 
     fn get_type(&self) -> Rc<RefCell<Self::HistoryType>>;
 
     fn set_max_nb_lines(&mut self, _: usize) {}
+
+    /// Rewraps the lines written at `old_columns` to `new_columns`, see [`Reflow`].
+    ///
+    /// `max_lines` limits the reflow to the newest lines, the older lines were kept at
+    /// the columns they were written. `None` reflows all the lines.
+    ///
+    /// @return `None` if the history can't be reflowed, the lines were kept unchanged.
+    fn reflow_lines(
+        &mut self,
+        _old_columns: i32,
+        _new_columns: i32,
+        _max_lines: Option<usize>,
+    ) -> Option<Reflow> {
+        None
+    }
+}
+
+/// Reflows the history, or the newest `max_lines` lines of it, by rebuilding them: the lines
+/// were read out and reflowed, then added back after they were removed by `truncate`.
+///
+/// The reflowed lines start from a logical line, so the older lines wrapped into them were
+/// never split.
+fn rebuild_reflowed<T: HistoryScroll>(
+    scroll: &mut T,
+    old_columns: i32,
+    new_columns: i32,
+    max_lines: Option<usize>,
+    truncate: fn(&mut T, i32),
+) -> Reflow {
+    let count = scroll.get_lines();
+    let tail = max_lines.map_or(0, |max_lines| {
+        (count - max_lines.min(i32::MAX as usize) as i32).max(0)
+    });
+    let mut first_line = tail;
+    while first_line > 0 && first_line < count && scroll.is_wrapped_line(first_line - 1) {
+        first_line += 1;
+    }
+    if first_line >= count {
+        first_line = tail;
+    }
+
+    let lines = (first_line..count)
+        .map(|lineno| {
+            let len = scroll.get_line_len(lineno);
+            let mut cells = vec![Character::default(); len as usize];
+            scroll.get_cells(lineno, 0, len, &mut cells);
+
+            let mut properties = scroll.get_line_marks(lineno);
+            if scroll.is_wrapped_line(lineno) {
+                properties |= LINE_WRAPPED;
+            }
+            ReflowLine::new(cells, properties)
+        })
+        .collect();
+    let mut reflow = Reflow::new(lines, old_columns as usize, new_columns as usize, None);
+    reflow.set_first_line(first_line as usize);
+
+    truncate(scroll, first_line);
+    for line in reflow.take_lines() {
+        let (wrapped, marks) = (line.is_wrapped(), line.properties & LINE_SEMANTIC_MARKS);
+        scroll.add_cells_list(line.cells);
+        scroll.add_line(wrapped, marks);
+    }
+    reflow.set_dropped(
+        (first_line as usize + reflow.line_count()).saturating_sub(scroll.get_lines() as usize),
+    );
+
+    reflow
 }
 pub trait HistoryScrollWrapper {
     fn type_(&self) -> HistoryTypeEnum;
@@ -61,12 +136,19 @@ pub trait HistoryScrollWrapper {
     fn get_line_len(&self, lineno: i32) -> i32;
     fn get_cells(&self, lineno: i32, colno: i32, count: i32, res: &mut [Character]);
     fn is_wrapped_line(&self, lineno: i32) -> bool;
+    fn get_line_marks(&self, lineno: i32) -> LineProperty;
     fn get_cell(&self, lineno: i32, colno: i32) -> Character;
     fn add_cells(&self, character: &[Character], count: i32);
     fn add_cells_list(&self, list: Vec<Character>);
-    fn add_line(&self, previous_wrapped: bool);
+    fn add_line(&self, previous_wrapped: bool, marks: LineProperty);
     fn get_type(&self) -> Rc<RefCell<dyn HistoryType>>;
     fn set_max_nb_lines(&self, nb_lines: usize);
+    fn reflow_lines(
+        &self,
+        old_columns: i32,
+        new_columns: i32,
+        max_lines: Option<usize>,
+    ) -> Option<Reflow>;
 }
 impl<T: HistoryScroll> HistoryScrollWrapper for RefCell<T> {
     fn has_scroll(&self) -> bool {
@@ -89,6 +171,10 @@ impl<T: HistoryScroll> HistoryScrollWrapper for RefCell<T> {
         self.borrow_mut().is_wrapped_line(lineno)
     }
 
+    fn get_line_marks(&self, lineno: i32) -> LineProperty {
+        self.borrow_mut().get_line_marks(lineno)
+    }
+
     fn get_cell(&self, lineno: i32, colno: i32) -> Character {
         self.borrow_mut().get_cell(lineno, colno)
     }
@@ -101,8 +187,8 @@ impl<T: HistoryScroll> HistoryScrollWrapper for RefCell<T> {
         self.borrow_mut().add_cells_list(list)
     }
 
-    fn add_line(&self, previous_wrapped: bool) {
-        self.borrow_mut().add_line(previous_wrapped)
+    fn add_line(&self, previous_wrapped: bool, marks: LineProperty) {
+        self.borrow_mut().add_line(previous_wrapped, marks)
     }
 
     fn get_type(&self) -> Rc<RefCell<dyn HistoryType>> {
@@ -113,6 +199,16 @@ impl<T: HistoryScroll> HistoryScrollWrapper for RefCell<T> {
         self.borrow_mut().set_max_nb_lines(nb_lines)
     }
 
+    fn reflow_lines(
+        &self,
+        old_columns: i32,
+        new_columns: i32,
+        max_lines: Option<usize>,
+    ) -> Option<Reflow> {
+        self.borrow_mut()
+            .reflow_lines(old_columns, new_columns, max_lines)
+    }
+
     fn type_(&self) -> HistoryTypeEnum {
         self.borrow().get_type().borrow().type_()
     }
@@ -261,13 +357,19 @@ impl HistoryType for HistoryTypeFile {
                     .unwrap()
                     .get_cells(i as i32, 0, size, &mut tmp_line);
                 scroll.add_cells(&tmp_line, size);
-                scroll.add_line(old.as_ref().unwrap().is_wrapped_line(i as i32));
+                scroll.add_line(
+                    old.as_ref().unwrap().is_wrapped_line(i as i32),
+                    old.as_ref().unwrap().get_line_marks(i as i32),
+                );
             } else {
                 old.as_ref()
                     .unwrap()
                     .get_cells(i as i32, 0, size, &mut line);
                 scroll.add_cells(&line, size);
-                scroll.add_line(old.as_ref().unwrap().is_wrapped_line(i as i32));
+                scroll.add_line(
+                    old.as_ref().unwrap().is_wrapped_line(i as i32),
+                    old.as_ref().unwrap().get_line_marks(i as i32),
+                );
             }
         }
         Rc::new(scroll.wrap())
@@ -322,11 +424,11 @@ impl HistoryType for HistoryTypeBuffer {
                         let mut tmp_line = vec![Character::default(); size as usize];
                         old.get_cells(i, 0, size, &mut tmp_line);
                         new_scroll.add_cells(&tmp_line, size);
-                        new_scroll.add_line(old.is_wrapped_line(i));
+                        new_scroll.add_line(old.is_wrapped_line(i), old.get_line_marks(i));
                     } else {
                         old.get_cells(i, 0, size, &mut line);
                         new_scroll.add_cells(&line, size);
-                        new_scroll.add_line(old.is_wrapped_line(i));
+                        new_scroll.add_line(old.is_wrapped_line(i), old.get_line_marks(i));
                     }
                 }
 
@@ -375,3 +477,61 @@ impl HistoryType for CompactHistoryType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::character::CharacterUnion;
+    use libc::wchar_t;
+
+    fn history(lines: &[(&str, bool)]) -> HistoryScrollBuffer {
+        let mut scroll = HistoryScrollBuffer::new(None);
+        for &(text, wrapped) in lines {
+            let cells = text
+                .chars()
+                .map(|c| {
+                    let mut ch = Character::default();
+                    ch.character_union = CharacterUnion::from(c as wchar_t);
+                    ch
+                })
+                .collect();
+            scroll.add_cells_list(cells);
+            scroll.add_line(wrapped, LINE_DEFAULT);
+        }
+        scroll
+    }
+
+    fn lines_text(scroll: &mut HistoryScrollBuffer) -> Vec<String> {
+        (0..scroll.get_lines())
+            .map(|lineno| {
+                let len = scroll.get_line_len(lineno);
+                let mut cells = vec![Character::default(); len as usize];
+                scroll.get_cells(lineno, 0, len, &mut cells);
+                cells
+                    .iter()
+                    .map(|c| char::from_u32(c.character_union.data() as u32).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reflow_lines() {
+        let lines = [("abcd", true), ("ef", false), ("gh", false)];
+
+        // All the lines were reflowed by default.
+        let mut scroll = history(&lines);
+        scroll.reflow_lines(4, 8, None);
+        assert_eq!(lines_text(&mut scroll), ["abcdef", "gh"]);
+
+        // The limited reflow starts from a logical line, the older lines were kept.
+        let mut scroll = history(&lines);
+        scroll.reflow_lines(4, 8, Some(2));
+        assert_eq!(lines_text(&mut scroll), ["abcd", "ef", "gh"]);
+        assert!(scroll.is_wrapped_line(0));
+
+        let mut scroll = history(&lines);
+        scroll.reflow_lines(4, 8, Some(3));
+        assert_eq!(lines_text(&mut scroll), ["abcdef", "gh"]);
+    }
+}
@@ -1,7 +1,7 @@
 use super::{HistoryScroll, HistoryTypeBlockArray};
 use crate::tools::{
     block_array::{BlockArray, ENTRIES},
-    character::Character,
+    character::{Character, LineProperty, LINE_DEFAULT},
 };
 use libc::{c_void, memcpy, memset};
 use std::{collections::HashMap, mem::size_of, rc::Rc, cell::RefCell};
@@ -14,6 +14,7 @@ pub struct HistoryScrollBlockArray {
 
     block_array: BlockArray,
     line_lengths: HashMap<i32, usize>,
+    line_marks: HashMap<i32, LineProperty>,
 }
 impl HistoryScrollBlockArray {
     pub fn new(size: usize) -> Self {
@@ -21,6 +22,7 @@ impl HistoryScrollBlockArray {
             history_type: Rc::new(RefCell::new(HistoryTypeBlockArray::new(size))),
             block_array: BlockArray::new(),
             line_lengths: HashMap::new(),
+            line_marks: HashMap::new(),
         }
     }
 }
@@ -74,6 +76,10 @@ impl HistoryScroll for HistoryScrollBlockArray {
         false
     }
 
+    fn get_line_marks(&mut self, lineno: i32) -> LineProperty {
+        self.line_marks.get(&lineno).copied().unwrap_or(LINE_DEFAULT)
+    }
+
     fn add_cells(&mut self, character: &[Character], count: i32) {
         let block = self.block_array.last_block();
 
@@ -95,7 +101,11 @@ impl HistoryScroll for HistoryScrollBlockArray {
         }
     }
 
-    fn add_line(&mut self, _: bool) {}
+    fn add_line(&mut self, _: bool, marks: LineProperty) {
+        if marks != LINE_DEFAULT {
+            self.line_marks.insert(self.block_array.get_current(), marks);
+        }
+    }
 
     fn get_type(&self) -> Rc<RefCell<Self::HistoryType>>{
         self.history_type.clone()
@@ -1,5 +1,8 @@
-use super::{HistoryScroll, HistoryTypeBuffer};
-use crate::tools::character::Character;
+use super::{rebuild_reflowed, HistoryScroll, HistoryTypeBuffer};
+use crate::tools::{
+    character::{Character, LineProperty, LINE_DEFAULT},
+    reflow::Reflow,
+};
 use bitvec::vec::BitVec;
 use libc::{c_void, memcpy, memset};
 use std::{cell::RefCell, mem::size_of, rc::Rc};
@@ -13,6 +16,7 @@ pub struct HistoryScrollBuffer {
 
     history_buffer: Vec<HistoryLine>,
     wrapped_line: BitVec,
+    line_marks: Vec<LineProperty>,
     max_line_count: i32,
     used_lines: i32,
     head: i32,
@@ -25,6 +29,7 @@ impl HistoryScrollBuffer {
             history_type: Rc::new(RefCell::new(HistoryTypeBuffer::new(max_nb_lines))),
             history_buffer: vec![],
             wrapped_line: BitVec::new(),
+            line_marks: vec![],
             max_line_count: 0,
             used_lines: 0,
             head: 0,
@@ -47,6 +52,29 @@ impl HistoryScrollBuffer {
     pub fn max_nb_lines(&self) -> i32 {
         self.max_line_count
     }
+
+    /// Removes the lines from `lineno` to the end.
+    fn truncate(&mut self, lineno: i32) {
+        if lineno >= self.used_lines {
+            return;
+        }
+
+        // Rotate the full ring buffer, so the line number was the buffer index.
+        if self.used_lines == self.max_line_count {
+            let first = ((self.head + 1) % self.max_line_count) as usize;
+            self.history_buffer.rotate_left(first);
+            self.line_marks.rotate_left(first);
+            self.wrapped_line.rotate_left(first);
+        }
+
+        for i in lineno as usize..self.used_lines as usize {
+            self.history_buffer[i] = vec![];
+            self.line_marks[i] = LINE_DEFAULT;
+            self.wrapped_line.set(i, false);
+        }
+        self.used_lines = lineno;
+        self.head = lineno - 1;
+    }
 }
 impl HistoryScroll for HistoryScrollBuffer {
     type HistoryType = HistoryTypeBuffer;
@@ -118,6 +146,16 @@ impl HistoryScroll for HistoryScrollBuffer {
         }
     }
 
+    fn get_line_marks(&mut self, lineno: i32) -> LineProperty {
+        assert!(lineno >= 0 && lineno < self.max_line_count);
+
+        if lineno < self.used_lines {
+            self.line_marks[self.buffer_index(lineno)]
+        } else {
+            LINE_DEFAULT
+        }
+    }
+
     fn add_cells(&mut self, character: &[Character], _count: i32) {
         let new_line = character.to_vec();
         self.add_cells_list(new_line);
@@ -135,11 +173,13 @@ impl HistoryScroll for HistoryScrollBuffer {
         let buffer_index = self.buffer_index(self.used_lines - 1);
         self.history_buffer[buffer_index] = list;
         self.wrapped_line.set(buffer_index, false);
+        self.line_marks[buffer_index] = LINE_DEFAULT;
     }
 
-    fn add_line(&mut self, previous_wrapped: bool) {
+    fn add_line(&mut self, previous_wrapped: bool, marks: LineProperty) {
         let buffer_index = self.buffer_index(self.used_lines - 1);
         self.wrapped_line.set(buffer_index, previous_wrapped);
+        self.line_marks[buffer_index] = marks;
     }
 
     fn get_type(&self) -> Rc<RefCell<Self::HistoryType>> {
@@ -149,16 +189,16 @@ impl HistoryScroll for HistoryScrollBuffer {
     fn set_max_nb_lines(&mut self, nb_lines: usize) {
         let old_buffer = &self.history_buffer;
         let mut new_buffer = vec![vec![]; nb_lines];
+        let mut new_line_marks = vec![LINE_DEFAULT; nb_lines];
 
         for (i, nb) in new_buffer
             .iter_mut()
             .enumerate()
             .take((self.used_lines as usize).min(nb_lines))
         {
-            *nb = old_buffer
-                .get(self.buffer_index(i as i32))
-                .unwrap()
-                .to_owned()
+            let buffer_index = self.buffer_index(i as i32);
+            *nb = old_buffer.get(buffer_index).unwrap().to_owned();
+            new_line_marks[i] = self.line_marks[buffer_index];
         }
 
         self.used_lines = self.used_lines.min(nb_lines as i32);
@@ -170,7 +210,23 @@ impl HistoryScroll for HistoryScrollBuffer {
         };
 
         self.history_buffer = new_buffer;
+        self.line_marks = new_line_marks;
         self.wrapped_line.resize(nb_lines, false);
         self.get_type().borrow_mut().nb_lines = nb_lines;
     }
+
+    fn reflow_lines(
+        &mut self,
+        old_columns: i32,
+        new_columns: i32,
+        max_lines: Option<usize>,
+    ) -> Option<Reflow> {
+        Some(rebuild_reflowed(
+            self,
+            old_columns,
+            new_columns,
+            max_lines,
+            Self::truncate,
+        ))
+    }
 }
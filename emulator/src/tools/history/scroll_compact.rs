@@ -1,7 +1,8 @@
-use super::{CompactHistoryType, HistoryScroll};
+use super::{rebuild_reflowed, CompactHistoryType, HistoryScroll};
 use crate::tools::{
-    character::{Character, CharacterUnion},
+    character::{Character, CharacterUnion, LineProperty, LINE_DEFAULT},
     character_color::CharacterColor,
+    reflow::Reflow,
     system_ffi::{mmap, munmap, MAP_ANON, MAP_FAILED, MAP_PRIVATE, PROT_READ, PROT_WRITE},
 };
 use libc::{c_void, wchar_t};
@@ -22,6 +23,8 @@ pub struct CharacterFormat {
     bg_color: CharacterColor,
     start_pos: u16,
     rendition: wchar_t,
+    hyperlink: u32,
+    underline_color: CharacterColor,
 }
 impl CharacterFormat {
     pub fn new(c: &Character) -> Self {
@@ -30,6 +33,8 @@ impl CharacterFormat {
             bg_color: c.background_color,
             start_pos: 0,
             rendition: c.rendition,
+            hyperlink: c.hyperlink,
+            underline_color: c.underline_color,
         }
     }
 
@@ -37,18 +42,24 @@ impl CharacterFormat {
         other.rendition == self.rendition
             && other.fg_color == self.fg_color
             && other.bg_color == self.bg_color
+            && other.hyperlink == self.hyperlink
+            && other.underline_color == self.underline_color
     }
 
     pub fn equals_with_character(&self, other: &Character) -> bool {
         other.rendition == self.rendition
             && other.foreground_color == self.fg_color
             && other.background_color == self.bg_color
+            && other.hyperlink == self.hyperlink
+            && other.underline_color == self.underline_color
     }
 
     pub fn set_format(&mut self, c: &Character) {
         self.rendition = c.rendition;
         self.fg_color = c.foreground_color;
         self.bg_color = c.background_color;
+        self.hyperlink = c.hyperlink;
+        self.underline_color = c.underline_color;
     }
 }
 
@@ -168,6 +179,7 @@ pub struct CompactHistoryLine {
     text: *mut wchar_t,
     text_ref: Option<&'static mut [wchar_t]>,
     wrapped: bool,
+    marks: LineProperty,
 }
 impl CompactHistoryLine {
     #[allow(clippy::new_ret_no_self)]
@@ -241,6 +253,7 @@ impl CompactHistoryLine {
             text_ref,
             format_length,
             wrapped,
+            marks: LINE_DEFAULT,
         }
     }
 
@@ -265,6 +278,8 @@ impl CompactHistoryLine {
         r.rendition = self.format_array_ref.as_ref().unwrap()[format_pos].rendition;
         r.foreground_color = self.format_array_ref.as_ref().unwrap()[format_pos].fg_color;
         r.background_color = self.format_array_ref.as_ref().unwrap()[format_pos].bg_color;
+        r.hyperlink = self.format_array_ref.as_ref().unwrap()[format_pos].hyperlink;
+        r.underline_color = self.format_array_ref.as_ref().unwrap()[format_pos].underline_color;
     }
 
     pub fn is_wrapped(&self) -> bool {
@@ -275,6 +290,14 @@ impl CompactHistoryLine {
         self.wrapped = wrapped
     }
 
+    pub fn marks(&self) -> LineProperty {
+        self.marks
+    }
+
+    pub fn set_marks(&mut self, marks: LineProperty) {
+        self.marks = marks
+    }
+
     pub fn get_length(&self) -> usize {
         self.length
     }
@@ -319,6 +342,11 @@ impl CompactHistoryScroll {
     pub fn max_nb_lines(&self) -> u32 {
         self.max_line_count
     }
+
+    /// Removes the lines from `lineno` to the end.
+    fn truncate(&mut self, lineno: i32) {
+        self.lines.truncate(lineno.max(0) as usize)
+    }
 }
 impl HistoryScroll for CompactHistoryScroll {
     type HistoryType = CompactHistoryType;
@@ -353,15 +381,21 @@ impl HistoryScroll for CompactHistoryScroll {
         self.lines[lineno as usize].is_wrapped()
     }
 
+    fn get_line_marks(&mut self, lineno: i32) -> LineProperty {
+        assert!(lineno < self.lines.len() as i32);
+        self.lines[lineno as usize].marks()
+    }
+
     fn add_cells(&mut self, character: &[Character], count: i32) {
         let mut new_line = vec![Character::default(); count as usize];
         new_line.copy_from_slice(&character[0..count as usize])
     }
 
-    fn add_line(&mut self, previous_wrapped: bool) {
+    fn add_line(&mut self, previous_wrapped: bool, marks: LineProperty) {
         let line = self.lines.last_mut();
         if let Some(line) = line {
-            line.set_wrapped(previous_wrapped)
+            line.set_wrapped(previous_wrapped);
+            line.set_marks(marks);
         }
     }
 
@@ -376,4 +410,68 @@ impl HistoryScroll for CompactHistoryScroll {
             self.lines.remove(0);
         }
     }
+
+    fn reflow_lines(
+        &mut self,
+        old_columns: i32,
+        new_columns: i32,
+        max_lines: Option<usize>,
+    ) -> Option<Reflow> {
+        Some(rebuild_reflowed(
+            self,
+            old_columns,
+            new_columns,
+            max_lines,
+            Self::truncate,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{
+        character::UnderlineStyle,
+        character_color::{COLOR_SPACE_256, COLOR_SPACE_RGB},
+    };
+    use std::mem::ManuallyDrop;
+
+    #[test]
+    fn test_character_format_round_trip() {
+        let mut curly = Character::new(
+            'a' as wchar_t,
+            CharacterColor::default_foreground(),
+            CharacterColor::default_background(),
+            UnderlineStyle::Curly.rendition(),
+        );
+        curly.underline_color = CharacterColor::new(COLOR_SPACE_RGB, 0x102030);
+        let mut double = curly;
+        double.rendition = UnderlineStyle::Double.rendition();
+        let mut indexed = double;
+        indexed.underline_color = CharacterColor::new(COLOR_SPACE_256, 100);
+
+        let format = CharacterFormat::new(&curly);
+        assert!(format.equals_with_character(&curly));
+        assert!(!format.equals_with_character(&double));
+        assert!(!CharacterFormat::new(&double).equals_with_character(&indexed));
+
+        // The line was expected to be allocated in the block list, see `CompactHistoryLine::new`.
+        let characters = [curly, curly, double, indexed];
+        let line = ManuallyDrop::new(CompactHistoryLine::create(
+            characters.to_vec(),
+            CompactHistoryBlockList::new(),
+        ));
+        assert_eq!(line.format_length, 3);
+
+        let mut cells = [Character::default(); 4];
+        line.get_characters(&mut cells, 4, 0);
+        for (cell, expected) in cells.iter().zip(characters) {
+            assert!(cell.equals_format(&expected));
+            assert_eq!(cell.underline_style(), expected.underline_style());
+        }
+        assert_eq!(
+            cells[3].underline_color,
+            CharacterColor::new(COLOR_SPACE_256, 100)
+        );
+    }
 }
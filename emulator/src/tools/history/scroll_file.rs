@@ -1,6 +1,7 @@
-use super::{HistoryScroll, HistoryTypeFile, MAP_THRESHOLD};
+use super::{rebuild_reflowed, HistoryScroll, HistoryTypeFile, MAP_THRESHOLD};
 use crate::tools::{
-    character::Character,
+    character::{Character, LineProperty, LINE_SEMANTIC_MARKS, LINE_WRAPPED},
+    reflow::Reflow,
     system_ffi::{mmap, munmap, MAP_FAILED, MAP_PRIVATE, PROT_READ, SEEK_SET},
 };
 use libc::{c_void, close, dup, fileno, lseek, read, tmpfile, write, FILE};
@@ -115,6 +116,17 @@ impl HistoryFile {
         self.length
     }
 
+    /// Discards the bytes after `len`, they were overwritten by the following `add`.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.length {
+            return;
+        }
+        if !self.file_map.is_null() {
+            self.unmap()
+        }
+        self.length = len;
+    }
+
     /// mmaps the file in read-only mode
     pub fn map(&mut self) {
         assert!(self.file_map.is_null());
@@ -189,6 +201,18 @@ impl HistoryScrollFile {
         }
     }
 
+    fn get_line_flags(&mut self, lineno: i32) -> u8 {
+        let mut flag = 0u8;
+        if lineno >= 0 && lineno <= self.get_lines() {
+            self.line_flags.get(
+                &mut flag,
+                size_of::<u8>() as i32,
+                lineno * size_of::<u8>() as i32,
+            );
+        }
+        flag
+    }
+
     fn start_of_line(&mut self, lineno: i32) -> i32 {
         if lineno <= 0 {
             return 0;
@@ -209,6 +233,19 @@ impl HistoryScrollFile {
         }
         self.cells.len() as i32
     }
+
+    /// Removes the lines from `lineno` to the end, the data in files was not read.
+    fn truncate(&mut self, lineno: i32) {
+        let lineno = lineno.max(0);
+        if lineno >= self.get_lines() {
+            return;
+        }
+
+        let start_of_line = self.start_of_line(lineno);
+        self.cells.truncate(start_of_line as usize);
+        self.index.truncate(lineno as usize * size_of::<i32>());
+        self.line_flags.truncate(lineno as usize * size_of::<u8>());
+    }
 }
 impl HistoryScroll for HistoryScrollFile {
     type HistoryType = HistoryTypeFile;
@@ -236,16 +273,11 @@ impl HistoryScroll for HistoryScrollFile {
     }
 
     fn is_wrapped_line(&mut self, lineno: i32) -> bool {
-        if lineno >= 0 && lineno <= self.get_lines() {
-            let mut flag = 0u8;
-            self.line_flags.get(
-                &mut flag,
-                size_of::<u8>() as i32,
-                lineno * size_of::<u8>() as i32,
-            );
-            return flag > 0;
-        }
-        false
+        self.get_line_flags(lineno) & LINE_WRAPPED != 0
+    }
+
+    fn get_line_marks(&mut self, lineno: i32) -> LineProperty {
+        self.get_line_flags(lineno) & LINE_SEMANTIC_MARKS
     }
 
     fn add_cells(&mut self, character: &[Character], count: i32) {
@@ -255,7 +287,7 @@ impl HistoryScroll for HistoryScrollFile {
         );
     }
 
-    fn add_line(&mut self, previous_wrapped: bool) {
+    fn add_line(&mut self, previous_wrapped: bool, marks: LineProperty) {
         if self.index.is_mapped() {
             self.index.unmap()
         }
@@ -263,7 +295,8 @@ impl HistoryScroll for HistoryScrollFile {
         let locn = self.cells.len();
         self.index
             .add(&locn as *const usize as *const u8, size_of::<i32>() as i32);
-        let flags = if previous_wrapped { 0x01u8 } else { 0x00 };
+        let mut flags = if previous_wrapped { LINE_WRAPPED } else { 0x00 };
+        flags |= marks & LINE_SEMANTIC_MARKS;
         self.line_flags
             .add(&flags as *const u8, size_of::<u8>() as i32)
     }
@@ -271,4 +304,19 @@ impl HistoryScroll for HistoryScrollFile {
     fn get_type(&self) -> Rc<RefCell<Self::HistoryType>> {
         self.history_type.clone()
     }
+
+    fn reflow_lines(
+        &mut self,
+        old_columns: i32,
+        new_columns: i32,
+        max_lines: Option<usize>,
+    ) -> Option<Reflow> {
+        Some(rebuild_reflowed(
+            self,
+            old_columns,
+            new_columns,
+            max_lines,
+            Self::truncate,
+        ))
+    }
 }
@@ -1,5 +1,5 @@
 use super::{HistoryScroll, HistoryTypeNone};
-use crate::tools::character::Character;
+use crate::tools::character::{Character, LineProperty};
 use std::{rc::Rc, cell::RefCell};
 
 ///////////////////////////////////////////////////////////////////////
@@ -38,7 +38,7 @@ impl HistoryScroll for HistoryScrollNone {
 
     fn add_cells(&mut self, _character: &[Character], _count: i32) {}
 
-    fn add_line(&mut self, _previous_wrapped: bool) {}
+    fn add_line(&mut self, _previous_wrapped: bool, _marks: LineProperty) {}
 
     fn get_type(&self) -> Rc<RefCell<Self::HistoryType>> {
         self.history_type.clone()
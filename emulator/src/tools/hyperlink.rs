@@ -0,0 +1,264 @@
+#![allow(dead_code)]
+use lazy_static::lazy_static;
+use log::warn;
+use std::{
+    collections::{BTreeMap, HashMap},
+    process::Command,
+    sync::Mutex,
+    thread,
+};
+
+/// The id of [`Character`](crate::tools::character::Character) which was not a part of any hyperlink.
+pub const NO_HYPERLINK: u32 = 0;
+
+/// The maximum length of hyperlink uri, longer uri will be ignored.
+pub const MAX_HYPERLINK_LENGTH: usize = 2083;
+
+/// The maximum number of hyperlinks kept in [`HyperlinkTable`], the least recently used ones
+/// were evicted.
+pub const MAX_HYPERLINKS: usize = 4096;
+
+/// The uri schemes can be opened by [`open_url()`], the others were ignored.
+const OPENABLE_SCHEMES: [&str; 4] = ["http", "https", "ftp", "mailto"];
+
+/// Hyperlink declared by the `OSC 8` escape sequence:
+///
+/// `ESC ] 8 ; params ; URI ST`
+///
+/// `params` is a colon separated `key=value` list, the only key we care about was `id`.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
+pub struct Hyperlink {
+    /// The explicit id specified by `id=xxx` in params, empty when it was an anonymous hyperlink.
+    pub id: String,
+    /// The target uri of hyperlink.
+    pub uri: String,
+}
+
+impl Hyperlink {
+    /// Parse the text part of `OSC 8` sequence(the text after `8;`).
+    ///
+    /// @return `None` if the uri was empty, which means the end of a hyperlink.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (params, uri) = text.split_once(';')?;
+        if uri.is_empty() || uri.len() > MAX_HYPERLINK_LENGTH {
+            return None;
+        }
+
+        let id = params
+            .split(':')
+            .find_map(|param| param.strip_prefix("id="))
+            .unwrap_or_default()
+            .to_string();
+
+        Some(Self {
+            id,
+            uri: uri.to_string(),
+        })
+    }
+}
+
+/// A table which stores all the hyperlinks, referenced by a `u32` id.
+/// The id was stored in [`Character`](crate::tools::character::Character),
+/// so that it can be carried through the screen image and history.
+///
+/// Hyperlinks with the same explicit id and uri share the same table id, every anonymous
+/// hyperlink gets its own table id even if the uri was the same. The table ids were never
+/// reused, so the characters referencing an evicted hyperlink just lose the link.
+#[derive(Debug, Default)]
+pub struct HyperlinkTable {
+    /// The hyperlinks with the stamp of their last use.
+    links: HashMap<u32, (Hyperlink, u64)>,
+    /// The table ids ordered by the stamp of last use, the least recently used one was evicted.
+    uses: BTreeMap<u64, u32>,
+    /// The table ids of hyperlinks with explicit id.
+    ids: HashMap<Hyperlink, u32>,
+    last_id: u32,
+    last_use: u64,
+}
+
+lazy_static! {
+    static ref HYPERLINK_TABLE: Mutex<HyperlinkTable> = Mutex::new(HyperlinkTable::default());
+}
+
+impl HyperlinkTable {
+    /// Adds the hyperlink to the table and returns the id can be used later to
+    /// look up the hyperlink using [`HyperlinkTable::lookup()`]
+    ///
+    /// If the hyperlink with the same explicit id already exists in the table, the id of the
+    /// existing one will be returned.
+    #[inline]
+    pub fn register(link: Hyperlink) -> u32 {
+        HYPERLINK_TABLE.lock().unwrap().insert(link)
+    }
+
+    /// Looks up the hyperlink which was added to the table using [`HyperlinkTable::register()`].
+    #[inline]
+    pub fn lookup(id: u32) -> Option<Hyperlink> {
+        HYPERLINK_TABLE.lock().unwrap().get(id).cloned()
+    }
+
+    /// Looks up the uri of hyperlink with `id`.
+    #[inline]
+    pub fn uri(id: u32) -> Option<String> {
+        Self::lookup(id).map(|link| link.uri)
+    }
+
+    /// @return [`NO_HYPERLINK`] if the table ids were used up.
+    fn insert(&mut self, link: Hyperlink) -> u32 {
+        let explicit = !link.id.is_empty();
+        if explicit {
+            if let Some(&id) = self.ids.get(&link) {
+                self.get(id);
+                return id;
+            }
+        }
+
+        let Some(id) = self.last_id.checked_add(1) else {
+            return NO_HYPERLINK;
+        };
+        self.last_id = id;
+
+        if self.links.len() >= MAX_HYPERLINKS {
+            if let Some((_, evicted)) = self.uses.pop_first() {
+                if let Some((evicted, _)) = self.links.remove(&evicted) {
+                    self.ids.remove(&evicted);
+                }
+            }
+        }
+        if explicit {
+            self.ids.insert(link.clone(), id);
+        }
+        self.last_use += 1;
+        self.uses.insert(self.last_use, id);
+        self.links.insert(id, (link, self.last_use));
+        id
+    }
+
+    /// Gets the hyperlink and marks it as the most recently used one.
+    fn get(&mut self, id: u32) -> Option<&Hyperlink> {
+        if id == NO_HYPERLINK {
+            return None;
+        }
+        let (link, used) = self.links.get_mut(&id)?;
+        self.uses.remove(used);
+        self.last_use += 1;
+        *used = self.last_use;
+        self.uses.insert(self.last_use, id);
+        Some(link)
+    }
+}
+
+/// Opens the url by the opener of platform, `xdg-open`, `open` on macOS, or the url
+/// protocol handler on Windows.
+///
+/// @return false if the scheme of url was not allowed or the opener failed to start.
+pub fn open_url(url: &str) -> bool {
+    let allowed = url.split_once(':').is_some_and(|(scheme, _)| {
+        OPENABLE_SCHEMES
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(scheme))
+    });
+    if !allowed {
+        warn!("[open_url] The scheme of url `{}` was not allowed.", url);
+        return false;
+    }
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+
+    match command.arg(url).spawn() {
+        Ok(mut child) => {
+            // Reap the opener when it exits.
+            thread::spawn(move || child.wait());
+            true
+        }
+        Err(e) => {
+            warn!("[open_url] Open url `{}` failed: {}", url, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open_url, Hyperlink, HyperlinkTable, MAX_HYPERLINKS, NO_HYPERLINK};
+
+    #[test]
+    fn test_parse() {
+        let link = Hyperlink::parse("id=foo:bar=baz;https://example.com").unwrap();
+        assert_eq!(link.id, "foo");
+        assert_eq!(link.uri, "https://example.com");
+
+        let link = Hyperlink::parse(";file://host/tmp/a;b").unwrap();
+        assert!(link.id.is_empty());
+        assert_eq!(link.uri, "file://host/tmp/a;b");
+
+        assert!(Hyperlink::parse(";").is_none());
+        assert!(Hyperlink::parse("id=foo;").is_none());
+        assert!(Hyperlink::parse("").is_none());
+    }
+
+    #[test]
+    fn test_register() {
+        let a = HyperlinkTable::register(Hyperlink::parse(";https://a.com").unwrap());
+        let b = HyperlinkTable::register(Hyperlink::parse(";https://a.com").unwrap());
+        let c = HyperlinkTable::register(Hyperlink::parse("id=1;https://a.com").unwrap());
+        let d = HyperlinkTable::register(Hyperlink::parse("id=1;https://a.com").unwrap());
+        // Anonymous hyperlinks were never merged.
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(c, d);
+        assert_eq!(HyperlinkTable::uri(c).unwrap(), "https://a.com");
+        assert!(HyperlinkTable::lookup(0).is_none());
+    }
+
+    #[test]
+    fn test_open_url_scheme() {
+        assert!(!open_url("file:///etc/passwd"));
+        assert!(!open_url("javascript:alert(1)"));
+        assert!(!open_url("no-scheme"));
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut table = HyperlinkTable::default();
+        let first = table.insert(Hyperlink::parse("id=1;https://a.com").unwrap());
+        let second = table.insert(Hyperlink::parse(";https://b.com").unwrap());
+        for _ in 2..MAX_HYPERLINKS {
+            table.insert(Hyperlink::parse(";https://b.com").unwrap());
+        }
+        // Reusing the explicit id refreshes the hyperlink, the least recently used was evicted.
+        assert_eq!(
+            table.insert(Hyperlink::parse("id=1;https://a.com").unwrap()),
+            first
+        );
+        table.insert(Hyperlink::parse(";https://b.com").unwrap());
+        assert_eq!(table.links.len(), MAX_HYPERLINKS);
+        assert_eq!(table.uses.len(), MAX_HYPERLINKS);
+        assert!(table.get(first).is_some());
+        assert!(table.get(second).is_none());
+
+        for _ in 0..MAX_HYPERLINKS {
+            table.insert(Hyperlink::parse(";https://b.com").unwrap());
+        }
+        assert!(table.get(first).is_none());
+
+        // The evicted id was not reused.
+        let again = table.insert(Hyperlink::parse("id=1;https://a.com").unwrap());
+        assert!(again > first + MAX_HYPERLINKS as u32);
+
+        table.last_id = u32::MAX;
+        assert_eq!(
+            table.insert(Hyperlink::parse(";https://c.com").unwrap()),
+            NO_HYPERLINK
+        );
+    }
+}
@@ -0,0 +1,640 @@
+#![allow(dead_code)]
+use super::{
+    graphics::TerminalImage,
+    png::{decode_png, MAX_PNG_SIZE},
+};
+use flate2::read::ZlibDecoder;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// The default memory quota of the stored images, the oldest images were evicted when exceeded.
+pub const KITTY_IMAGE_STORE_QUOTA: usize = 320 << 20;
+
+/// The formats of transmitted data.
+pub const KITTY_FORMAT_RGB: u32 = 24;
+pub const KITTY_FORMAT_RGBA: u32 = 32;
+pub const KITTY_FORMAT_PNG: u32 = 100;
+
+/// The temporary files must contain this string in their paths, to prevent the terminal
+/// from deleting the arbitrary files.
+const TEMP_FILE_MARKER: &str = "tty-graphics-protocol";
+
+/// The command of kitty graphics protocol: `ESC _ G <control data> ; <payload> ESC \`,
+/// see https://sw.kovidgoyal.net/kitty/graphics-protocol/
+///
+/// The control data was the comma-separated `key=value` pairs, the omitted keys take the
+/// default values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphicsCommand {
+    /// `a`: t(transmit), T(transmit and display), q(query), p(put) or d(delete).
+    pub action: char,
+    /// `q`: 1 to suppress the OK responses, 2 to suppress the error responses as well.
+    pub quiet: u32,
+    /// `f`: 24(RGB), 32(RGBA) or 100(PNG).
+    pub format: u32,
+    /// `t`: d(direct), f(file), t(temporary file) or s(shared memory).
+    pub medium: char,
+    /// `o`: z(zlib) compressed.
+    pub compression: Option<char>,
+    /// `m`: whether more chunks of data will follow.
+    pub more: bool,
+    /// `i`, `I`, `p`: image id, image number and placement id.
+    pub image_id: u32,
+    pub image_number: u32,
+    pub placement_id: u32,
+    /// `s`, `v`: the size of RGB(A) image in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// `S`, `O`: the size and offset of the data to read from file.
+    pub size: u64,
+    pub offset: u64,
+    /// `x`, `y`, `w`, `h`: the source rectangle of image to display. <br>
+    /// Or the cell position and range of ids of deletion.
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    /// `X`, `Y`: the offset in pixels within the first cell.
+    pub x_offset: u32,
+    pub y_offset: u32,
+    /// `c`, `r`: the columns and rows to display the image, scaled if specified.
+    pub columns: u32,
+    pub rows: u32,
+    /// `C`: 1 to not move the cursor after placing the image.
+    pub cursor_movement: u32,
+    /// `z`: the z-index of placement.
+    pub z_index: i32,
+    /// `d`: the specifier of deletion, the uppercase ones free the image data as well.
+    pub delete: char,
+    /// The base64 encoded payload.
+    pub payload: String,
+}
+
+impl Default for GraphicsCommand {
+    fn default() -> Self {
+        Self {
+            action: 't',
+            quiet: 0,
+            format: KITTY_FORMAT_RGBA,
+            medium: 'd',
+            compression: None,
+            more: false,
+            image_id: 0,
+            image_number: 0,
+            placement_id: 0,
+            width: 0,
+            height: 0,
+            size: 0,
+            offset: 0,
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+            x_offset: 0,
+            y_offset: 0,
+            columns: 0,
+            rows: 0,
+            cursor_movement: 0,
+            z_index: 0,
+            delete: 'a',
+            payload: String::new(),
+        }
+    }
+}
+
+impl GraphicsCommand {
+    /// Parses the command from the text after `ESC _ G`.
+    ///
+    /// @return `None` if there was any malformed `key=value` pair.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (control, payload) = text.split_once(';').unwrap_or((text, ""));
+        let mut command = GraphicsCommand {
+            payload: payload.to_string(),
+            ..Default::default()
+        };
+
+        for pair in control.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=')?;
+            let char_value = || {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => None,
+                }
+            };
+
+            match key {
+                "a" => command.action = char_value()?,
+                "q" => command.quiet = value.parse().ok()?,
+                "f" => command.format = value.parse().ok()?,
+                "t" => command.medium = char_value()?,
+                "o" => command.compression = Some(char_value()?),
+                "m" => command.more = value == "1",
+                "i" => command.image_id = value.parse().ok()?,
+                "I" => command.image_number = value.parse().ok()?,
+                "p" => command.placement_id = value.parse().ok()?,
+                "s" => command.width = value.parse().ok()?,
+                "v" => command.height = value.parse().ok()?,
+                "S" => command.size = value.parse().ok()?,
+                "O" => command.offset = value.parse().ok()?,
+                "x" => command.x = value.parse().ok()?,
+                "y" => command.y = value.parse().ok()?,
+                "w" => command.w = value.parse().ok()?,
+                "h" => command.h = value.parse().ok()?,
+                "X" => command.x_offset = value.parse().ok()?,
+                "Y" => command.y_offset = value.parse().ok()?,
+                "c" => command.columns = value.parse().ok()?,
+                "r" => command.rows = value.parse().ok()?,
+                "C" => command.cursor_movement = value.parse().ok()?,
+                "z" => command.z_index = value.parse().ok()?,
+                "d" => command.delete = char_value()?,
+                // The unsupported keys like the unicode placeholder were ignored.
+                _ => {}
+            }
+        }
+
+        Some(command)
+    }
+}
+
+/// Formats the response: `ESC _ G i=<id>[,I=<number>][,p=<placement id>];<message> ESC \`.
+///
+/// @return `None` if the response was suppressed by `q`, or the command carried no id
+/// and number.
+pub fn graphics_response(
+    command: &GraphicsCommand,
+    image_id: u32,
+    result: &Result<(), String>,
+) -> Option<String> {
+    if command.image_id == 0 && command.image_number == 0 {
+        return None;
+    }
+    let message = match result {
+        Ok(_) if command.quiet >= 1 => return None,
+        Ok(_) => "OK",
+        Err(_) if command.quiet >= 2 => return None,
+        Err(err) => err.as_str(),
+    };
+
+    let mut response = format!("\u{001b}_Gi={}", image_id);
+    if command.image_number != 0 {
+        response.push_str(&format!(",I={}", command.image_number));
+    }
+    if command.placement_id != 0 {
+        response.push_str(&format!(",p={}", command.placement_id));
+    }
+    response.push_str(&format!(";{}\u{001b}\\", message));
+    Some(response)
+}
+
+struct StoredImage {
+    image: Rc<TerminalImage>,
+    number: u32,
+    /// Used to find the oldest images.
+    sequence: u64,
+}
+
+/// The images transmitted by kitty graphics protocol, each session has its own store.
+///
+/// The total memory of the images was limited by the quota, the oldest images were evicted
+/// when exceeded, but the pixels were still kept by the existing placements on screen.
+pub struct KittyImageStore {
+    images: HashMap<u32, StoredImage>,
+    used: usize,
+    quota: usize,
+    /// The first chunk of the transmission in progress, with payload of all the received chunks.
+    loading: Option<GraphicsCommand>,
+    /// The next id allocated for the images transmitted without id.
+    next_id: u32,
+    sequence: u64,
+}
+
+impl Default for KittyImageStore {
+    #[inline]
+    fn default() -> Self {
+        Self::new(KITTY_IMAGE_STORE_QUOTA)
+    }
+}
+
+impl KittyImageStore {
+    pub fn new(quota: usize) -> Self {
+        Self {
+            images: HashMap::new(),
+            used: 0,
+            quota,
+            loading: None,
+            next_id: 1 << 31,
+            sequence: 0,
+        }
+    }
+
+    /// The memory quota of the stored images.
+    #[inline]
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+
+    /// Whether a chunked transmission was in progress.
+    #[inline]
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
+    /// Receives the transmission command, the chunks were merged until the last one (`m=0`)
+    /// arrived, and only the control data of the first chunk was used.
+    ///
+    /// @return The first command and the loaded image, `None` if more chunks were expected.
+    pub fn receive(
+        &mut self,
+        command: GraphicsCommand,
+    ) -> Option<(GraphicsCommand, Result<TerminalImage, String>)> {
+        let more = command.more;
+        let mut first = match self.loading.take() {
+            Some(mut first) => {
+                first.payload.push_str(&command.payload);
+                first
+            }
+            None => command,
+        };
+
+        if first.payload.len() > self.quota {
+            return Some((first, Err("EFBIG:the image data was too large".to_string())));
+        }
+        if more {
+            self.loading = Some(first);
+            return None;
+        }
+
+        let result = load_image(&first, self.quota);
+        first.payload.clear();
+        Some((first, result))
+    }
+
+    /// Stores the image, the existing image with same id was replaced.
+    pub fn insert(&mut self, id: u32, number: u32, image: TerminalImage) -> Rc<TerminalImage> {
+        self.remove(id);
+
+        let image = Rc::new(image);
+        self.used += image.pixels().len();
+        self.sequence += 1;
+        self.images.insert(
+            id,
+            StoredImage {
+                image: image.clone(),
+                number,
+                sequence: self.sequence,
+            },
+        );
+
+        while self.used > self.quota {
+            let oldest = self
+                .images
+                .iter()
+                .filter(|(other, _)| **other != id)
+                .min_by_key(|(_, stored)| stored.sequence)
+                .map(|(id, _)| *id);
+            match oldest {
+                Some(oldest) => self.remove(oldest),
+                None => break,
+            };
+        }
+
+        image
+    }
+
+    #[inline]
+    pub fn get(&self, id: u32) -> Option<Rc<TerminalImage>> {
+        self.images.get(&id).map(|stored| stored.image.clone())
+    }
+
+    /// Returns the id of the newest image with the image `number`.
+    pub fn id_of_number(&self, number: u32) -> Option<u32> {
+        self.images
+            .iter()
+            .filter(|(_, stored)| stored.number == number)
+            .max_by_key(|(_, stored)| stored.sequence)
+            .map(|(id, _)| *id)
+    }
+
+    /// Allocates an unused id for the image transmitted without id.
+    pub fn allocate_id(&mut self) -> u32 {
+        while self.next_id == 0 || self.images.contains_key(&self.next_id) {
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        id
+    }
+
+    /// Returns the ids of all the stored images.
+    #[inline]
+    pub fn ids(&self) -> Vec<u32> {
+        self.images.keys().copied().collect()
+    }
+
+    /// Removes the image with `id`, returns `false` if it was not found.
+    pub fn remove(&mut self, id: u32) -> bool {
+        match self.images.remove(&id) {
+            Some(stored) => {
+                self.used -= stored.image.pixels().len();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.images.clear();
+        self.used = 0;
+        self.loading = None;
+    }
+}
+
+/// Loads the image from the transmission medium, decompresses and decodes it.
+///
+/// The data read from file and inflated from zlib was limited to `limit` bytes, or the
+/// size of the RGB(A) pixels.
+fn load_image(command: &GraphicsCommand, limit: usize) -> Result<TerminalImage, String> {
+    let limit = match command.format {
+        KITTY_FORMAT_RGB | KITTY_FORMAT_RGBA => {
+            let bytes_per_pixel = (command.format / 8) as usize;
+            limit.min(command.width as usize * command.height as usize * bytes_per_pixel)
+        }
+        _ => limit,
+    };
+
+    let payload = base64::decode(&command.payload)
+        .map_err(|_| "EINVAL:the payload was not valid base64 data".to_string())?;
+
+    let data = match command.medium {
+        'd' => payload,
+        'f' | 't' => {
+            let path = String::from_utf8(payload)
+                .map_err(|_| "EINVAL:the file path was not valid utf-8".to_string())?;
+            read_file(command, &path, limit)?
+        }
+        _ => return Err("EINVAL:unsupported transmission medium".to_string()),
+    };
+
+    let data = match command.compression {
+        None => data,
+        Some('z') => {
+            let mut inflated = vec![];
+            ZlibDecoder::new(data.as_slice())
+                .take(limit as u64 + 1)
+                .read_to_end(&mut inflated)
+                .map_err(|_| "EINVAL:failed to inflate the zlib compressed data".to_string())?;
+            if inflated.len() > limit {
+                return Err("ENOMEM:the inflated data exceeded the limit".to_string());
+            }
+            inflated
+        }
+        Some(_) => return Err("EINVAL:unsupported compression".to_string()),
+    };
+
+    match command.format {
+        KITTY_FORMAT_PNG => {
+            decode_png(&data).ok_or_else(|| "EBADPNG:failed to decode the PNG data".to_string())
+        }
+        KITTY_FORMAT_RGB | KITTY_FORMAT_RGBA => {
+            let (width, height) = (command.width, command.height);
+            if width == 0 || height == 0 || width > MAX_PNG_SIZE || height > MAX_PNG_SIZE {
+                return Err("EINVAL:invalid image size".to_string());
+            }
+
+            let bytes_per_pixel = (command.format / 8) as usize;
+            let expected = width as usize * height as usize * bytes_per_pixel;
+            if data.len() < expected {
+                return Err("ENODATA:insufficient image data".to_string());
+            }
+
+            let pixels = if command.format == KITTY_FORMAT_RGBA {
+                data[..expected].to_vec()
+            } else {
+                data[..expected]
+                    .chunks(3)
+                    .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                    .collect()
+            };
+            Ok(TerminalImage::new(width, height, pixels))
+        }
+        _ => Err("EINVAL:unsupported format".to_string()),
+    }
+}
+
+/// Reads the data of `S` bytes at offset `O` from the regular file, at most `limit` bytes.
+/// The temporary file was deleted after reading.
+fn read_file(command: &GraphicsCommand, path: &str, limit: usize) -> Result<Vec<u8>, String> {
+    let is_temp = command.medium == 't';
+    let path = if is_temp {
+        temp_file_path(Path::new(path))
+            .ok_or_else(|| "EPERM:not a temporary file of graphics protocol".to_string())?
+    } else {
+        PathBuf::from(path)
+    };
+
+    let read = || -> Result<Vec<u8>, String> {
+        let mut file = File::open(&path).map_err(|e| format!("EBADF:{}", e))?;
+        let metadata = file.metadata().map_err(|e| format!("EBADF:{}", e))?;
+        if !metadata.is_file() {
+            return Err("EBADF:not a regular file".to_string());
+        }
+
+        let available = metadata.len().saturating_sub(command.offset);
+        let size = match command.size {
+            0 => available,
+            size => size.min(available),
+        };
+        if size > limit as u64 {
+            return Err("EFBIG:the image data was too large".to_string());
+        }
+
+        file.seek(SeekFrom::Start(command.offset))
+            .map_err(|e| format!("EBADF:{}", e))?;
+        let mut data = Vec::with_capacity(size as usize);
+        file.take(size)
+            .read_to_end(&mut data)
+            .map_err(|e| format!("EBADF:{}", e))?;
+        Ok(data)
+    };
+    let result = read();
+
+    if is_temp {
+        let _ = std::fs::remove_file(&path);
+    }
+    result
+}
+
+/// Returns the canonical path of the temporary file, which was located directly in a
+/// temporary directory with the marker in its file name, `None` otherwise.
+///
+/// The path was canonicalized to resolve `..` and the symbolic links, so the files
+/// outside of temporary directories could never be deleted.
+fn temp_file_path(path: &Path) -> Option<PathBuf> {
+    let path = std::fs::canonicalize(path).ok()?;
+    if !path
+        .file_name()?
+        .to_string_lossy()
+        .contains(TEMP_FILE_MARKER)
+    {
+        return None;
+    }
+
+    let parent = path.parent()?;
+    let in_temp_dir = [std::env::temp_dir(), "/tmp".into(), "/dev/shm".into()]
+        .iter()
+        .filter_map(|dir| std::fs::canonicalize(dir).ok())
+        .any(|dir| dir == parent);
+    in_temp_dir.then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        let command = GraphicsCommand::parse("a=T,f=24,s=2,v=1,i=7,z=-1,q=2;AAAA").unwrap();
+        assert_eq!(command.action, 'T');
+        assert_eq!(command.format, KITTY_FORMAT_RGB);
+        assert_eq!((command.width, command.height), (2, 1));
+        assert_eq!(command.image_id, 7);
+        assert_eq!(command.z_index, -1);
+        assert_eq!(command.quiet, 2);
+        assert_eq!(command.payload, "AAAA");
+
+        let command = GraphicsCommand::parse("a=d,d=I,i=3").unwrap();
+        assert_eq!(command.action, 'd');
+        assert_eq!(command.delete, 'I');
+        assert_eq!(command.payload, "");
+
+        assert!(GraphicsCommand::parse("a=T,i=x").is_none());
+        assert!(GraphicsCommand::parse("a").is_none());
+    }
+
+    #[test]
+    fn test_receive_chunks() {
+        let mut store = KittyImageStore::default();
+        // 2x1 RGB pixels: [255, 0, 0], [0, 255, 0] in two chunks.
+        let first = GraphicsCommand::parse("a=T,f=24,s=2,v=1,i=1,m=1;/wAA").unwrap();
+        assert!(store.receive(first).is_none());
+        assert!(store.is_loading());
+
+        let last = GraphicsCommand::parse("m=0;AP8A").unwrap();
+        let (command, image) = store.receive(last).unwrap();
+        assert!(!store.is_loading());
+        assert_eq!(command.action, 'T');
+        assert_eq!(command.image_id, 1);
+
+        let image = image.unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 0), [0, 255, 0, 255]);
+
+        let short = GraphicsCommand::parse("f=32,s=2,v=2,i=2;AAAA").unwrap();
+        assert!(store.receive(short).unwrap().1.is_err());
+        let temp = GraphicsCommand::parse("t=t,i=2;L2V0Yy9wYXNzd2Q=").unwrap();
+        assert!(store
+            .receive(temp)
+            .unwrap()
+            .1
+            .unwrap_err()
+            .starts_with("EPERM"));
+    }
+
+    #[test]
+    fn test_load_limits() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        // 1MB of zeros inflated for a 2x1 RGB image.
+        let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+        let payload = base64::encode(encoder.finish().unwrap());
+        let command = GraphicsCommand::parse(&format!("f=24,s=2,v=1,o=z;{}", payload)).unwrap();
+        assert!(load_image(&command, KITTY_IMAGE_STORE_QUOTA)
+            .unwrap_err()
+            .starts_with("ENOMEM"));
+
+        let path = std::env::temp_dir().join("termio-kitty-graphics-large.rgb");
+        std::fs::write(&path, vec![0; 64]).unwrap();
+        let payload = base64::encode(path.to_string_lossy().as_bytes());
+        let command = GraphicsCommand::parse(&format!("f=100,t=f;{}", payload)).unwrap();
+        assert!(load_image(&command, 32).unwrap_err().starts_with("EFBIG"));
+        let command =
+            GraphicsCommand::parse(&format!("f=24,s=2,v=1,S=6,O=8,t=f;{}", payload)).unwrap();
+        assert!(load_image(&command, 32).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let temp_dir = std::env::temp_dir();
+        let victim = temp_dir.join("termio-kitty-graphics-victim");
+        std::fs::write(&victim, [255, 0, 0]).unwrap();
+        let marker_dir = temp_dir.join(TEMP_FILE_MARKER);
+        std::fs::create_dir_all(&marker_dir).unwrap();
+
+        // Escaped from the directory with marker by `..`, the file must not be deleted.
+        let traversal = marker_dir.join("..").join("termio-kitty-graphics-victim");
+        let payload = base64::encode(traversal.to_string_lossy().as_bytes());
+        let command = GraphicsCommand::parse(&format!("f=24,s=1,v=1,t=t;{}", payload)).unwrap();
+        assert!(load_image(&command, KITTY_IMAGE_STORE_QUOTA)
+            .unwrap_err()
+            .starts_with("EPERM"));
+        assert!(victim.exists());
+        std::fs::remove_file(&victim).unwrap();
+
+        let temp = temp_dir.join(format!("{}-termio-test.rgb", TEMP_FILE_MARKER));
+        std::fs::write(&temp, [255, 0, 0]).unwrap();
+        let payload = base64::encode(temp.to_string_lossy().as_bytes());
+        let command = GraphicsCommand::parse(&format!("f=24,s=1,v=1,t=t;{}", payload)).unwrap();
+        let image = load_image(&command, KITTY_IMAGE_STORE_QUOTA).unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert!(!temp.exists());
+    }
+
+    #[test]
+    fn test_image_store() {
+        let image = || TerminalImage::new(2, 2, vec![0; 16]);
+        let mut store = KittyImageStore::new(40);
+
+        store.insert(1, 5, image());
+        store.insert(2, 5, image());
+        assert_eq!(store.id_of_number(5), Some(2));
+
+        // Exceeded the quota, the oldest image was evicted.
+        store.insert(3, 0, image());
+        assert!(store.get(1).is_none());
+        assert!(store.get(2).is_some());
+        assert!(store.get(3).is_some());
+
+        let id = store.allocate_id();
+        assert!(id != 0 && store.get(id).is_none());
+        assert!(store.remove(2));
+        assert!(!store.remove(2));
+    }
+
+    #[test]
+    fn test_graphics_response() {
+        let command = GraphicsCommand::parse("i=3,p=4").unwrap();
+        assert_eq!(
+            graphics_response(&command, 3, &Ok(())).unwrap(),
+            "\u{001b}_Gi=3,p=4;OK\u{001b}\\"
+        );
+
+        let command = GraphicsCommand::parse("I=9,q=1").unwrap();
+        assert!(graphics_response(&command, 10, &Ok(())).is_none());
+        assert_eq!(
+            graphics_response(&command, 10, &Err("ENOENT:not found".to_string())).unwrap(),
+            "\u{001b}_Gi=10,I=9;ENOENT:not found\u{001b}\\"
+        );
+
+        let command = GraphicsCommand::parse("a=T").unwrap();
+        assert!(graphics_response(&command, 1, &Ok(())).is_none());
+    }
+}
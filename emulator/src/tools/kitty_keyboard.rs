@@ -0,0 +1,390 @@
+#![allow(dead_code)]
+use super::translators::CTRL_MODIFIER;
+use tmui::tlib::namespace::{AsNumeric, KeyCode, KeyboardModifier};
+
+/// The progressive enhancement flags of kitty keyboard protocol,
+/// see https://sw.kovidgoyal.net/kitty/keyboard-protocol/
+pub const KITTY_DISAMBIGUATE: u8 = 1;
+pub const KITTY_REPORT_EVENT_TYPES: u8 = 2;
+pub const KITTY_REPORT_ALTERNATE_KEYS: u8 = 4;
+pub const KITTY_REPORT_ALL_KEYS: u8 = 8;
+pub const KITTY_REPORT_TEXT: u8 = 16;
+pub const KITTY_FLAGS_MASK: u8 = 31;
+
+/// The modifier bits of kitty keyboard protocol, the value reported was `1 + bits`.
+pub const KITTY_SHIFT: u32 = 1;
+pub const KITTY_ALT: u32 = 2;
+pub const KITTY_CTRL: u32 = 4;
+pub const KITTY_SUPER: u32 = 8;
+
+/// The maximum depth of the flags stack, the oldest entries were evicted when exceeded.
+const MAX_FLAGS_STACK_DEPTH: usize = 16;
+
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum KeyEventType {
+    #[default]
+    Press = 1,
+    Repeat,
+    Release,
+}
+
+/// The stack of enhancement flags, the main and alternate screens have their own stacks.
+///
+/// - `CSI > flags u`: Push the flags.
+/// - `CSI < number u`: Pop `number` entries.
+/// - `CSI = flags ; mode u`: Set(1), or(2), or clear(3) the current flags.
+/// - `CSI ? u`: Query the current flags.
+#[derive(Debug, Default, Clone)]
+pub struct KeyboardFlagsStack {
+    stack: Vec<u8>,
+}
+
+impl KeyboardFlagsStack {
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.stack.last().copied().unwrap_or_default()
+    }
+
+    pub fn push(&mut self, flags: u8) {
+        if self.stack.len() >= MAX_FLAGS_STACK_DEPTH {
+            self.stack.remove(0);
+        }
+        self.stack.push(flags & KITTY_FLAGS_MASK)
+    }
+
+    /// Popping more entries than the stack holds resets all the flags.
+    #[inline]
+    pub fn pop(&mut self, number: usize) {
+        let len = self.stack.len().saturating_sub(number.max(1));
+        self.stack.truncate(len)
+    }
+
+    pub fn set(&mut self, flags: u8, mode: i32) {
+        let flags = flags & KITTY_FLAGS_MASK;
+        let current = self.flags();
+        let new_flags = match mode {
+            2 => current | flags,
+            3 => current & !flags,
+            _ => flags,
+        };
+        match self.stack.last_mut() {
+            Some(top) => *top = new_flags,
+            None => self.stack.push(new_flags),
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.stack.clear()
+    }
+}
+
+/// The key in kitty keyboard protocol.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KittyKey {
+    /// Keys represented by unicode code point, encoded as `CSI code u`.
+    Codepoint(u32),
+    /// Functional keys encoded as `CSI number ~` or `CSI 1 X`.
+    Functional(u32, char),
+    /// Modifier keys, only reported in the report all keys mode.
+    Modifier(u32),
+}
+
+const ESCAPE: u32 = 27;
+const ENTER: u32 = 13;
+const TAB: u32 = 9;
+const BACKSPACE: u32 = 127;
+
+impl KittyKey {
+    /// Get the key of kitty keyboard protocol from key code.
+    pub fn from_key_code(key_code: KeyCode) -> Option<Self> {
+        let key = match key_code {
+            KeyCode::KeyEscape => Self::Codepoint(ESCAPE),
+            KeyCode::KeyEnter | KeyCode::KeyReturn => Self::Codepoint(ENTER),
+            KeyCode::KeyTab | KeyCode::KeyBacktab => Self::Codepoint(TAB),
+            KeyCode::KeyBackspace => Self::Codepoint(BACKSPACE),
+            KeyCode::KeyInsert => Self::Functional(2, '~'),
+            KeyCode::KeyDelete => Self::Functional(3, '~'),
+            KeyCode::KeyPageUp => Self::Functional(5, '~'),
+            KeyCode::KeyPageDown => Self::Functional(6, '~'),
+            KeyCode::KeyUp => Self::Functional(1, 'A'),
+            KeyCode::KeyDown => Self::Functional(1, 'B'),
+            KeyCode::KeyRight => Self::Functional(1, 'C'),
+            KeyCode::KeyLeft => Self::Functional(1, 'D'),
+            KeyCode::KeyEnd => Self::Functional(1, 'F'),
+            KeyCode::KeyHome => Self::Functional(1, 'H'),
+            KeyCode::KeyF1 => Self::Functional(1, 'P'),
+            KeyCode::KeyF2 => Self::Functional(1, 'Q'),
+            KeyCode::KeyF3 => Self::Functional(13, '~'),
+            KeyCode::KeyF4 => Self::Functional(1, 'S'),
+            KeyCode::KeyF5 => Self::Functional(15, '~'),
+            KeyCode::KeyF6 => Self::Functional(17, '~'),
+            KeyCode::KeyF7 => Self::Functional(18, '~'),
+            KeyCode::KeyF8 => Self::Functional(19, '~'),
+            KeyCode::KeyF9 => Self::Functional(20, '~'),
+            KeyCode::KeyF10 => Self::Functional(21, '~'),
+            KeyCode::KeyF11 => Self::Functional(23, '~'),
+            KeyCode::KeyF12 => Self::Functional(24, '~'),
+            KeyCode::KeyCapsLock => Self::Codepoint(57358),
+            KeyCode::KeyScrollLock => Self::Codepoint(57359),
+            KeyCode::KeyNumLock => Self::Codepoint(57360),
+            KeyCode::KeyPrint => Self::Codepoint(57361),
+            KeyCode::KeyPause => Self::Codepoint(57362),
+            KeyCode::KeyMenu => Self::Codepoint(57363),
+            KeyCode::KeyShift => Self::Modifier(57441),
+            KeyCode::KeyControl => Self::Modifier(57442),
+            KeyCode::KeyAlt => Self::Modifier(57443),
+            KeyCode::KeyMeta => Self::Modifier(57444),
+            _ => {
+                // The printable keys, reported as the lower case code point.
+                let code = key_code.as_numeric();
+                let c = char::from_u32(code).filter(|c| !c.is_control())?;
+                Self::Codepoint(c.to_lowercase().next().unwrap_or(c) as u32)
+            }
+        };
+        Some(key)
+    }
+}
+
+/// Get the modifier bits of kitty keyboard protocol.
+pub fn kitty_modifiers(modifier: KeyboardModifier) -> u32 {
+    let super_modifier = if CTRL_MODIFIER == KeyboardModifier::ControlModifier {
+        KeyboardModifier::MetaModifier
+    } else {
+        KeyboardModifier::ControlModifier
+    };
+
+    let mut bits = 0;
+    if modifier.has(KeyboardModifier::ShiftModifier) {
+        bits |= KITTY_SHIFT;
+    }
+    if modifier.has(KeyboardModifier::AltModifier) {
+        bits |= KITTY_ALT;
+    }
+    if modifier.has(CTRL_MODIFIER) {
+        bits |= KITTY_CTRL;
+    }
+    if modifier.has(super_modifier) {
+        bits |= KITTY_SUPER;
+    }
+    bits
+}
+
+/// Encode the key event by kitty keyboard protocol with the enhancement `flags`.
+///
+/// @param text: The text generated by the key event.
+/// @return `None` if the event should be sent by the legacy encoding, or not be sent for
+/// the release events.
+pub fn encode_kitty_key(
+    key: KittyKey,
+    modifiers: u32,
+    event_type: KeyEventType,
+    text: &str,
+    flags: u8,
+) -> Option<String> {
+    let report_all = flags & KITTY_REPORT_ALL_KEYS != 0;
+    let disambiguate = flags & KITTY_DISAMBIGUATE != 0 || report_all;
+    if event_type != KeyEventType::Press && flags & KITTY_REPORT_EVENT_TYPES == 0 {
+        return None;
+    }
+    // Enter, Tab and Backspace have no release events unless all keys were reported.
+    if event_type == KeyEventType::Release
+        && !report_all
+        && matches!(key, KittyKey::Codepoint(ENTER | TAB | BACKSPACE))
+    {
+        return None;
+    }
+
+    let escape = report_all
+        || match key {
+            KittyKey::Codepoint(ESCAPE) => disambiguate,
+            // Keep the legacy encoding without modifiers, so that user can still type `reset`
+            // in shell after a program crashed without restoring the flags.
+            KittyKey::Codepoint(ENTER | TAB | BACKSPACE) => disambiguate && modifiers != 0,
+            KittyKey::Codepoint(_) => {
+                disambiguate && modifiers & (KITTY_ALT | KITTY_CTRL | KITTY_SUPER) != 0
+            }
+            KittyKey::Functional(..) => event_type != KeyEventType::Press,
+            KittyKey::Modifier(_) => false,
+        };
+    if !escape {
+        return None;
+    }
+
+    // The modifiers and event type, omitted when they were the default value.
+    let mut modifiers_field = String::new();
+    if modifiers != 0 || event_type != KeyEventType::Press {
+        modifiers_field.push_str(&(modifiers + 1).to_string());
+    }
+    if event_type != KeyEventType::Press {
+        modifiers_field.push_str(&format!(":{}", event_type as u8));
+    }
+
+    let (number, trailer) = match key {
+        KittyKey::Codepoint(code) | KittyKey::Modifier(code) => (code, 'u'),
+        KittyKey::Functional(number, trailer) => (number, trailer),
+    };
+
+    let mut key_field = number.to_string();
+    if let KittyKey::Codepoint(code) = key {
+        let mut chars = text.chars();
+        if let (Some(shifted), None) = (chars.next(), chars.next()) {
+            if flags & KITTY_REPORT_ALTERNATE_KEYS != 0
+                && modifiers & KITTY_SHIFT != 0
+                && shifted as u32 != code
+                && !shifted.is_control()
+            {
+                key_field.push_str(&format!(":{}", shifted as u32));
+            }
+        }
+    }
+
+    let text_field = if report_all
+        && flags & KITTY_REPORT_TEXT != 0
+        && event_type != KeyEventType::Release
+        && trailer == 'u'
+        && !text.chars().any(|c| c.is_control())
+    {
+        text.chars()
+            .map(|c| (c as u32).to_string())
+            .collect::<Vec<_>>()
+            .join(":")
+    } else {
+        String::new()
+    };
+
+    let mut seq = String::from("\u{1b}[");
+    if trailer == 'u' || trailer == '~' || !modifiers_field.is_empty() {
+        seq.push_str(&key_field);
+    }
+    if !modifiers_field.is_empty() || !text_field.is_empty() {
+        seq.push(';');
+        seq.push_str(&modifiers_field);
+    }
+    if !text_field.is_empty() {
+        seq.push(';');
+        seq.push_str(&text_field);
+    }
+    seq.push(trailer);
+    Some(seq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_stack() {
+        let mut stack = KeyboardFlagsStack::default();
+        assert_eq!(stack.flags(), 0);
+
+        stack.push(KITTY_DISAMBIGUATE);
+        stack.push(KITTY_DISAMBIGUATE | KITTY_REPORT_EVENT_TYPES);
+        assert_eq!(stack.flags(), 3);
+
+        stack.set(KITTY_REPORT_EVENT_TYPES, 3);
+        assert_eq!(stack.flags(), KITTY_DISAMBIGUATE);
+        stack.set(KITTY_REPORT_ALL_KEYS, 2);
+        assert_eq!(stack.flags(), KITTY_DISAMBIGUATE | KITTY_REPORT_ALL_KEYS);
+
+        stack.pop(1);
+        assert_eq!(stack.flags(), KITTY_DISAMBIGUATE);
+        stack.pop(5);
+        assert_eq!(stack.flags(), 0);
+
+        for _ in 0..MAX_FLAGS_STACK_DEPTH + 5 {
+            stack.push(KITTY_DISAMBIGUATE);
+        }
+        stack.pop(MAX_FLAGS_STACK_DEPTH - 1);
+        assert_eq!(stack.flags(), KITTY_DISAMBIGUATE);
+    }
+
+    #[test]
+    fn test_encode() {
+        let a = KittyKey::Codepoint(97);
+        let press = KeyEventType::Press;
+
+        // Plain text keys use the legacy encoding.
+        assert_eq!(encode_kitty_key(a, 0, press, "a", KITTY_DISAMBIGUATE), None);
+        assert_eq!(
+            encode_kitty_key(a, KITTY_SHIFT, press, "A", KITTY_DISAMBIGUATE),
+            None
+        );
+        assert_eq!(
+            encode_kitty_key(a, KITTY_CTRL, press, "", KITTY_DISAMBIGUATE).unwrap(),
+            "\u{1b}[97;5u"
+        );
+        assert_eq!(
+            encode_kitty_key(
+                KittyKey::Codepoint(ESCAPE),
+                0,
+                press,
+                "",
+                KITTY_DISAMBIGUATE
+            )
+            .unwrap(),
+            "\u{1b}[27u"
+        );
+        assert_eq!(
+            encode_kitty_key(
+                KittyKey::Codepoint(ENTER),
+                0,
+                press,
+                "\r",
+                KITTY_DISAMBIGUATE
+            ),
+            None
+        );
+        assert_eq!(
+            encode_kitty_key(KittyKey::Codepoint(TAB), KITTY_SHIFT, press, "", 1).unwrap(),
+            "\u{1b}[9;2u"
+        );
+
+        // Event types.
+        let release = KeyEventType::Release;
+        assert_eq!(encode_kitty_key(a, KITTY_CTRL, release, "", 1), None);
+        assert_eq!(
+            encode_kitty_key(a, KITTY_CTRL, release, "", 3).unwrap(),
+            "\u{1b}[97;5:3u"
+        );
+        assert_eq!(
+            encode_kitty_key(KittyKey::Functional(1, 'A'), 0, release, "", 3).unwrap(),
+            "\u{1b}[1;1:3A"
+        );
+        assert_eq!(
+            encode_kitty_key(KittyKey::Functional(5, '~'), 0, KeyEventType::Repeat, "", 3).unwrap(),
+            "\u{1b}[5;1:2~"
+        );
+        let enter = KittyKey::Codepoint(ENTER);
+        assert_eq!(encode_kitty_key(enter, 0, release, "", 3), None);
+        assert_eq!(encode_kitty_key(enter, KITTY_SHIFT, release, "", 3), None);
+        assert_eq!(
+            encode_kitty_key(enter, 0, KeyEventType::Repeat, "\r", 3),
+            None
+        );
+        assert_eq!(
+            encode_kitty_key(enter, 0, release, "", 10).unwrap(),
+            "\u{1b}[13;1:3u"
+        );
+
+        // All keys as escape codes, alternate keys and associated text.
+        assert_eq!(
+            encode_kitty_key(KittyKey::Functional(1, 'A'), 0, press, "", 8).unwrap(),
+            "\u{1b}[A"
+        );
+        assert_eq!(encode_kitty_key(a, 0, press, "a", 8).unwrap(), "\u{1b}[97u");
+        assert_eq!(
+            encode_kitty_key(a, KITTY_SHIFT, press, "A", 31).unwrap(),
+            "\u{1b}[97:65;2;65u"
+        );
+        assert_eq!(
+            encode_kitty_key(a, 0, press, "a", 24).unwrap(),
+            "\u{1b}[97;;97u"
+        );
+        assert_eq!(
+            encode_kitty_key(KittyKey::Modifier(57441), KITTY_SHIFT, press, "", 8).unwrap(),
+            "\u{1b}[57441;2u"
+        );
+    }
+}
@@ -5,6 +5,24 @@ pub mod character_color;
 pub mod event;
 pub mod filter;
 pub mod history;
+pub mod reflow;
 pub mod character;
+pub mod hyperlink;
+pub mod clipboard;
+pub mod working_directory;
+pub mod dynamic_color;
+pub mod kitty_keyboard;
+pub mod vt52_keyboard;
+pub mod graphics;
+pub mod sixel;
+pub mod png;
+pub mod kitty_graphics;
+pub mod terminfo;
+pub mod notification;
+pub mod progress;
+pub mod grapheme;
+pub mod char_width;
+pub mod codec;
+pub mod charset;
 pub mod terminal_character_decoder;
-pub mod text_stream;
\ No newline at end of file
+pub mod text_stream;
\ No newline at end of file
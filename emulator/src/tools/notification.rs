@@ -0,0 +1,256 @@
+#![allow(dead_code)]
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// The maximum number of kitty notifications waiting for the remaining chunks.
+const MAX_PENDING_NOTIFICATIONS: usize = 16;
+/// The maximum length of the title or body of notification, the exceeded part will be dropped.
+const MAX_NOTIFICATION_LENGTH: usize = 4096;
+
+/// The desktop notification requested by terminal program:
+///
+/// - `ESC ] 9 ; body ST` (iTerm2)
+/// - `ESC ] 777 ; notify ; title ; body ST` (urxvt)
+/// - `ESC ] 99 ; metadata ; payload ST` (kitty), see [`KittyNotifications`]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+
+impl Notification {
+    /// Parse the text part of `OSC 9` sequence(the text after `9;`), it has no title.
+    ///
+    /// @return `None` if the body was empty.
+    pub fn parse_osc9(text: &str) -> Option<Self> {
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            title: String::new(),
+            body: truncate(text).to_string(),
+        })
+    }
+
+    /// Parse the text part of `OSC 777` sequence(the text after `777;`), the body may
+    /// contain the semicolons.
+    ///
+    /// @return `None` if it was not the `notify` command or the notification was empty.
+    pub fn parse_osc777(text: &str) -> Option<Self> {
+        let mut params = text.splitn(3, ';');
+        if params.next() != Some("notify") {
+            return None;
+        }
+        let title = params.next().unwrap_or_default();
+        let body = params.next().unwrap_or_default();
+        if title.is_empty() && body.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            title: truncate(title).to_string(),
+            body: truncate(body).to_string(),
+        })
+    }
+}
+
+/// Assembles the kitty notifications: `ESC ] 99 ; metadata ; payload ST`.
+///
+/// The metadata was colon separated `key=value` pairs, the supported keys:
+/// - `i`: The identifier of notification, the chunks with the same identifier were joined.
+/// - `d`: 0 if there were more chunks, 1(default) if the notification was done.
+/// - `p`: The type of payload, `title`(default) or `body`, others were ignored.
+/// - `e`: 1 if the payload was base64 encoded, 0(default) if it was plain text.
+#[derive(Debug, Default)]
+pub struct KittyNotifications {
+    pending: HashMap<String, Notification>,
+}
+
+impl KittyNotifications {
+    /// Receive the text part of `OSC 99` sequence(the text after `99;`).
+    ///
+    /// @return The notification when it was done, `None` if it was waiting for the
+    /// remaining chunks or it was empty.
+    pub fn receive(&mut self, text: &str) -> Option<Notification> {
+        let (metadata, payload) = text.split_once(';').unwrap_or((text, ""));
+
+        let mut id = "";
+        let mut done = true;
+        let mut payload_type = "title";
+        let mut encoded = false;
+        for (key, value) in metadata.split(':').filter_map(|kv| kv.split_once('=')) {
+            match key {
+                "i" => id = value,
+                "d" => done = value != "0",
+                "p" => payload_type = value,
+                "e" => encoded = value == "1",
+                _ => {}
+            }
+        }
+
+        let payload = if encoded {
+            base64::decode(payload)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .unwrap_or_default()
+        } else {
+            payload.to_string()
+        };
+
+        if !self.pending.contains_key(id) && self.pending.len() >= MAX_PENDING_NOTIFICATIONS {
+            return None;
+        }
+        let notification = self.pending.entry(id.to_string()).or_default();
+        match payload_type {
+            "title" => notification.title.push_str(&payload),
+            "body" => notification.body.push_str(&payload),
+            _ => {}
+        }
+        notification
+            .title
+            .truncate(floor_boundary(&notification.title));
+        notification
+            .body
+            .truncate(floor_boundary(&notification.body));
+
+        if !done {
+            return None;
+        }
+        let notification = self.pending.remove(id)?;
+        if notification.title.is_empty() && notification.body.is_empty() {
+            return None;
+        }
+        Some(notification)
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.pending.clear()
+    }
+}
+
+/// Limits the notifications of a session to `burst` in every `interval`,
+/// so a looping script can't spam the user.
+#[derive(Debug)]
+pub struct NotificationRateLimiter {
+    burst: usize,
+    interval: Duration,
+    recent: VecDeque<Instant>,
+}
+
+impl Default for NotificationRateLimiter {
+    #[inline]
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(10))
+    }
+}
+
+impl NotificationRateLimiter {
+    #[inline]
+    pub fn new(burst: usize, interval: Duration) -> Self {
+        Self {
+            burst,
+            interval,
+            recent: VecDeque::with_capacity(burst),
+        }
+    }
+
+    /// Returns true if the notification arrived at `now` was allowed, and records it.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while let Some(first) = self.recent.front() {
+            if now.duration_since(*first) >= self.interval {
+                self.recent.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.recent.len() >= self.burst {
+            return false;
+        }
+        self.recent.push_back(now);
+        true
+    }
+}
+
+#[inline]
+fn truncate(text: &str) -> &str {
+    &text[..floor_boundary(text)]
+}
+
+/// The largest char boundary not exceeding [`MAX_NOTIFICATION_LENGTH`].
+fn floor_boundary(text: &str) -> usize {
+    if text.len() <= MAX_NOTIFICATION_LENGTH {
+        return text.len();
+    }
+    let mut idx = MAX_NOTIFICATION_LENGTH;
+    while !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notification() {
+        assert_eq!(
+            Notification::parse_osc9("done"),
+            Some(Notification {
+                title: String::new(),
+                body: "done".to_string()
+            })
+        );
+        assert!(Notification::parse_osc9("").is_none());
+
+        assert_eq!(
+            Notification::parse_osc777("notify;Build;finished; 0 errors"),
+            Some(Notification {
+                title: "Build".to_string(),
+                body: "finished; 0 errors".to_string()
+            })
+        );
+        assert!(Notification::parse_osc777("notify;;").is_none());
+        assert!(Notification::parse_osc777("preexec").is_none());
+    }
+
+    #[test]
+    fn test_kitty_notifications() {
+        let mut notifications = KittyNotifications::default();
+        assert_eq!(
+            notifications.receive(";Hello"),
+            Some(Notification {
+                title: "Hello".to_string(),
+                body: String::new()
+            })
+        );
+
+        // Chunked notification with base64 encoded body("world").
+        assert!(notifications.receive("i=1:d=0;Build").is_none());
+        assert!(notifications.receive("i=2:d=0;Other").is_none());
+        assert_eq!(
+            notifications.receive("i=1:p=body:e=1;d29ybGQ="),
+            Some(Notification {
+                title: "Build".to_string(),
+                body: "world".to_string()
+            })
+        );
+        assert!(notifications.receive("i=3:p=close;").is_none());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = NotificationRateLimiter::new(2, Duration::from_secs(10));
+        let now = Instant::now();
+        assert!(limiter.allow(now));
+        assert!(limiter.allow(now + Duration::from_secs(1)));
+        assert!(!limiter.allow(now + Duration::from_secs(2)));
+        assert!(limiter.allow(now + Duration::from_secs(10)));
+        assert!(!limiter.allow(now + Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,412 @@
+#![allow(dead_code)]
+use super::graphics::TerminalImage;
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+/// The maximum width and height of decoded images.
+pub const MAX_PNG_SIZE: u32 = 10000;
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// The (x, y) start and step of the seven passes of Adam7 interlacing.
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+const COLOR_GRAYSCALE: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_INDEXED: u8 = 3;
+const COLOR_GRAYSCALE_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+/// Decodes the PNG image (e.g. transmitted by kitty graphics protocol) to RGBA pixels.
+///
+/// All the standard color types, bit depths and the Adam7 interlacing were supported,
+/// the ancillary chunks except `tRNS` were ignored, and the CRCs were not verified.
+///
+/// @return `None` if the data was not a valid PNG image.
+pub fn decode_png(data: &[u8]) -> Option<TerminalImage> {
+    if data.len() < PNG_SIGNATURE.len() || data[..PNG_SIGNATURE.len()] != PNG_SIGNATURE {
+        return None;
+    }
+
+    let mut header: Option<Header> = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut idat = vec![];
+
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+        let ty = &data[pos + 4..pos + 8];
+        let chunk = data.get(pos + 8..(pos + 8).checked_add(len)?)?;
+        // Skip the CRC.
+        pos += 12 + len;
+
+        match ty {
+            b"IHDR" => header = Some(Header::parse(chunk)?),
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => idat.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header?;
+    let scanlines = header.scanlines_size()?;
+    let mut raw = Vec::with_capacity(scanlines);
+    ZlibDecoder::new(idat.as_slice())
+        .take(scanlines as u64)
+        .read_to_end(&mut raw)
+        .ok()?;
+    if raw.len() < scanlines {
+        return None;
+    }
+
+    let (width, height) = (header.width, header.height);
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    let converter = Converter {
+        header: &header,
+        palette,
+        transparency,
+    };
+
+    let passes: &[(u32, u32, u32, u32)] = if header.interlaced {
+        &ADAM7_PASSES
+    } else {
+        &[(0, 0, 1, 1)]
+    };
+    let mut raw = raw.as_mut_slice();
+
+    for (x0, y0, dx, dy) in passes.iter().copied() {
+        let (pass_width, pass_height) = (pass_size(width, x0, dx), pass_size(height, y0, dy));
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let row_size = header.row_size(pass_width);
+        let (pass, rest) = raw.split_at_mut((row_size + 1) * pass_height as usize);
+        raw = rest;
+
+        unfilter(pass, row_size, header.filter_unit())?;
+
+        for (py, row) in pass.chunks(row_size + 1).enumerate() {
+            let y = y0 + py as u32 * dy;
+            for px in 0..pass_width {
+                let x = x0 + px * dx;
+                let idx = ((y * width + x) * 4) as usize;
+                pixels[idx..idx + 4].copy_from_slice(&converter.rgba(&row[1..], px as usize));
+            }
+        }
+    }
+
+    Some(TerminalImage::new(width, height, pixels))
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(chunk: &[u8]) -> Option<Self> {
+        if chunk.len() < 13 {
+            return None;
+        }
+        let width = u32::from_be_bytes(chunk[0..4].try_into().ok()?);
+        let height = u32::from_be_bytes(chunk[4..8].try_into().ok()?);
+        let (bit_depth, color_type) = (chunk[8], chunk[9]);
+
+        let valid_depth = match color_type {
+            COLOR_GRAYSCALE => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+            COLOR_INDEXED => matches!(bit_depth, 1 | 2 | 4 | 8),
+            COLOR_RGB | COLOR_GRAYSCALE_ALPHA | COLOR_RGBA => matches!(bit_depth, 8 | 16),
+            _ => false,
+        };
+        if !valid_depth
+            || width == 0
+            || height == 0
+            || width > MAX_PNG_SIZE
+            || height > MAX_PNG_SIZE
+        {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: chunk[12] == 1,
+        })
+    }
+
+    #[inline]
+    fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_RGB => 3,
+            COLOR_GRAYSCALE_ALPHA => 2,
+            COLOR_RGBA => 4,
+            _ => 1,
+        }
+    }
+
+    #[inline]
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// The distance of bytes to the corresponding byte of previous pixel, used in filters.
+    #[inline]
+    fn filter_unit(&self) -> usize {
+        (self.bits_per_pixel() / 8).max(1)
+    }
+
+    /// The bytes of a row of `width` pixels, exclude the filter type byte.
+    #[inline]
+    fn row_size(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// The total bytes of the scanlines of all the passes.
+    fn scanlines_size(&self) -> Option<usize> {
+        let passes: &[(u32, u32, u32, u32)] = if self.interlaced {
+            &ADAM7_PASSES
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+
+        passes.iter().try_fold(0usize, |size, (x0, y0, dx, dy)| {
+            let (w, h) = (
+                pass_size(self.width, *x0, *dx),
+                pass_size(self.height, *y0, *dy),
+            );
+            if w == 0 || h == 0 {
+                return Some(size);
+            }
+            size.checked_add((self.row_size(w) + 1).checked_mul(h as usize)?)
+        })
+    }
+}
+
+struct Converter<'a> {
+    header: &'a Header,
+    palette: &'a [u8],
+    transparency: &'a [u8],
+}
+
+impl<'a> Converter<'a> {
+    /// Returns the `idx`th sample of the row in its original bit depth.
+    #[inline]
+    fn sample(&self, row: &[u8], idx: usize) -> u16 {
+        match self.header.bit_depth {
+            16 => u16::from_be_bytes([row[idx * 2], row[idx * 2 + 1]]),
+            8 => row[idx] as u16,
+            depth => {
+                let depth = depth as usize;
+                let bit = idx * depth;
+                let shift = 8 - depth - bit % 8;
+                ((row[bit / 8] >> shift) as u16) & ((1 << depth) - 1)
+            }
+        }
+    }
+
+    /// Scales the sample to 8 bits.
+    #[inline]
+    fn scale(&self, sample: u16) -> u8 {
+        match self.header.bit_depth {
+            16 => (sample >> 8) as u8,
+            8 => sample as u8,
+            depth => (sample as u32 * 255 / ((1 << depth) - 1)) as u8,
+        }
+    }
+
+    /// Whether the raw samples equal to the single transparent color of `tRNS` chunk.
+    #[inline]
+    fn is_transparent(&self, samples: &[u16]) -> bool {
+        self.transparency.len() >= samples.len() * 2
+            && samples.iter().enumerate().all(|(i, sample)| {
+                u16::from_be_bytes([self.transparency[i * 2], self.transparency[i * 2 + 1]])
+                    == *sample
+            })
+    }
+
+    fn rgba(&self, row: &[u8], x: usize) -> [u8; 4] {
+        let channels = self.header.channels();
+        let s = |c: usize| self.sample(row, x * channels + c);
+
+        match self.header.color_type {
+            COLOR_GRAYSCALE => {
+                let gray = s(0);
+                let alpha = if self.is_transparent(&[gray]) { 0 } else { 255 };
+                let gray = self.scale(gray);
+                [gray, gray, gray, alpha]
+            }
+            COLOR_RGB => {
+                let (r, g, b) = (s(0), s(1), s(2));
+                let alpha = if self.is_transparent(&[r, g, b]) {
+                    0
+                } else {
+                    255
+                };
+                [self.scale(r), self.scale(g), self.scale(b), alpha]
+            }
+            COLOR_INDEXED => {
+                let idx = s(0) as usize;
+                let alpha = self.transparency.get(idx).copied().unwrap_or(255);
+                match self.palette.get(idx * 3..idx * 3 + 3) {
+                    Some(rgb) => [rgb[0], rgb[1], rgb[2], alpha],
+                    None => [0, 0, 0, 0],
+                }
+            }
+            COLOR_GRAYSCALE_ALPHA => {
+                let gray = self.scale(s(0));
+                [gray, gray, gray, self.scale(s(1))]
+            }
+            _ => [
+                self.scale(s(0)),
+                self.scale(s(1)),
+                self.scale(s(2)),
+                self.scale(s(3)),
+            ],
+        }
+    }
+}
+
+#[inline]
+fn pass_size(size: u32, start: u32, step: u32) -> u32 {
+    if size > start {
+        (size - start).div_ceil(step)
+    } else {
+        0
+    }
+}
+
+/// Reverses the filters of scanlines in place, each of them was started with the filter type.
+fn unfilter(data: &mut [u8], row_size: usize, unit: usize) -> Option<()> {
+    let stride = row_size + 1;
+    let rows = data.len() / stride;
+
+    for y in 0..rows {
+        let (prev, current) = data.split_at_mut(y * stride);
+        let prev = if y == 0 {
+            None
+        } else {
+            Some(&prev[(y - 1) * stride + 1..])
+        };
+        let filter = current[0];
+        let row = &mut current[1..stride];
+
+        for i in 0..row_size {
+            let a = if i >= unit { row[i - unit] } else { 0 };
+            let b = prev.map_or(0, |prev| prev[i]);
+            let c = match prev {
+                Some(prev) if i >= unit => prev[i - unit],
+                _ => 0,
+            };
+
+            row[i] = row[i].wrapping_add(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return None,
+            });
+        }
+    }
+
+    Some(())
+}
+
+#[inline]
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn chunk(png: &mut Vec<u8>, ty: &[u8], data: &[u8]) {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(ty);
+        png.extend_from_slice(data);
+        // The CRC was not verified.
+        png.extend_from_slice(&[0; 4]);
+    }
+
+    fn encode(width: u32, height: u32, depth: u8, color_type: u8, scanlines: &[u8]) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        let mut ihdr = vec![];
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+        chunk(&mut png, b"IHDR", &ihdr);
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(scanlines).unwrap();
+        chunk(&mut png, b"IDAT", &encoder.finish().unwrap());
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn test_decode_png() {
+        // 2x2 RGBA, the second row used the Up filter.
+        let scanlines = [
+            0, 255, 0, 0, 255, 0, 255, 0, 128, //
+            2, 0, 0, 255, 0, 0, 0, 0, 0,
+        ];
+        let image = decode_png(&encode(2, 2, 8, COLOR_RGBA, &scanlines)).unwrap();
+        assert_eq!(image.width(), 2);
+        assert_eq!(image.height(), 2);
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 0), [0, 255, 0, 128]);
+        assert_eq!(image.pixel(0, 1), [255, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 1), [0, 255, 0, 128]);
+
+        // 3x1 1-bit grayscale, with the Sub filter which takes no effect on the single byte.
+        let image = decode_png(&encode(3, 1, 1, COLOR_GRAYSCALE, &[1, 0b1010_0000])).unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 255, 255, 255]);
+        assert_eq!(image.pixel(1, 0), [0, 0, 0, 255]);
+        assert_eq!(image.pixel(2, 0), [255, 255, 255, 255]);
+
+        assert!(decode_png(b"not a png").is_none());
+        assert!(decode_png(&encode(2, 2, 8, COLOR_RGBA, &scanlines[..9])).is_none());
+    }
+
+    #[test]
+    fn test_unfilter() {
+        // Average and Paeth filters.
+        let mut data = [3, 10, 20, 3, 5, 5, 4, 1, 2];
+        unfilter(&mut data, 2, 1).unwrap();
+        assert_eq!(data[1..3], [10, 25]);
+        assert_eq!(data[4..6], [10, 22]);
+        assert_eq!(data[7..9], [11, 24]);
+    }
+}
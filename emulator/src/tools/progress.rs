@@ -0,0 +1,105 @@
+#![allow(dead_code)]
+
+/// The state of progress reported by the ConEmu's `OSC 9;4` escape sequence:
+///
+/// `ESC ] 9 ; 4 ; st ; pr ST`
+///
+/// `st` is the state, `pr` is the progress in percent (0-100).
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ProgressState {
+    /// No progress, the indicator should be hidden.
+    #[default]
+    None = 0,
+    Normal,
+    Error,
+    /// The progress is unknown, the indicator should be shown as busy.
+    Indeterminate,
+    Paused,
+}
+impl From<u8> for ProgressState {
+    #[inline]
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Normal,
+            2 => Self::Error,
+            3 => Self::Indeterminate,
+            4 => Self::Paused,
+            _ => Self::None,
+        }
+    }
+}
+
+/// The progress of session reported by the terminal program.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Progress {
+    pub state: ProgressState,
+    /// The progress in percent (0-100), meaningless in the `None` and `Indeterminate` states.
+    pub percent: u8,
+}
+
+impl Progress {
+    /// Parse the text part of `OSC 9;4` sequence(the text after `9;4`, may be empty or
+    /// starts with `;`), the omitted `st` and `pr` were 0, `pr` was clamped to 100.
+    ///
+    /// @return `None` if the state or progress was not a number.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut params = text.strip_prefix(';').unwrap_or(text).split(';');
+        let mut next = || match params.next() {
+            Some("") | None => Some(0),
+            Some(param) => param.parse::<u32>().ok(),
+        };
+
+        let state = next()?;
+        let percent = next()?.min(100) as u8;
+        if state > 4 {
+            return None;
+        }
+        let state = ProgressState::from(state as u8);
+
+        Some(match state {
+            ProgressState::None | ProgressState::Indeterminate => Self { state, percent: 0 },
+            _ => Self { state, percent },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(
+            Progress::parse(";1;42"),
+            Some(Progress {
+                state: ProgressState::Normal,
+                percent: 42
+            })
+        );
+        assert_eq!(
+            Progress::parse(";4;200"),
+            Some(Progress {
+                state: ProgressState::Paused,
+                percent: 100
+            })
+        );
+        assert_eq!(
+            Progress::parse(";3;50"),
+            Some(Progress {
+                state: ProgressState::Indeterminate,
+                percent: 0
+            })
+        );
+        assert_eq!(Progress::parse(""), Some(Progress::default()));
+        assert_eq!(
+            Progress::parse(";2"),
+            Some(Progress {
+                state: ProgressState::Error,
+                percent: 0
+            })
+        );
+        assert!(Progress::parse(";5;10").is_none());
+        assert!(Progress::parse(";x").is_none());
+    }
+}
@@ -0,0 +1,293 @@
+#![allow(dead_code)]
+use super::character::{Character, LineProperty, LINE_DEFAULT, LINE_SEMANTIC_MARKS, LINE_WRAPPED};
+
+/// A physical line of the screen image or history.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct ReflowLine {
+    pub cells: Vec<Character>,
+    /// The properties of line, [`LINE_WRAPPED`] if the line was wrapped into the next line.
+    pub properties: LineProperty,
+}
+
+impl ReflowLine {
+    #[inline]
+    pub fn new(cells: Vec<Character>, properties: LineProperty) -> Self {
+        Self { cells, properties }
+    }
+
+    #[inline]
+    pub fn is_wrapped(&self) -> bool {
+        self.properties & LINE_WRAPPED != 0
+    }
+}
+
+/// Rewraps the physical lines written at the old columns to the new columns.
+///
+/// The lines wrapped into the next line were joined to the logical lines, then split again
+/// at the new columns, the wide characters were never split. The trailing blanks of logical
+/// line were dropped, except the ones before the anchor (usually the cursor).
+///
+/// The positions on the old lines can be mapped to the new lines by [`Reflow::map`], so the
+/// cursor and selection stay on the same logical character. The lines before the
+/// [`first line`](Reflow::set_first_line) were not reflowed and kept unchanged.
+#[derive(Debug, Default)]
+pub struct Reflow {
+    lines: Vec<ReflowLine>,
+    new_columns: usize,
+    // For each old line: (index of logical line, offset in logical line).
+    old_lines: Vec<(usize, usize)>,
+    // For each logical line: index of the first new line.
+    logical_starts: Vec<usize>,
+    // For each new line: offset in its logical line.
+    new_offsets: Vec<usize>,
+    // The number of lines before the reflowed lines.
+    first_line: usize,
+    // The number of lines dropped from the top, include the lines not reflowed.
+    dropped: usize,
+}
+
+impl Reflow {
+    /// @param anchor The (column, line) whose preceding blanks were kept.
+    pub fn new(
+        lines: Vec<ReflowLine>,
+        old_columns: usize,
+        new_columns: usize,
+        anchor: Option<(usize, usize)>,
+    ) -> Self {
+        let new_columns = new_columns.max(1);
+        let mut reflow = Self {
+            new_columns,
+            ..Default::default()
+        };
+
+        let count = lines.len();
+        let mut logical: Vec<Character> = vec![];
+        let mut properties = LINE_DEFAULT;
+        let mut start_new_logical = true;
+
+        for (i, mut line) in lines.into_iter().enumerate() {
+            if start_new_logical {
+                properties = line.properties & !LINE_WRAPPED;
+                start_new_logical = false;
+            }
+            reflow
+                .old_lines
+                .push((reflow.logical_starts.len(), logical.len()));
+
+            let wrapped = line.is_wrapped();
+            if wrapped {
+                line.cells.resize(old_columns, Character::default());
+            } else {
+                let keep = match anchor {
+                    Some((column, anchor_line)) if anchor_line == i => column.min(old_columns),
+                    _ => 0,
+                };
+                let mut len = line.cells.len();
+                while len > keep && line.cells[len - 1] == Character::default() {
+                    len -= 1;
+                }
+                line.cells.truncate(len);
+            }
+            logical.append(&mut line.cells);
+
+            // The last line may be wrapped into the lines not reflowed.
+            if !wrapped || i + 1 == count {
+                reflow.split(std::mem::take(&mut logical), properties, wrapped);
+                start_new_logical = true;
+            }
+        }
+
+        reflow
+    }
+
+    /// Split the logical line at the new columns, the last line was wrapped if `continued`.
+    fn split(&mut self, logical: Vec<Character>, properties: LineProperty, continued: bool) {
+        self.logical_starts.push(self.lines.len());
+
+        let mut start = 0;
+        loop {
+            let mut end = (start + self.new_columns).min(logical.len());
+            // The second half of wide character was the cell with data 0, move the whole
+            // wide character to the next line.
+            if end < logical.len() && end > start + 1 && logical[end].character_union.data() == 0 {
+                end -= 1;
+            }
+            let last = end >= logical.len();
+
+            let mut line_properties = if start == 0 {
+                properties
+            } else {
+                properties & !LINE_SEMANTIC_MARKS
+            };
+            if !last || continued {
+                line_properties |= LINE_WRAPPED;
+            }
+            self.new_offsets.push(start);
+            self.lines.push(ReflowLine::new(
+                logical[start..end].to_vec(),
+                line_properties,
+            ));
+
+            if last {
+                break;
+            }
+            start = end;
+        }
+    }
+
+    #[inline]
+    pub fn lines(&self) -> &[ReflowLine] {
+        &self.lines
+    }
+
+    /// Takes the reflowed lines out, the positions can still be mapped.
+    #[inline]
+    pub fn take_lines(&mut self) -> Vec<ReflowLine> {
+        std::mem::take(&mut self.lines)
+    }
+
+    /// The number of reflowed lines, include the dropped ones.
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.new_offsets.len()
+    }
+
+    /// Sets the number of lines dropped from the top, e.g. the history was full.
+    #[inline]
+    pub fn set_dropped(&mut self, dropped: usize) {
+        self.dropped = dropped
+    }
+
+    /// Sets the number of lines kept unchanged before the reflowed lines, the line numbers
+    /// passed to [`Reflow::map`] count them in.
+    #[inline]
+    pub fn set_first_line(&mut self, first_line: usize) {
+        self.first_line = first_line
+    }
+
+    #[inline]
+    pub fn first_line(&self) -> usize {
+        self.first_line
+    }
+
+    /// Map the position (`column`, `line`) on the old lines to the new lines.
+    ///
+    /// @return `None` if the line was out of range or it was dropped.
+    pub fn map(&self, column: usize, line: usize) -> Option<(usize, usize)> {
+        if line < self.first_line {
+            return Some((column, line.checked_sub(self.dropped)?));
+        }
+        let (logical, offset) = *self.old_lines.get(line - self.first_line)?;
+        let position = offset + column;
+
+        let first = self.logical_starts[logical];
+        let last = self
+            .logical_starts
+            .get(logical + 1)
+            .copied()
+            .unwrap_or(self.new_offsets.len());
+        let new_line = (first..last)
+            .rev()
+            .find(|&i| self.new_offsets[i] <= position)
+            .unwrap_or(first);
+
+        Some((
+            position - self.new_offsets[new_line],
+            (self.first_line + new_line).checked_sub(self.dropped)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::character::CharacterUnion;
+    use libc::wchar_t;
+
+    fn line(text: &str, properties: LineProperty) -> ReflowLine {
+        let cells = text
+            .chars()
+            .map(|c| {
+                let mut ch = Character::default();
+                ch.character_union = CharacterUnion::from(if c == '_' { 0 } else { c as wchar_t });
+                ch
+            })
+            .collect();
+        ReflowLine::new(cells, properties)
+    }
+
+    fn text(line: &ReflowLine) -> String {
+        line.cells
+            .iter()
+            .map(|c| match c.character_union.data() {
+                0 => '_',
+                c => char::from_u32(c as u32).unwrap(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reflow_narrower() {
+        let lines = vec![
+            line("abcdef", LINE_WRAPPED),
+            line("gh    ", LINE_DEFAULT),
+            line("xy    ", LINE_DEFAULT),
+        ];
+        let reflow = Reflow::new(lines, 6, 4, None);
+        let texts: Vec<String> = reflow.lines().iter().map(text).collect();
+        assert_eq!(texts, vec!["abcd", "efgh", "xy"]);
+        assert!(reflow.lines()[0].is_wrapped());
+        assert!(!reflow.lines()[1].is_wrapped());
+
+        // 'g' was at (0, 1), now at (2, 1); 'x' moved to the third line.
+        assert_eq!(reflow.map(0, 1), Some((2, 1)));
+        assert_eq!(reflow.map(1, 2), Some((1, 2)));
+        assert!(reflow.map(0, 3).is_none());
+    }
+
+    #[test]
+    fn test_reflow_wider() {
+        let lines = vec![
+            line("abc", LINE_WRAPPED),
+            line("def", LINE_WRAPPED),
+            line("g  ", LINE_DEFAULT),
+            line("hi ", LINE_WRAPPED),
+        ];
+        let mut reflow = Reflow::new(lines, 3, 8, Some((2, 2)));
+        let texts: Vec<String> = reflow.lines().iter().map(text).collect();
+        // The blank before anchor was kept.
+        assert_eq!(texts, vec!["abcdefg ", "hi "]);
+        // The last line was still wrapped into the following lines.
+        assert!(reflow.lines()[1].is_wrapped());
+        assert_eq!(reflow.map(2, 2), Some((8, 0)));
+        assert_eq!(reflow.map(1, 1), Some((4, 0)));
+
+        reflow.set_dropped(1);
+        assert!(reflow.map(0, 0).is_none());
+    }
+
+    #[test]
+    fn test_reflow_first_line() {
+        let lines = vec![line("abcdef", LINE_WRAPPED), line("gh", LINE_DEFAULT)];
+        let mut reflow = Reflow::new(lines, 6, 4, None);
+        reflow.set_first_line(10);
+        // The lines before the first line were kept.
+        assert_eq!(reflow.map(3, 9), Some((3, 9)));
+        // 'g' was at (0, 11), now at (2, 11).
+        assert_eq!(reflow.map(0, 11), Some((2, 11)));
+
+        reflow.set_dropped(2);
+        assert!(reflow.map(0, 1).is_none());
+        assert_eq!(reflow.map(0, 2), Some((0, 0)));
+        assert_eq!(reflow.map(0, 11), Some((2, 9)));
+    }
+
+    #[test]
+    fn test_reflow_wide_character() {
+        // The wide character `W_` can't be split at the 4th column.
+        let lines = vec![line("abcW_d", LINE_DEFAULT)];
+        let reflow = Reflow::new(lines, 6, 4, None);
+        let texts: Vec<String> = reflow.lines().iter().map(text).collect();
+        assert_eq!(texts, vec!["abc", "W_d"]);
+    }
+}
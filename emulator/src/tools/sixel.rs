@@ -0,0 +1,314 @@
+#![allow(dead_code)]
+use super::graphics::TerminalImage;
+
+/// The number of color registers, reported by `XTSMGRAPHICS`.
+pub const SIXEL_COLOR_REGISTERS: usize = 256;
+/// The maximum width and height of sixel images in pixels, reported by `XTSMGRAPHICS`.
+pub const SIXEL_MAX_GEOMETRY: u32 = 4096;
+/// The maximum repeat count of `!Pn`, to protect against the malformed data.
+const MAX_REPEAT: u32 = SIXEL_MAX_GEOMETRY;
+
+/// The default color registers of VT340, in RGB percentages.
+const VT340_PALETTE: [(u32, u32, u32); 16] = [
+    (0, 0, 0),
+    (20, 20, 80),
+    (80, 13, 13),
+    (20, 80, 20),
+    (80, 20, 80),
+    (20, 80, 80),
+    (80, 80, 20),
+    (53, 53, 53),
+    (26, 26, 26),
+    (33, 33, 60),
+    (60, 26, 26),
+    (33, 60, 33),
+    (60, 33, 60),
+    (33, 60, 60),
+    (60, 60, 33),
+    (80, 80, 80),
+];
+
+/// The opaque flag of the pixels in canvas, the unset pixels were 0.
+const OPAQUE: u32 = 0xff00_0000;
+
+/// Decodes the sixel graphics: `DCS P1 ; P2 ; P3 q <sixel data> ST`.
+///
+/// @param params The parameters `P1 ; P2 ; P3`, only `P2` (background select) was used,
+/// the pixels which were not drawn remain transparent when `P2` is 1, otherwise they were
+/// drawn with the color register 0. <br>
+/// @param data The sixel data after the final character `q`.
+///
+/// @return `None` if no pixel was drawn.
+pub fn decode_sixel(params: &str, data: &str) -> Option<TerminalImage> {
+    let transparent = params.split(';').nth(1).map_or(false, |p2| p2 == "1");
+
+    let mut decoder = SixelDecoder::new(transparent);
+    decoder.decode(data);
+    decoder.finish()
+}
+
+/// The decoder of sixel data, see the chapter 14 of the VT330/VT340 programmer reference manual.
+pub struct SixelDecoder {
+    /// The color registers, in `0xRRGGBB`.
+    palette: [u32; SIXEL_COLOR_REGISTERS],
+    /// The selected color register.
+    color: usize,
+    /// The current column of the sixel cursor.
+    x: u32,
+    /// The top row of current sixel band.
+    y: u32,
+    /// The size declared by raster attributes.
+    raster_width: u32,
+    raster_height: u32,
+    /// The extent of drawn pixels.
+    width: u32,
+    height: u32,
+    transparent: bool,
+    /// The canvas of pixels, in `0xAARRGGBB` row by row.
+    rows: Vec<Vec<u32>>,
+}
+
+impl SixelDecoder {
+    pub fn new(transparent: bool) -> Self {
+        let mut palette = [0; SIXEL_COLOR_REGISTERS];
+        for (i, (r, g, b)) in VT340_PALETTE.iter().enumerate() {
+            palette[i] = rgb_from_percent(*r, *g, *b);
+        }
+
+        Self {
+            palette,
+            color: 0,
+            x: 0,
+            y: 0,
+            raster_width: 0,
+            raster_height: 0,
+            width: 0,
+            height: 0,
+            transparent,
+            rows: vec![],
+        }
+    }
+
+    pub fn decode(&mut self, data: &str) {
+        let bytes = data.as_bytes();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let c = bytes[i];
+            i += 1;
+
+            match c {
+                b'?'..=b'~' => self.put_sixel(c, 1),
+                b'!' => {
+                    // Graphics repeat introducer: !Pn <sixel>
+                    let params = read_params(bytes, &mut i);
+                    let count = params.first().copied().unwrap_or(1).clamp(1, MAX_REPEAT);
+                    if let Some(c @ b'?'..=b'~') = bytes.get(i).copied() {
+                        i += 1;
+                        self.put_sixel(c, count);
+                    }
+                }
+                b'"' => {
+                    // Raster attributes: "Pan ; Pad ; Ph ; Pv, the aspect ratio was ignored.
+                    let params = read_params(bytes, &mut i);
+                    if let (Some(ph), Some(pv)) = (params.get(2), params.get(3)) {
+                        self.raster_width = (*ph).min(SIXEL_MAX_GEOMETRY);
+                        self.raster_height = (*pv).min(SIXEL_MAX_GEOMETRY);
+                    }
+                }
+                b'#' => {
+                    // Color introducer: #Pc to select, or #Pc ; Pu ; Px ; Py ; Pz to define.
+                    let params = read_params(bytes, &mut i);
+                    let pc = match params.first() {
+                        Some(pc) => *pc as usize % SIXEL_COLOR_REGISTERS,
+                        None => continue,
+                    };
+                    if params.len() >= 5 {
+                        let (px, py, pz) = (params[2], params[3], params[4]);
+                        match params[1] {
+                            1 => self.palette[pc] = rgb_from_hls(px, py, pz),
+                            2 => self.palette[pc] = rgb_from_percent(px, py, pz),
+                            _ => {}
+                        }
+                    }
+                    self.color = pc;
+                }
+                // Graphics carriage return.
+                b'$' => self.x = 0,
+                // Graphics new line.
+                b'-' => {
+                    self.x = 0;
+                    self.y = (self.y + 6).min(SIXEL_MAX_GEOMETRY);
+                }
+                // Control characters like the line breaks were ignored.
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the decoded image, `None` if nothing was drawn.
+    pub fn finish(self) -> Option<TerminalImage> {
+        let width = self.width.max(self.raster_width);
+        let height = self.height.max(self.raster_height);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let background = if self.transparent {
+            0
+        } else {
+            OPAQUE | self.palette[0]
+        };
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height as usize {
+            let row = self.rows.get(y);
+            for x in 0..width as usize {
+                let argb = match row.and_then(|row| row.get(x)) {
+                    Some(argb) if *argb != 0 => *argb,
+                    _ => background,
+                };
+                pixels.extend_from_slice(&[
+                    (argb >> 16) as u8,
+                    (argb >> 8) as u8,
+                    argb as u8,
+                    (argb >> 24) as u8,
+                ]);
+            }
+        }
+
+        Some(TerminalImage::new(width, height, pixels))
+    }
+
+    /// Draws the sixel `c` (the six vertical pixels) `count` times with the selected color.
+    fn put_sixel(&mut self, c: u8, count: u32) {
+        let bits = c - b'?';
+        let end = (self.x + count).min(SIXEL_MAX_GEOMETRY);
+
+        if bits != 0 && end > self.x {
+            let argb = OPAQUE | self.palette[self.color];
+
+            for bit in 0..6 {
+                if bits & (1 << bit) == 0 {
+                    continue;
+                }
+                let y = self.y + bit;
+                if y >= SIXEL_MAX_GEOMETRY {
+                    break;
+                }
+
+                if self.rows.len() <= y as usize {
+                    self.rows.resize(y as usize + 1, vec![]);
+                }
+                let row = &mut self.rows[y as usize];
+                if row.len() < end as usize {
+                    row.resize(end as usize, 0);
+                }
+                row[self.x as usize..end as usize].fill(argb);
+
+                self.height = self.height.max(y + 1);
+            }
+            self.width = self.width.max(end);
+        }
+
+        self.x = end;
+    }
+}
+
+/// Reads the numeric parameters separated by `;` from `bytes` at `i`, the omitted parameters were 0.
+fn read_params(bytes: &[u8], i: &mut usize) -> Vec<u32> {
+    let mut params = vec![];
+    let mut current: Option<u32> = None;
+
+    while let Some(c) = bytes.get(*i) {
+        match c {
+            b'0'..=b'9' => {
+                let value = current.unwrap_or_default();
+                current = Some(value.saturating_mul(10).saturating_add((c - b'0') as u32));
+            }
+            b';' => params.push(current.take().unwrap_or_default()),
+            _ => break,
+        }
+        *i += 1;
+    }
+    if let Some(value) = current {
+        params.push(value);
+    }
+
+    params
+}
+
+#[inline]
+fn rgb_from_percent(r: u32, g: u32, b: u32) -> u32 {
+    let component = |p: u32| (p.min(100) * 255 + 50) / 100;
+    (component(r) << 16) | (component(g) << 8) | component(b)
+}
+
+/// Converts the HLS color to RGB, note that the hue angle of blue was 0 degree in DEC's HLS,
+/// red was 120 and green was 240.
+fn rgb_from_hls(h: u32, l: u32, s: u32) -> u32 {
+    let h = ((h % 360 + 240) % 360) as f32;
+    let l = l.min(100) as f32 / 100.;
+    let s = s.min(100) as f32 / 100.;
+
+    let c = (1. - (2. * l - 1.).abs()) * s;
+    let x = c * (1. - ((h / 60.) % 2. - 1.).abs());
+    let m = l - c / 2.;
+
+    let (r, g, b) = match h as u32 / 60 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    let component = |v: f32| ((v + m) * 255.).round() as u32;
+
+    (component(r) << 16) | (component(g) << 8) | component(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_sixel() {
+        // Two columns of red, and one column of blue with only the top pixel.
+        let image = decode_sixel("0;1;0", "#1;2;100;0;0#1!2~#2;2;0;0;100#2@").unwrap();
+        assert_eq!(image.width(), 3);
+        assert_eq!(image.height(), 6);
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 5), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(2, 0), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(2, 1), [0, 0, 0, 0]);
+
+        // Not transparent, the background was filled with color register 0.
+        let image = decode_sixel("0;0;0", "#0;2;0;100;0#1;2;100;0;0#1?~").unwrap();
+        assert_eq!(image.pixel(0, 0), [0, 255, 0, 255]);
+        assert_eq!(image.pixel(1, 0), [255, 0, 0, 255]);
+
+        // Graphics new line and carriage return.
+        let image = decode_sixel("0;1", "#1;2;100;100;100~-$@").unwrap();
+        assert_eq!(image.width(), 1);
+        assert_eq!(image.height(), 7);
+        assert_eq!(image.pixel(0, 6), [255, 255, 255, 255]);
+
+        // Raster attributes declare the size of image.
+        let image = decode_sixel("", "\"1;1;10;12#1@").unwrap();
+        assert_eq!(image.width(), 10);
+        assert_eq!(image.height(), 12);
+
+        assert!(decode_sixel("0;1", "").is_none());
+        assert!(decode_sixel("0;1", "#1???").is_none());
+    }
+
+    #[test]
+    fn test_sixel_colors() {
+        assert_eq!(rgb_from_percent(100, 0, 50), 0xff0080);
+        assert_eq!(rgb_from_hls(0, 50, 100), 0x0000ff);
+        assert_eq!(rgb_from_hls(120, 50, 100), 0xff0000);
+        assert_eq!(rgb_from_hls(240, 50, 100), 0x00ff00);
+        assert_eq!(rgb_from_hls(0, 100, 0), 0xffffff);
+    }
+}
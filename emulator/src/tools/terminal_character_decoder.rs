@@ -7,9 +7,8 @@ use super::{
 use crate::{
     core::uwchar_t,
     tools::{
-        character::{RE_BOLD, RE_UNDERLINE},
+        character::{ExtendedCharTable, RE_BOLD, RE_EXTEND_CHAR, RE_UNDERLINE},
         character_color::FontWeight,
-        system_ffi::wcwidth,
     },
 };
 use libc::wchar_t;
@@ -155,11 +154,30 @@ impl<'a> TerminalCharacterDecoder<'a> for PlainTextDecoder<'a> {
             if i >= output_count {
                 break;
             }
-            #[cfg(not(target_os = "windows"))]
-            plain_text.push_slice([character[i as usize].character_union.data() as u32]);
-            #[cfg(target_os = "windows")]
-            plain_text.push_slice([character[i as usize].character_union.data()]);
-            i += 1.max(wcwidth(character[i as usize].character_union.data()));
+            let ch = &character[i as usize];
+            if ch.rendition & RE_EXTEND_CHAR != 0 {
+                // The grapheme cluster stored in the extended char table.
+                let mut len = 0;
+                let points = ExtendedCharTable::instance()
+                    .lookup_extended_char(ch.character_union.data(), &mut len)
+                    .unwrap_or_default();
+                #[cfg(not(target_os = "windows"))]
+                plain_text.push_slice(points.iter().map(|&c| c as u32).collect::<Vec<_>>());
+                #[cfg(target_os = "windows")]
+                plain_text.push_slice(&points);
+            } else {
+                #[cfg(not(target_os = "windows"))]
+                plain_text.push_slice([ch.character_union.data() as u32]);
+                #[cfg(target_os = "windows")]
+                plain_text.push_slice([ch.character_union.data()]);
+            }
+
+            // Skip the cells occupied by the wide character, the width was decided by
+            // the screen when the character was displayed.
+            i += 1;
+            while i < output_count && character[i as usize].character_union.data() == 0 {
+                i += 1;
+            }
         }
         output.append(&plain_text.to_string().expect(TRANSMIT_U16STRING_ERROR))
     }
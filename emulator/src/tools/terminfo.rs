@@ -0,0 +1,172 @@
+#![allow(dead_code)]
+
+/// The terminal name reported by `XTGETTCAP` as the `TN` capability.
+pub const TERMINAL_NAME: &str = "xterm-256color";
+
+/// The built-in terminfo capabilities answered by `XTGETTCAP`, the boolean capabilities
+/// have empty values.
+const CAPABILITIES: &[(&str, &str)] = &[
+    // Booleans.
+    ("am", ""),
+    ("bce", ""),
+    ("km", ""),
+    ("mir", ""),
+    ("msgr", ""),
+    ("xenl", ""),
+    ("AX", ""),
+    ("XT", ""),
+    ("Tc", ""),
+    // Numbers.
+    ("colors", "256"),
+    ("Co", "256"),
+    ("it", "8"),
+    ("pairs", "65536"),
+    // Strings.
+    ("TN", TERMINAL_NAME),
+    ("bel", "\x07"),
+    ("blink", "\x1b[5m"),
+    ("bold", "\x1b[1m"),
+    ("dim", "\x1b[2m"),
+    ("sitm", "\x1b[3m"),
+    ("ritm", "\x1b[23m"),
+    ("smul", "\x1b[4m"),
+    ("rmul", "\x1b[24m"),
+    ("rev", "\x1b[7m"),
+    ("invis", "\x1b[8m"),
+    ("smxx", "\x1b[9m"),
+    ("rmxx", "\x1b[29m"),
+    ("smso", "\x1b[7m"),
+    ("rmso", "\x1b[27m"),
+    ("sgr0", "\x1b(B\x1b[m"),
+    ("op", "\x1b[39;49m"),
+    (
+        "setaf",
+        "\x1b[%?%p1%{8}%<%t3%p1%d%e%p1%{16}%<%t9%p1%{8}%-%d%e38;5;%p1%d%;m",
+    ),
+    (
+        "setab",
+        "\x1b[%?%p1%{8}%<%t4%p1%d%e%p1%{16}%<%t10%p1%{8}%-%d%e48;5;%p1%d%;m",
+    ),
+    ("setrgbf", "\x1b[38:2:%p1%d:%p2%d:%p3%dm"),
+    ("setrgbb", "\x1b[48:2:%p1%d:%p2%d:%p3%dm"),
+    ("Smulx", "\x1b[4:%p1%dm"),
+    (
+        "Setulc",
+        "\x1b[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%dm",
+    ),
+    ("Ss", "\x1b[%p1%d q"),
+    ("Se", "\x1b[2 q"),
+    ("Ms", "\x1b]52;%p1%s;%p2%s\x07"),
+    ("Sync", "\x1b[?2026%?%p1%{1}%-%tl%eh%;"),
+    ("BE", "\x1b[?2004h"),
+    ("BD", "\x1b[?2004l"),
+    ("PS", "\x1b[200~"),
+    ("PE", "\x1b[201~"),
+    ("tsl", "\x1b]2;"),
+    ("fsl", "\x07"),
+    ("cup", "\x1b[%i%p1%d;%p2%dH"),
+    ("home", "\x1b[H"),
+    ("clear", "\x1b[H\x1b[2J"),
+    ("ed", "\x1b[J"),
+    ("el", "\x1b[K"),
+    ("el1", "\x1b[1K"),
+    ("civis", "\x1b[?25l"),
+    ("cnorm", "\x1b[?25h"),
+    ("smcup", "\x1b[?1049h"),
+    ("rmcup", "\x1b[?1049l"),
+    ("smkx", "\x1b[?1h\x1b="),
+    ("rmkx", "\x1b[?1l\x1b>"),
+    ("csr", "\x1b[%i%p1%d;%p2%dr"),
+    ("ich", "\x1b[%p1%d@"),
+    ("dch", "\x1b[%p1%dP"),
+    ("il", "\x1b[%p1%dL"),
+    ("dl", "\x1b[%p1%dM"),
+    ("ech", "\x1b[%p1%dX"),
+    ("indn", "\x1b[%p1%dS"),
+    ("rin", "\x1b[%p1%dT"),
+    // Keys.
+    ("kbs", "\x7f"),
+    ("kcuu1", "\x1bOA"),
+    ("kcud1", "\x1bOB"),
+    ("kcuf1", "\x1bOC"),
+    ("kcub1", "\x1bOD"),
+    ("khome", "\x1bOH"),
+    ("kend", "\x1bOF"),
+    ("kich1", "\x1b[2~"),
+    ("kdch1", "\x1b[3~"),
+    ("kpp", "\x1b[5~"),
+    ("knp", "\x1b[6~"),
+    ("kcbt", "\x1b[Z"),
+    ("kf1", "\x1bOP"),
+    ("kf2", "\x1bOQ"),
+    ("kf3", "\x1bOR"),
+    ("kf4", "\x1bOS"),
+    ("kf5", "\x1b[15~"),
+    ("kf6", "\x1b[17~"),
+    ("kf7", "\x1b[18~"),
+    ("kf8", "\x1b[19~"),
+    ("kf9", "\x1b[20~"),
+    ("kf10", "\x1b[21~"),
+    ("kf11", "\x1b[23~"),
+    ("kf12", "\x1b[24~"),
+];
+
+/// Returns the value of terminfo capability `name`, `None` if it was unknown.
+pub fn get_capability(name: &str) -> Option<&'static str> {
+    CAPABILITIES
+        .iter()
+        .find(|(capability, _)| *capability == name)
+        .map(|(_, value)| *value)
+}
+
+/// Answers the XTGETTCAP request: `DCS + q <hex name> ; <hex name> ... ST`.
+///
+/// Each capability was answered by `DCS 1 + r <hex name> = <hex value> ST`, or
+/// `DCS 0 + r <hex name> ST` if it was unknown.
+pub fn xtgettcap_response(request: &str) -> String {
+    let mut response = String::new();
+
+    for hex_name in request.split(';').filter(|name| !name.is_empty()) {
+        let value = hex::decode(hex_name)
+            .ok()
+            .and_then(|name| String::from_utf8(name).ok())
+            .and_then(|name| get_capability(&name));
+
+        match value {
+            Some(value) => response.push_str(&format!(
+                "\u{001b}P1+r{}={}\u{001b}\\",
+                hex_name,
+                hex::encode(value)
+            )),
+            None => response.push_str(&format!("\u{001b}P0+r{}\u{001b}\\", hex_name)),
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xtgettcap() {
+        assert_eq!(get_capability("colors"), Some("256"));
+        assert_eq!(get_capability("Tc"), Some(""));
+        assert!(get_capability("unknown").is_none());
+
+        // "TN" and "Co"
+        assert_eq!(
+            xtgettcap_response("544e;436f"),
+            format!(
+                "\u{001b}P1+r544e={}\u{001b}\\\u{001b}P1+r436f=323536\u{001b}\\",
+                hex::encode(TERMINAL_NAME)
+            )
+        );
+        // Unknown capability and malformed hex.
+        assert_eq!(
+            xtgettcap_response("7a7a;xyz"),
+            "\u{001b}P0+r7a7a\u{001b}\\\u{001b}P0+rxyz\u{001b}\\"
+        );
+    }
+}
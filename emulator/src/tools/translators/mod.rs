@@ -47,6 +47,16 @@ fn one_or_zero(value: bool) -> u8 {
     }
 }
 
+/// Get the xterm style modifier parameter, `1 + (shift | alt << 1 | ctrl << 2)`.
+#[inline]
+fn xterm_modifier_value(modifiers: KeyboardModifier) -> u8 {
+    let mut modifier_value = 1u8;
+    modifier_value += one_or_zero(modifiers.has(KeyboardModifier::ShiftModifier));
+    modifier_value += one_or_zero(modifiers.has(KeyboardModifier::AltModifier)) << 1;
+    modifier_value += one_or_zero(modifiers.has(CTRL_MODIFIER)) << 2;
+    modifier_value
+}
+
 #[inline]
 fn is_printable_char(ch: u8) -> bool {
     (32..127).contains(&ch)
@@ -235,6 +245,72 @@ impl AsNumeric<u16> for Command {
 }
 implements_enum_value!(Command, u16);
 
+/// The levels of xterm's modifyOtherKeys mode, set by `CSI > 4 ; level m`.
+///
+/// When enabled, the modified keys were sent as `CSI 27 ; modifier ; code ~`.
+#[repr(u8)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ModifyOtherKeys {
+    #[default]
+    Disabled = 0,
+    /// Only the modified keys which have no well-known behavior were encoded,
+    /// e.g. `Ctrl+Shift+A` and `Ctrl+1`, `Ctrl+A` still sends the control character.
+    Level1,
+    /// All the modified keys were encoded, except the ones only modified by `Shift`.
+    Level2,
+}
+impl ModifyOtherKeys {
+    #[inline]
+    pub fn from_level(level: i32) -> Self {
+        match level {
+            1 => Self::Level1,
+            2 => Self::Level2,
+            _ => Self::Disabled,
+        }
+    }
+
+    #[inline]
+    pub fn level(&self) -> i32 {
+        *self as i32
+    }
+
+    /// Get the `CSI 27 ; modifier ; code ~` sequence of the key.
+    ///
+    /// @return `None` if the key should be sent by the translator entries.
+    pub fn encode(&self, key_code: u32, modifiers: KeyboardModifier) -> Option<Vec<u8>> {
+        if *self == Self::Disabled {
+            return None;
+        }
+        let shift = modifiers.has(KeyboardModifier::ShiftModifier);
+        let alt = modifiers.has(KeyboardModifier::AltModifier);
+        let ctrl = modifiers.has(CTRL_MODIFIER);
+
+        let code = match key_code {
+            x if x == KeyCode::KeyReturn as u32 || x == KeyCode::KeyEnter as u32 => 13,
+            x if x == KeyCode::KeyTab as u32 => 9,
+            x if x == KeyCode::KeyBackspace as u32 => 127,
+            x if x == KeyCode::KeyEscape as u32 => 27,
+            // The letters' key code were upper case.
+            0x41..=0x5a if !shift => key_code + 0x20,
+            0x20..=0x7e => key_code,
+            _ => return None,
+        };
+
+        let modify = match self {
+            Self::Disabled => false,
+            // Ctrl+@ to Ctrl+_ have the well-known control characters.
+            Self::Level1 => ctrl && (shift || !(0x40..=0x5f).contains(&key_code)),
+            Self::Level2 => ctrl || alt || (shift && (code == 13 || code == 27 || code == 127)),
+        };
+        if !modify {
+            return None;
+        }
+
+        let modifier_value = xterm_modifier_value(modifiers);
+        Some(format!("\x1b[27;{};{}~", modifier_value, code).into_bytes())
+    }
+}
+
 /// Represents an association between a key sequence pressed by the user
 /// and the character sequence and commands associated with it for a particular KeyboardTranslator.
 #[derive(Debug, PartialEq, Eq)]
@@ -312,10 +388,7 @@ impl Entry {
         let mut expand_text = self.text.clone();
 
         if expand_wild_cards {
-            let mut modifier_value = 1u8;
-            modifier_value += one_or_zero(modifiers.has(KeyboardModifier::ShiftModifier));
-            modifier_value += one_or_zero(modifiers.has(KeyboardModifier::AltModifier)) << 1;
-            modifier_value += one_or_zero(modifiers.has(CTRL_MODIFIER)) << 2;
+            let modifier_value = xterm_modifier_value(modifiers);
 
             // for i in 0..self.text.len() {
             for et in expand_text.iter_mut().take(self.text.len()) {
@@ -713,12 +786,25 @@ impl KeyboardTranslator {
     /// @param keyCode A key code from the Qt::Key enum
     /// @param modifiers A combination of modifiers
     /// @param state Optional flags which specify the current state of the terminal
+    /// @param modify_other_keys The xterm's modifyOtherKeys level, the matched modified keys were
+    /// encoded as `CSI 27 ; modifier ; code ~` regardless of the entries.
     pub fn find_entry(
         &self,
         key_code: u32,
         modifiers: KeyboardModifier,
         state: Option<State>,
+        modify_other_keys: ModifyOtherKeys,
     ) -> Rc<Entry> {
+        if let Some(text) = modify_other_keys.encode(key_code, modifiers) {
+            let mut entry = Entry::new();
+            entry.key_code = key_code;
+            entry.modifiers = modifiers;
+            entry.modifier_mask = modifiers;
+            entry.text = text;
+            entry.is_null = false;
+            return Rc::new(entry);
+        }
+
         let state = state.unwrap_or(State::None);
         for it in self.entries.iter() {
             if *it.0 == key_code {
@@ -776,7 +862,8 @@ impl KeyboardTranslator {
 
 #[cfg(test)]
 mod tests {
-    use super::{KEY_REGEX, TITLE_REGEX};
+    use super::{KeyboardTranslator, ModifyOtherKeys, CTRL_MODIFIER, KEY_REGEX, TITLE_REGEX};
+    use tmui::tlib::namespace::{KeyCode, KeyboardModifier};
 
     #[test]
     fn test_regex() {
@@ -795,4 +882,32 @@ mod tests {
         let s = caps.get(3).unwrap().as_str();
         println!("3: {}", s);
     }
+
+    #[test]
+    fn test_modify_other_keys() {
+        let translator = KeyboardTranslator::new("test");
+        let ctrl_shift = CTRL_MODIFIER.or(KeyboardModifier::ShiftModifier);
+        let text = |key: KeyCode, modifiers: KeyboardModifier, level: i32| {
+            translator
+                .find_entry(
+                    key as u32,
+                    modifiers,
+                    None,
+                    ModifyOtherKeys::from_level(level),
+                )
+                .text(None, None)
+        };
+
+        assert!(text(KeyCode::KeyA, ctrl_shift, 0).is_empty());
+        assert_eq!(text(KeyCode::KeyA, ctrl_shift, 1), b"\x1b[27;6;65~");
+        assert!(text(KeyCode::KeyA, CTRL_MODIFIER, 1).is_empty());
+        assert_eq!(text(KeyCode::KeyA, CTRL_MODIFIER, 2), b"\x1b[27;5;97~");
+        assert_eq!(text(KeyCode::Key1, CTRL_MODIFIER, 1), b"\x1b[27;5;49~");
+        assert_eq!(
+            text(KeyCode::KeyReturn, KeyboardModifier::ShiftModifier, 2),
+            b"\x1b[27;2;13~"
+        );
+        assert!(text(KeyCode::KeyA, KeyboardModifier::ShiftModifier, 2).is_empty());
+        assert!(text(KeyCode::KeyUp, CTRL_MODIFIER, 2).is_empty());
+    }
 }
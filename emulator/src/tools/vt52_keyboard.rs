@@ -0,0 +1,98 @@
+#![allow(dead_code)]
+use super::translators::CTRL_MODIFIER;
+use tmui::tlib::namespace::{KeyCode, KeyboardModifier};
+
+/// Encode the key event in VT52 mode (DECANM reset):
+/// - The cursor keys send `ESC A`, `ESC B`, `ESC C` and `ESC D`.
+/// - The function keys F1-F4 were the PF1-PF4 keys, send `ESC P`, `ESC Q`, `ESC R` and `ESC S`.
+/// - The keypad keys send `ESC ? x` in the alternate keypad mode (`ESC =`).
+///
+/// @param text: The text generated by the key event.
+/// @param keypad: Whether the key was on the keypad.
+/// @param app_keypad: Whether the alternate keypad mode was enabled.
+/// @return `None` if the key has no VT52 specific encoding, or it was pressed with
+/// modifiers, it should be sent by the keyboard translator.
+pub fn encode_vt52_key(
+    key_code: KeyCode,
+    text: &str,
+    modifiers: KeyboardModifier,
+    keypad: bool,
+    app_keypad: bool,
+) -> Option<&'static str> {
+    if modifiers.has(KeyboardModifier::ShiftModifier)
+        || modifiers.has(KeyboardModifier::AltModifier)
+        || modifiers.has(CTRL_MODIFIER)
+    {
+        return None;
+    }
+
+    let sequence = match key_code {
+        KeyCode::KeyUp => "\x1bA",
+        KeyCode::KeyDown => "\x1bB",
+        KeyCode::KeyRight => "\x1bC",
+        KeyCode::KeyLeft => "\x1bD",
+        KeyCode::KeyF1 => "\x1bP",
+        KeyCode::KeyF2 => "\x1bQ",
+        KeyCode::KeyF3 => "\x1bR",
+        KeyCode::KeyF4 => "\x1bS",
+        KeyCode::KeyEnter if keypad && app_keypad => "\x1b?M",
+        _ if keypad && app_keypad => match text {
+            "0" => "\x1b?p",
+            "1" => "\x1b?q",
+            "2" => "\x1b?r",
+            "3" => "\x1b?s",
+            "4" => "\x1b?t",
+            "5" => "\x1b?u",
+            "6" => "\x1b?v",
+            "7" => "\x1b?w",
+            "8" => "\x1b?x",
+            "9" => "\x1b?y",
+            // The PC keypad has no comma key, the `+` was placed at the same position.
+            "," | "+" => "\x1b?l",
+            "-" => "\x1b?m",
+            "." => "\x1b?n",
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(sequence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_vt52_key() {
+        let none = KeyboardModifier::NoModifier;
+        assert_eq!(
+            encode_vt52_key(KeyCode::KeyUp, "", none, false, false),
+            Some("\x1bA")
+        );
+        assert_eq!(
+            encode_vt52_key(KeyCode::KeyF4, "", none, false, false),
+            Some("\x1bS")
+        );
+        assert_eq!(
+            encode_vt52_key(KeyCode::Key5, "5", none, true, true),
+            Some("\x1b?u")
+        );
+        assert_eq!(
+            encode_vt52_key(KeyCode::KeyEnter, "\r", none, true, true),
+            Some("\x1b?M")
+        );
+        // Numeric keypad mode.
+        assert_eq!(encode_vt52_key(KeyCode::Key5, "5", none, true, false), None);
+        assert_eq!(encode_vt52_key(KeyCode::Key5, "5", none, false, true), None);
+        assert_eq!(
+            encode_vt52_key(
+                KeyCode::KeyUp,
+                "",
+                KeyboardModifier::ShiftModifier,
+                false,
+                false
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,196 @@
+#![allow(dead_code)]
+use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
+
+/// The host name of local machine, `None` if it was unavailable.
+static LOCAL_HOSTNAME: Lazy<Option<String>> = Lazy::new(local_hostname);
+
+/// The current working directory reported by the `OSC 7` escape sequence:
+///
+/// `ESC ] 7 ; file://host/path ST`
+///
+/// The path was percent-encoded, `host` may be empty which means the local host.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct WorkingDirectory {
+    /// The host name of the shell, empty when it was not reported.
+    pub host: String,
+    /// The decoded absolute path of current directory.
+    pub path: String,
+}
+
+impl WorkingDirectory {
+    /// Parse the text part of `OSC 7` sequence(the text after `7;`).
+    ///
+    /// @return `None` if the text was not a valid `file` url.
+    pub fn parse(text: &str) -> Option<Self> {
+        let url = text.strip_prefix("file://")?;
+        let (host, path) = match url.find('/') {
+            Some(idx) => url.split_at(idx),
+            None => return None,
+        };
+
+        Some(Self {
+            host: host.to_string(),
+            path: percent_decode(path)?,
+        })
+    }
+
+    /// Whether the directory was on the local machine, the host was empty, `localhost`
+    /// or the host name of local machine.
+    pub fn is_local(&self) -> bool {
+        if self.host.is_empty() || self.host.eq_ignore_ascii_case("localhost") {
+            return true;
+        }
+        LOCAL_HOSTNAME
+            .as_deref()
+            .is_some_and(|local| same_host(&self.host, local))
+    }
+
+    /// Convert the path to the local path, on windows the url path like `/C:/Users`
+    /// should drop the leading slash.
+    pub fn local_path(&self) -> PathBuf {
+        let path = self.path.as_str();
+        let bytes = path.as_bytes();
+        if cfg!(target_os = "windows")
+            && bytes.len() >= 3
+            && bytes[0] == b'/'
+            && bytes[1].is_ascii_alphabetic()
+            && bytes[2] == b':'
+        {
+            PathBuf::from(&path[1..])
+        } else {
+            PathBuf::from(path)
+        }
+    }
+}
+
+/// Quote the path as one word of POSIX shell, the single quotes in path were escaped.
+pub fn sh_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+/// Quote the path as one argument of `cmd.exe`, the metacharacters like `&` were kept
+/// in the double quotes, and the `%` was escaped outside of them to avoid the expansion.
+///
+/// @return `None` if the path contained the double quote, which was not allowed in the
+///         windows path and can not be escaped.
+pub fn cmd_quote(path: &Path) -> Option<String> {
+    let path = path.to_string_lossy();
+    if path.contains('"') {
+        return None;
+    }
+    let quoted = path
+        .split('%')
+        .map(|part| format!("\"{}\"", part))
+        .collect::<Vec<_>>()
+        .join("^%");
+    Some(quoted)
+}
+
+/// Whether the host names referred to the same host, the domain part was ignored if
+/// one of them was not fully qualified.
+fn same_host(a: &str, b: &str) -> bool {
+    if a.eq_ignore_ascii_case(b) {
+        return true;
+    }
+    if a.contains('.') && b.contains('.') {
+        return false;
+    }
+    let short = |host: &str| {
+        host.split('.')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    short(a) == short(b)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn local_hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+#[cfg(target_os = "windows")]
+fn local_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+/// Decode the percent-encoded text, return `None` if the escapes were malformed
+/// or the decoded bytes were not valid utf-8.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let dir = WorkingDirectory::parse("file://localhost/home/user/my%20dir").unwrap();
+        assert_eq!(dir.host, "localhost");
+        assert_eq!(dir.path, "/home/user/my dir");
+
+        let dir = WorkingDirectory::parse("file:///tmp").unwrap();
+        assert_eq!(dir.host, "");
+        assert_eq!(dir.path, "/tmp");
+
+        let dir = WorkingDirectory::parse("file://host/%E4%BD%A0%E5%A5%BD").unwrap();
+        assert_eq!(dir.path, "/你好");
+
+        assert!(WorkingDirectory::parse("http://host/tmp").is_none());
+        assert!(WorkingDirectory::parse("file://host").is_none());
+        assert!(WorkingDirectory::parse("file://host/tmp%2").is_none());
+        assert!(WorkingDirectory::parse("file://host/tmp%zz").is_none());
+    }
+
+    #[test]
+    fn test_is_local() {
+        assert!(WorkingDirectory::parse("file:///tmp").unwrap().is_local());
+        assert!(WorkingDirectory::parse("file://LocalHost/tmp")
+            .unwrap()
+            .is_local());
+        if let Some(host) = LOCAL_HOSTNAME.as_deref() {
+            let url = format!("file://{}/tmp", host);
+            assert!(WorkingDirectory::parse(&url).unwrap().is_local());
+        }
+
+        assert!(same_host("devbox", "DevBox.example.com"));
+        assert!(!same_host("devbox.a.com", "devbox.b.com"));
+        assert!(!same_host("devbox", "remote"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(sh_quote(Path::new("/tmp/my dir")), "'/tmp/my dir'");
+        assert_eq!(sh_quote(Path::new("/tmp/it's")), "'/tmp/it'\\''s'");
+
+        assert_eq!(cmd_quote(Path::new(r"C:\a & b")).unwrap(), r#""C:\a & b""#);
+        assert_eq!(
+            cmd_quote(Path::new(r"C:\100%dir%")).unwrap(),
+            r#""C:\100"^%"dir"^%"""#
+        );
+        assert!(cmd_quote(Path::new(r#"C:\"a""#)).is_none());
+    }
+}
@@ -0,0 +1,46 @@
+use tmui::{
+    input::checkbox::Checkbox,
+    input::Input,
+    label::Label,
+    prelude::*,
+    tlib::object::{ObjectImpl, ObjectSubclass},
+    widget::WidgetImpl,
+};
+
+#[extends(Widget, Layout(HBox))]
+#[derive(Childrenable)]
+pub struct CheckBundle {
+    #[children]
+    label: Tr<Label>,
+
+    #[children]
+    checkbox: Tr<Checkbox>,
+}
+
+impl ObjectSubclass for CheckBundle {
+    const NAME: &'static str = "CheckBundle";
+}
+
+impl ObjectImpl for CheckBundle {}
+
+impl WidgetImpl for CheckBundle {}
+
+impl CheckBundle {
+    #[inline]
+    pub fn new(label: &str) -> Tr<Self> {
+        let mut cb = Self::new_alloc();
+        cb.label.set_margin_top(3);
+        cb.label.set_text(label);
+        cb
+    }
+
+    #[inline]
+    pub fn set_spacing(&mut self, spacing: i32) {
+        self.checkbox.set_margin_left(spacing);
+    }
+
+    #[inline]
+    pub fn value(&self) -> bool {
+        self.checkbox.value()
+    }
+}
@@ -1,3 +1,5 @@
+pub mod check_bundle;
 pub mod number_bundle;
 pub mod password_bundle;
+pub mod progress_indicator;
 pub mod text_bundle;
\ No newline at end of file
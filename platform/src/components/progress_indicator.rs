@@ -0,0 +1,77 @@
+use emulator::core::session::{Progress, ProgressState};
+use tmui::{
+    prelude::*,
+    tlib::object::{ObjectImpl, ObjectSubclass},
+    widget::WidgetImpl,
+};
+
+/// The bar shows the progress reported by the terminal program (`OSC 9;4`),
+/// it was hidden when there was no progress.
+#[extends(Widget)]
+pub struct ProgressIndicator {
+    progress: Progress,
+}
+
+impl ObjectSubclass for ProgressIndicator {
+    const NAME: &'static str = "ProgressIndicator";
+}
+
+impl ObjectImpl for ProgressIndicator {
+    fn initialize(&mut self) {
+        self.set_vexpand(true);
+        self.hide();
+    }
+}
+
+impl WidgetImpl for ProgressIndicator {
+    fn paint(&mut self, painter: &mut Painter) {
+        let rect = self.contents_rect_f(Some(Coordinate::Widget));
+        painter.fill_rect(rect, Color::GREY_LIGHT);
+        paint_progress(painter, rect, self.progress);
+    }
+}
+
+/// Fill the part of `rect` finished by the progress, in the color of progress state.
+pub fn paint_progress(painter: &mut Painter, rect: FRect, progress: Progress) {
+    let (color, ratio) = match progress.state {
+        ProgressState::None => return,
+        ProgressState::Normal => (Color::rgb(80, 180, 80), progress.percent),
+        // The error without progress fills the whole bar.
+        ProgressState::Error if progress.percent == 0 => (Color::rgb(220, 70, 70), 100),
+        ProgressState::Error => (Color::rgb(220, 70, 70), progress.percent),
+        // The progress was unknown, fill the whole bar.
+        ProgressState::Indeterminate => (Color::rgb(100, 150, 230), 100),
+        ProgressState::Paused => (Color::rgb(230, 180, 60), progress.percent),
+    };
+    let width = rect.width() * ratio as f32 / 100.;
+    painter.fill_rect(
+        FRect::new(rect.left(), rect.top(), width, rect.height()),
+        color,
+    );
+}
+
+impl ProgressIndicator {
+    #[inline]
+    pub fn new() -> Tr<Self> {
+        Self::new_alloc()
+    }
+
+    #[inline]
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    pub fn set_progress(&mut self, progress: Progress) {
+        if self.progress == progress {
+            return;
+        }
+        self.progress = progress;
+
+        if progress.state == ProgressState::None {
+            self.hide();
+        } else {
+            self.show();
+            self.update();
+        }
+    }
+}
@@ -1,9 +1,22 @@
 use super::left_panel::LeftPanel;
-use emulator::core::terminal_emulator::TerminalEmulator;
+use crate::ui::{
+    clipboard_confirm::ClipboardConfirmWindow,
+    ctx_menu::{
+        menu_selection::{CtxMenuSelectionCreator, MenuSelection},
+        selection_bld::CtxMenuLoc,
+        selection_enum::SelectionEnum,
+        CtxMenu,
+    },
+};
+use cli::session::SessionPropsId;
+use emulator::{core::terminal_emulator::TerminalEmulator, tools::filter::FilterObject};
 use tmui::{
+    popup::Popupable,
     prelude::*,
+    tlib::connect,
     tlib::namespace::Orientation,
     tlib::object::{ObjectImpl, ObjectSubclass},
+    views::list_view::list_node::ListNode,
     widget::WidgetImpl,
 };
 
@@ -26,7 +39,75 @@ impl ObjectImpl for CentralPanel {
         self.set_hexpand(true);
         self.set_vexpand(true);
         self.set_strict_children_layout(true);
+
+        connect!(
+            self.terminal,
+            session_clipboard_access_request(),
+            self,
+            on_clipboard_access_request(SessionPropsId, u64, bool)
+        );
+        connect!(
+            self.terminal,
+            session_filter_actions_request(),
+            self,
+            on_filter_actions_request(SessionPropsId, Point)
+        );
+
+        self.add_popup(CtxMenu::new(CtxMenuLoc::TerminalView).to_dyn_popup_tr());
     }
 }
 
 impl WidgetImpl for CentralPanel {}
+
+impl CentralPanel {
+    #[inline]
+    fn on_clipboard_access_request(&mut self, id: SessionPropsId, request_id: u64, read: bool) {
+        ClipboardConfirmWindow::open(self.window(), id, request_id, read);
+    }
+
+    #[inline]
+    fn on_filter_actions_request(&mut self, id: SessionPropsId, position: Point) {
+        self.show_popup(position);
+        let popup = self.get_popup_mut().unwrap();
+        popup.set_property(PROP_SESSION_ID, id.to_value());
+    }
+
+    #[inline]
+    pub fn open_link_pressed(ctx_menu: &mut CtxMenu, _node: &mut ListNode) {
+        Self::activate_filter_action(ctx_menu, FilterObject::ACTION_OPEN)
+    }
+
+    #[inline]
+    pub fn copy_link_pressed(ctx_menu: &mut CtxMenu, _node: &mut ListNode) {
+        Self::activate_filter_action(ctx_menu, FilterObject::ACTION_COPY)
+    }
+
+    fn activate_filter_action(ctx_menu: &mut CtxMenu, action: &str) {
+        ctx_menu.hide();
+
+        let id = ctx_menu
+            .get_property(PROP_SESSION_ID)
+            .unwrap()
+            .get::<SessionPropsId>();
+        ctx_menu
+            .window()
+            .find_id_mut(TerminalEmulator::id())
+            .unwrap()
+            .downcast_mut::<TerminalEmulator>()
+            .unwrap()
+            .activate_filter_action(id, action);
+    }
+}
+
+impl CtxMenuSelectionCreator for CentralPanel {
+    #[inline]
+    fn create_selections() -> Vec<MenuSelection> {
+        vec![
+            MenuSelection::new(SelectionEnum::OpenLink),
+            MenuSelection::new(SelectionEnum::CopyLink),
+        ]
+    }
+}
+
+// Constants:
+const PROP_SESSION_ID: &str = "session_id";
@@ -1,11 +1,68 @@
+use crate::{
+    components::progress_indicator::ProgressIndicator, ui::notification_list::NotificationList,
+};
+use cli::session::SessionPropsId;
+use emulator::core::{
+    session::{Progress, ProgressState},
+    terminal_emulator::TerminalEmulator,
+};
+use std::{collections::VecDeque, time::SystemTime};
 use tmui::{
+    label::Label,
+    popup::Popupable,
     prelude::*,
-    tlib::object::{ObjectImpl, ObjectSubclass},
-    widget::WidgetImpl,
+    tlib::{
+        connect,
+        events::MouseEvent,
+        object::{ObjectImpl, ObjectSubclass},
+        run_after,
+    },
+    widget::{WidgetFinder, WidgetImpl},
 };
 
+/// The maximum number of notifications kept in the status bar, the oldest were dropped.
+const MAX_NOTIFICATIONS: usize = 100;
+
+/// The desktop notification requested by the terminal program of session.
+#[derive(Debug, Clone)]
+pub struct SessionNotification {
+    pub session_id: SessionPropsId,
+    pub title: String,
+    pub body: String,
+    pub time: SystemTime,
+}
+
+impl SessionNotification {
+    /// The title and body in one line.
+    pub fn summary(&self) -> String {
+        if self.title.is_empty() {
+            self.body.clone()
+        } else if self.body.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{}: {}", self.title, self.body)
+        }
+    }
+}
+
 #[extends(Widget, Layout(HBox))]
-pub struct StatusBar {}
+#[derive(Childrenable)]
+#[run_after]
+pub struct StatusBar {
+    /// Shows the latest notification and the number of unread notifications.
+    #[children]
+    notification_label: Tr<Label>,
+
+    /// Shows the progress of the session which reported the progress latest.
+    #[children]
+    progress_indicator: Tr<ProgressIndicator>,
+    progress_session: Option<SessionPropsId>,
+
+    /// The notifications of all sessions, the latest at the back.
+    notifications: VecDeque<SessionNotification>,
+    /// The number of notifications which were not read.
+    unread: usize,
+}
 
 impl ObjectSubclass for StatusBar {
     const NAME: &'static str = "StatusBar";
@@ -19,14 +76,123 @@ impl ObjectImpl for StatusBar {
         self.height_request(20);
 
         self.set_borders(1., 0., 0., 0.);
+
+        self.notification_label.set_margin_left(5);
+        self.notification_label.set_margin_top(2);
+
+        self.progress_indicator.width_request(120);
+        self.progress_indicator.set_margin_top(6);
+        self.progress_indicator.set_margin_bottom(6);
+        self.progress_indicator.set_margin_left(10);
+
+        self.add_popup(NotificationList::new().to_dyn_popup_tr());
     }
 }
 
-impl WidgetImpl for StatusBar {}
+impl WidgetImpl for StatusBar {
+    #[inline]
+    fn run_after(&mut self) {
+        let emulators = self.window().finds::<TerminalEmulator>();
+        let emulator = emulators.first().unwrap();
+        connect!(
+            emulator,
+            session_notification(),
+            self,
+            on_session_notification(SessionPropsId, String, String)
+        );
+        connect!(
+            emulator,
+            session_progress_changed(),
+            self,
+            on_session_progress_changed(SessionPropsId, u8, u8)
+        );
+    }
+
+    /// Show the notification list, and all the notifications were read.
+    fn on_mouse_released(&mut self, evt: &MouseEvent) {
+        if !self.notifications.is_empty() {
+            let pos = self.map_to_global(&evt.position().into());
+            let notifications: Vec<SessionNotification> =
+                self.notifications.iter().cloned().collect();
+            if let Some(list) = self
+                .get_popup_mut()
+                .and_then(|popup| popup.downcast_mut::<NotificationList>())
+            {
+                list.set_notifications(notifications.iter());
+            }
+            self.show_popup(pos);
+        }
+        self.mark_notifications_read();
+    }
+}
 
 impl StatusBar {
     #[inline]
     pub fn new() -> Box<Self> {
         Object::new(&[])
     }
+
+    /// Returns the notifications of all sessions, the latest at the back.
+    #[inline]
+    pub fn notifications(&self) -> &VecDeque<SessionNotification> {
+        &self.notifications
+    }
+
+    #[inline]
+    pub fn unread_notifications(&self) -> usize {
+        self.unread
+    }
+
+    #[inline]
+    pub fn mark_notifications_read(&mut self) {
+        self.unread = 0;
+        self.update_notification_label();
+    }
+
+    #[inline]
+    pub fn clear_notifications(&mut self) {
+        self.notifications.clear();
+        self.mark_notifications_read();
+    }
+
+    fn on_session_notification(&mut self, session_id: SessionPropsId, title: String, body: String) {
+        if self.notifications.len() >= MAX_NOTIFICATIONS {
+            self.notifications.pop_front();
+        }
+        self.notifications.push_back(SessionNotification {
+            session_id,
+            title,
+            body,
+            time: SystemTime::now(),
+        });
+        self.unread += 1;
+
+        self.update_notification_label();
+    }
+
+    fn on_session_progress_changed(&mut self, session_id: SessionPropsId, state: u8, percent: u8) {
+        let progress = Progress {
+            state: state.into(),
+            percent,
+        };
+        if progress.state == ProgressState::None {
+            // Only the latest session in progress was shown, ignore the others.
+            if self.progress_session != Some(session_id) {
+                return;
+            }
+            self.progress_session = None;
+        } else {
+            self.progress_session = Some(session_id);
+        }
+
+        self.progress_indicator.set_progress(progress);
+    }
+
+    fn update_notification_label(&mut self) {
+        let text = match self.notifications.back() {
+            Some(latest) if self.unread > 0 => format!("[{}] {}", self.unread, latest.summary()),
+            _ => String::new(),
+        };
+        self.notification_label.set_text(&text);
+    }
 }
@@ -0,0 +1,139 @@
+use cli::session::SessionPropsId;
+use emulator::core::terminal_emulator::TerminalEmulator;
+use tlib::{connect, events::MouseEvent};
+use tmui::{
+    button::Button,
+    label::Label,
+    prelude::*,
+    tlib::object::{ObjectImpl, ObjectSubclass},
+    widget::WidgetImpl,
+    window::{win_builder::WindowBuilder, win_config::WindowConfig},
+};
+
+const CONFIRM_WIN_WIDTH: u32 = 400;
+const CONFIRM_WIN_HEIGHT: u32 = 150;
+
+const PARAM_SESSION_ID: &str = "session_id";
+const PARAM_REQUEST_ID: &str = "request_id";
+const PARAM_READ: &str = "read";
+
+/// Asks the user whether the terminal program was allowed to access the clipboard by `OSC 52`,
+/// shown when the [`ClipboardAccess::Ask`](emulator::core::session::ClipboardAccess::Ask)
+/// policy was configured.
+#[extends(Widget, Layout(VBox))]
+#[derive(Childrenable)]
+pub struct ClipboardConfirmWindow {
+    #[children]
+    message: Tr<Label>,
+
+    #[children]
+    #[derivative(Default(value = r#"Button::new(Some("Allow"))"#))]
+    allow_btn: Tr<Button>,
+
+    #[children]
+    #[derivative(Default(value = r#"Button::new(Some("Deny"))"#))]
+    deny_btn: Tr<Button>,
+
+    session_id: SessionPropsId,
+    request_id: u64,
+}
+
+impl ObjectSubclass for ClipboardConfirmWindow {
+    const NAME: &'static str = "ClipboardConfirmWindow";
+}
+
+impl ObjectImpl for ClipboardConfirmWindow {
+    fn initialize(&mut self) {
+        self.set_spacing(10);
+        self.set_hexpand(true);
+        self.set_vexpand(true);
+        self.set_homogeneous(false);
+
+        let win = ApplicationWindow::window();
+        self.session_id = win
+            .get_param::<SessionPropsId>(PARAM_SESSION_ID)
+            .unwrap_or_default();
+        self.request_id = win.get_param::<u64>(PARAM_REQUEST_ID).unwrap_or_default();
+        let read = win.get_param::<bool>(PARAM_READ).unwrap_or_default();
+        self.message.set_text(if read {
+            "The terminal program requests to read the clipboard."
+        } else {
+            "The terminal program requests to set the clipboard."
+        });
+        self.message.set_margin_top(20);
+        self.message.set_halign(Align::Center);
+
+        for btn in [&mut self.allow_btn, &mut self.deny_btn] {
+            btn.width_request(50);
+            btn.height_request(20);
+            btn.set_halign(Align::Center);
+        }
+
+        connect!(self.allow_btn, mouse_pressed(), self, allow(MouseEvent));
+        connect!(self.deny_btn, mouse_pressed(), self, deny(MouseEvent));
+    }
+}
+
+impl WidgetImpl for ClipboardConfirmWindow {}
+
+impl ClipboardConfirmWindow {
+    #[inline]
+    pub fn new() -> Tr<Self> {
+        Self::new_alloc()
+    }
+
+    /// Open the modal window on the center of `win` to ask the user.
+    pub fn open(
+        win: &mut ApplicationWindow,
+        session_id: SessionPropsId,
+        request_id: u64,
+        read: bool,
+    ) {
+        let win_size = win.size();
+        let win_pos = win.outer_position();
+        let pos = Point::new(
+            win_pos.x() + (win_size.width() - CONFIRM_WIN_WIDTH as i32) / 2,
+            win_pos.y() + (win_size.height() - CONFIRM_WIN_HEIGHT as i32) / 2,
+        );
+
+        win.create_window(
+            WindowBuilder::default()
+                .config(
+                    WindowConfig::builder()
+                        .width(CONFIRM_WIN_WIDTH)
+                        .height(CONFIRM_WIN_HEIGHT)
+                        .title("Clipboard Access".to_string())
+                        .position(pos)
+                        .build(),
+                )
+                .modal(true)
+                .param(PARAM_SESSION_ID, session_id)
+                .param(PARAM_REQUEST_ID, request_id)
+                .param(PARAM_READ, read)
+                .on_activate(|win| win.child(ClipboardConfirmWindow::new())),
+        );
+    }
+
+    #[inline]
+    pub fn allow(&mut self, _: MouseEvent) {
+        self.answer(true)
+    }
+
+    #[inline]
+    pub fn deny(&mut self, _: MouseEvent) {
+        self.answer(false)
+    }
+
+    fn answer(&mut self, allow: bool) {
+        let (session_id, request_id) = (self.session_id, self.request_id);
+        self.window().call_response(move |win| {
+            win.find_id_mut(TerminalEmulator::id())
+                .unwrap()
+                .downcast_mut::<TerminalEmulator>()
+                .unwrap()
+                .answer_clipboard_request(session_id, request_id, allow);
+        });
+
+        self.window().close();
+    }
+}
@@ -1,4 +1,7 @@
-use crate::ui::sessions::SessionCredentialTree;
+use crate::{
+    layout::central_panel::CentralPanel,
+    ui::sessions::{SessionBar, SessionCredentialTree},
+};
 use tmui::views::list_view::ListView;
 
 use super::menu_selection::CtxMenuSelectionCreator;
@@ -8,12 +11,16 @@ pub enum CtxMenuLoc {
     #[default]
     Unknown,
     SessionCredentialTree,
+    SessionBar,
+    TerminalView,
 }
 
 impl CtxMenuLoc {
     pub(super) fn bld_selections(&self, view: &mut ListView) {
         let selections = match self {
             Self::SessionCredentialTree => SessionCredentialTree::create_selections(),
+            Self::SessionBar => SessionBar::create_selections(),
+            Self::TerminalView => CentralPanel::create_selections(),
             _ => vec![],
         };
 
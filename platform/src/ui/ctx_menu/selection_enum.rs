@@ -1,4 +1,7 @@
-use crate::ui::sessions::session_credential_tree;
+use crate::{
+    layout::central_panel::CentralPanel,
+    ui::sessions::{session_credential_tree, SessionBar},
+};
 use std::fmt::Display;
 use tmui::{tlib::events::MouseEvent, views::list_view::list_node::ListNode};
 
@@ -8,6 +11,10 @@ use super::CtxMenu;
 pub enum SelectionEnum {
     NewSession,
     NewGroup,
+    ToggleNotifications,
+    DuplicateSession,
+    OpenLink,
+    CopyLink,
 }
 
 impl Display for SelectionEnum {
@@ -15,6 +22,10 @@ impl Display for SelectionEnum {
         match self {
             Self::NewSession => f.write_str(STR_NEW_SESSION),
             Self::NewGroup => f.write_str(STR_NEW_GROUP),
+            Self::ToggleNotifications => f.write_str(STR_TOGGLE_NOTIFICATIONS),
+            Self::DuplicateSession => f.write_str(STR_DUPLICATE_SESSION),
+            Self::OpenLink => f.write_str(STR_OPEN_LINK),
+            Self::CopyLink => f.write_str(STR_COPY_LINK),
         }
     }
 }
@@ -26,6 +37,10 @@ impl SelectionEnum {
         match str {
             STR_NEW_SESSION => Self::NewSession,
             STR_NEW_GROUP => Self::NewGroup,
+            STR_TOGGLE_NOTIFICATIONS => Self::ToggleNotifications,
+            STR_DUPLICATE_SESSION => Self::DuplicateSession,
+            STR_OPEN_LINK => Self::OpenLink,
+            STR_COPY_LINK => Self::CopyLink,
             _ => panic!("Unknown selection."),
         }
     }
@@ -47,6 +62,10 @@ impl SelectionEnum {
                     ctx_menu, node,
                 )
             }
+            Self::ToggleNotifications => SessionBar::toggle_notifications_pressed(ctx_menu, node),
+            Self::DuplicateSession => SessionBar::duplicate_session_pressed(ctx_menu, node),
+            Self::OpenLink => CentralPanel::open_link_pressed(ctx_menu, node),
+            Self::CopyLink => CentralPanel::copy_link_pressed(ctx_menu, node),
         }
     }
 }
@@ -54,3 +73,7 @@ impl SelectionEnum {
 /// Constants:
 pub const STR_NEW_SESSION: &str = "New Session";
 pub const STR_NEW_GROUP: &str = "New Group";
+pub const STR_TOGGLE_NOTIFICATIONS: &str = "Mute/Unmute Notifications";
+pub const STR_DUPLICATE_SESSION: &str = "Duplicate Session";
+pub const STR_OPEN_LINK: &str = "Open Link";
+pub const STR_COPY_LINK: &str = "Copy Link Address";
@@ -1,6 +1,7 @@
 use super::sessions::SESSION_CREDENTIAL_TREE;
 use crate::components::{
-    number_bundle::NumberBundle, password_bundle::PasswordBundle, text_bundle::TextBundle,
+    check_bundle::CheckBundle, number_bundle::NumberBundle, password_bundle::PasswordBundle,
+    text_bundle::TextBundle,
 };
 use cli::{
     auth::{connect_info::ConnectInfo, credential::Credential},
@@ -8,10 +9,12 @@ use cli::{
     persistence::mgr::PersistenceMgr,
     session::cfg::SessionCfg,
 };
+use emulator::tools::codec::{TerminalCodec, DEFAULT_ENCODING};
 use log::debug;
 use tlib::{connect, events::MouseEvent};
 use tmui::{
     button::Button,
+    label::Label,
     prelude::*,
     tlib::object::{ObjectImpl, ObjectSubclass},
     views::tree_view::TreeView,
@@ -37,6 +40,18 @@ pub struct EditWindow {
     #[derivative(Default(value = r#"NumberBundle::new("Port:")"#))]
     port: Tr<NumberBundle>,
 
+    #[children]
+    #[derivative(Default(value = r#"TextBundle::new("Encoding:")"#))]
+    encoding: Tr<TextBundle>,
+
+    #[children]
+    #[derivative(Default(value = r#"CheckBundle::new("Allow Resize Request:")"#))]
+    allow_resize_request: Tr<CheckBundle>,
+
+    /// Shows why the submitted values were rejected.
+    #[children]
+    error: Tr<Label>,
+
     #[children]
     #[derivative(Default(value = r#"Button::new(Some("Submit"))"#))]
     submit_btn: Tr<Button>,
@@ -63,6 +78,9 @@ impl ObjectImpl for EditWindow {
         self.port.set_min(0.);
         self.port.set_max(65535.);
 
+        self.error.set_color(Color::RED);
+        self.error.set_halign(Align::Center);
+
         self.submit_btn.width_request(50);
         self.submit_btn.height_request(20);
         self.submit_btn.set_halign(Align::Center);
@@ -94,6 +112,17 @@ impl EditWindow {
         let user = self.user.value();
         let password = self.password.value();
         let port = self.port.val().unwrap() as u32;
+        let allow_resize_request = self.allow_resize_request.value();
+        // Empty for the default encoding, e.g. `GBK` or `Big5` for the legacy hosts.
+        let encoding = match self.encoding.value().trim() {
+            "" => DEFAULT_ENCODING.to_string(),
+            encoding if TerminalCodec::new(encoding).is_some() => encoding.to_string(),
+            encoding => {
+                self.error
+                    .set_text(&format!("Unknown encoding `{}`.", encoding));
+                return;
+            }
+        };
         let group_id = ApplicationWindow::window()
             .get_param::<u32>("group_id")
             .unwrap();
@@ -114,7 +143,10 @@ impl EditWindow {
             group.add_node(&credential);
 
             let group = group.get_value::<String>(0).unwrap();
-            PersistenceMgr::add_session(SessionCfg::new(credential, group));
+            let mut session = SessionCfg::new(credential, group);
+            session.set_encoding(encoding);
+            session.set_allow_resize_request(allow_resize_request);
+            PersistenceMgr::add_session(session);
         });
 
         self.window().close();
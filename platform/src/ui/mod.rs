@@ -1,6 +1,8 @@
 pub mod activity_bar;
 pub mod app_icon;
+pub mod clipboard_confirm;
 pub mod ctx_menu;
 pub mod edit_window;
+pub mod notification_list;
 pub mod win_control_buttons;
 pub mod sessions;
\ No newline at end of file
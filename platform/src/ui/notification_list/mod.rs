@@ -0,0 +1,97 @@
+use crate::layout::status_bar::SessionNotification;
+use tmui::{
+    graphics::box_shadow::{BoxShadow, ShadowSide},
+    prelude::*,
+    scroll_area::LayoutMode,
+    tlib::object::{ObjectImpl, ObjectSubclass},
+    views::{
+        cell::{cell_render::TextCellRender, Cell, CellStringBuilder},
+        list_view::{list_view_object::ListViewObject, ListView},
+        node::node_render::NodeRender,
+    },
+    widget::WidgetImpl,
+};
+
+/// The popup lists the desktop notifications of all sessions, the latest at the top.
+#[extends(Popup)]
+#[derive(Childable)]
+pub struct NotificationList {
+    #[child]
+    list: Tr<ListView>,
+}
+
+impl ObjectSubclass for NotificationList {
+    const NAME: &'static str = "NotificationList";
+}
+
+impl ObjectImpl for NotificationList {
+    fn initialize(&mut self) {
+        self.width_request(400);
+        self.height_request(300);
+
+        self.set_borders(1., 1., 1., 1.);
+        self.set_border_color(Color::GREY_LIGHT);
+        self.set_box_shadow(BoxShadow::new(
+            8.,
+            Color::BLACK,
+            None,
+            Some(ShadowSide::new(&[ShadowSide::RIGHT, ShadowSide::TOP])),
+            None,
+            None,
+        ));
+
+        self.list.set_vexpand(true);
+        self.list.set_hexpand(true);
+        self.list.set_layout_mode(LayoutMode::Overlay);
+    }
+}
+
+impl WidgetImpl for NotificationList {}
+
+impl PopupImpl for NotificationList {
+    /// Shown above the `point`, the status bar was at the bottom of window.
+    #[inline]
+    fn calculate_position(&self, _: Rect, point: Point) -> Point {
+        Point::new(point.x(), point.y() - self.size().height())
+    }
+
+    #[inline]
+    fn is_modal(&self) -> bool {
+        true
+    }
+}
+
+impl NotificationList {
+    #[inline]
+    pub fn new() -> Tr<Self> {
+        Self::new_alloc()
+    }
+
+    /// Replace the listed notifications, `notifications` were ordered the latest at the back.
+    pub fn set_notifications<'a>(
+        &mut self,
+        notifications: impl DoubleEndedIterator<Item = &'a SessionNotification>,
+    ) {
+        self.list.clear();
+        for notification in notifications.rev() {
+            self.list.add_node(&NotificationItem(notification));
+        }
+    }
+}
+
+struct NotificationItem<'a>(&'a SessionNotification);
+
+impl ListViewObject for NotificationItem<'_> {
+    #[inline]
+    fn cells(&self) -> Vec<Cell> {
+        vec![CellStringBuilder::default()
+            .value(self.0.summary())
+            .cell_render(TextCellRender::builder().color(Color::BLACK).build())
+            .build()]
+    }
+
+    #[inline]
+    fn node_render(&self) -> NodeRender {
+        NodeRender::builder().build()
+    }
+}
@@ -1,12 +1,59 @@
+use crate::{
+    components::progress_indicator::paint_progress,
+    ui::{
+        ctx_menu::{
+            menu_selection::{CtxMenuSelectionCreator, MenuSelection},
+            selection_bld::CtxMenuLoc,
+            selection_enum::SelectionEnum,
+            CtxMenu,
+        },
+        sessions::session_credential_tree::service::SessionCredentialService,
+    },
+};
+use cli::session::{SessionExt, SessionProps, SessionPropsId};
+use emulator::core::{
+    session::{Progress, ProgressState},
+    terminal_emulator::TerminalEmulator,
+};
+use log::warn;
+use std::collections::{HashMap, HashSet};
 use tmui::{
+    popup::Popupable,
     prelude::*,
-    tlib::object::{ObjectImpl, ObjectSubclass},
-    widget::WidgetImpl,
+    tlib::{
+        connect,
+        events::MouseEvent,
+        namespace::MouseButton,
+        object::{ObjectImpl, ObjectSubclass},
+        run_after,
+    },
+    views::list_view::list_node::ListNode,
+    widget::{WidgetFinder, WidgetImpl},
 };
 
+/// The width of each session tab.
+const TAB_WIDTH: f32 = 150.;
+/// The size of the badge shows the number of unread notifications.
+const BADGE_SIZE: f32 = 16.;
+/// The height of the progress bar at the bottom of tab.
+const PROGRESS_HEIGHT: f32 = 3.;
+
 #[extends(Widget, Layout(HBox))]
 #[derive(Childrenable)]
-pub struct SessionBar {}
+#[run_after]
+pub struct SessionBar {
+    /// The started sessions, each one has a tab.
+    tabs: Vec<SessionPropsId>,
+    /// The number of unread notifications of each session, shown as the badge of tab.
+    badges: HashMap<SessionPropsId, usize>,
+    /// The sessions whose desktop notifications were muted.
+    muted: HashSet<SessionPropsId>,
+    /// The progress of each session reported by `OSC 9;4`, shown as the indicator of tab.
+    progresses: HashMap<SessionPropsId, Progress>,
+    /// The sessions whose last command failed (`OSC 133;D` with non-zero exit code),
+    /// shown as the failure badge of tab.
+    failures: HashSet<SessionPropsId>,
+}
 
 impl ObjectSubclass for SessionBar {
     const NAME: &'static str = "SessionBar";
@@ -18,7 +65,329 @@ impl ObjectImpl for SessionBar {
 
         self.set_vexpand(true);
         self.set_hexpand(true);
+
+        self.add_popup(CtxMenu::new(CtxMenuLoc::SessionBar).to_dyn_popup_tr());
+    }
+}
+
+impl WidgetImpl for SessionBar {
+    #[inline]
+    fn run_after(&mut self) {
+        let emulators = self.window().finds::<TerminalEmulator>();
+        let emulator = emulators.first().unwrap();
+        connect!(
+            emulator,
+            session_started(),
+            self,
+            on_session_started(SessionPropsId)
+        );
+        connect!(
+            emulator,
+            session_notification(),
+            self,
+            on_session_notification(SessionPropsId, String, String)
+        );
+        connect!(
+            emulator,
+            session_progress_changed(),
+            self,
+            on_session_progress_changed(SessionPropsId, u8, u8)
+        );
+        connect!(
+            emulator,
+            session_command_finished(),
+            self,
+            on_session_command_finished(SessionPropsId, i32)
+        );
+        connect!(
+            emulator,
+            session_finished(),
+            self,
+            on_session_finished(SessionPropsId)
+        );
+    }
+
+    fn paint(&mut self, painter: &mut Painter) {
+        let rect = self.contents_rect_f(Some(Coordinate::Widget));
+
+        for (i, id) in self.tabs.iter().enumerate() {
+            let tab = FRect::new(
+                rect.left() + i as f32 * TAB_WIDTH,
+                rect.top(),
+                TAB_WIDTH,
+                rect.height(),
+            );
+            if tab.left() >= rect.right() {
+                break;
+            }
+
+            let mut title = SessionProps::get(*id)
+                .map(|props| props.credential().shown_name().to_string())
+                .unwrap_or_default();
+            if self.muted.contains(id) {
+                title.push_str(" (muted)");
+            }
+            painter.set_color(Color::BLACK);
+            painter.draw_paragraph(
+                &title,
+                (tab.left() + 5., tab.top() + 5.),
+                0.,
+                TAB_WIDTH - 2. * BADGE_SIZE - 20.,
+                Some(1),
+                true,
+            );
+
+            if self.failures.contains(id) {
+                let failure_rect = FRect::new(
+                    tab.right() - 2. * BADGE_SIZE - 10.,
+                    tab.top() + (tab.height() - BADGE_SIZE) / 2.,
+                    BADGE_SIZE,
+                    BADGE_SIZE,
+                );
+                painter.fill_rect(failure_rect, Color::rgb(230, 140, 30));
+                painter.set_color(Color::WHITE);
+                painter.draw_paragraph(
+                    "!",
+                    (failure_rect.left() + 6., failure_rect.top()),
+                    0.,
+                    BADGE_SIZE,
+                    Some(1),
+                    false,
+                );
+            }
+
+            let badge = self.badge(*id);
+            if badge > 0 {
+                let badge_rect = FRect::new(
+                    tab.right() - BADGE_SIZE - 5.,
+                    tab.top() + (tab.height() - BADGE_SIZE) / 2.,
+                    BADGE_SIZE,
+                    BADGE_SIZE,
+                );
+                painter.fill_rect(badge_rect, Color::rgb(220, 70, 70));
+
+                let text = if badge > 9 {
+                    "9+".to_string()
+                } else {
+                    badge.to_string()
+                };
+                painter.set_color(Color::WHITE);
+                painter.draw_paragraph(
+                    &text,
+                    (badge_rect.left() + 3., badge_rect.top()),
+                    0.,
+                    BADGE_SIZE,
+                    Some(1),
+                    false,
+                );
+            }
+
+            if let Some(progress) = self.progresses.get(id) {
+                let bar = FRect::new(
+                    tab.left(),
+                    tab.bottom() - PROGRESS_HEIGHT,
+                    TAB_WIDTH,
+                    PROGRESS_HEIGHT,
+                );
+                paint_progress(painter, bar, *progress);
+            }
+
+            painter.set_color(Color::GREY_MEDIUM);
+            painter.draw_line_f(tab.right(), tab.top(), tab.right(), tab.bottom());
+        }
+    }
+
+    fn on_mouse_released(&mut self, evt: &MouseEvent) {
+        let Some(id) = self.tab_at(evt.position().0) else {
+            return;
+        };
+
+        match evt.mouse_button() {
+            MouseButton::LeftButton => {
+                self.clear_badge(id);
+                self.emulator().switch_session(id);
+            }
+            MouseButton::RightButton => {
+                let bar_id = self.id();
+                self.show_popup(self.map_to_global(&evt.position().into()));
+                let popup = self.get_popup_mut().unwrap();
+                popup.set_property(PROP_SESSION_ID, id.to_value());
+                popup.set_property(PROP_SESSION_BAR_ID, bar_id.to_value());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl CtxMenuSelectionCreator for SessionBar {
+    #[inline]
+    fn create_selections() -> Vec<MenuSelection> {
+        vec![
+            MenuSelection::new(SelectionEnum::DuplicateSession),
+            MenuSelection::new(SelectionEnum::ToggleNotifications),
+        ]
+    }
+}
+
+impl SessionBar {
+    /// Returns the number of unread notifications of session.
+    #[inline]
+    pub fn badge(&self, id: SessionPropsId) -> usize {
+        self.badges.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Clear the badge when the notifications of session were read.
+    #[inline]
+    pub fn clear_badge(&mut self, id: SessionPropsId) {
+        if self.badges.remove(&id).is_some() {
+            self.update();
+        }
+    }
+
+    /// Returns the progress of session, [`ProgressState::None`] if there was no progress.
+    #[inline]
+    pub fn progress(&self, id: SessionPropsId) -> Progress {
+        self.progresses.get(&id).copied().unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn is_notifications_muted(&self, id: SessionPropsId) -> bool {
+        self.muted.contains(&id)
+    }
+
+    /// Mute or unmute the desktop notifications of session.
+    pub fn set_notifications_muted(&mut self, id: SessionPropsId, muted: bool) {
+        if muted {
+            self.muted.insert(id);
+            self.badges.remove(&id);
+        } else {
+            self.muted.remove(&id);
+        }
+        self.emulator().set_notifications_muted(id, muted);
+        self.update();
+    }
+
+    pub fn toggle_notifications_pressed(ctx_menu: &mut CtxMenu, _node: &mut ListNode) {
+        ctx_menu.hide();
+
+        let id = ctx_menu
+            .get_property(PROP_SESSION_ID)
+            .unwrap()
+            .get::<SessionPropsId>();
+        let bar_id = ctx_menu
+            .get_property(PROP_SESSION_BAR_ID)
+            .unwrap()
+            .get::<ObjectId>();
+
+        if let Some(bar) = ctx_menu.find_id_mut::<SessionBar>(bar_id) {
+            let muted = bar.is_notifications_muted(id);
+            bar.set_notifications_muted(id, !muted);
+        }
+    }
+
+    /// Start a new session with the same credential of session, local shell session starts
+    /// in the current working directory of it.
+    pub fn duplicate_session_pressed(ctx_menu: &mut CtxMenu, _node: &mut ListNode) {
+        ctx_menu.hide();
+
+        let source = ctx_menu
+            .get_property(PROP_SESSION_ID)
+            .unwrap()
+            .get::<SessionPropsId>();
+        let Some(credential) = SessionProps::get(source).map(|props| props.credential().clone())
+        else {
+            warn!(
+                "[SessionBar::duplicate_session_pressed] Session `{}` was not found.",
+                source
+            );
+            return;
+        };
+
+        let emulator = ctx_menu
+            .window()
+            .find_id_mut(TerminalEmulator::id())
+            .unwrap()
+            .downcast_mut::<TerminalEmulator>()
+            .unwrap();
+        let credential_id = credential.id();
+        let id = SessionProps::create(credential);
+        if emulator.duplicate_session(source, id).is_some() {
+            SessionCredentialService::apply_session_cfg(emulator, id, credential_id);
+        } else {
+            SessionProps::remove(id);
+        }
+    }
+
+    #[inline]
+    fn emulator(&mut self) -> &mut TerminalEmulator {
+        self.window()
+            .find_id_mut(TerminalEmulator::id())
+            .unwrap()
+            .downcast_mut::<TerminalEmulator>()
+            .unwrap()
+    }
+
+    /// Returns the session of tab at the `x` position of widget.
+    fn tab_at(&self, x: i32) -> Option<SessionPropsId> {
+        if x < 0 {
+            return None;
+        }
+        self.tabs.get((x as f32 / TAB_WIDTH) as usize).copied()
+    }
+
+    #[inline]
+    fn on_session_started(&mut self, id: SessionPropsId) {
+        self.tabs.push(id);
+        self.update();
+    }
+
+    #[inline]
+    fn on_session_finished(&mut self, id: SessionPropsId) {
+        self.tabs.retain(|tab| *tab != id);
+        self.badges.remove(&id);
+        self.muted.remove(&id);
+        self.progresses.remove(&id);
+        self.failures.remove(&id);
+        self.update();
+    }
+
+    /// Whether the last command of session has failed.
+    #[inline]
+    pub fn is_failed(&self, id: SessionPropsId) -> bool {
+        self.failures.contains(&id)
+    }
+
+    fn on_session_command_finished(&mut self, id: SessionPropsId, exit_code: i32) {
+        let changed = if exit_code != 0 {
+            self.failures.insert(id)
+        } else {
+            self.failures.remove(&id)
+        };
+        if changed {
+            self.update();
+        }
+    }
+
+    fn on_session_progress_changed(&mut self, id: SessionPropsId, state: u8, percent: u8) {
+        let progress = Progress {
+            state: state.into(),
+            percent,
+        };
+        if progress.state == ProgressState::None {
+            self.progresses.remove(&id);
+        } else {
+            self.progresses.insert(id, progress);
+        }
+        self.update();
+    }
+
+    #[inline]
+    fn on_session_notification(&mut self, id: SessionPropsId, _title: String, _body: String) {
+        *self.badges.entry(id).or_default() += 1;
+        self.update();
     }
 }
 
-impl WidgetImpl for SessionBar {}
+// Constants:
+pub const PROP_SESSION_ID: &str = "session_id";
+pub const PROP_SESSION_BAR_ID: &str = "session_bar_id";
@@ -4,11 +4,14 @@ use crate::ui::{
     sessions::{PROP_TREE_NODE_ID, PROP_TREE_VIEW_ID},
 };
 use cli::{
-    auth::credential::Credential,
+    auth::credential::{Credential, CredentialId},
     persistence::mgr::PersistenceMgr,
-    session::{session_grp::SessionGroup, SessionExt, SessionProps},
+    session::{session_grp::SessionGroup, SessionExt, SessionProps, SessionPropsId},
+};
+use emulator::core::{
+    session::{CharWidth, ClipboardPolicy},
+    terminal_emulator::TerminalEmulator,
 };
-use emulator::core::terminal_emulator::TerminalEmulator;
 use log::warn;
 use tmui::{
     input::{dialog::InputDialog, text::Text, Input},
@@ -146,9 +149,32 @@ impl SessionCredentialService {
                 .downcast_mut::<TerminalEmulator>()
                 .unwrap();
             let protocol_type = credential.protocol_type();
-            emulator.start_session(SessionProps::create(credential), protocol_type);
+            let credential_id = credential.id();
+            let id = SessionProps::create(credential);
+            emulator.start_session(id, protocol_type);
+            Self::apply_session_cfg(emulator, id, credential_id);
         } else {
             warn!("Get `Credential` from `TreeNode` failed.")
         }
     }
+
+    /// Apply the persisted configurations of credential to the started session.
+    pub fn apply_session_cfg(
+        emulator: &mut TerminalEmulator,
+        id: SessionPropsId,
+        credential_id: CredentialId,
+    ) {
+        if let Some(width_cfg) = PersistenceMgr::get_width_cfg(credential_id) {
+            emulator.set_char_width(id, CharWidth::from(&width_cfg));
+        }
+        if let Some(encoding) = PersistenceMgr::get_encoding(credential_id) {
+            emulator.set_encoding(id, &encoding);
+        }
+        if let Some(clipboard_cfg) = PersistenceMgr::get_clipboard_cfg(credential_id) {
+            emulator.set_clipboard_policy(id, ClipboardPolicy::from(clipboard_cfg));
+        }
+        if let Some(allow) = PersistenceMgr::get_allow_resize_request(credential_id) {
+            emulator.set_allow_resize_request(id, allow);
+        }
+    }
 }